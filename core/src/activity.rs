@@ -0,0 +1,85 @@
+use std::time::Duration;
+use tokio::select;
+use tokio::sync::watch;
+use tokio::time::sleep;
+
+/// Tells plugins whether the user is idle or the system is under load, so periodic work can slow
+/// down in the meantime.
+#[derive(Debug, Clone)]
+pub struct Activity {
+    idle: watch::Receiver<bool>,
+    slowdown: u32,
+    throttled: Option<watch::Receiver<bool>>,
+    throttle: u32,
+}
+
+impl Activity {
+    pub fn new(idle: watch::Receiver<bool>, slowdown: u32) -> Self {
+        Self {
+            idle,
+            slowdown,
+            throttled: None,
+            throttle: 1,
+        }
+    }
+
+    /// Additionally slows down by the factor while the governor reports the system under load.
+    pub fn throttled_by(mut self, throttled: watch::Receiver<bool>, throttle: u32) -> Self {
+        self.throttled = Some(throttled);
+        self.throttle = throttle;
+        self
+    }
+
+    pub fn is_idle(&self) -> bool {
+        *self.idle.borrow()
+    }
+
+    pub fn is_throttled(&self) -> bool {
+        self.throttled
+            .as_ref()
+            .map_or(false, |throttled| *throttled.borrow())
+    }
+
+    /// Time between periodic updates, stretched by the configured factors while the user is idle
+    /// or the system is under load.
+    pub fn period(&self, normal: Duration) -> Duration {
+        let mut period = normal;
+
+        if self.is_idle() {
+            period *= self.slowdown.max(1);
+        }
+
+        if self.is_throttled() {
+            period *= self.throttle.max(1);
+        }
+
+        period
+    }
+
+    /// Waits for the next periodic update, waking up early when the user becomes active again or
+    /// the load changes.
+    pub async fn sleep(&mut self, normal: Duration) {
+        self.idle.borrow_and_update();
+
+        if let Some(throttled) = &mut self.throttled {
+            throttled.borrow_and_update();
+        }
+
+        let period = self.period(normal);
+        let Self {
+            idle, throttled, ..
+        } = self;
+        let throttle_changed = async {
+            match throttled {
+                Some(throttled) => throttled.changed().await,
+                None => std::future::pending().await,
+            }
+        };
+
+        select! {
+            _ = sleep(period) => {}
+            Ok(()) = idle.changed() => {}
+            Ok(()) = throttle_changed => {}
+        }
+    }
+}
@@ -0,0 +1,605 @@
+use async_osc::OscType;
+use AddressDirection::{Both, Input, Output};
+use ArgumentKind::{Bool, Float, Int};
+
+pub const CHATBOX_INPUT: &str = "/chatbox/input";
+pub const CHATBOX_TYPING: &str = "/chatbox/typing";
+/// Address VRChat reports the ID of a newly loaded avatar on, and changes the avatar when sent to.
+pub const AVATAR_CHANGE: &str = "/avatar/change";
+
+/// Type of an argument VRChat expects or sends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgumentKind {
+    Bool,
+    Int,
+    Float,
+    String,
+}
+
+impl ArgumentKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            ArgumentKind::Bool => "bool",
+            ArgumentKind::Int => "int",
+            ArgumentKind::Float => "float",
+            ArgumentKind::String => "string",
+        }
+    }
+
+    pub fn accepts(self, arg: &OscType) -> bool {
+        matches!(
+            (self, arg),
+            (ArgumentKind::Bool, OscType::Bool(_))
+                | (ArgumentKind::Int, OscType::Int(_))
+                | (ArgumentKind::Float, OscType::Float(_))
+                | (ArgumentKind::String, OscType::String(_))
+        )
+    }
+}
+
+/// Which way messages on an address go.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressDirection {
+    /// Sent to VRChat.
+    Input,
+    /// Sent by VRChat.
+    Output,
+    Both,
+}
+
+/// An address VRChat handles the same way for every avatar.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StandardAddress {
+    pub address: &'static str,
+    pub arguments: &'static [ArgumentKind],
+    /// Number of trailing arguments which can be left out.
+    pub optional: usize,
+    pub direction: AddressDirection,
+    pub description: &'static str,
+}
+
+impl StandardAddress {
+    /// Whether VRChat takes the arguments, leaving out optional trailing ones.
+    pub fn accepts(&self, args: &[OscType]) -> bool {
+        args.len() + self.optional >= self.arguments.len()
+            && args.len() <= self.arguments.len()
+            && self
+                .arguments
+                .iter()
+                .zip(args)
+                .all(|(kind, arg)| kind.accepts(arg))
+    }
+
+    /// Types of the arguments, like `string, bool, bool`.
+    pub fn signature(&self) -> String {
+        self.arguments
+            .iter()
+            .map(|kind| kind.name())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+const fn entry(
+    address: &'static str,
+    arguments: &'static [ArgumentKind],
+    direction: AddressDirection,
+    description: &'static str,
+) -> StandardAddress {
+    StandardAddress {
+        address,
+        arguments,
+        optional: 0,
+        direction,
+        description,
+    }
+}
+
+const VECTOR: &[ArgumentKind] = &[Float, Float, Float];
+
+/// The addresses VRChat documents for inputs, the chatbox, avatar changes, tracking and the
+/// parameters built into every avatar.
+pub const STANDARD_ADDRESSES: &[StandardAddress] = &[
+    entry(
+        "/input/Vertical",
+        &[Float],
+        Input,
+        "Move forwards (1) or backwards (-1)",
+    ),
+    entry(
+        "/input/Horizontal",
+        &[Float],
+        Input,
+        "Move right (1) or left (-1)",
+    ),
+    entry(
+        "/input/LookHorizontal",
+        &[Float],
+        Input,
+        "Turn right (1) or left (-1)",
+    ),
+    entry("/input/UseAxisRight", &[Float], Input, "Use held item"),
+    entry("/input/GrabAxisRight", &[Float], Input, "Grab item"),
+    entry(
+        "/input/MoveHoldFB",
+        &[Float],
+        Input,
+        "Move held item forwards or backwards",
+    ),
+    entry(
+        "/input/SpinHoldCwCcw",
+        &[Float],
+        Input,
+        "Spin held item clockwise or counter-clockwise",
+    ),
+    entry(
+        "/input/SpinHoldUD",
+        &[Float],
+        Input,
+        "Spin held item up or down",
+    ),
+    entry(
+        "/input/SpinHoldLR",
+        &[Float],
+        Input,
+        "Spin held item left or right",
+    ),
+    entry("/input/MoveForward", &[Int], Input, "Move forwards while 1"),
+    entry(
+        "/input/MoveBackward",
+        &[Int],
+        Input,
+        "Move backwards while 1",
+    ),
+    entry("/input/MoveLeft", &[Int], Input, "Strafe left while 1"),
+    entry("/input/MoveRight", &[Int], Input, "Strafe right while 1"),
+    entry(
+        "/input/LookLeft",
+        &[Int],
+        Input,
+        "Turn left while 1, snap turning in VR",
+    ),
+    entry(
+        "/input/LookRight",
+        &[Int],
+        Input,
+        "Turn right while 1, snap turning in VR",
+    ),
+    entry("/input/Jump", &[Int], Input, "Jump when set to 1"),
+    entry("/input/Run", &[Int], Input, "Run while 1"),
+    entry("/input/ComfortLeft", &[Int], Input, "Snap turn left in VR"),
+    entry(
+        "/input/ComfortRight",
+        &[Int],
+        Input,
+        "Snap turn right in VR",
+    ),
+    entry(
+        "/input/DropRight",
+        &[Int],
+        Input,
+        "Drop the item in the right hand",
+    ),
+    entry(
+        "/input/UseRight",
+        &[Int],
+        Input,
+        "Use the item in the right hand",
+    ),
+    entry(
+        "/input/GrabRight",
+        &[Int],
+        Input,
+        "Grab with the right hand",
+    ),
+    entry(
+        "/input/DropLeft",
+        &[Int],
+        Input,
+        "Drop the item in the left hand",
+    ),
+    entry(
+        "/input/UseLeft",
+        &[Int],
+        Input,
+        "Use the item in the left hand",
+    ),
+    entry("/input/GrabLeft", &[Int], Input, "Grab with the left hand"),
+    entry("/input/PanicButton", &[Int], Input, "Turn on safe mode"),
+    entry(
+        "/input/QuickMenuToggleLeft",
+        &[Int],
+        Input,
+        "Toggle the quick menu",
+    ),
+    entry(
+        "/input/QuickMenuToggleRight",
+        &[Int],
+        Input,
+        "Toggle the quick menu",
+    ),
+    entry(
+        "/input/Voice",
+        &[Int],
+        Input,
+        "Toggle the microphone, or push to talk while 1",
+    ),
+    StandardAddress {
+        address: CHATBOX_INPUT,
+        arguments: &[ArgumentKind::String, Bool, Bool],
+        optional: 1,
+        direction: Input,
+        description: "Chatbox text, sent right away when the first bool is true, with the \
+            notification sound unless the second bool is false",
+    },
+    entry(
+        CHATBOX_TYPING,
+        &[Bool],
+        Input,
+        "Typing indicator of the chatbox",
+    ),
+    entry(
+        AVATAR_CHANGE,
+        &[ArgumentKind::String],
+        Both,
+        "ID of the avatar loaded, or to change to",
+    ),
+    entry(
+        "/tracking/trackers/1/position",
+        VECTOR,
+        Input,
+        "Position of tracker 1",
+    ),
+    entry(
+        "/tracking/trackers/1/rotation",
+        VECTOR,
+        Input,
+        "Euler angles of tracker 1",
+    ),
+    entry(
+        "/tracking/trackers/2/position",
+        VECTOR,
+        Input,
+        "Position of tracker 2",
+    ),
+    entry(
+        "/tracking/trackers/2/rotation",
+        VECTOR,
+        Input,
+        "Euler angles of tracker 2",
+    ),
+    entry(
+        "/tracking/trackers/3/position",
+        VECTOR,
+        Input,
+        "Position of tracker 3",
+    ),
+    entry(
+        "/tracking/trackers/3/rotation",
+        VECTOR,
+        Input,
+        "Euler angles of tracker 3",
+    ),
+    entry(
+        "/tracking/trackers/4/position",
+        VECTOR,
+        Input,
+        "Position of tracker 4",
+    ),
+    entry(
+        "/tracking/trackers/4/rotation",
+        VECTOR,
+        Input,
+        "Euler angles of tracker 4",
+    ),
+    entry(
+        "/tracking/trackers/5/position",
+        VECTOR,
+        Input,
+        "Position of tracker 5",
+    ),
+    entry(
+        "/tracking/trackers/5/rotation",
+        VECTOR,
+        Input,
+        "Euler angles of tracker 5",
+    ),
+    entry(
+        "/tracking/trackers/6/position",
+        VECTOR,
+        Input,
+        "Position of tracker 6",
+    ),
+    entry(
+        "/tracking/trackers/6/rotation",
+        VECTOR,
+        Input,
+        "Euler angles of tracker 6",
+    ),
+    entry(
+        "/tracking/trackers/7/position",
+        VECTOR,
+        Input,
+        "Position of tracker 7",
+    ),
+    entry(
+        "/tracking/trackers/7/rotation",
+        VECTOR,
+        Input,
+        "Euler angles of tracker 7",
+    ),
+    entry(
+        "/tracking/trackers/8/position",
+        VECTOR,
+        Input,
+        "Position of tracker 8",
+    ),
+    entry(
+        "/tracking/trackers/8/rotation",
+        VECTOR,
+        Input,
+        "Euler angles of tracker 8",
+    ),
+    entry(
+        "/tracking/trackers/head/position",
+        VECTOR,
+        Input,
+        "Position of the head, for alignment",
+    ),
+    entry(
+        "/tracking/trackers/head/rotation",
+        VECTOR,
+        Input,
+        "Euler angles of the head",
+    ),
+    entry(
+        "/tracking/eye/EyesClosedAmount",
+        &[Float],
+        Input,
+        "How far the eyes are closed, from 0 to 1",
+    ),
+    entry(
+        "/tracking/eye/CenterPitchYaw",
+        &[Float, Float],
+        Input,
+        "Gaze as pitch and yaw in degrees",
+    ),
+    entry(
+        "/tracking/eye/CenterPitchYawDist",
+        &[Float, Float, Float],
+        Input,
+        "Gaze as pitch and yaw in degrees, with the distance in meters",
+    ),
+    entry(
+        "/tracking/eye/CenterVec",
+        VECTOR,
+        Input,
+        "Gaze as normalized direction",
+    ),
+    entry(
+        "/tracking/eye/CenterVecFull",
+        VECTOR,
+        Input,
+        "Gaze as direction, with the distance as length",
+    ),
+    entry(
+        "/tracking/eye/LeftRightPitchYaw",
+        &[Float, Float, Float, Float],
+        Input,
+        "Gaze of each eye as pitch and yaw in degrees",
+    ),
+    entry(
+        "/tracking/eye/LeftRightVec",
+        &[Float, Float, Float, Float, Float, Float],
+        Input,
+        "Gaze of each eye as normalized direction",
+    ),
+    entry(
+        "/avatar/parameters/IsLocal",
+        &[Bool],
+        Output,
+        "Whether the avatar is worn by the user",
+    ),
+    entry(
+        "/avatar/parameters/Viseme",
+        &[Int],
+        Output,
+        "Viseme of the speech, from 0 to 14",
+    ),
+    entry(
+        "/avatar/parameters/Voice",
+        &[Float],
+        Output,
+        "Loudness of the microphone",
+    ),
+    entry(
+        "/avatar/parameters/GestureLeft",
+        &[Int],
+        Output,
+        "Gesture of the left hand, from 0 to 7",
+    ),
+    entry(
+        "/avatar/parameters/GestureRight",
+        &[Int],
+        Output,
+        "Gesture of the right hand, from 0 to 7",
+    ),
+    entry(
+        "/avatar/parameters/GestureLeftWeight",
+        &[Float],
+        Output,
+        "Trigger of the left hand",
+    ),
+    entry(
+        "/avatar/parameters/GestureRightWeight",
+        &[Float],
+        Output,
+        "Trigger of the right hand",
+    ),
+    entry(
+        "/avatar/parameters/AngularY",
+        &[Float],
+        Output,
+        "Turning speed",
+    ),
+    entry(
+        "/avatar/parameters/VelocityX",
+        &[Float],
+        Output,
+        "Sideways speed",
+    ),
+    entry(
+        "/avatar/parameters/VelocityY",
+        &[Float],
+        Output,
+        "Vertical speed",
+    ),
+    entry(
+        "/avatar/parameters/VelocityZ",
+        &[Float],
+        Output,
+        "Forward speed",
+    ),
+    entry(
+        "/avatar/parameters/VelocityMagnitude",
+        &[Float],
+        Output,
+        "Total speed",
+    ),
+    entry(
+        "/avatar/parameters/Upright",
+        &[Float],
+        Output,
+        "How upright the user stands, from 0 to 1",
+    ),
+    entry(
+        "/avatar/parameters/Grounded",
+        &[Bool],
+        Output,
+        "Whether the avatar touches the ground",
+    ),
+    entry(
+        "/avatar/parameters/Seated",
+        &[Bool],
+        Output,
+        "Whether the user sits in a station",
+    ),
+    entry(
+        "/avatar/parameters/AFK",
+        &[Bool],
+        Output,
+        "Whether the user is away",
+    ),
+    entry(
+        "/avatar/parameters/TrackingType",
+        &[Int],
+        Output,
+        "Kind of tracking, like 6 for full body",
+    ),
+    entry(
+        "/avatar/parameters/VRMode",
+        &[Int],
+        Output,
+        "1 in VR, 0 on desktop",
+    ),
+    entry(
+        "/avatar/parameters/MuteSelf",
+        &[Bool],
+        Output,
+        "Whether the microphone is muted",
+    ),
+    entry(
+        "/avatar/parameters/InStation",
+        &[Bool],
+        Output,
+        "Whether the user is in a station",
+    ),
+    entry(
+        "/avatar/parameters/Earmuffs",
+        &[Bool],
+        Output,
+        "Whether earmuffs are on",
+    ),
+    entry(
+        "/avatar/parameters/IsOnFriendsList",
+        &[Bool],
+        Output,
+        "Whether the wearer is a friend",
+    ),
+    entry(
+        "/avatar/parameters/AvatarVersion",
+        &[Int],
+        Output,
+        "3 for avatars of SDK 3",
+    ),
+    entry(
+        "/avatar/parameters/ScaleModified",
+        &[Bool],
+        Output,
+        "Whether the avatar is scaled",
+    ),
+    entry(
+        "/avatar/parameters/ScaleFactor",
+        &[Float],
+        Output,
+        "Scale relative to the default",
+    ),
+    entry(
+        "/avatar/parameters/ScaleFactorInverse",
+        &[Float],
+        Output,
+        "Inverse of the scale",
+    ),
+    entry(
+        "/avatar/parameters/EyeHeightAsMeters",
+        &[Float],
+        Output,
+        "Eye height in meters",
+    ),
+    entry(
+        "/avatar/parameters/EyeHeightAsPercent",
+        &[Float],
+        Output,
+        "Eye height relative to the range allowed",
+    ),
+];
+
+/// Turns a bare parameter name into its address, while full addresses are kept as they are.
+pub fn parameter_address(name: &str) -> String {
+    if name.starts_with('/') {
+        name.to_string()
+    } else {
+        format!("/avatar/parameters/{}", name)
+    }
+}
+
+/// Parses an argument the way it would be written in the config or typed into a prompt, falling
+/// back to a string.
+pub fn parse_argument(text: &str) -> OscType {
+    match text {
+        "true" => OscType::Bool(true),
+        "false" => OscType::Bool(false),
+        _ => {
+            if let Ok(int) = text.parse() {
+                OscType::Int(int)
+            } else if let Ok(float) = text.parse() {
+                OscType::Float(float)
+            } else {
+                OscType::String(text.trim_matches('"').to_string())
+            }
+        }
+    }
+}
+
+pub fn lookup(address: &str) -> Option<&'static StandardAddress> {
+    STANDARD_ADDRESSES
+        .iter()
+        .find(|entry| entry.address == address)
+}
+
+/// Standard addresses starting with what was typed so far, for suggestions.
+pub fn completions(prefix: &str) -> impl Iterator<Item = &'static StandardAddress> + '_ {
+    STANDARD_ADDRESSES
+        .iter()
+        .filter(move |entry| entry.address.starts_with(prefix))
+}
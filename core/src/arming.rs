@@ -0,0 +1,40 @@
+use crate::command::{Command, MANAGER};
+use crate::notification::NotificationAction;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Manager command arming the shocks again.
+pub const ARM: &str = "arm";
+
+/// Manager command disarming the shocks.
+pub const DISARM: &str = "disarm";
+
+/// Whether plugins acting on the body of the user, like sending shocks, are allowed to. The flag
+/// is kept by the manager instead of the plugins, so restarting a plugin never arms it again, and
+/// starts out disarmed.
+#[derive(Debug, Clone, Default)]
+pub struct Arming {
+    armed: Arc<AtomicBool>,
+}
+
+impl Arming {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_armed(&self) -> bool {
+        self.armed.load(Ordering::Relaxed)
+    }
+
+    pub fn set_armed(&self, armed: bool) {
+        self.armed.store(armed, Ordering::Relaxed);
+    }
+}
+
+/// Notification action arming or disarming the shocks when clicked.
+pub fn action(arm: bool) -> NotificationAction {
+    NotificationAction::new(
+        if arm { "Arm shocks" } else { "Disarm shocks" },
+        Command::new(MANAGER, if arm { ARM } else { DISARM }, ""),
+    )
+}
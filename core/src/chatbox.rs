@@ -0,0 +1,189 @@
+use crate::addresses::{CHATBOX_INPUT, CHATBOX_TYPING};
+use crate::osc::{OscSender, OutgoingMessage};
+use anyhow::Result;
+use async_osc::{OscMessage, OscType};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::sync::Notify;
+
+/// Maximum number of characters VRChat displays in the chatbox.
+pub const MAX_LENGTH: usize = 144;
+
+/// Chatbox messages waiting to be sent, shared between all plugins. Every plugin has a single slot
+/// in the queue, so a newer message replaces its pending one instead of queueing up behind it.
+#[derive(Debug, Clone, Default)]
+pub struct ChatboxQueue {
+    messages: Arc<Mutex<VecDeque<OutgoingMessage>>>,
+    notify: Arc<Notify>,
+    lines: Arc<Mutex<Vec<(&'static str, String)>>>,
+    lines_changed: Arc<Notify>,
+    direct_sent: Arc<Mutex<Option<Instant>>>,
+    composed: Arc<AtomicBool>,
+}
+
+impl ChatboxQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues the message, returning the number of messages pending afterwards.
+    pub fn push(&self, message: OutgoingMessage) -> usize {
+        let mut messages = self.messages.lock().unwrap();
+
+        match messages
+            .iter_mut()
+            .find(|pending| pending.source == message.source)
+        {
+            Some(pending) => *pending = message,
+            None => messages.push_back(message),
+        }
+
+        self.notify.notify_one();
+        messages.len()
+    }
+
+    pub fn pop(&self) -> Option<OutgoingMessage> {
+        self.messages.lock().unwrap().pop_front()
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.lock().unwrap().len()
+    }
+
+    /// Whether a message of the source is still waiting to be sent.
+    pub fn is_pending(&self, source: &str) -> bool {
+        self.messages
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|pending| pending.source == source)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Waits until a message was pushed since the last call.
+    pub async fn wait(&self) {
+        self.notify.notified().await;
+    }
+
+    /// Sets the line of a plugin, which the chatbox plugin combines with those of the others into
+    /// a single message. An empty text removes the line.
+    pub fn set_line(&self, source: &'static str, text: &str) {
+        let mut lines = self.lines.lock().unwrap();
+        let position = lines
+            .iter()
+            .position(|(line_source, _)| *line_source == source);
+
+        match (position, text.is_empty()) {
+            (Some(position), true) => {
+                lines.remove(position);
+            }
+            (Some(position), false) => lines[position].1 = text.to_string(),
+            (None, true) => return,
+            (None, false) => lines.push((source, text.to_string())),
+        }
+
+        self.lines_changed.notify_one();
+    }
+
+    /// Lines of all plugins, in the order they were first set.
+    pub fn lines(&self) -> Vec<(&'static str, String)> {
+        self.lines.lock().unwrap().clone()
+    }
+
+    /// Waits until a line changed since the last call.
+    pub async fn wait_lines(&self) {
+        self.lines_changed.notified().await;
+    }
+
+    /// When a plugin last queued a message of its own through `Chatbox::send`.
+    pub fn direct_sent(&self) -> Option<Instant> {
+        *self.direct_sent.lock().unwrap()
+    }
+
+    /// Marks whether the chatbox plugin is running, which is what shows the lines at all.
+    pub fn set_composed(&self, composed: bool) {
+        self.composed.store(composed, Ordering::Relaxed);
+    }
+
+    pub fn is_composed(&self) -> bool {
+        self.composed.load(Ordering::Relaxed)
+    }
+}
+
+/// Handle for writing to the VRChat chatbox.
+#[derive(Debug, Clone)]
+pub struct Chatbox {
+    osc: OscSender,
+    queue: ChatboxQueue,
+}
+
+impl Chatbox {
+    pub fn new(osc: OscSender, queue: ChatboxQueue) -> Self {
+        Self { osc, queue }
+    }
+
+    fn message(&self, text: &str, notify: bool) -> OutgoingMessage {
+        OutgoingMessage::new(
+            self.osc.source(),
+            OscMessage {
+                addr: CHATBOX_INPUT.to_string(),
+                args: vec![
+                    OscType::String(text.chars().take(MAX_LENGTH).collect()),
+                    OscType::Bool(true),
+                    OscType::Bool(notify),
+                ],
+            },
+        )
+    }
+
+    /// Queues the text, truncated to the maximum length VRChat accepts. The manager sends queued
+    /// messages as fast as the rate limit of VRChat allows, and returns the number of messages
+    /// pending afterwards. The chatbox plugin holds back the combined lines for a while after.
+    pub fn send(&self, text: &str, notify: bool) -> usize {
+        *self.queue.direct_sent.lock().unwrap() = Some(Instant::now());
+        self.queue.push(self.message(text, notify))
+    }
+
+    /// Queues the lines combined by the chatbox plugin, which unlike `send` does not hold back
+    /// the next ones.
+    pub fn send_lines(&self, text: &str) -> usize {
+        self.queue.push(self.message(text, false))
+    }
+
+    /// Whether the last message queued through `send` is still waiting to be sent, so sending
+    /// another one now would replace it.
+    pub fn is_pending(&self) -> bool {
+        self.queue.is_pending(self.osc.source())
+    }
+
+    /// Sets the line of the plugin shown by the chatbox plugin, next to the lines of other plugins.
+    /// Unlike `send`, lines of different plugins do not replace each other.
+    pub fn set_line(&self, text: &str) {
+        self.queue.set_line(self.osc.source(), text);
+    }
+
+    /// Whether lines set through `set_line` are shown, which needs the chatbox plugin to run.
+    /// Plugins can fall back to `send` otherwise.
+    pub fn is_composed(&self) -> bool {
+        self.queue.is_composed()
+    }
+
+    /// Number of chatbox messages of all plugins still waiting to be sent.
+    pub fn queue_depth(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub async fn set_typing(&self, typing: bool) -> Result<()> {
+        self.osc
+            .send(OscMessage {
+                addr: CHATBOX_TYPING.to_string(),
+                args: vec![OscType::Bool(typing)],
+            })
+            .await
+    }
+}
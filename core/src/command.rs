@@ -0,0 +1,94 @@
+use log::warn;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+
+/// Number of commands buffered before slow plugins miss some.
+const CAPACITY: usize = 64;
+
+/// Plugin name addressing the manager itself, e.g. for reloading the plugins.
+pub const MANAGER: &str = "manager";
+
+/// Plugin name addressing every plugin at once.
+pub const ALL: &str = "*";
+
+/// Command sent to all plugins once VRChat finished resetting the avatar parameters, so plugins
+/// persisting state know to push it again.
+pub const RESYNC: &str = "resync";
+
+/// Request handed to a plugin from outside the OSC traffic, like a tray entry, the control API or
+/// a notification action.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Command {
+    /// Name of the plugin the command is meant for.
+    pub plugin: &'static str,
+    pub name: &'static str,
+    pub argument: String,
+}
+
+impl Command {
+    pub fn new(plugin: &'static str, name: &'static str, argument: impl Into<String>) -> Self {
+        Self {
+            plugin,
+            name,
+            argument: argument.into(),
+        }
+    }
+}
+
+/// Hands commands to the plugins they are meant for. Commands for plugins which are not running
+/// are dropped.
+#[derive(Debug, Clone)]
+pub struct CommandSender {
+    tx: broadcast::Sender<Command>,
+}
+
+impl Default for CommandSender {
+    fn default() -> Self {
+        Self {
+            tx: broadcast::channel(CAPACITY).0,
+        }
+    }
+}
+
+impl CommandSender {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn send(&self, command: Command) {
+        let _ = self.tx.send(command);
+    }
+
+    /// Receiver of the commands meant for the given plugin.
+    pub fn subscribe(&self, plugin: &'static str) -> Commands {
+        Commands {
+            plugin,
+            rx: self.tx.subscribe(),
+        }
+    }
+}
+
+/// Receives the commands meant for a plugin.
+pub struct Commands {
+    plugin: &'static str,
+    rx: broadcast::Receiver<Command>,
+}
+
+impl Commands {
+    /// Waits for the next command. Never returns once the manager stopped sending commands, so it
+    /// can be used as a branch of `select!` loops.
+    pub async fn recv(&mut self) -> Command {
+        loop {
+            match self.rx.recv().await {
+                Ok(command) if command.plugin == self.plugin || command.plugin == ALL => {
+                    return command
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("{} missed {} commands", self.plugin, skipped);
+                }
+                Err(RecvError::Closed) => std::future::pending().await,
+            }
+        }
+    }
+}
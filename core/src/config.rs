@@ -0,0 +1,178 @@
+use anyhow::Result;
+use serde::de::{DeserializeOwned, DeserializeSeed, IgnoredAny, MapAccess, Visitor};
+use serde::Deserializer;
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+/// The raw configuration section of a plugin, taken from `[plugins.<name>]` in the config file.
+#[derive(Debug, Clone, Default)]
+pub struct PluginConfig {
+    table: toml::Table,
+    /// Whole config file and the dotted path of the section within it, so errors found while
+    /// checking can point to their line.
+    source: Option<(Arc<str>, String)>,
+}
+
+impl PluginConfig {
+    pub fn new(table: toml::Table) -> Self {
+        Self {
+            table,
+            source: None,
+        }
+    }
+
+    pub fn with_source(mut self, source: Arc<str>, section: &str) -> Self {
+        self.source = Some((source, section.to_string()));
+        self
+    }
+
+    pub fn parse<T: DeserializeOwned>(&self) -> Result<T> {
+        Ok(toml::Value::Table(self.table.clone()).try_into()?)
+    }
+
+    /// Parses the section like `parse` does, but only to return the keys which are not part of
+    /// it, e.g. because of a typo. Invalid values fail, pointing to their line where the config
+    /// file is known.
+    pub fn check<T: DeserializeOwned>(&self) -> Result<Vec<String>> {
+        let mut unknown = vec![];
+
+        match &self.source {
+            Some((source, section)) => {
+                let path: Vec<&str> = section.split('.').collect();
+                Section::<T> {
+                    path: &path,
+                    unknown: &mut unknown,
+                    section: PhantomData,
+                }
+                .deserialize(toml::Deserializer::new(source))?;
+            }
+            None => {
+                let _: T =
+                    serde_ignored::deserialize(toml::Value::Table(self.table.clone()), |path| {
+                        unknown.push(path.to_string())
+                    })?;
+            }
+        }
+
+        Ok(unknown)
+    }
+}
+
+/// Implements `Plugin::check_config` within an `impl Plugin` block by calling
+/// `PluginConfig::check` with the type `run` parses the section into.
+#[macro_export]
+macro_rules! check_config {
+    ($config:ty) => {
+        fn check_config(
+            &self,
+            config: &$crate::PluginConfig,
+        ) -> ::anyhow::Result<::std::vec::Vec<::std::string::String>> {
+            config.check::<$config>()
+        }
+    };
+}
+
+/// Deserializes a single section out of the whole config file, skipping everything else.
+struct Section<'a, T> {
+    path: &'a [&'a str],
+    unknown: &'a mut Vec<String>,
+    section: PhantomData<T>,
+}
+
+impl<'de, 'a, T: DeserializeOwned> DeserializeSeed<'de> for Section<'a, T> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        if self.path.is_empty() {
+            let unknown = self.unknown;
+            let _: T =
+                serde_ignored::deserialize(deserializer, |path| unknown.push(path.to_string()))?;
+            return Ok(());
+        }
+
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, 'a, T: DeserializeOwned> Visitor<'de> for Section<'a, T> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "a table containing {}", self.path.join("."))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        let mut seed = Some(self);
+
+        while let Some(key) = map.next_key::<String>()? {
+            match seed.take() {
+                Some(section) if key == section.path[0] => map.next_value_seed(Section::<T> {
+                    path: &section.path[1..],
+                    unknown: section.unknown,
+                    section: PhantomData,
+                })?,
+                other => {
+                    seed = other;
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+
+        // Sections which are missing get their defaults, which cannot be wrong.
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigFieldKind {
+    Bool,
+    Integer,
+    Float,
+    String,
+    /// A string which should not be displayed, like an API key.
+    Secret,
+    /// A string restricted to one of the given values.
+    Choice(&'static [&'static str]),
+    /// An OSC address, for which the standard addresses of VRChat are suggested.
+    Address,
+}
+
+/// Describes a single option within the configuration section of a plugin, used to generate
+/// editing forms for it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConfigField {
+    pub key: &'static str,
+    pub label: &'static str,
+    pub kind: ConfigFieldKind,
+    /// Whether the option can be adjusted from the avatar menu.
+    pub menu: bool,
+    /// Lowest and highest value numbers set from the avatar menu are clamped to.
+    pub range: Option<(f64, f64)>,
+}
+
+impl ConfigField {
+    pub const fn new(key: &'static str, label: &'static str, kind: ConfigFieldKind) -> Self {
+        Self {
+            key,
+            label,
+            kind,
+            menu: false,
+            range: None,
+        }
+    }
+
+    /// Binds the option to an avatar parameter named `OSCM/<plugin>/<key>`, so it can be changed
+    /// from the radial menu in game. Only bools, numbers and choices can be bound, where choices
+    /// are selected by their index.
+    pub const fn menu(mut self) -> Self {
+        self.menu = true;
+        self
+    }
+
+    /// Limits the values the avatar menu can set, e.g. to what a device accepts.
+    pub const fn range(mut self, min: f64, max: f64) -> Self {
+        self.range = Some((min, max));
+        self
+    }
+}
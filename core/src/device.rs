@@ -0,0 +1,76 @@
+use anyhow::Result;
+use log::{info, warn};
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::sleep;
+
+/// Longest time between two attempts to reconnect to a device.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Reports whether the device driven by a plugin is available, so the host can show a degraded
+/// state while the device is gone instead of the plugin failing, e.g. while a dongle is replugged.
+#[derive(Clone)]
+pub struct DeviceStatus {
+    name: &'static str,
+    connected: Arc<AtomicBool>,
+    report: Arc<dyn Fn(bool) + Send + Sync>,
+}
+
+impl DeviceStatus {
+    pub fn new(name: &'static str, report: impl Fn(bool) + Send + Sync + 'static) -> Self {
+        Self {
+            name,
+            connected: Arc::new(AtomicBool::new(true)),
+            report: Arc::new(report),
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
+    /// Reports a change of the device availability. Repeating the current state has no effect.
+    pub fn set_connected(&self, connected: bool) {
+        if self.connected.swap(connected, Ordering::Relaxed) != connected {
+            (self.report)(connected);
+        }
+    }
+
+    /// Keeps trying to connect until it succeeds, with a growing delay between the attempts. The
+    /// device counts as disconnected meanwhile.
+    pub async fn connect<T, F, Fut>(&self, mut connect: F) -> T
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut delay = Duration::from_secs(1);
+
+        loop {
+            match connect().await {
+                Ok(device) => {
+                    if !self.is_connected() {
+                        info!("Device of {} is back", self.name);
+                    }
+
+                    self.set_connected(true);
+                    return device;
+                }
+                Err(error) => {
+                    if self.is_connected() {
+                        warn!(
+                            "Device of {} is not available, waiting for it: {:#}",
+                            self.name, error
+                        );
+                    }
+
+                    self.set_connected(false);
+                }
+            }
+
+            sleep(delay).await;
+            delay = (delay * 2).min(MAX_RETRY_DELAY);
+        }
+    }
+}
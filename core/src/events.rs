@@ -0,0 +1,46 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// Something notable which happened, like a plugin starting or a shock being sent, as written to
+/// the event log.
+#[derive(Debug, Clone, Serialize)]
+pub struct Event {
+    pub source: &'static str,
+    pub kind: &'static str,
+    #[serde(flatten)]
+    pub fields: BTreeMap<&'static str, String>,
+}
+
+/// Records events for the event log, a machine-readable trail of what the manager and its plugins
+/// did, meant for scripts and log shippers. Events are dropped while the event log is disabled.
+#[derive(Clone)]
+pub struct Events {
+    source: &'static str,
+    record: Arc<dyn Fn(Event) + Send + Sync>,
+}
+
+impl Events {
+    pub fn new(record: impl Fn(Event) + Send + Sync + 'static) -> Self {
+        Self {
+            source: "manager",
+            record: Arc::new(record),
+        }
+    }
+
+    /// Handle recording into the same log, with the given plugin as source.
+    pub fn with_source(&self, source: &'static str) -> Self {
+        Self {
+            source,
+            record: self.record.clone(),
+        }
+    }
+
+    pub fn record(&self, kind: &'static str, fields: Vec<(&'static str, String)>) {
+        (self.record)(Event {
+            source: self.source,
+            kind,
+            fields: fields.into_iter().collect(),
+        });
+    }
+}
@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+/// Longest time the watched ones are expected to go without a beat while responsive.
+pub const INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug)]
+struct Entry {
+    last: Instant,
+    recover: Arc<Notify>,
+}
+
+impl Entry {
+    fn new() -> Self {
+        Self {
+            last: Instant::now(),
+            recover: Arc::new(Notify::new()),
+        }
+    }
+}
+
+/// Liveness of the subsystems and plugins, watched by the host to recover the ones which hang.
+/// Only those which beat at least once are watched, so plugins which do nothing but wait for
+/// messages do not have to take part.
+#[derive(Debug, Clone, Default)]
+pub struct Heartbeats {
+    entries: Arc<Mutex<HashMap<&'static str, Entry>>>,
+}
+
+impl Heartbeats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn heartbeat(&self, name: &'static str) -> Heartbeat {
+        Heartbeat {
+            name,
+            heartbeats: self.clone(),
+        }
+    }
+
+    /// Names of the watched ones which did not beat within the timeout.
+    pub fn stalled(&self, timeout: Duration) -> Vec<&'static str> {
+        self.entries
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, entry)| entry.last.elapsed() > timeout)
+            .map(|(name, _)| *name)
+            .collect()
+    }
+
+    /// Asks a stalled one to start over and gives it another timeout to do so.
+    pub fn recover(&self, name: &str) {
+        if let Some(entry) = self.entries.lock().unwrap().get_mut(name) {
+            entry.last = Instant::now();
+            entry.recover.notify_one();
+        }
+    }
+
+    /// Stops watching, e.g. once a plugin stopped.
+    pub fn remove(&self, name: &str) {
+        self.entries.lock().unwrap().remove(name);
+    }
+
+    fn recover_signal(&self, name: &'static str) -> Arc<Notify> {
+        self.entries
+            .lock()
+            .unwrap()
+            .entry(name)
+            .or_insert_with(Entry::new)
+            .recover
+            .clone()
+    }
+}
+
+/// Handle for a single subsystem or plugin to report that it is still responsive.
+#[derive(Debug, Clone)]
+pub struct Heartbeat {
+    name: &'static str,
+    heartbeats: Heartbeats,
+}
+
+impl Heartbeat {
+    pub fn beat(&self) {
+        self.heartbeats
+            .entries
+            .lock()
+            .unwrap()
+            .entry(self.name)
+            .or_insert_with(Entry::new)
+            .last = Instant::now();
+    }
+
+    /// Resolves once the host found this one stalled, which is the sign to start over, e.g. on a
+    /// new socket. Waiting for it starts the watch.
+    pub async fn stalled(&self) {
+        self.heartbeats.recover_signal(self.name).notified().await;
+    }
+}
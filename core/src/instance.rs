@@ -0,0 +1,107 @@
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+
+/// Who can join an instance, as encoded in the tags of its location.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InstanceAccess {
+    Public,
+    FriendsPlus,
+    Friends,
+    InvitePlus,
+    Invite,
+    GroupPublic,
+    GroupPlus,
+    Group,
+}
+
+impl InstanceAccess {
+    /// Whether joining requires an invite from someone inside.
+    pub fn is_private(self) -> bool {
+        matches!(self, Self::Invite | Self::InvitePlus)
+    }
+}
+
+/// The instance the user is in, as reported by the VRChat log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instance {
+    pub world_id: String,
+    /// Instance part of the location, including its tags.
+    pub instance_id: String,
+    pub world_name: String,
+    pub access: InstanceAccess,
+    pub region: Option<String>,
+}
+
+impl Instance {
+    /// Parses a location like `wrld_…:12345~hidden(usr_…)~region(eu)`.
+    pub fn parse(location: &str, world_name: &str) -> Option<Self> {
+        let (world_id, instance_id) = location.split_once(':')?;
+
+        if !world_id.starts_with("wrld_") {
+            return None;
+        }
+
+        let mut access = None;
+        let mut unknown_tags = false;
+        let mut can_request_invite = false;
+        let mut region = None;
+
+        for tag in instance_id.split('~').skip(1) {
+            let (name, value) = match tag.split_once('(') {
+                Some((name, value)) => (name, value.trim_end_matches(')')),
+                None => (tag, ""),
+            };
+
+            match name {
+                "hidden" => access = Some(InstanceAccess::FriendsPlus),
+                "friends" => access = Some(InstanceAccess::Friends),
+                "private" => access = Some(InstanceAccess::Invite),
+                "canRequestInvite" => can_request_invite = true,
+                "group" => access = Some(InstanceAccess::Group),
+                "groupAccessType" => {
+                    access = Some(match value {
+                        "public" => InstanceAccess::GroupPublic,
+                        "plus" => InstanceAccess::GroupPlus,
+                        _ => InstanceAccess::Group,
+                    })
+                }
+                "region" => region = Some(value.to_string()),
+                "nonce" | "strict" => {}
+                _ => unknown_tags = true,
+            }
+        }
+
+        if access == Some(InstanceAccess::Invite) && can_request_invite {
+            access = Some(InstanceAccess::InvitePlus);
+        }
+
+        // Public instances carry no access tag, while a tag VRChat added since may restrict who
+        // can join, so those count as the most private ones until they are known.
+        let access = access.unwrap_or(if unknown_tags {
+            InstanceAccess::Invite
+        } else {
+            InstanceAccess::Public
+        });
+
+        Some(Self {
+            world_id: world_id.to_string(),
+            instance_id: instance_id.to_string(),
+            world_name: world_name.to_string(),
+            access,
+            region,
+        })
+    }
+
+    /// Link opening the instance on the VRChat website, from where it can be joined.
+    pub fn link(&self) -> String {
+        format!(
+            "https://vrchat.com/home/launch?worldId={}&instanceId={}",
+            self.world_id, self.instance_id
+        )
+    }
+}
+
+/// Follows the instance the user is in, which is `None` outside of any instance or while the
+/// VRChat log is not available.
+pub type InstanceWatcher = watch::Receiver<Option<Instance>>;
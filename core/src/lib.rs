@@ -0,0 +1,43 @@
+//! Core types for building plugins for the VRChat OSC manager.
+//!
+//! Plugins implement the [`Plugin`] trait and receive a [`PluginContext`] when started, which
+//! gives them access to the OSC traffic, their configuration section, the chatbox and
+//! notification APIs, commands from the tray and the control API, the availability of the device
+//! they drive, the instance the user is in and what happens in it, metrics for the session
+//! summary, the event log and a heartbeat to have them restarted when they hang.
+
+pub mod activity;
+pub mod addresses;
+pub mod arming;
+pub mod chatbox;
+pub mod command;
+pub mod config;
+pub mod device;
+pub mod events;
+pub mod heartbeat;
+pub mod instance;
+pub mod metrics;
+pub mod notification;
+pub mod osc;
+pub mod plugin;
+pub mod readiness;
+pub mod trigger;
+pub mod vrchat_log;
+
+pub use activity::Activity;
+pub use addresses::{StandardAddress, STANDARD_ADDRESSES};
+pub use arming::Arming;
+pub use chatbox::{Chatbox, ChatboxQueue};
+pub use command::{Command, CommandSender, Commands, ALL, MANAGER, RESYNC};
+pub use config::{ConfigField, ConfigFieldKind, PluginConfig};
+pub use device::DeviceStatus;
+pub use events::{Event, Events};
+pub use heartbeat::{Heartbeat, Heartbeats};
+pub use instance::{Instance, InstanceAccess, InstanceWatcher};
+pub use metrics::{MetricSummary, Metrics};
+pub use notification::{Notification, NotificationAction, Notifier};
+pub use osc::{OscReceiver, OscSender, OutgoingMessage};
+pub use plugin::{ParameterAccess, ParameterUsage, Plugin, PluginContext, PluginFuture};
+pub use readiness::Readiness;
+pub use trigger::{Trigger, TriggerEvent};
+pub use vrchat_log::{VrchatLogEvent, VrchatLogReceiver};
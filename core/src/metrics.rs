@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Aggregate of the values recorded for a metric.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MetricSummary {
+    pub count: u64,
+    pub sum: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl MetricSummary {
+    fn new(value: f64) -> Self {
+        Self {
+            count: 1,
+            sum: value,
+            min: value,
+            max: value,
+        }
+    }
+
+    fn record(&mut self, value: f64) {
+        self.count += 1;
+        self.sum += value;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    pub fn average(&self) -> f64 {
+        self.sum / self.count as f64
+    }
+}
+
+/// Values plugins record while VRChat is running, like the heart rate, and things they count, like
+/// interactions, which end up in the summary of the session.
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    summaries: Arc<Mutex<HashMap<String, MetricSummary>>>,
+    counts: Arc<Mutex<HashMap<String, u64>>>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a value under a name as shown to the user, e.g. `Heart rate`.
+    pub fn record(&self, name: &str, value: f64) {
+        let mut summaries = self.summaries.lock().unwrap();
+
+        match summaries.get_mut(name) {
+            Some(summary) => summary.record(value),
+            None => {
+                summaries.insert(name.to_string(), MetricSummary::new(value));
+            }
+        }
+    }
+
+    /// Counts one occurrence under a name as shown to the user, e.g. `Headpats`.
+    pub fn count(&self, name: &str) {
+        *self
+            .counts
+            .lock()
+            .unwrap()
+            .entry(name.to_string())
+            .or_default() += 1;
+    }
+
+    /// Returns the summaries recorded so far, sorted by name.
+    pub fn snapshot(&self) -> Vec<(String, MetricSummary)> {
+        let mut summaries: Vec<_> = self
+            .summaries
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, summary)| (name.clone(), *summary))
+            .collect();
+        summaries.sort_by(|(a, _), (b, _)| a.cmp(b));
+        summaries
+    }
+
+    /// Returns the summaries recorded so far, sorted by name, and starts over.
+    pub fn take(&self) -> Vec<(String, MetricSummary)> {
+        let mut summaries: Vec<_> = self.summaries.lock().unwrap().drain().collect();
+        summaries.sort_by(|(a, _), (b, _)| a.cmp(b));
+        summaries
+    }
+
+    /// Returns the counts so far, sorted by name, and starts over.
+    pub fn take_counts(&self) -> Vec<(String, u64)> {
+        let mut counts: Vec<_> = self.counts.lock().unwrap().drain().collect();
+        counts.sort_by(|(a, _), (b, _)| a.cmp(b));
+        counts
+    }
+}
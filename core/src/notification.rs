@@ -0,0 +1,59 @@
+use crate::command::Command;
+use anyhow::Result;
+use tokio::sync::mpsc;
+
+/// Button shown on a notification where supported. When clicked, the command is handed to the
+/// plugin it is meant for, or handled by the manager itself.
+#[derive(Debug, Clone)]
+pub struct NotificationAction {
+    pub label: String,
+    pub command: Command,
+}
+
+impl NotificationAction {
+    pub fn new(label: impl Into<String>, command: Command) -> Self {
+        Self {
+            label: label.into(),
+            command,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub title: String,
+    pub body: String,
+    pub actions: Vec<NotificationAction>,
+}
+
+/// Handle for showing notifications to the user outside of VRChat.
+#[derive(Debug, Clone)]
+pub struct Notifier {
+    tx: mpsc::Sender<Notification>,
+}
+
+impl Notifier {
+    pub fn new(tx: mpsc::Sender<Notification>) -> Self {
+        Self { tx }
+    }
+
+    pub async fn notify(&self, title: impl Into<String>, body: impl Into<String>) -> Result<()> {
+        self.notify_with_actions(title, body, vec![]).await
+    }
+
+    pub async fn notify_with_actions(
+        &self,
+        title: impl Into<String>,
+        body: impl Into<String>,
+        actions: Vec<NotificationAction>,
+    ) -> Result<()> {
+        self.tx
+            .send(Notification {
+                title: title.into(),
+                body: body.into(),
+                actions,
+            })
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,105 @@
+use crate::addresses::parameter_address;
+use anyhow::Result;
+use async_osc::{OscMessage, OscType};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
+
+/// Numeric value of an argument, where bools count as zero and one.
+pub fn value(arg: &OscType) -> Option<f64> {
+    match arg {
+        OscType::Bool(value) => Some(if *value { 1. } else { 0. }),
+        OscType::Int(value) => Some(*value as f64),
+        OscType::Long(value) => Some(*value as f64),
+        OscType::Float(value) => Some(*value as f64),
+        OscType::Double(value) => Some(*value),
+        _ => None,
+    }
+}
+
+/// Messages received from VRChat, shared between all plugins. They are reference counted, so the
+/// fan-out to every plugin does not copy the address and arguments for each of them.
+pub type OscReceiver = broadcast::Receiver<Arc<OscMessage>>;
+
+/// A message queued for sending, tagged with the name of the plugin it originates from.
+#[derive(Debug, Clone)]
+pub struct OutgoingMessage {
+    pub source: &'static str,
+    pub message: OscMessage,
+    /// Makes the message a time-limited override, after which the previous value of the address
+    /// is restored.
+    pub restore_after: Option<Duration>,
+}
+
+impl OutgoingMessage {
+    pub fn new(source: &'static str, message: OscMessage) -> Self {
+        Self {
+            source,
+            message,
+            restore_after: None,
+        }
+    }
+}
+
+/// Handle for queueing messages to be sent to VRChat.
+#[derive(Debug, Clone)]
+pub struct OscSender {
+    tx: mpsc::Sender<OutgoingMessage>,
+    source: &'static str,
+}
+
+impl OscSender {
+    pub fn new(tx: mpsc::Sender<OutgoingMessage>, source: &'static str) -> Self {
+        Self { tx, source }
+    }
+
+    /// Name of the plugin the messages originate from.
+    pub fn source(&self) -> &'static str {
+        self.source
+    }
+
+    pub async fn send(&self, message: OscMessage) -> Result<()> {
+        self.tx
+            .send(OutgoingMessage::new(self.source, message))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn send_parameter(&self, parameter: &str, value: OscType) -> Result<()> {
+        self.send(OscMessage {
+            addr: parameter_address(parameter),
+            args: vec![value],
+        })
+        .await
+    }
+
+    /// Sets the address to the value of the message for the given time, after which the manager
+    /// restores the value it had before. A newer override of the same address replaces the running
+    /// one, while regular writes meanwhile are held back and become the value to restore.
+    pub async fn send_for(&self, message: OscMessage, duration: Duration) -> Result<()> {
+        self.tx
+            .send(OutgoingMessage {
+                source: self.source,
+                message,
+                restore_after: Some(duration),
+            })
+            .await?;
+        Ok(())
+    }
+
+    pub async fn send_parameter_for(
+        &self,
+        parameter: &str,
+        value: OscType,
+        duration: Duration,
+    ) -> Result<()> {
+        self.send_for(
+            OscMessage {
+                addr: parameter_address(parameter),
+                args: vec![value],
+            },
+            duration,
+        )
+        .await
+    }
+}
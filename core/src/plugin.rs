@@ -0,0 +1,138 @@
+use crate::activity::Activity;
+use crate::addresses;
+use crate::arming::Arming;
+use crate::chatbox::{Chatbox, ChatboxQueue};
+use crate::command::Commands;
+use crate::config::{ConfigField, PluginConfig};
+use crate::device::DeviceStatus;
+use crate::events::Events;
+use crate::heartbeat::Heartbeat;
+use crate::instance::InstanceWatcher;
+use crate::metrics::Metrics;
+use crate::notification::Notifier;
+use crate::osc::{OscReceiver, OscSender};
+use crate::readiness::Readiness;
+use crate::vrchat_log::VrchatLogReceiver;
+use anyhow::Result;
+use async_osc::OscMessage;
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use tokio_graceful_shutdown::SubsystemHandle;
+
+pub type PluginFuture = Pin<Box<dyn Future<Output = Result<()>> + Send>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParameterAccess {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+/// An avatar parameter used by a plugin, where the address may contain `*` wildcards.
+#[derive(Debug, Clone)]
+pub struct ParameterUsage {
+    pub address: String,
+    pub access: ParameterAccess,
+}
+
+impl ParameterUsage {
+    /// Turns a bare parameter name into its address, while full addresses are kept as they are.
+    pub fn new(name: &str, access: ParameterAccess) -> Self {
+        Self {
+            address: addresses::parameter_address(name),
+            access,
+        }
+    }
+
+    pub fn read(name: &str) -> Self {
+        Self::new(name, ParameterAccess::Read)
+    }
+
+    pub fn write(name: &str) -> Self {
+        Self::new(name, ParameterAccess::Write)
+    }
+}
+
+/// Everything a plugin gets handed when it is started.
+pub struct PluginContext {
+    pub osc: OscSender,
+    pub receiver: OscReceiver,
+    pub config: PluginConfig,
+    pub data_dir: PathBuf,
+    pub notifier: Notifier,
+    pub activity: Activity,
+    pub chatbox_queue: ChatboxQueue,
+    pub device: DeviceStatus,
+    pub instance: InstanceWatcher,
+    pub vrchat_log: VrchatLogReceiver,
+    pub heartbeat: Heartbeat,
+    pub readiness: Readiness,
+    pub metrics: Metrics,
+    pub events: Events,
+    pub commands: Commands,
+    pub arming: Arming,
+}
+
+impl PluginContext {
+    pub fn chatbox(&self) -> Chatbox {
+        Chatbox::new(self.osc.clone(), self.chatbox_queue.clone())
+    }
+}
+
+pub trait Plugin: Send {
+    /// Name of the plugin, also used as key of its configuration section.
+    fn name(&self) -> &'static str;
+
+    /// Dotted path of the configuration section of the plugin within the config file.
+    fn config_section(&self) -> String {
+        format!("plugins.{}", self.name())
+    }
+
+    /// Options within the configuration section which can be edited by the user.
+    fn config_fields(&self) -> Vec<ConfigField> {
+        vec![]
+    }
+
+    /// Avatar parameters the plugin reads or writes with the given configuration, used for
+    /// documenting setups.
+    fn parameters(&self, _config: &PluginConfig) -> Vec<ParameterUsage> {
+        vec![]
+    }
+
+    /// Whether a failure of the plugin shuts down the application, instead of only being reported.
+    /// Users can additionally mark plugins as required through the config file.
+    fn required(&self) -> bool {
+        false
+    }
+
+    /// Checks the configuration section whenever the config file is loaded, returning the keys it
+    /// does not know, so typos do not go unnoticed. Plugins with a section implement it through
+    /// `PluginConfig::check` with the type `run` parses the section into.
+    fn check_config(&self, _config: &PluginConfig) -> Result<Vec<String>> {
+        Ok(vec![])
+    }
+
+    /// Whether the plugin initializes within `run` and reports through the `Readiness` of its
+    /// context once it is done, instead of being ready right after `setup`.
+    fn lazy(&self) -> bool {
+        false
+    }
+
+    /// Checks the configuration and prepares what the plugin needs, called right before `run`. An
+    /// error keeps the plugin from starting and is reported like a failure of it. It runs on a
+    /// blocking thread in the background, so slow initialization only delays this plugin.
+    fn setup(&mut self, _config: &PluginConfig) -> Result<()> {
+        Ok(())
+    }
+
+    /// Messages sent once the plugin stopped, e.g. to bring parameters it drives back to rest. They
+    /// are taken from the plugin before `run` consumes it, so this is no teardown point, which is
+    /// the end of `run` instead.
+    fn reset_messages(&self, _config: &PluginConfig) -> Vec<OscMessage> {
+        vec![]
+    }
+
+    /// Runs the plugin until it finishes or a shutdown is requested through the subsystem handle.
+    fn run(self: Box<Self>, context: PluginContext, subsys: SubsystemHandle) -> PluginFuture;
+}
@@ -0,0 +1,31 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Reports that a plugin finished initializing, so the host can show which plugins are still
+/// starting. Only plugins returning `true` from `Plugin::lazy` report it themselves, e.g. once
+/// their BLE adapter was found, all others are ready as soon as their setup is done.
+#[derive(Clone)]
+pub struct Readiness {
+    ready: Arc<AtomicBool>,
+    report: Arc<dyn Fn() + Send + Sync>,
+}
+
+impl Readiness {
+    pub fn new(report: impl Fn() + Send + Sync + 'static) -> Self {
+        Self {
+            ready: Arc::new(AtomicBool::new(false)),
+            report: Arc::new(report),
+        }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::Relaxed)
+    }
+
+    /// Reports the plugin as ready. Repeating it has no effect.
+    pub fn set_ready(&self) {
+        if !self.ready.swap(true, Ordering::Relaxed) {
+            (self.report)();
+        }
+    }
+}
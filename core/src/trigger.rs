@@ -0,0 +1,146 @@
+use crate::osc;
+use async_osc::OscType;
+use std::time::{Duration, Instant};
+
+/// Something that happened to a [`Trigger`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriggerEvent {
+    /// The value reached the threshold, outside of the cooldown.
+    Pressed,
+    /// The value stayed at or above the threshold for the hold duration.
+    Held,
+    /// The value dropped below the threshold again, after being pressed for the given duration.
+    Released(Duration),
+}
+
+/// Turns the values of a bool or float parameter into presses, taking care of edge detection,
+/// debouncing, holds and cooldowns.
+///
+/// Values at or above the threshold count as pressed. Presses within the debounce duration of the
+/// previous change are ignored, and a press within the cooldown of the last reported press is
+/// swallowed together with its release. Holds are not signalled by a message, so plugins using
+/// them have to call [`Trigger::poll`] once the [`Trigger::deadline`] passed.
+#[derive(Debug, Clone)]
+pub struct Trigger {
+    threshold: f64,
+    debounce: Duration,
+    hold: Option<Duration>,
+    cooldown: Duration,
+    pressed_at: Option<Instant>,
+    changed_at: Option<Instant>,
+    reported_at: Option<Instant>,
+    suppressed: bool,
+    held: bool,
+}
+
+impl Default for Trigger {
+    fn default() -> Self {
+        Self::new(0.5)
+    }
+}
+
+impl Trigger {
+    pub fn new(threshold: f64) -> Self {
+        Self {
+            threshold,
+            debounce: Duration::ZERO,
+            hold: None,
+            cooldown: Duration::ZERO,
+            pressed_at: None,
+            changed_at: None,
+            reported_at: None,
+            suppressed: false,
+            held: false,
+        }
+    }
+
+    pub fn debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    pub fn hold(mut self, hold: Duration) -> Self {
+        self.hold = Some(hold);
+        self
+    }
+
+    pub fn cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+
+    pub fn is_pressed(&self) -> bool {
+        self.pressed_at.is_some()
+    }
+
+    /// Feeds the argument of a received message, ignoring arguments without a numeric value.
+    pub fn update(&mut self, arg: &OscType) -> Option<TriggerEvent> {
+        self.update_at(osc::value(arg)?, Instant::now())
+    }
+
+    pub fn update_at(&mut self, value: f64, now: Instant) -> Option<TriggerEvent> {
+        let pressed = value >= self.threshold;
+
+        if pressed == self.is_pressed() {
+            return None;
+        }
+
+        // Only presses are debounced, as a dropped release would keep the trigger pressed until
+        // the value happens to change again.
+        if pressed
+            && self.changed_at.map_or(false, |changed_at| {
+                now.duration_since(changed_at) < self.debounce
+            })
+        {
+            return None;
+        }
+
+        self.changed_at = Some(now);
+
+        if pressed {
+            self.pressed_at = Some(now);
+            self.held = false;
+            self.suppressed = self.reported_at.map_or(false, |reported_at| {
+                now.duration_since(reported_at) < self.cooldown
+            });
+
+            if self.suppressed {
+                return None;
+            }
+
+            self.reported_at = Some(now);
+            return Some(TriggerEvent::Pressed);
+        }
+
+        let pressed_at = self.pressed_at.take()?;
+
+        if std::mem::take(&mut self.suppressed) {
+            return None;
+        }
+
+        Some(TriggerEvent::Released(now.duration_since(pressed_at)))
+    }
+
+    /// When the current press turns into a hold, if it can still become one.
+    pub fn deadline(&self) -> Option<Instant> {
+        if self.held || self.suppressed {
+            return None;
+        }
+
+        Some(self.pressed_at? + self.hold?)
+    }
+
+    /// Reports a hold once the deadline passed.
+    pub fn poll(&mut self) -> Option<TriggerEvent> {
+        self.poll_at(Instant::now())
+    }
+
+    pub fn poll_at(&mut self, now: Instant) -> Option<TriggerEvent> {
+        if self.deadline()? > now {
+            return None;
+        }
+
+        self.held = true;
+        Some(TriggerEvent::Held)
+    }
+}
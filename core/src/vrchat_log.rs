@@ -0,0 +1,23 @@
+use crate::instance::Instance;
+use tokio::sync::broadcast;
+
+/// Something VRChat wrote to its log, as it happens.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VrchatLogEvent {
+    /// The user finished joining an instance.
+    WorldJoined(Instance),
+    /// The user left the instance they were in, e.g. to join another one.
+    WorldLeft,
+    /// A player switched avatars, including the user themselves.
+    AvatarChanged {
+        player: String,
+        avatar: String,
+    },
+    /// A player joined the instance, which is also reported for those already in it when joining.
+    PlayerJoined(String),
+    PlayerLeft(String),
+}
+
+/// Receives the events of the VRChat log since the plugin started. Events from before are not
+/// replayed, the instance joined before is found in the `InstanceWatcher` instead.
+pub type VrchatLogReceiver = broadcast::Receiver<VrchatLogEvent>;
@@ -0,0 +1,99 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+/// Time spent running and messages handled by a single plugin since the application started.
+#[derive(Debug, Default)]
+pub struct PluginUsage {
+    busy_nanos: AtomicU64,
+    sent: AtomicU64,
+    received: AtomicU64,
+}
+
+impl PluginUsage {
+    pub fn record_received(&self) {
+        self.received.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Snapshot of the usage of a plugin, as reported through the control API.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginUsageStatus {
+    pub name: String,
+    pub busy_ms: u64,
+    pub sent: u64,
+    pub received: u64,
+}
+
+/// Usage per plugin, kept across plugin restarts.
+#[derive(Debug, Default)]
+pub struct Accounting {
+    plugins: Mutex<HashMap<&'static str, Arc<PluginUsage>>>,
+}
+
+impl Accounting {
+    pub fn usage(&self, name: &'static str) -> Arc<PluginUsage> {
+        self.plugins
+            .lock()
+            .unwrap()
+            .entry(name)
+            .or_default()
+            .clone()
+    }
+
+    /// Counts an outgoing message, unless its source is no plugin.
+    pub fn record_sent(&self, source: &str) {
+        if let Some(usage) = self.plugins.lock().unwrap().get(source) {
+            usage.sent.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn snapshot(&self) -> Vec<PluginUsageStatus> {
+        let mut statuses: Vec<_> = self
+            .plugins
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, usage)| PluginUsageStatus {
+                name: name.to_string(),
+                busy_ms: usage.busy_nanos.load(Ordering::Relaxed) / 1_000_000,
+                sent: usage.sent.load(Ordering::Relaxed),
+                received: usage.received.load(Ordering::Relaxed),
+            })
+            .collect();
+
+        statuses.sort_by(|a, b| a.name.cmp(&b.name));
+        statuses
+    }
+}
+
+/// Wraps the future of a plugin, adding the time spent polling it to its usage. Work the plugin
+/// hands off to tasks of its own is not covered.
+pub struct Measured<F> {
+    future: F,
+    usage: Arc<PluginUsage>,
+}
+
+impl<F> Measured<F> {
+    pub fn new(future: F, usage: Arc<PluginUsage>) -> Self {
+        Self { future, usage }
+    }
+}
+
+impl<F: Future + Unpin> Future for Measured<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let started = Instant::now();
+        let result = Pin::new(&mut self.future).poll(cx);
+        self.usage
+            .busy_nanos
+            .fetch_add(started.elapsed().as_nanos() as u64, Ordering::Relaxed);
+        result
+    }
+}
@@ -0,0 +1,166 @@
+use anyhow::{Context, Result};
+use directories::BaseDirs;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum ParameterType {
+    Bool,
+    Int,
+    Float,
+}
+
+impl ParameterType {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Bool => "bool",
+            Self::Int => "int",
+            Self::Float => "float",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Endpoint {
+    pub address: String,
+    #[serde(rename = "type")]
+    pub kind: ParameterType,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Parameter {
+    pub name: String,
+    pub input: Option<Endpoint>,
+    pub output: Option<Endpoint>,
+}
+
+impl Parameter {
+    pub fn kind(&self) -> Option<ParameterType> {
+        self.input
+            .as_ref()
+            .or(self.output.as_ref())
+            .map(|endpoint| endpoint.kind)
+    }
+}
+
+/// OSC config VRChat generates for every avatar used while OSC is enabled.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AvatarConfig {
+    pub id: String,
+    pub name: String,
+    pub parameters: Vec<Parameter>,
+}
+
+/// Steam app ID of VRChat.
+#[cfg(not(target_os = "windows"))]
+const VRCHAT_APP_ID: &str = "438100";
+
+/// Finds the path of the Steam library VRChat is installed in, listed in the library folders
+/// file as a `path` entry followed by the `apps` installed there.
+#[cfg(not(target_os = "windows"))]
+fn find_library(library_folders: &str) -> Option<PathBuf> {
+    let mut library = None;
+
+    for line in library_folders.lines() {
+        let mut values = line.split('"').skip(1).step_by(2);
+
+        match (values.next(), values.next()) {
+            (Some("path"), Some(path)) => library = Some(PathBuf::from(path)),
+            (Some(VRCHAT_APP_ID), Some(_)) => return library,
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Wine prefix Proton runs VRChat in, which lives within the Steam library VRChat is installed in.
+#[cfg(not(target_os = "windows"))]
+pub fn proton_prefix(base_dirs: &BaseDirs) -> PathBuf {
+    let steam = base_dirs.home_dir().join(".steam/steam");
+    let library = std::fs::read_to_string(steam.join("steamapps/libraryfolders.vdf"))
+        .ok()
+        .and_then(|library_folders| find_library(&library_folders))
+        .unwrap_or(steam);
+
+    library
+        .join("steamapps/compatdata")
+        .join(VRCHAT_APP_ID)
+        .join("pfx")
+}
+
+/// Directory VRChat keeps its logs and OSC configs in.
+pub fn vrchat_dir(base_dirs: &BaseDirs) -> PathBuf {
+    #[cfg(target_os = "windows")]
+    let local_low = base_dirs.home_dir().join("AppData/LocalLow");
+
+    // VRChat is run through Proton on Linux, so its files live within the Wine prefix.
+    #[cfg(not(target_os = "windows"))]
+    let local_low = proton_prefix(base_dirs).join("drive_c/users/steamuser/AppData/LocalLow");
+
+    local_low.join("VRChat/VRChat")
+}
+
+pub fn vrchat_osc_dir(base_dirs: &BaseDirs) -> PathBuf {
+    vrchat_dir(base_dirs).join("OSC")
+}
+
+/// Lists the avatar configs of all users who have logged in on this machine.
+pub fn config_paths(osc_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths = vec![];
+
+    for user in std::fs::read_dir(osc_dir)? {
+        let avatars = user?.path().join("Avatars");
+
+        if let Ok(entries) = std::fs::read_dir(avatars) {
+            paths.extend(
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| entry.path())
+                    .filter(|path| path.extension().map_or(false, |ext| ext == "json")),
+            );
+        }
+    }
+
+    Ok(paths)
+}
+
+/// Finds the config of an avatar given either its path or ID. Without either, the most recently
+/// written config is taken, which belongs to the avatar used last.
+fn find(avatar: Option<&str>) -> Result<PathBuf> {
+    if let Some(path) = avatar.map(Path::new).filter(|path| path.is_file()) {
+        return Ok(path.to_path_buf());
+    }
+
+    let base_dirs = BaseDirs::new().context("Base directories not available")?;
+    let osc_dir = vrchat_osc_dir(&base_dirs);
+    let paths =
+        config_paths(&osc_dir).with_context(|| format!("Failed to read {}", osc_dir.display()))?;
+
+    match avatar {
+        Some(id) => paths
+            .into_iter()
+            .find(|path| path.file_stem().map_or(false, |stem| stem == id))
+            .with_context(|| format!("No config found for avatar {}", id)),
+        None => paths
+            .into_iter()
+            .max_by_key(|path| {
+                std::fs::metadata(path)
+                    .and_then(|metadata| metadata.modified())
+                    .unwrap_or(SystemTime::UNIX_EPOCH)
+            })
+            .with_context(|| format!("No avatar configs found in {}", osc_dir.display())),
+    }
+}
+
+pub async fn load(avatar: Option<&str>) -> Result<AvatarConfig> {
+    let path = find(avatar)?;
+    let contents = tokio::fs::read_to_string(&path)
+        .await
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+
+    // VRChat writes the file with a byte order mark.
+    serde_json::from_str(contents.trim_start_matches('\u{feff}'))
+        .with_context(|| format!("Failed to parse {}", path.display()))
+}
@@ -0,0 +1,333 @@
+use crate::accounting::PluginUsage;
+use crate::state::SharedState;
+use crate::StatusEvent;
+use anyhow::Result;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, mpsc};
+use tokio::time::interval;
+use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
+use vrc_osc_manager_core::OscReceiver;
+
+/// Number of received messages buffered for each relayed subscriber.
+const RELAY_CAPACITY: usize = 128;
+
+/// A queue this full counts as congested, even if nothing was dropped yet.
+const CONGESTION_PERCENT: usize = 80;
+
+/// Minimum time between two warnings about the same channel.
+const WARNING_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A subscriber with messages queued which did not take any for this long counts as stalled.
+const STALL_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Depth and drop counters of a single channel, updated by whoever feeds it.
+#[derive(Debug)]
+struct ChannelStats {
+    capacity: usize,
+    depth: AtomicUsize,
+    dropped: AtomicU64,
+    stalled: AtomicBool,
+    closed: AtomicBool,
+}
+
+impl ChannelStats {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            depth: AtomicUsize::new(0),
+            dropped: AtomicU64::new(0),
+            stalled: AtomicBool::new(false),
+            closed: AtomicBool::new(false),
+        }
+    }
+}
+
+/// Reads the current depth of a channel, or `None` once it is gone.
+type Probe = Box<dyn Fn() -> Option<usize> + Send + Sync>;
+
+struct Channel {
+    name: String,
+    stats: Arc<ChannelStats>,
+    probe: Option<Probe>,
+}
+
+/// Snapshot of a channel, as reported through the control API.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChannelStatus {
+    pub name: String,
+    pub depth: usize,
+    pub capacity: usize,
+    pub dropped: u64,
+    #[serde(default)]
+    pub stalled: bool,
+}
+
+impl ChannelStatus {
+    fn congested(&self) -> bool {
+        self.stalled || self.depth * 100 >= self.capacity * CONGESTION_PERCENT
+    }
+}
+
+/// Registry of the internal channels, so a consumer falling behind can be named instead of
+/// messages silently vanishing.
+#[derive(Default)]
+pub struct Channels {
+    channels: Mutex<Vec<Channel>>,
+}
+
+impl fmt::Debug for Channels {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let channels = self.channels.lock().unwrap();
+        f.debug_list()
+            .entries(channels.iter().map(|channel| &channel.name))
+            .finish()
+    }
+}
+
+impl Channels {
+    fn register(&self, name: String, stats: Arc<ChannelStats>, probe: Option<Probe>) {
+        let mut channels = self.channels.lock().unwrap();
+        channels.retain(|channel| channel.name != name);
+        channels.push(Channel { name, stats, probe });
+    }
+
+    /// Watches a queue, whose depth is read from its sender. Senders wait for free space instead
+    /// of dropping, so a full queue shows up as congestion.
+    pub fn watch<T: Send + 'static>(&self, name: &str, tx: &mpsc::Sender<T>) {
+        let capacity = tx.max_capacity();
+        let tx = tx.downgrade();
+
+        self.register(
+            name.to_string(),
+            Arc::new(ChannelStats::new(capacity)),
+            Some(Box::new(move || {
+                tx.upgrade().map(|tx| tx.max_capacity() - tx.capacity())
+            })),
+        );
+    }
+
+    /// Watches the received messages, whose depth is the number of messages the slowest subscriber
+    /// did not see yet.
+    pub fn watch_broadcast<T: Send + 'static>(&self, name: &str, tx: &broadcast::Sender<T>) {
+        let tx = tx.clone();
+
+        self.register(
+            name.to_string(),
+            Arc::new(ChannelStats::new(RELAY_CAPACITY)),
+            Some(Box::new(move || Some(tx.len()))),
+        );
+    }
+
+    /// Hands a subscriber its own copy of the received messages, so one falling behind shows up
+    /// under its own name and messages it misses are counted. Messages handed to a plugin are
+    /// added to its usage. Subscribers declaring parameters they read are expected to keep taking
+    /// messages from the start, so one stuck right away, or leaving messages queued for a while,
+    /// is reported as stalled as well.
+    pub fn relay(
+        &self,
+        name: &str,
+        mut source: OscReceiver,
+        usage: Option<Arc<PluginUsage>>,
+        reads_parameters: bool,
+    ) -> OscReceiver {
+        let (tx, rx) = broadcast::channel(RELAY_CAPACITY);
+        let stats = Arc::new(ChannelStats::new(RELAY_CAPACITY));
+        self.register(name.to_string(), stats.clone(), None);
+
+        tokio::spawn(async move {
+            let mut refresh = interval(Duration::from_secs(1));
+            // Other subscribers which never read their messages are not falling behind, they
+            // just have no use for them.
+            let mut consumed = reads_parameters;
+            let mut last_depth = 0;
+            let mut last_progress = Instant::now();
+
+            loop {
+                tokio::select! {
+                    message = source.recv() => {
+                        match message {
+                            Ok(message) => {
+                                let depth = tx.len();
+                                consumed |= depth < last_depth;
+
+                                if depth == 0 || depth < last_depth {
+                                    last_progress = Instant::now();
+                                }
+
+                                // The channel overwrites its oldest message once full.
+                                if consumed && depth >= RELAY_CAPACITY {
+                                    stats.dropped.fetch_add(1, Ordering::Relaxed);
+                                }
+
+                                if tx.send(message).is_err() {
+                                    break;
+                                }
+
+                                if let Some(usage) = &usage {
+                                    usage.record_received();
+                                }
+                            }
+                            Err(RecvError::Lagged(skipped)) => {
+                                stats.dropped.fetch_add(skipped, Ordering::Relaxed);
+                            }
+                            Err(RecvError::Closed) => break,
+                        }
+                    }
+                    _ = refresh.tick() => {
+                        if tx.receiver_count() == 0 {
+                            break;
+                        }
+
+                        let depth = tx.len();
+                        consumed |= depth < last_depth;
+
+                        if depth == 0 || depth < last_depth {
+                            last_progress = Instant::now();
+                        }
+                    }
+                }
+
+                last_depth = tx.len();
+
+                if consumed {
+                    stats.depth.store(last_depth, Ordering::Relaxed);
+                    stats.stalled.store(
+                        reads_parameters && last_progress.elapsed() >= STALL_TIMEOUT,
+                        Ordering::Relaxed,
+                    );
+                }
+            }
+
+            stats.closed.store(true, Ordering::Relaxed);
+        });
+
+        rx
+    }
+
+    /// Current state of all channels, dropping the ones which were closed.
+    pub fn snapshot(&self) -> Vec<ChannelStatus> {
+        let mut channels = self.channels.lock().unwrap();
+        let mut statuses = vec![];
+
+        channels.retain(|channel| {
+            let depth = match &channel.probe {
+                Some(probe) => match probe() {
+                    Some(depth) => depth,
+                    None => return false,
+                },
+                None if channel.stats.closed.load(Ordering::Relaxed) => return false,
+                None => channel.stats.depth.load(Ordering::Relaxed),
+            };
+
+            statuses.push(ChannelStatus {
+                name: channel.name.clone(),
+                depth,
+                capacity: channel.stats.capacity,
+                dropped: channel.stats.dropped.load(Ordering::Relaxed),
+                stalled: channel.stats.stalled.load(Ordering::Relaxed),
+            });
+            true
+        });
+
+        statuses
+    }
+}
+
+/// What was last reported about a channel.
+#[derive(Default)]
+struct Report {
+    dropped: u64,
+    congested: bool,
+    warned: Option<Instant>,
+}
+
+/// Periodically checks the channels and warns about the ones falling behind.
+pub struct BackpressureMonitor {
+    state: Arc<SharedState>,
+    status_tx: mpsc::Sender<StatusEvent>,
+    reports: HashMap<String, Report>,
+    congested: bool,
+}
+
+impl BackpressureMonitor {
+    pub fn new(state: Arc<SharedState>, status_tx: mpsc::Sender<StatusEvent>) -> Self {
+        Self {
+            state,
+            status_tx,
+            reports: HashMap::new(),
+            congested: false,
+        }
+    }
+
+    fn check(&mut self) -> bool {
+        let statuses = self.state.channels.snapshot();
+        self.reports
+            .retain(|name, _| statuses.iter().any(|status| &status.name == name));
+        let mut any_congested = false;
+
+        for status in statuses {
+            let report = self.reports.entry(status.name.clone()).or_default();
+            let dropped = status.dropped.saturating_sub(report.dropped);
+            let congested = status.congested() || dropped > 0;
+            any_congested |= congested;
+
+            if congested
+                && (dropped > 0 || !report.congested)
+                && report
+                    .warned
+                    .map_or(true, |warned| warned.elapsed() >= WARNING_INTERVAL)
+            {
+                if status.stalled {
+                    warn!(
+                        "{} stopped taking messages, {} of {} messages queued and {} dropped since the last warning",
+                        status.name, status.depth, status.capacity, dropped
+                    );
+                } else {
+                    warn!(
+                        "{} is falling behind, {} of {} messages queued and {} dropped since the last warning",
+                        status.name, status.depth, status.capacity, dropped
+                    );
+                }
+                report.warned = Some(Instant::now());
+                report.dropped = status.dropped;
+            }
+
+            report.congested = congested;
+        }
+
+        any_congested
+    }
+
+    async fn monitor(&mut self) -> Result<()> {
+        let mut interval = interval(Duration::from_secs(1));
+
+        loop {
+            interval.tick().await;
+            let congested = self.check();
+
+            if congested != self.congested {
+                self.congested = congested;
+                self.status_tx
+                    .send(StatusEvent::Congested(congested))
+                    .await?;
+            }
+        }
+    }
+
+    pub async fn run(mut self, subsys: SubsystemHandle) -> Result<()> {
+        match (self.monitor().cancel_on_shutdown(&subsys)).await {
+            Ok(Ok(())) => subsys.request_shutdown(),
+            Ok(Err(error)) => return Err(error),
+            Err(CancelledByShutdown) => {}
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,209 @@
+use crate::config::BitPackConfig;
+use async_osc::{OscMessage, OscType};
+use std::collections::HashMap;
+use vrc_osc_manager_core::addresses::parameter_address;
+
+fn bit_set(value: u32, bit: usize) -> bool {
+    value.checked_shr(bit as u32).unwrap_or(0) & 1 == 1
+}
+
+/// Encodes the value into individual bool parameters, with the first parameter holding the least
+/// significant bit. Bits which do not fit into the given parameters are discarded.
+pub fn encode(bits: &[String], value: u32) -> Vec<OscMessage> {
+    bits.iter()
+        .enumerate()
+        .map(|(bit, parameter)| OscMessage {
+            addr: parameter_address(parameter),
+            args: vec![OscType::Bool(bit_set(value, bit))],
+        })
+        .collect()
+}
+
+/// Expands outgoing int or enum messages on packed parameters into their bool parameters.
+pub struct Encoder {
+    packed: HashMap<String, BitPackConfig>,
+}
+
+impl Encoder {
+    pub fn new(configs: &[BitPackConfig]) -> Self {
+        Self {
+            packed: configs
+                .iter()
+                .map(|config| (parameter_address(&config.parameter), config.clone()))
+                .collect(),
+        }
+    }
+
+    pub fn encode(&self, message: &OscMessage) -> Option<Vec<OscMessage>> {
+        let config = self.packed.get(&message.addr)?;
+
+        let value = match message.args.as_slice() {
+            [OscType::Int(value)] => *value as u32,
+            [OscType::String(label)] => {
+                config.values.iter().position(|value| value == label)? as u32
+            }
+            _ => return None,
+        };
+
+        Some(encode(&config.bits, value))
+    }
+}
+
+/// Reassembles incoming bool parameter updates into the value of their packed parameter.
+pub struct Decoder {
+    bit_owners: HashMap<String, (String, usize)>,
+    values: HashMap<String, u32>,
+}
+
+impl Decoder {
+    pub fn new(configs: &[BitPackConfig]) -> Self {
+        let mut bit_owners = HashMap::new();
+
+        for config in configs {
+            let address = parameter_address(&config.parameter);
+
+            for (bit, parameter) in config.bits.iter().enumerate() {
+                bit_owners.insert(parameter_address(parameter), (address.clone(), bit));
+            }
+        }
+
+        Self {
+            bit_owners,
+            values: HashMap::new(),
+        }
+    }
+
+    /// Returns a message carrying the packed value when the incoming message changed it. As VRChat
+    /// sends every bool on its own, intermediate values may be observed while several bits change.
+    pub fn decode(&mut self, message: &OscMessage) -> Option<OscMessage> {
+        let (address, bit) = self.bit_owners.get(&message.addr)?;
+
+        let set = match message.args.as_slice() {
+            [OscType::Bool(set)] => *set,
+            _ => return None,
+        };
+
+        let mask = 1u32.checked_shl(*bit as u32).unwrap_or(0);
+        let value = self.values.entry(address.clone()).or_insert(0);
+        let previous = *value;
+
+        if set {
+            *value |= mask;
+        } else {
+            *value &= !mask;
+        }
+
+        if *value == previous {
+            return None;
+        }
+
+        Some(OscMessage {
+            addr: address.clone(),
+            args: vec![OscType::Int(*value as i32)],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> BitPackConfig {
+        BitPackConfig {
+            parameter: "Mode".to_string(),
+            bits: vec![
+                "Mode0".to_string(),
+                "Mode1".to_string(),
+                "Mode2".to_string(),
+            ],
+            values: vec!["off".to_string(), "on".to_string(), "auto".to_string()],
+        }
+    }
+
+    fn message(value: OscType) -> OscMessage {
+        OscMessage {
+            addr: "/avatar/parameters/Mode".to_string(),
+            args: vec![value],
+        }
+    }
+
+    /// Feeds the bits to a fresh decoder the way VRChat sends them, one by one.
+    fn decode(bits: &[OscMessage]) -> Option<OscMessage> {
+        let mut decoder = Decoder::new(&[config()]);
+        bits.iter().filter_map(|bit| decoder.decode(bit)).last()
+    }
+
+    #[test]
+    fn round_trips_ints() {
+        let encoder = Encoder::new(&[config()]);
+
+        for value in 1..8 {
+            let bits = encoder.encode(&message(OscType::Int(value))).unwrap();
+            assert_eq!(decode(&bits), Some(message(OscType::Int(value))));
+        }
+    }
+
+    #[test]
+    fn encodes_labels_by_their_position() {
+        let encoder = Encoder::new(&[config()]);
+        let bits = encoder.encode(&message(OscType::String("auto".to_string())));
+
+        assert_eq!(
+            bits.unwrap()
+                .into_iter()
+                .map(|bit| (bit.addr, bit.args))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    "/avatar/parameters/Mode0".to_string(),
+                    vec![OscType::Bool(false)]
+                ),
+                (
+                    "/avatar/parameters/Mode1".to_string(),
+                    vec![OscType::Bool(true)]
+                ),
+                (
+                    "/avatar/parameters/Mode2".to_string(),
+                    vec![OscType::Bool(false)]
+                ),
+            ]
+        );
+        assert_eq!(
+            encoder.encode(&message(OscType::String("unknown".to_string()))),
+            None
+        );
+    }
+
+    #[test]
+    fn discards_bits_which_do_not_fit() {
+        let encoder = Encoder::new(&[config()]);
+        let bits = encoder.encode(&message(OscType::Int(9))).unwrap();
+
+        assert_eq!(decode(&bits), Some(message(OscType::Int(1))));
+    }
+
+    #[test]
+    fn ignores_other_addresses() {
+        let encoder = Encoder::new(&[config()]);
+        let mut decoder = Decoder::new(&[config()]);
+        let other = OscMessage {
+            addr: "/avatar/parameters/Other".to_string(),
+            args: vec![OscType::Int(1)],
+        };
+
+        assert_eq!(encoder.encode(&other), None);
+        assert_eq!(decoder.decode(&other), None);
+    }
+
+    #[test]
+    fn reports_only_changes() {
+        let mut decoder = Decoder::new(&[config()]);
+        let bit = OscMessage {
+            addr: "/avatar/parameters/Mode1".to_string(),
+            args: vec![OscType::Bool(true)],
+        };
+
+        assert_eq!(decoder.decode(&bit), Some(message(OscType::Int(2))));
+        assert_eq!(decoder.decode(&bit), None);
+    }
+}
@@ -0,0 +1,121 @@
+use crate::config::ChatboxConfig;
+use anyhow::Result;
+use async_osc::OscType;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::sleep;
+use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
+use vrc_osc_manager_core::{ChatboxQueue, OutgoingMessage};
+
+/// Sends the queued chatbox messages of all plugins, spaced out to stay within the rate limit of
+/// VRChat, which otherwise silently drops them.
+pub struct ChatboxScheduler {
+    queue: ChatboxQueue,
+    interval: Duration,
+    blocked_words: Vec<String>,
+    tx: mpsc::Sender<OutgoingMessage>,
+}
+
+/// Replaces every character of the blocked words, given in lowercase, with an asterisk, matching
+/// whole words only.
+pub fn mask(text: &str, blocked_words: &[String]) -> String {
+    let mut masked = String::with_capacity(text.len());
+    let mut word = String::new();
+
+    let flush = |word: &mut String, masked: &mut String| {
+        if blocked_words.contains(&word.to_lowercase()) {
+            masked.extend(word.chars().map(|_| '*'));
+        } else {
+            masked.push_str(word);
+        }
+
+        word.clear();
+    };
+
+    for char in text.chars() {
+        if char.is_alphanumeric() {
+            word.push(char);
+        } else {
+            flush(&mut word, &mut masked);
+            masked.push(char);
+        }
+    }
+
+    flush(&mut word, &mut masked);
+    masked
+}
+
+impl ChatboxScheduler {
+    pub fn new(
+        config: &ChatboxConfig,
+        queue: ChatboxQueue,
+        tx: mpsc::Sender<OutgoingMessage>,
+    ) -> Self {
+        Self {
+            queue,
+            interval: Duration::from_millis(config.interval_ms),
+            blocked_words: config
+                .blocked_words
+                .iter()
+                .map(|word| word.to_lowercase())
+                .collect(),
+            tx,
+        }
+    }
+
+    async fn schedule(&self) -> Result<()> {
+        loop {
+            while let Some(mut message) = self.queue.pop() {
+                if let Some(OscType::String(text)) = message.message.args.first_mut() {
+                    *text = mask(text, &self.blocked_words);
+                }
+
+                self.tx.send(message).await?;
+                sleep(self.interval).await;
+            }
+
+            self.queue.wait().await;
+        }
+    }
+
+    pub async fn run(self, subsys: SubsystemHandle) -> Result<()> {
+        match (self.schedule().cancel_on_shutdown(&subsys)).await {
+            Ok(Ok(())) => subsys.request_shutdown(),
+            Ok(Err(error)) => return Err(error),
+            Err(CancelledByShutdown) => {}
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blocked(words: &[&str]) -> Vec<String> {
+        words.iter().map(|word| word.to_string()).collect()
+    }
+
+    #[test]
+    fn masks_blocked_words_regardless_of_case() {
+        assert_eq!(
+            mask("Hello Darn world, DARN!", &blocked(&["darn"])),
+            "Hello **** world, ****!"
+        );
+    }
+
+    #[test]
+    fn masks_whole_words_only() {
+        assert_eq!(
+            mask("darnit darn-it", &blocked(&["darn"])),
+            "darnit ****-it"
+        );
+    }
+
+    #[test]
+    fn keeps_text_without_blocked_words() {
+        assert_eq!(mask("Grüße, Welt", &blocked(&["darn"])), "Grüße, Welt");
+        assert_eq!(mask("darn", &[]), "darn");
+    }
+}
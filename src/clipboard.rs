@@ -0,0 +1,28 @@
+use anyhow::Result;
+use log::warn;
+
+/// Copies the text to the clipboard. On Linux, the clipboard only refers to the application owning
+/// its contents, so a thread keeps serving the text until something else is copied.
+pub fn copy(text: String) {
+    std::thread::spawn(move || {
+        let result = arboard::Clipboard::new().and_then(|mut clipboard| {
+            #[cfg(target_os = "linux")]
+            {
+                use arboard::SetExtLinux;
+                clipboard.set().wait().text(text)
+            }
+            #[cfg(not(target_os = "linux"))]
+            clipboard.set_text(text)
+        });
+
+        if let Err(error) = result {
+            warn!("Failed to copy to the clipboard: {}", error);
+        }
+    });
+}
+
+/// Reads the text currently held by the clipboard.
+pub async fn paste() -> Result<String> {
+    let text = tokio::task::spawn_blocking(|| arboard::Clipboard::new()?.get_text()).await??;
+    Ok(text)
+}
@@ -0,0 +1,86 @@
+use crate::config::{ClusterConfig, ClusterRole};
+use anyhow::{bail, Result};
+use async_osc::{OscPacket, OscSocket};
+use log::{debug, info, warn};
+use std::net::IpAddr;
+use tokio::sync::mpsc;
+use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
+use tokio_stream::StreamExt;
+use vrc_osc_manager_core::OutgoingMessage;
+
+/// Accepts the output forwarded by secondaries on the primary and queues it for sending to VRChat.
+pub struct ClusterListener {
+    role: ClusterRole,
+    address: Option<IpAddr>,
+    port: u16,
+    secondaries: Vec<IpAddr>,
+    tx: mpsc::Sender<OutgoingMessage>,
+}
+
+impl ClusterListener {
+    pub fn new(config: &ClusterConfig, tx: mpsc::Sender<OutgoingMessage>) -> Self {
+        Self {
+            role: config.role,
+            address: config.listen_address,
+            port: config.port,
+            secondaries: config.secondaries.clone(),
+            tx,
+        }
+    }
+
+    async fn listen(&self, address: IpAddr) -> Result<()> {
+        let mut socket = OscSocket::bind((address, self.port)).await?;
+        info!("Accepting secondaries on {}:{}", address, self.port);
+
+        while let Some(packet) = socket.next().await {
+            // Anyone on the LAN can send garbage, which must not stop the primary.
+            let (packet, peer) = match packet {
+                Ok(packet) => packet,
+                Err(error) => {
+                    debug!("Dropped undecodable forwarded packet: {}", error);
+                    continue;
+                }
+            };
+
+            if !self.secondaries.contains(&peer.ip()) {
+                debug!("Dropped forwarded message from unknown secondary {}", peer);
+                continue;
+            }
+
+            if let OscPacket::Message(message) = packet {
+                self.tx
+                    .send(OutgoingMessage::new("cluster", message))
+                    .await?;
+            }
+        }
+
+        bail!("Cluster stream closed unexpectedly");
+    }
+
+    pub async fn run(self, subsys: SubsystemHandle) -> Result<()> {
+        if self.role != ClusterRole::Primary {
+            subsys.on_shutdown_requested().await;
+            return Ok(());
+        }
+
+        let Some(address) = self.address else {
+            warn!("Not accepting secondaries, as the cluster has no listen_address");
+            subsys.on_shutdown_requested().await;
+            return Ok(());
+        };
+
+        if self.secondaries.is_empty() {
+            warn!("Not accepting secondaries, as none are configured");
+            subsys.on_shutdown_requested().await;
+            return Ok(());
+        }
+
+        match (self.listen(address).cancel_on_shutdown(&subsys)).await {
+            Ok(Ok(())) => subsys.request_shutdown(),
+            Ok(Err(error)) => return Err(error),
+            Err(CancelledByShutdown) => {}
+        }
+
+        Ok(())
+    }
+}
@@ -1,32 +1,822 @@
-use anyhow::{Context, Result};
+use crate::plugins;
+use anyhow::{bail, Context, Result};
+use async_osc::{OscMessage, OscType};
+use chrono::NaiveTime;
 use directories::BaseDirs;
+use log::warn;
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{RootSchema, Schema, SchemaObject};
+use schemars::{schema_for, JsonSchema};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::PathBuf;
+use std::sync::Arc;
 use tokio::fs::{metadata, File};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use vrc_osc_manager_core::{addresses, PluginConfig};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default)]
+#[serde(default)]
+pub struct TrayConfig {
+    /// Folder of icons to use instead of the built-in ones.
+    pub icon_theme: String,
+    /// Whether the icon blinks with a badge while OSC traffic is flowing.
+    pub live_status: bool,
+    /// Messages sent by clicking their entry in the tray menu.
+    pub bookmarks: Vec<BookmarkConfig>,
+    /// Whether every plugin gets an entry in the tray menu showing its state, to start or stop it.
+    pub plugin_toggles: bool,
+}
+
+/// Value of a message, written as plain TOML bool, integer, float or string.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum ConfigValue {
+    Bool(bool),
+    Int(i32),
+    Float(f32),
+    String(String),
+}
+
+impl ConfigValue {
+    pub fn to_osc(&self) -> OscType {
+        match self {
+            ConfigValue::Bool(value) => OscType::Bool(*value),
+            ConfigValue::Int(value) => OscType::Int(*value),
+            ConfigValue::Float(value) => OscType::Float(*value),
+            ConfigValue::String(value) => OscType::String(value.clone()),
+        }
+    }
+}
+
+/// Sets a parameter back once the plugins stop, e.g. to not leave a heart rate display frozen, or
+/// only once the given plugin stops.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ResetConfig {
+    pub address: String,
+    pub value: ConfigValue,
+    #[serde(default)]
+    pub plugin: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct BookmarkConfig {
+    pub name: String,
+    pub address: String,
+    pub value: ConfigValue,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct BitPackConfig {
+    pub parameter: String,
+    pub bits: Vec<String>,
+    #[serde(default)]
+    pub values: Vec<String>,
+}
+
+/// Sends messages of a plugin or below an address prefix to a different destination. When both are
+/// given, a message has to match both.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RouteConfig {
+    #[serde(default)]
+    pub plugin: Option<String>,
+    #[serde(default)]
+    pub address_prefix: Option<String>,
+    pub destination: String,
+    #[serde(default)]
+    pub schedule: Option<String>,
+    /// Rules applied to the messages sent along just this route, in order.
+    #[serde(default)]
+    pub rules: Vec<RuleConfig>,
+    /// Steps per unit floats are rounded to, dropping values which did not change by a step.
+    pub float_steps: Option<u32>,
+}
+
+/// Another OSC application incoming messages are passed on to, as only one of them can listen on
+/// the port VRChat sends to.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct DownstreamConfig {
+    pub destination: String,
+    /// Address prefixes of the messages passed on, all messages are when empty.
+    #[serde(default)]
+    pub address_prefixes: Vec<String>,
+    /// Rules applied to the messages passed on to just this application, in order.
+    #[serde(default)]
+    pub rules: Vec<RuleConfig>,
+    /// Steps per unit floats are rounded to, dropping values which did not change by a step.
+    pub float_steps: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleDirection {
+    Inbound,
+    Outbound,
+    #[default]
+    Both,
+}
+
+/// Conversion between the units of a source and those an avatar expects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum UnitConversion {
+    CelsiusToFahrenheit,
+    FahrenheitToCelsius,
+    /// Beats per minute from 0 to 255 into the range from 0 to 1 a float parameter can sync.
+    BpmToNormalized,
+    NormalizedToBpm,
+    MetersToFeet,
+    FeetToMeters,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum MergePolicy {
+    #[default]
+    LastWriter,
+    Priority,
+    Max,
+    Min,
+}
+
+fn default_merge_window() -> u64 {
+    1000
+}
+
+/// Decides between plugins writing addresses matching the pattern, considering what each of them
+/// wrote within the last `window_ms`. With the priority policy, plugins listed first win, while
+/// unlisted ones come last.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct MergeConfig {
+    pub address: String,
+    #[serde(default)]
+    pub policy: MergePolicy,
+    #[serde(default)]
+    pub priority: Vec<String>,
+    #[serde(default = "default_merge_window")]
+    pub window_ms: u64,
+}
+
+/// Rewrites messages whose address matches the pattern, where `*` matches any sequence of
+/// characters, and whose value lies within `min` and `max`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RuleConfig {
+    pub address: String,
+    #[serde(default)]
+    pub direction: RuleDirection,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    #[serde(default)]
+    pub drop: bool,
+    pub rename: Option<String>,
+    pub convert: Option<UnitConversion>,
+    pub scale: Option<f64>,
+    pub offset: Option<f64>,
+    pub clamp_min: Option<f64>,
+    pub clamp_max: Option<f64>,
+    #[serde(default)]
+    pub duplicate: Vec<String>,
+    #[serde(default)]
+    pub schedule: Option<String>,
+}
+
+/// Addresses whose last value gets resent periodically, after avatar changes and game restarts.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct KeepaliveConfig {
+    /// Address patterns whose last value is resent.
+    pub addresses: Vec<String>,
+    /// Seconds between resending the values.
+    pub interval_seconds: u64,
+}
+
+impl Default for KeepaliveConfig {
+    fn default() -> Self {
+        Self {
+            addresses: vec![],
+            interval_seconds: 10,
+        }
+    }
+}
+
+/// Collects outgoing messages for up to `max_latency_ms` and sends them as bundles of at most
+/// `max_messages`, so high parameter rates need fewer datagrams. Zero sends every message on its own.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct BatchConfig {
+    /// Milliseconds an outgoing message may be held back, zero disables batching.
+    pub max_latency_ms: u64,
+    /// Maximum number of messages sent in one bundle.
+    pub max_messages: usize,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self {
+            max_latency_ms: 0,
+            max_messages: 32,
+        }
+    }
+}
+
+/// Treats at least `min_messages` parameters set to their default within the window as an
+/// avatar reset, which triggers a resync once `settle_ms` passed.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct ResetDetectionConfig {
+    /// Whether avatar resets are detected.
+    pub enabled: bool,
+    /// Number of different bool and int parameters set to their default within the window counting
+    /// as a reset.
+    pub min_messages: u32,
+    /// Milliseconds the default values have to arrive within.
+    pub window_ms: u64,
+    /// Milliseconds to wait for the burst to settle before resyncing.
+    pub settle_ms: u64,
+}
+
+impl Default for ResetDetectionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_messages: 20,
+            window_ms: 1000,
+            settle_ms: 500,
+        }
+    }
+}
+
+/// Caps for incoming messages per second, where zero disables the respective cap.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct FloodConfig {
+    /// Maximum messages per second from a single source.
+    pub per_source: u32,
+    /// Maximum messages per second on a single address.
+    pub per_address: u32,
+    /// Seconds a flooding source or address stays blocked.
+    pub block_seconds: u64,
+}
+
+impl Default for FloodConfig {
+    fn default() -> Self {
+        Self {
+            per_source: 5000,
+            per_address: 500,
+            block_seconds: 10,
+        }
+    }
+}
+
+/// Restricts which incoming messages reach the plugins. Empty allow lists allow everything, while
+/// deny lists take precedence over allow lists.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default)]
+#[serde(default)]
+pub struct FilterConfig {
+    /// Address patterns allowed to reach the plugins.
+    pub allow_addresses: Vec<String>,
+    /// Address patterns kept from the plugins.
+    pub deny_addresses: Vec<String>,
+    /// IPs allowed to send messages.
+    pub allow_sources: Vec<IpAddr>,
+    /// IPs whose messages are ignored.
+    pub deny_sources: Vec<IpAddr>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(default)]
 pub struct OscConfig {
+    /// Host VRChat runs on.
+    pub send_host: String,
+    /// Port VRChat receives OSC messages on.
     pub send_port: u16,
+    /// Address to receive OSC messages from VRChat on.
+    pub listen_address: IpAddr,
+    /// Port VRChat sends OSC messages to.
     pub receive_port: u16,
+    /// Float addresses rounded to the steps VRChat can sync.
+    pub quantize_addresses: Vec<String>,
+    /// Whether outgoing messages repeating the last value are dropped.
+    pub deduplicate: bool,
+    /// Address patterns which are always sent.
+    pub deduplicate_exempt: Vec<String>,
+    /// Values resent periodically and after avatar changes.
+    pub keepalive: KeepaliveConfig,
+    /// Batching of outgoing messages into bundles.
+    pub batch: BatchConfig,
+    /// Detection of avatar resets.
+    pub reset_detection: ResetDetectionConfig,
+    /// Parameters packed into bools for syncing.
+    pub bitpack: Vec<BitPackConfig>,
+    /// Destinations for messages other than VRChat.
+    pub routes: Vec<RouteConfig>,
+    /// Applications incoming messages are passed on to.
+    pub downstream: Vec<DownstreamConfig>,
+    /// Policies for addresses written by multiple plugins.
+    pub merge: Vec<MergeConfig>,
+    /// Address patterns frozen through the tray or a bool on /vrc-osc-manager/freeze.
+    pub freeze: Vec<String>,
+    /// Rewrites applied to incoming and outgoing messages.
+    pub rules: Vec<RuleConfig>,
+    /// Caps for incoming messages.
+    pub flood: FloodConfig,
+    /// Allow and deny lists for incoming messages.
+    pub filter: FilterConfig,
+    /// Whether VRChat clients on the network are discovered through OSCQuery.
+    #[cfg(feature = "discovery")]
+    pub discovery: bool,
+    /// Whether the manager advertises itself and looks up the port of VRChat through OSCQuery.
+    #[cfg(feature = "oscquery")]
+    pub oscquery: bool,
+}
+
+impl OscConfig {
+    pub fn sends_locally(&self) -> bool {
+        is_local_host(&self.send_host)
+    }
+}
+
+pub fn is_local_host(host: &str) -> bool {
+    host == "localhost"
+        || host
+            .parse::<IpAddr>()
+            .map_or(false, |address| address.is_loopback())
 }
 
 impl Default for OscConfig {
     fn default() -> Self {
         Self {
+            send_host: "127.0.0.1".to_string(),
             send_port: 9000,
+            listen_address: IpAddr::V4(Ipv4Addr::LOCALHOST),
             receive_port: 9001,
+            quantize_addresses: vec![],
+            deduplicate: true,
+            deduplicate_exempt: vec!["/chatbox/*".to_string()],
+            keepalive: KeepaliveConfig::default(),
+            batch: BatchConfig::default(),
+            reset_detection: ResetDetectionConfig::default(),
+            bitpack: vec![],
+            routes: vec![],
+            downstream: vec![],
+            merge: vec![],
+            freeze: vec![],
+            rules: vec![],
+            flood: FloodConfig::default(),
+            filter: FilterConfig::default(),
+            #[cfg(feature = "discovery")]
+            discovery: false,
+            #[cfg(feature = "oscquery")]
+            oscquery: false,
+        }
+    }
+}
+
+/// A time of day written as `HH:MM`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct TimeOfDay(pub NaiveTime);
+
+impl TryFrom<String> for TimeOfDay {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        NaiveTime::parse_from_str(&value, "%H:%M")
+            .map(TimeOfDay)
+            .map_err(|_| format!("invalid time of day {}, expected HH:MM", value))
+    }
+}
+
+impl JsonSchema for TimeOfDay {
+    fn schema_name() -> String {
+        "TimeOfDay".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        let mut schema = String::json_schema(gen).into_object();
+        schema.string().pattern = Some("^([01][0-9]|2[0-3]):[0-5][0-9]$".to_string());
+        schema.into()
+    }
+}
+
+impl From<TimeOfDay> for String {
+    fn from(time: TimeOfDay) -> Self {
+        time.0.format("%H:%M").to_string()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Weekday {
+    Mon,
+    Tue,
+    Wed,
+    Thu,
+    Fri,
+    Sat,
+    Sun,
+}
+
+/// A daily time window, optionally limited to some weekdays. A window ending before it starts
+/// runs past midnight.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default)]
+#[serde(default)]
+pub struct ScheduleConfig {
+    pub start: Option<TimeOfDay>,
+    pub end: Option<TimeOfDay>,
+    pub weekdays: Vec<Weekday>,
+}
+
+fn default_gain() -> f32 {
+    1.
+}
+
+/// Processing of the face tracking parameters matching the address pattern. The result can be
+/// mirrored to a second address, negated with `mirror_invert` for horizontal axes.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct FaceParameterConfig {
+    pub address: String,
+    #[serde(default = "default_gain")]
+    pub gain: f32,
+    #[serde(default)]
+    pub offset: f32,
+    pub clamp_min: Option<f32>,
+    pub clamp_max: Option<f32>,
+    #[serde(default)]
+    pub smoothing: f32,
+    pub mirror: Option<String>,
+    #[serde(default)]
+    pub mirror_invert: bool,
+}
+
+/// A profile applies to the avatar with the given ID, or to all others when the ID is left out.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct FaceProfileConfig {
+    pub avatar: Option<String>,
+    pub parameters: Vec<FaceParameterConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct FaceTrackingConfig {
+    /// Address to receive face tracking messages on.
+    pub listen_address: IpAddr,
+    /// Port to receive face tracking messages on, disabled when unset.
+    pub port: Option<u16>,
+    /// Profiles tuning the face tracking parameters.
+    pub profiles: Vec<FaceProfileConfig>,
+}
+
+impl Default for FaceTrackingConfig {
+    fn default() -> Self {
+        Self {
+            listen_address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: None,
+            profiles: vec![],
+        }
+    }
+}
+
+/// Slows down periodic plugins and pauses the listed ones while the parameter reports the user
+/// as AFK.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct IdleConfig {
+    /// Whether idle mode is entered while AFK.
+    pub enabled: bool,
+    /// Address reporting the AFK state.
+    pub parameter: String,
+    /// Factor periodic plugins slow down by while idle.
+    pub slowdown: u32,
+    /// Plugins stopped while idle.
+    pub pause_plugins: Vec<String>,
+}
+
+impl Default for IdleConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            parameter: "/avatar/parameters/AFK".to_string(),
+            slowdown: 6,
+            pause_plugins: vec![],
+        }
+    }
+}
+
+/// Toggles `parameter` once VRChat is running and expects it to be reported back within
+/// `timeout_seconds`, to verify the OSC link in both directions.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct SelfTestConfig {
+    /// Whether the self-test runs after startup.
+    pub enabled: bool,
+    /// Bool parameter of the avatar toggled by the test.
+    pub parameter: String,
+    /// Seconds to wait for VRChat to report the parameter back.
+    pub timeout_seconds: u64,
+}
+
+impl Default for SelfTestConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            parameter: "/avatar/parameters/OscSelfTest".to_string(),
+            timeout_seconds: 10,
+        }
+    }
+}
+
+/// Recovers the sender, the receiver and plugins which stopped responding, instead of leaving
+/// their features silently broken.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct WatchdogConfig {
+    /// Whether stuck subsystems are recovered.
+    pub enabled: bool,
+    /// Seconds without a heartbeat after which a subsystem counts as stuck.
+    pub timeout_seconds: u64,
+    /// Whether a notification is shown when something had to be recovered.
+    pub notify: bool,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            timeout_seconds: 30,
+            notify: true,
+        }
+    }
+}
+
+/// Machine-readable trail of what the manager and its plugins did, written as JSON lines.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct EventLogConfig {
+    /// Whether events are written.
+    pub enabled: bool,
+    /// File the events are written to, events.jsonl in the logs directory by default.
+    pub path: Option<PathBuf>,
+    /// Size in kilobytes after which the file is rotated.
+    pub max_size_kb: usize,
+    /// Number of rotated files kept besides the current one.
+    pub max_files: usize,
+}
+
+impl Default for EventLogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: None,
+            max_size_kb: 10240,
+            max_files: 5,
+        }
+    }
+}
+
+/// Recording of all OSC traffic, for debugging avatar parameters.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct MonitorConfig {
+    /// Whether incoming and outgoing messages are recorded.
+    pub enabled: bool,
+    /// File the messages are written to, a new one in the recordings directory per start by default.
+    pub path: Option<PathBuf>,
+}
+
+/// Measures the round trip through the avatar by sending a counter to `send_parameter`, which an
+/// animator of the avatar copies into `receive_parameter`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct PingConfig {
+    /// Whether the round trip is measured.
+    pub enabled: bool,
+    /// Int parameter the counter is sent to.
+    pub send_parameter: String,
+    /// Int parameter VRChat reports the copied counter on.
+    pub receive_parameter: String,
+    /// Int parameter the round trip is sent to in milliseconds, up to 255.
+    pub latency_parameter: Option<String>,
+    /// Seconds between pings.
+    pub interval_seconds: u64,
+}
+
+impl Default for PingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            send_parameter: "/avatar/parameters/OscPingOut".to_string(),
+            receive_parameter: "/avatar/parameters/OscPingIn".to_string(),
+            latency_parameter: Some("/avatar/parameters/OscPing".to_string()),
+            interval_seconds: 5,
+        }
+    }
+}
+
+/// Summary of every session, from VRChat starting until it stops.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct SessionConfig {
+    /// Whether summaries are appended to sessions.log in the data directory.
+    pub summary: bool,
+    /// Whether summaries are shown as notification.
+    pub notify: bool,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            summary: true,
+            notify: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct ChatboxConfig {
+    /// Milliseconds between two chatbox messages.
+    pub interval_ms: u64,
+    /// Words masked with asterisks in every chatbox message, regardless of case.
+    pub blocked_words: Vec<String>,
+}
+
+impl Default for ChatboxConfig {
+    fn default() -> Self {
+        Self {
+            interval_ms: 1500,
+            blocked_words: vec![],
+        }
+    }
+}
+
+/// Limits above which the plugins listed as low priority get throttled.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct GovernorConfig {
+    /// Whether low priority plugins get throttled under load.
+    pub enabled: bool,
+    /// CPU usage of the manager above which plugins get throttled.
+    pub max_cpu_percent: f32,
+    /// Number of queued outgoing messages above which plugins get throttled.
+    pub max_queue_depth: usize,
+    /// Factor throttled plugins slow down by.
+    pub throttle: u32,
+    /// Plugins which get throttled.
+    pub low_priority: Vec<String>,
+}
+
+impl Default for GovernorConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_cpu_percent: 10.,
+            max_queue_depth: 64,
+            throttle: 4,
+            low_priority: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ClusterRole {
+    #[default]
+    Standalone,
+    Primary,
+    Secondary,
+}
+
+/// Lets a secondary instance on another machine forward its plugin output to a primary instance,
+/// which owns the connection to VRChat.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct ClusterConfig {
+    /// Whether this instance runs standalone, as primary or as secondary.
+    pub role: ClusterRole,
+    /// Address a primary receives the output of secondaries on, which has to be given explicitly as
+    /// it is reachable from the LAN.
+    pub listen_address: Option<IpAddr>,
+    /// Port used between primary and secondaries.
+    pub port: u16,
+    /// Host of the primary a secondary forwards to.
+    pub primary: String,
+    /// IPs of the secondaries a primary accepts.
+    pub secondaries: Vec<IpAddr>,
+}
+
+impl Default for ClusterConfig {
+    fn default() -> Self {
+        Self {
+            role: ClusterRole::Standalone,
+            listen_address: None,
+            port: 9003,
+            primary: "".to_string(),
+            secondaries: vec![],
+        }
+    }
+}
+
+/// Reads the world from the rich presence VRChat shows on Steam, for setups where the manager has
+/// no access to the VRChat log, like streaming to a Quest.
+#[cfg(feature = "steam_presence")]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct SteamPresenceConfig {
+    /// Whether the world is read from the Steam profile.
+    pub enabled: bool,
+    /// 64-bit Steam ID of the account playing VRChat, whose game details have to be public.
+    pub steam_id: u64,
+    /// Steam Web API key, see <https://steamcommunity.com/dev/apikey>.
+    pub api_key: String,
+    /// Seconds between two lookups.
+    pub interval_seconds: u64,
+}
+
+#[cfg(feature = "steam_presence")]
+impl Default for SteamPresenceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            steam_id: 0,
+            api_key: String::new(),
+            interval_seconds: 60,
+        }
+    }
+}
+
+/// Polls the VRChat API with the session stored by `vrchat-login`.
+#[cfg(feature = "vrchat_api")]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct VrchatApiConfig {
+    /// Whether the VRChat API is polled.
+    pub enabled: bool,
+    /// Parameter receiving the number of online friends.
+    pub friends_parameter: Option<String>,
+    /// Whether the current world is taken from the API.
+    pub world: bool,
+    /// Seconds between two polls, at least 60 to stay clear of the rate limits.
+    pub interval_seconds: u64,
+}
+
+#[cfg(feature = "vrchat_api")]
+impl Default for VrchatApiConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            friends_parameter: Some("/avatar/parameters/OnlineFriends".to_string()),
+            world: true,
+            interval_seconds: 300,
+        }
+    }
+}
+
+/// Sits between another OSC application and VRChat. The application sends to the proxy port
+/// instead of VRChat, while the messages from VRChat are passed on to the port it listens on.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct ProxyConfig {
+    /// Whether the proxy is running.
+    pub enabled: bool,
+    /// Address the proxy receives the messages of the application on.
+    pub listen_address: IpAddr,
+    /// Port the application sends to instead of VRChat.
+    pub port: u16,
+    /// Host the application runs on.
+    pub forward_host: String,
+    /// Port the application receives the messages from VRChat on.
+    pub forward_port: Option<u16>,
+    /// File the proxied traffic is appended to, in the format of recordings.
+    pub record: Option<PathBuf>,
+}
+
+impl Default for ProxyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            listen_address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: 9010,
+            forward_host: "127.0.0.1".to_string(),
+            forward_port: None,
+            record: None,
         }
     }
 }
 
 #[cfg(feature = "pishock")]
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(default)]
 pub struct PiShockConfig {
+    /// Username of your PiShock account.
     pub username: String,
+    /// API key of your PiShock account.
     pub api_key: String,
+    /// Share code of the shocker.
     pub code: String,
+    /// Duration of shocks in seconds.
     pub duration: u8,
 }
 
@@ -41,26 +831,600 @@ impl Default for PiShockConfig {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[cfg(feature = "watch")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WatchMode {
+    Float,
+    Bits,
+}
+
+#[cfg(feature = "watch")]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct WatchConfig {
+    /// Whether the time is sent as floats or as individual bits.
+    pub mode: WatchMode,
+    /// Whether hours are sent in 24-hour format.
+    pub twenty_four_hour: bool,
+    /// Bool parameters receiving the bits of the hour.
+    pub hour_parameters: Vec<String>,
+    /// Bool parameters receiving the bits of the minute.
+    pub minute_parameters: Vec<String>,
+    /// Chatbox line, where {time} is replaced with the time.
+    pub chatbox: Option<String>,
+}
+
+#[cfg(feature = "watch")]
+impl Default for WatchConfig {
+    fn default() -> Self {
+        Self {
+            mode: WatchMode::Float,
+            twenty_four_hour: false,
+            hour_parameters: vec![],
+            minute_parameters: vec![],
+            chatbox: None,
+        }
+    }
+}
+
+#[cfg(feature = "control")]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct ControlConfig {
+    /// Whether the control API is served.
+    pub enabled: bool,
+    /// Address the control API listens on.
+    pub listen_address: IpAddr,
+    /// Port the control API listens on.
+    pub port: u16,
+    /// Bearer token, generated on first start when empty.
+    pub token: String,
+    /// Origins of web pages allowed to use the API.
+    pub allowed_origins: Vec<String>,
+    /// Whether the API is served over HTTPS.
+    pub tls: bool,
+    /// Additional host names or IPs of the generated certificate.
+    pub tls_names: Vec<String>,
+}
+
+#[cfg(feature = "control")]
+impl Default for ControlConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            listen_address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: 9002,
+            token: "".to_string(),
+            allowed_origins: vec![],
+            tls: false,
+            tls_names: vec![],
+        }
+    }
+}
+
+#[cfg(feature = "dbus_service")]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default)]
+#[serde(default)]
+pub struct DbusConfig {
+    /// Whether the D-Bus service is registered.
+    pub enabled: bool,
+}
+
+#[cfg(feature = "hotkey")]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default)]
+#[serde(default)]
+pub struct HotkeyConfig {
+    /// Key combination sending the clipboard to the chatbox, like `ctrl+shift+v`. Nothing is
+    /// registered while empty.
+    pub paste: String,
+}
+
+#[cfg(feature = "ipc")]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default)]
+#[serde(default)]
+pub struct IpcConfig {
+    /// Whether the IPC interface is served.
+    pub enabled: bool,
+    /// Path of the socket or name of the pipe, defaulting to one within the data directory on
+    /// Linux and `\\.\pipe\vrc-osc-manager` on Windows.
+    pub path: String,
+}
+
+#[cfg(feature = "menu_settings")]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct MenuSettingsConfig {
+    /// Whether plugin options can be changed through avatar parameters.
+    pub enabled: bool,
+    /// Address prefix of the parameters, followed by the plugin name and the option.
+    pub prefix: String,
+}
+
+#[cfg(feature = "menu_settings")]
+impl Default for MenuSettingsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            prefix: "/avatar/parameters/OSCM".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct ConfigWatcherConfig {
+    /// Whether changes to this file are applied right away instead of on reload from the tray.
+    pub enabled: bool,
+    /// Time without further changes before they are applied, as editors save in several steps.
+    pub debounce_ms: u64,
+}
+
+impl Default for ConfigWatcherConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            debounce_ms: 500,
+        }
+    }
+}
+
+#[cfg(feature = "installer")]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default)]
+#[serde(default)]
+pub struct PluginIndexConfig {
+    /// URL of the plugin index to install plugins from.
+    pub url: String,
+    /// Base64 encoded ed25519 public keys of trusted publishers, by the name the index lists.
+    pub publishers: HashMap<String, String>,
+    /// Whether plugins without a valid signature of a trusted publisher can be installed anyway.
+    pub developer_mode: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default)]
 #[serde(default)]
 pub struct Config {
+    /// Plugins which are not started.
+    pub disabled_plugins: Vec<String>,
+
+    /// Plugins whose failure shuts down the application.
+    pub required_plugins: Vec<String>,
+
+    /// Parameters set back once the plugins stop.
+    pub resets: Vec<ResetConfig>,
+
+    /// Connection to VRChat and processing of messages.
     pub osc: OscConfig,
 
+    /// Appearance of the tray icon.
+    pub tray: TrayConfig,
+
+    /// Forwarding between instances on multiple machines.
+    pub cluster: ClusterConfig,
+
+    /// Proxy between another OSC application and VRChat.
+    pub proxy: ProxyConfig,
+
+    /// Idle mode while AFK.
+    pub idle: IdleConfig,
+
+    /// Round-trip test of the OSC link after startup.
+    pub self_test: SelfTestConfig,
+
+    /// Continuous measurement of the round trip through the avatar.
+    pub ping: PingConfig,
+
+    /// Recovery of stuck subsystems.
+    pub watchdog: WatchdogConfig,
+
+    /// Summaries of the time spent in VRChat.
+    pub session: SessionConfig,
+
+    /// JSON lines log of events.
+    pub event_log: EventLogConfig,
+
+    /// Recording of the OSC traffic.
+    pub monitor: MonitorConfig,
+
+    /// World lookup through the Steam profile.
+    #[cfg(feature = "steam_presence")]
+    pub steam_presence: SteamPresenceConfig,
+
+    /// Friends and world names from the VRChat API.
+    #[cfg(feature = "vrchat_api")]
+    pub vrchat_api: VrchatApiConfig,
+
+    /// Throttling of plugins under load.
+    pub governor: GovernorConfig,
+
+    /// Rate of chatbox messages.
+    pub chatbox: ChatboxConfig,
+
+    /// Processing of face tracking parameters.
+    pub face_tracking: FaceTrackingConfig,
+
+    /// Reload on changes of the config file.
+    pub config_watcher: ConfigWatcherConfig,
+
+    /// Named time windows for plugins, rules and routes.
+    pub schedules: HashMap<String, ScheduleConfig>,
+
+    /// Schedules plugins only run within, by plugin name.
+    pub plugin_schedules: HashMap<String, String>,
+
+    /// Watch plugin.
+    #[cfg(feature = "watch")]
+    pub watch: WatchConfig,
+
+    /// PiShock plugin.
     #[cfg(feature = "pishock")]
     pub pishock: PiShockConfig,
+
+    /// Index of installable plugins.
+    #[cfg(feature = "installer")]
+    pub plugin_index: PluginIndexConfig,
+
+    /// HTTP control API.
+    #[cfg(feature = "control")]
+    pub control: ControlConfig,
+
+    /// IPC interface over a local socket.
+    #[cfg(feature = "ipc")]
+    pub ipc: IpcConfig,
+
+    /// Desktop wide hotkeys.
+    #[cfg(feature = "hotkey")]
+    pub hotkey: HotkeyConfig,
+
+    /// D-Bus service on Linux.
+    #[cfg(feature = "dbus_service")]
+    pub dbus: DbusConfig,
+
+    /// Plugin options changed from the avatar menu.
+    #[cfg(feature = "menu_settings")]
+    pub menu_settings: MenuSettingsConfig,
+
+    /// Sections of the individual plugins.
+    #[serde(skip_serializing_if = "toml::Table::is_empty")]
+    #[schemars(with = "serde_json::Map<String, serde_json::Value>")]
+    pub plugins: toml::Table,
 }
 
-pub async fn load_config() -> Result<Config> {
+impl Config {
+    pub fn plugin_enabled(&self, name: &str) -> bool {
+        !self
+            .disabled_plugins
+            .iter()
+            .any(|disabled| disabled == name)
+    }
+
+    pub fn plugin_required(&self, name: &str) -> bool {
+        self.required_plugins
+            .iter()
+            .any(|required| required == name)
+    }
+
+    /// Messages resetting the parameters declared for the plugin, or for no plugin in particular.
+    pub fn reset_messages(&self, plugin: Option<&str>) -> Vec<OscMessage> {
+        self.resets
+            .iter()
+            .filter(|reset| reset.plugin.as_deref() == plugin)
+            .map(|reset| OscMessage {
+                addr: reset.address.clone(),
+                args: vec![reset.value.to_osc()],
+            })
+            .collect()
+    }
+
+    pub fn plugin_schedule(&self, name: &str) -> Option<&str> {
+        self.plugin_schedules.get(name).map(String::as_str)
+    }
+
+    fn validate(&self) -> Result<()> {
+        let references = self
+            .plugin_schedules
+            .values()
+            .chain(
+                self.osc
+                    .rules
+                    .iter()
+                    .filter_map(|rule| rule.schedule.as_ref()),
+            )
+            .chain(
+                self.osc
+                    .routes
+                    .iter()
+                    .filter_map(|route| route.schedule.as_ref()),
+            );
+
+        for name in references {
+            if !self.schedules.contains_key(name) {
+                bail!("Unknown schedule {}", name);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Warns about values sent to standard addresses which VRChat ignores, as it does so silently.
+    fn check_standard_addresses(&self) {
+        let values = self
+            .tray
+            .bookmarks
+            .iter()
+            .map(|bookmark| (&bookmark.address, &bookmark.value))
+            .chain(
+                self.resets
+                    .iter()
+                    .map(|reset| (&reset.address, &reset.value)),
+            );
+
+        for (address, value) in values {
+            if let Some(entry) = addresses::lookup(address) {
+                if !entry.accepts(&[value.to_osc()]) {
+                    warn!(
+                        "{} takes {}, VRChat ignores the value configured for it",
+                        address,
+                        entry.signature()
+                    );
+                }
+            }
+        }
+    }
+
+    /// Secondaries hand their output to the primary untouched, which then applies its own
+    /// processing on the way to VRChat.
+    pub fn send_config(&self) -> OscConfig {
+        match self.cluster.role {
+            ClusterRole::Secondary => OscConfig {
+                // Messages are forwarded as they are, so nothing must be deduplicated before
+                // the primary applies its processing.
+                deduplicate: false,
+                send_host: self.cluster.primary.clone(),
+                send_port: self.cluster.port,
+                ..OscConfig::default()
+            },
+            _ => self.osc.clone(),
+        }
+    }
+
+    /// The messages of VRChat reach the application behind the proxy as one more downstream
+    /// application, so they are passed on along a single path.
+    pub fn receive_config(&self) -> OscConfig {
+        let mut config = self.osc.clone();
+
+        if let (true, Some(port)) = (self.proxy.enabled, self.proxy.forward_port) {
+            config.downstream.push(DownstreamConfig {
+                destination: format!("{}:{}", self.proxy.forward_host, port),
+                address_prefixes: vec![],
+                rules: vec![],
+                float_steps: None,
+            });
+        }
+
+        config
+    }
+
+    pub fn plugin_config(&self, name: &str) -> PluginConfig {
+        match self.plugins.get(name) {
+            Some(toml::Value::Table(table)) => PluginConfig::new(table.clone()),
+            _ => PluginConfig::default(),
+        }
+    }
+}
+
+/// Removes the value at a dotted path like a plugin config section from a config value.
+fn take(value: &mut toml::Value, path: &str) -> Option<toml::Value> {
+    let (parent, key) = match path.rsplit_once('.') {
+        Some((parent, key)) => (
+            parent
+                .split('.')
+                .try_fold(value, |value, key| value.get_mut(key))?,
+            key,
+        ),
+        None => (value, path),
+    };
+
+    parent.as_table_mut()?.remove(key)
+}
+
+/// Plugins whose config section differs from the previous config, or `None` if anything outside
+/// of the plugin sections changed as well, which may affect every plugin.
+pub fn changed_plugins(config: &Arc<Config>, previous: &Config) -> Option<Vec<&'static str>> {
+    let mut current = toml::Value::try_from(&**config).ok()?;
+    let mut previous = toml::Value::try_from(previous).ok()?;
+    let mut changed = vec![];
+
+    for plugin in plugins::builtin(config) {
+        let section = plugin.config_section();
+
+        if take(&mut current, &section) != take(&mut previous, &section) {
+            changed.push(plugin.name());
+        }
+    }
+
+    (current == previous).then_some(changed)
+}
+
+/// JSON Schema of the config file, derived from the config structs of the enabled features.
+pub fn schema() -> RootSchema {
+    schema_for!(Config)
+}
+
+/// Follows references to the definition of a schema.
+fn resolve<'a>(
+    definitions: &'a BTreeMap<String, Schema>,
+    schema: &'a SchemaObject,
+) -> &'a SchemaObject {
+    let reference = schema.reference.as_ref().or_else(|| {
+        schema
+            .subschemas
+            .as_ref()
+            .and_then(|subschemas| subschemas.all_of.as_ref())
+            .and_then(|all_of| all_of.first())
+            .and_then(|first| match first {
+                Schema::Object(object) => object.reference.as_ref(),
+                Schema::Bool(_) => None,
+            })
+    });
+
+    match reference
+        .and_then(|reference| reference.strip_prefix("#/definitions/"))
+        .and_then(|name| definitions.get(name))
+    {
+        Some(Schema::Object(object)) => object,
+        _ => schema,
+    }
+}
+
+fn description<'a>(
+    definitions: &'a BTreeMap<String, Schema>,
+    schema: &'a SchemaObject,
+) -> Option<&'a str> {
+    let description = |schema: &'a SchemaObject| {
+        schema
+            .metadata
+            .as_ref()
+            .and_then(|metadata| metadata.description.as_deref())
+    };
+
+    description(schema).or_else(|| description(resolve(definitions, schema)))
+}
+
+fn write_comment(output: &mut String, text: &str) {
+    for line in text.lines() {
+        let _ = writeln!(output, "# {}", line);
+    }
+}
+
+fn write_table(
+    output: &mut String,
+    definitions: &BTreeMap<String, Schema>,
+    schema: &SchemaObject,
+    table: &toml::Table,
+    path: &str,
+) {
+    let schema = resolve(definitions, schema);
+    let properties = schema.object.as_ref().map(|object| &object.properties);
+    let property = |key: &str| match properties.and_then(|properties| properties.get(key)) {
+        Some(Schema::Object(object)) => Some(object),
+        _ => None,
+    };
+
+    // Plain values have to precede the sub tables of a table.
+    for (key, value) in table.iter().filter(|(_, value)| !value.is_table()) {
+        if let Some(text) = property(key).and_then(|schema| description(definitions, schema)) {
+            write_comment(output, text);
+        }
+
+        let _ = writeln!(output, "{} = {}", key, value);
+    }
+
+    // Options without a default are missing from the serialized config.
+    for (key, schema) in properties
+        .into_iter()
+        .flatten()
+        .filter(|(key, _)| !table.contains_key(key.as_str()))
+    {
+        if let Schema::Object(schema) = schema {
+            if let Some(text) = description(definitions, schema) {
+                write_comment(output, text);
+            }
+        }
+
+        let _ = writeln!(output, "# {} is not set by default", key);
+    }
+
+    for (key, value) in table {
+        let toml::Value::Table(value) = value else {
+            continue;
+        };
+
+        let path = if path.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", path, key)
+        };
+
+        output.push('\n');
+
+        if let Some(text) = property(key).and_then(|schema| description(definitions, schema)) {
+            write_comment(output, text);
+        }
+
+        let _ = writeln!(output, "[{}]", path);
+        let default_schema = SchemaObject::default();
+        write_table(
+            output,
+            definitions,
+            property(key).unwrap_or(&default_schema),
+            value,
+            &path,
+        );
+    }
+}
+
+/// Renders the config with a comment explaining every option, taken from the config schema.
+pub fn commented(config: &Config) -> Result<String> {
+    let schema = schema();
+    let toml::Value::Table(table) = toml::Value::try_from(config)? else {
+        bail!("Config did not serialize to a table");
+    };
+
+    let mut output = String::new();
+    write_comment(
+        &mut output,
+        &format!(
+            "Configuration of VRC OSC Manager v{}, every option is listed with its default.",
+            crate::VERSION
+        ),
+    );
+    output.push('\n');
+    write_table(&mut output, &schema.definitions, &schema.schema, &table, "");
+
+    // Writing a file the manager then refuses to start with would be worse than writing none.
+    parse_config(&output).context("Generated config does not parse back")?;
+
+    Ok(output)
+}
+
+/// Looks up a dotted path like a plugin config section within a config value.
+#[cfg(any(feature = "gui", feature = "menu_settings"))]
+pub fn lookup<'a>(value: &'a toml::Value, path: &str) -> Option<&'a toml::Value> {
+    path.split('.').try_fold(value, |value, key| value.get(key))
+}
+
+/// Looks up a dotted path within an editable config document, creating missing tables.
+#[cfg(any(feature = "gui", feature = "menu_settings"))]
+pub fn section_mut<'a>(
+    document: &'a mut toml_edit::Document,
+    path: &str,
+) -> &'a mut toml_edit::Item {
+    let mut keys = path.split('.');
+    let mut item = &mut document[keys.next().unwrap_or_default()];
+
+    for key in keys {
+        item = &mut item[key];
+    }
+
+    item
+}
+
+pub fn config_path() -> Result<PathBuf> {
     let base_dirs = BaseDirs::new().context("Base directories not available")?;
-    let config_dir = base_dirs.config_dir();
-    let path = config_dir.join("vrc-osc-manager.toml");
+    Ok(base_dirs.config_dir().join("vrc-osc-manager.toml"))
+}
+
+pub async fn load_config() -> Result<Config> {
+    let path = config_path()?;
 
     if metadata(&path).await.is_err() {
         let config: Config = Default::default();
+        let content = commented(&config)?;
         let mut file = File::create(&path)
             .await
             .with_context(|| format!("Failed to open {}", path.display()))?;
-        file.write_all(toml::to_string(&config)?.as_bytes()).await?;
+        file.write_all(content.as_bytes()).await?;
         return Ok(config);
     }
 
@@ -69,7 +1433,88 @@ pub async fn load_config() -> Result<Config> {
         .with_context(|| format!("Failed to open {}", path.display()))?;
     let mut toml_config = String::new();
     file.read_to_string(&mut toml_config).await?;
-    let config: Config = toml::from_str(&toml_config)?;
+
+    parse_config(&toml_config)
+}
+
+/// Line of a key within the config file, found by following the table headers, which covers the
+/// way keys are usually written by hand.
+fn line_of(toml_config: &str, path: &str) -> Option<usize> {
+    let (table, key) = path.rsplit_once('.').unwrap_or(("", path));
+    let mut current = String::new();
+    let mut array_lengths: HashMap<String, usize> = HashMap::new();
+
+    for (index, line) in toml_config.lines().enumerate() {
+        let line = line.trim();
+
+        if let Some(header) = line.strip_prefix("[[") {
+            let name = header.split("]]").next().unwrap_or_default().trim();
+            let length = array_lengths.entry(name.to_string()).or_default();
+            current = format!("{}.{}", name, length);
+            *length += 1;
+        } else if let Some(header) = line.strip_prefix('[') {
+            current = header
+                .split(']')
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .to_string();
+        } else if current == table {
+            if let Some((candidate, _)) = line.split_once('=') {
+                if candidate.trim().trim_matches('"') == key {
+                    return Some(index + 1);
+                }
+            }
+
+            continue;
+        }
+
+        if current == path {
+            return Some(index + 1);
+        }
+    }
+
+    None
+}
+
+/// Parses the config file strictly, rejecting keys that neither the config nor the section of a
+/// plugin knows about, as these are usually typos that would otherwise be silently ignored.
+pub fn parse_config(toml_config: &str) -> Result<Config> {
+    let mut unknown = vec![];
+    let config: Config =
+        serde_ignored::deserialize(toml::Deserializer::new(toml_config), |path| {
+            unknown.push(path.to_string())
+        })?;
+    config.validate()?;
+    config.check_standard_addresses();
+
+    let source: Arc<str> = Arc::from(toml_config);
+
+    for plugin in plugins::builtin(&Arc::new(config.clone())) {
+        let section = plugin.config_section();
+        let plugin_config = config
+            .plugin_config(plugin.name())
+            .with_source(source.clone(), &section);
+        let keys = plugin
+            .check_config(&plugin_config)
+            .with_context(|| format!("Invalid [{}] section", section))?;
+        unknown.extend(keys.into_iter().map(|key| format!("{}.{}", section, key)));
+    }
+
+    // Plugins with a section outside of `[plugins]` check keys the config file knows as well.
+    unknown.sort();
+    unknown.dedup();
+
+    if !unknown.is_empty() {
+        let keys: Vec<_> = unknown
+            .iter()
+            .map(|key| match line_of(toml_config, key) {
+                Some(line) => format!("{} (line {})", key, line),
+                None => key.clone(),
+            })
+            .collect();
+        warn!("Unknown keys in the config file: {}", keys.join(", "));
+    }
 
     Ok(config)
 }
@@ -0,0 +1,100 @@
+use crate::config::{config_path, parse_config, ConfigWatcherConfig};
+use crate::tray::TrayMessage;
+use anyhow::{bail, Context, Result};
+use debounced::debounced;
+use log::{debug, error, info};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::time::Duration;
+use tokio::fs::read_to_string;
+use tokio::sync::mpsc;
+use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::StreamExt;
+
+/// Reloads the plugins whenever the config file changes, the same way reloading from the tray
+/// does.
+pub struct ConfigWatcher {
+    config: ConfigWatcherConfig,
+    tray_tx: mpsc::Sender<TrayMessage>,
+}
+
+impl ConfigWatcher {
+    pub fn new(config: &ConfigWatcherConfig, tray_tx: mpsc::Sender<TrayMessage>) -> Self {
+        Self {
+            config: config.clone(),
+            tray_tx,
+        }
+    }
+
+    async fn watch(&self) -> Result<()> {
+        let path = config_path()?;
+        // Editors tend to replace the file instead of writing to it, which ends a watch on the
+        // file itself, so the directory is watched instead.
+        let dir = path
+            .parent()
+            .context("Config file has no parent directory")?
+            .to_path_buf();
+
+        let (event_tx, event_rx) = mpsc::channel(16);
+        let mut watcher: RecommendedWatcher =
+            notify::recommended_watcher(move |event: notify::Result<Event>| {
+                let _ = event_tx.blocking_send(event);
+            })?;
+        watcher
+            .watch(&dir, RecursiveMode::NonRecursive)
+            .with_context(|| format!("Failed to watch {}", dir.display()))?;
+
+        let watched = path.clone();
+        let events = ReceiverStream::new(event_rx).filter(move |event| match event {
+            Ok(event) => event.paths.contains(&watched),
+            Err(_) => true,
+        });
+        let mut events = debounced(events, Duration::from_millis(self.config.debounce_ms));
+        let mut current = read_to_string(&path).await.unwrap_or_default();
+
+        info!("Watching {} for changes", path.display());
+
+        while let Some(event) = events.next().await {
+            if let Err(error) = event {
+                error!("Failed to watch the config file: {}", error);
+                continue;
+            }
+
+            let Ok(toml_config) = read_to_string(&path).await else {
+                debug!("Config file is gone, waiting for it to come back");
+                continue;
+            };
+
+            if toml_config == current {
+                continue;
+            }
+
+            current = toml_config;
+
+            if let Err(error) = parse_config(&current) {
+                error!("Not applying the changed config: {:#}", error);
+                continue;
+            }
+
+            info!("Config file changed");
+            self.tray_tx.send(TrayMessage::ReloadPlugins).await?;
+        }
+
+        bail!("Config watcher stopped unexpectedly");
+    }
+
+    pub async fn run(self, subsys: SubsystemHandle) -> Result<()> {
+        if !self.config.enabled {
+            subsys.on_shutdown_requested().await;
+            return Ok(());
+        }
+
+        match (self.watch().cancel_on_shutdown(&subsys)).await {
+            Ok(Ok(())) => subsys.request_shutdown(),
+            Ok(Err(error)) => return Err(error),
+            Err(CancelledByShutdown) => {}
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,176 @@
+use crate::control::to_json;
+use crate::remote::{print_status, Remote, RemoteAction};
+use crate::rules;
+use anyhow::Result;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{stdin, AsyncBufReadExt, BufReader};
+use tokio::time::sleep;
+use vrc_osc_manager_core::addresses;
+
+const HELP: &str = "\
+Commands:
+  send <address> <value>  send a message, where the value is true, false, a number or a string
+  get [pattern]           show the last received values, optionally matching an address pattern
+  addresses [prefix]      list the standard addresses of VRChat, optionally starting with the prefix
+  watch <pattern>         print changes of parameters matching the pattern as they arrive
+  unwatch <pattern>       stop watching a pattern
+  enable <plugin>         enable a plugin until the config is reloaded
+  disable <plugin>        disable a plugin until the config is reloaded
+  status                  show the status of the manager
+  reload                  reload the config and restart the plugins
+  pause, resume           pause or resume the output
+  help                    show this help
+  quit                    leave the console";
+
+/// Polls the parameter cache of the manager, printing changes of watched addresses.
+async fn watch(remote: Arc<Remote>, patterns: Arc<Mutex<Vec<String>>>) {
+    let mut last: BTreeMap<String, Value> = BTreeMap::new();
+
+    loop {
+        sleep(Duration::from_millis(500)).await;
+
+        if patterns.lock().unwrap().is_empty() {
+            continue;
+        }
+
+        let Ok(parameters) = remote.parameters().await else {
+            continue;
+        };
+
+        let patterns = patterns.lock().unwrap().clone();
+
+        for (addr, value) in &parameters {
+            if patterns.iter().any(|pattern| rules::matches(pattern, addr))
+                && last.get(addr) != Some(value)
+            {
+                println!("{} = {}", addr, value);
+            }
+        }
+
+        last = parameters;
+    }
+}
+
+async fn execute(
+    remote: &Remote,
+    patterns: &Mutex<Vec<String>>,
+    command: &str,
+    argument: &str,
+) -> Result<bool> {
+    match command {
+        "send" => match argument.split_once(char::is_whitespace) {
+            Some((addr, value)) => {
+                let addr = addresses::parameter_address(addr);
+                let arg = addresses::parse_argument(value.trim());
+
+                // VRChat silently ignores values of the wrong type.
+                if let Some(entry) = addresses::lookup(&addr) {
+                    if !entry.accepts(std::slice::from_ref(&arg)) {
+                        println!("Warning: {} takes {}", addr, entry.signature());
+                    }
+                }
+
+                remote.send_message(&addr, to_json(&arg)).await?
+            }
+            None => println!("Usage: send <address> <value>"),
+        },
+        "get" => {
+            let pattern = if argument.is_empty() {
+                "*".to_string()
+            } else {
+                addresses::parameter_address(argument)
+            };
+
+            for (addr, value) in remote.parameters().await? {
+                if rules::matches(&pattern, &addr) {
+                    println!("{} = {}", addr, value);
+                }
+            }
+        }
+        "addresses" => {
+            for entry in addresses::completions(argument) {
+                println!(
+                    "{:<40} {:<22} {}",
+                    entry.address,
+                    entry.signature(),
+                    entry.description
+                );
+            }
+        }
+        "watch" if !argument.is_empty() => patterns
+            .lock()
+            .unwrap()
+            .push(addresses::parameter_address(argument)),
+        "unwatch" => patterns
+            .lock()
+            .unwrap()
+            .retain(|pattern| *pattern != addresses::parameter_address(argument)),
+        "enable" if !argument.is_empty() => remote.perform(&RemoteAction::Enable(argument)).await?,
+        "disable" if !argument.is_empty() => {
+            remote.perform(&RemoteAction::Disable(argument)).await?
+        }
+        "status" => print_status(&remote.status().await?),
+        "reload" => remote.perform(&RemoteAction::Reload).await?,
+        "pause" => remote.perform(&RemoteAction::Pause).await?,
+        "resume" => remote.perform(&RemoteAction::Resume).await?,
+        "help" => println!("{}", HELP),
+        "quit" | "exit" => return Ok(false),
+        "" => {}
+        _ => println!("Unknown command {}, type help for a list", command),
+    }
+
+    Ok(true)
+}
+
+/// Interactive prompt talking to a running manager through its control API.
+pub async fn run(
+    url: &str,
+    token: Option<&str>,
+    cert: Option<&Path>,
+    data_dir: &Path,
+) -> Result<()> {
+    // Locally, the generated token can be read right from the data directory.
+    let token = match token {
+        Some(token) => token.to_string(),
+        None => tokio::fs::read_to_string(data_dir.join("control-token"))
+            .await
+            .unwrap_or_default()
+            .trim()
+            .to_string(),
+    };
+
+    let remote = Arc::new(Remote::new(url, &token, cert).await?);
+    let patterns = Arc::new(Mutex::new(vec![]));
+    let watcher = tokio::spawn(watch(remote.clone(), patterns.clone()));
+
+    print_status(&remote.status().await?);
+    println!("Type help for a list of commands");
+
+    let mut lines = BufReader::new(stdin()).lines();
+
+    loop {
+        print!("> ");
+        std::io::stdout().flush()?;
+
+        let Some(line) = lines.next_line().await? else {
+            break;
+        };
+
+        let line = line.trim();
+        let (command, argument) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+
+        match execute(&remote, &patterns, command, argument.trim()).await {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(error) => println!("Error: {:#}", error),
+        }
+    }
+
+    watcher.abort();
+    Ok(())
+}
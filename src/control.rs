@@ -0,0 +1,642 @@
+use crate::accounting::PluginUsageStatus;
+use crate::backpressure::ChannelStatus;
+use crate::config::ControlConfig;
+use crate::secret::constant_time_eq;
+use crate::state::SharedState;
+use crate::tray::TrayMessage;
+use anyhow::{bail, Context, Result};
+use async_osc::{OscMessage, OscType};
+use hyper::header::{AUTHORIZATION, CONTENT_TYPE, ORIGIN, UPGRADE};
+use hyper::server::conn::Http;
+use hyper::service::service_fn;
+use hyper::{Body, Method, Request, Response, StatusCode};
+use log::{debug, error, info, warn};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::fs::{create_dir_all, metadata, read_to_string, OpenOptions};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio::time::{interval, sleep};
+use tokio::{select, spawn};
+use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
+use tokio_rustls::rustls::{Certificate, PrivateKey, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+use vrc_osc_manager_core::MetricSummary;
+
+#[cfg(feature = "chatbot")]
+use crate::plugins;
+#[cfg(feature = "chatbot")]
+use vrc_osc_manager_core::Command;
+
+#[derive(Serialize, Deserialize)]
+pub struct Status {
+    pub version: String,
+    pub plugins_running: bool,
+    pub output_paused: bool,
+    pub disabled_plugins: Vec<String>,
+    pub sent: u64,
+    pub received: u64,
+    /// Round trip through the avatar measured last, while pinging is enabled.
+    #[serde(default)]
+    pub round_trip_ms: Option<u64>,
+    /// World VRChat is in, if known.
+    #[serde(default)]
+    pub world: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Metrics {
+    channels: Vec<ChannelStatus>,
+    plugins: Vec<PluginUsageStatus>,
+}
+
+#[derive(Deserialize)]
+struct SendRequest {
+    address: String,
+    value: Value,
+}
+
+pub fn to_json(arg: &OscType) -> Value {
+    match arg {
+        OscType::Bool(value) => Value::from(*value),
+        OscType::Int(value) => Value::from(*value),
+        OscType::Long(value) => Value::from(*value),
+        OscType::Float(value) => Value::from(*value),
+        OscType::Double(value) => Value::from(*value),
+        OscType::String(value) => Value::from(value.as_str()),
+        _ => Value::Null,
+    }
+}
+
+pub fn from_json(value: Value) -> Option<OscType> {
+    match value {
+        Value::Bool(value) => Some(OscType::Bool(value)),
+        Value::Number(number) => match number.as_i64() {
+            Some(int) => i32::try_from(int).ok().map(OscType::Int),
+            None => number.as_f64().map(|float| OscType::Float(float as f32)),
+        },
+        Value::String(value) => Some(OscType::String(value)),
+        _ => None,
+    }
+}
+
+const DASHBOARD: &str = include_str!("../assets/dashboard.html");
+
+/// Number of samples kept per chart of the dashboard, one per minute for a day.
+const SAMPLES: usize = 24 * 60;
+
+#[derive(Clone, Copy, Serialize)]
+struct TrafficSample {
+    time: i64,
+    sent: u64,
+    received: u64,
+}
+
+#[derive(Clone, Copy, Serialize)]
+struct MetricSample {
+    time: i64,
+    value: f64,
+}
+
+/// What the dashboard charts over time.
+#[derive(Default)]
+struct History {
+    traffic: VecDeque<TrafficSample>,
+    /// Average per minute of the values plugins record, like the heart rate.
+    metrics: BTreeMap<String, VecDeque<MetricSample>>,
+}
+
+fn push_sample<T>(samples: &mut VecDeque<T>, sample: T) {
+    if samples.len() == SAMPLES {
+        samples.pop_front();
+    }
+
+    samples.push_back(sample);
+}
+
+/// Samples the traffic counters and the metrics every minute, storing what happened since the last
+/// sample.
+async fn sample(state: Arc<SharedState>, history: Arc<Mutex<History>>) {
+    let mut ticks = interval(Duration::from_secs(60));
+    let mut last = (state.stats.sent(), state.stats.received());
+    let mut last_metrics = HashMap::new();
+    ticks.tick().await;
+
+    loop {
+        ticks.tick().await;
+        let time = chrono::Local::now().timestamp();
+        let current = (state.stats.sent(), state.stats.received());
+        let mut history = history.lock().unwrap();
+
+        push_sample(
+            &mut history.traffic,
+            TrafficSample {
+                time,
+                sent: current.0 - last.0,
+                received: current.1 - last.1,
+            },
+        );
+        last = current;
+
+        for (name, summary) in state.metrics.snapshot() {
+            // Summaries start over with every session.
+            let (count, sum) = match last_metrics.get(&name) {
+                Some(MetricSummary { count, sum, .. }) if *count <= summary.count => {
+                    (summary.count - count, summary.sum - sum)
+                }
+                _ => (summary.count, summary.sum),
+            };
+
+            if count > 0 {
+                push_sample(
+                    history.metrics.entry(name.clone()).or_default(),
+                    MetricSample {
+                        time,
+                        value: sum / count as f64,
+                    },
+                );
+            }
+
+            last_metrics.insert(name, summary);
+        }
+    }
+}
+
+/// Reads the token generated on a previous start, or generates a new one readable only by the user.
+async fn load_token(path: &Path) -> Result<String> {
+    if let Ok(token) = read_to_string(path).await {
+        let token = token.trim();
+
+        // An empty token would let every request through.
+        if token.is_empty() {
+            bail!(
+                "{} is empty, delete it to generate a new token",
+                path.display()
+            );
+        }
+
+        return Ok(token.to_string());
+    }
+
+    let token: String = rand::thread_rng()
+        .gen::<[u8; 32]>()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+
+    write_private(path, &token).await?;
+    info!("Generated control API token in {}", path.display());
+    Ok(token)
+}
+
+async fn write_private(path: &Path, contents: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent).await?;
+    }
+
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+
+    let mut file = options
+        .open(path)
+        .await
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    file.write_all(contents.as_bytes()).await?;
+    Ok(())
+}
+
+/// Loads the certificate and key for TLS, generating a self-signed certificate on first use.
+async fn load_tls_config(config: &ControlConfig, data_dir: &Path) -> Result<ServerConfig> {
+    let cert_path = data_dir.join("control-cert.pem");
+    let key_path = data_dir.join("control-key.pem");
+
+    if metadata(&cert_path).await.is_err() {
+        let mut names = vec!["localhost".to_string()];
+
+        if !config.listen_address.is_unspecified() {
+            names.push(config.listen_address.to_string());
+        }
+
+        names.extend(config.tls_names.iter().cloned());
+
+        let cert = rcgen::generate_simple_self_signed(names)?;
+        write_private(&key_path, &cert.serialize_private_key_pem()).await?;
+        tokio::fs::write(&cert_path, cert.serialize_pem()?).await?;
+        info!(
+            "Generated self-signed certificate in {}",
+            cert_path.display()
+        );
+    }
+
+    let certs: Vec<Certificate> =
+        rustls_pemfile::certs(&mut read_to_string(&cert_path).await?.as_bytes())?
+            .into_iter()
+            .map(Certificate)
+            .collect();
+    let key = rustls_pemfile::pkcs8_private_keys(&mut read_to_string(&key_path).await?.as_bytes())?
+        .into_iter()
+        .next()
+        .with_context(|| format!("No private key found in {}", key_path.display()))?;
+
+    if let Some(cert) = certs.first() {
+        let fingerprint: Vec<String> = Sha256::digest(&cert.0)
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect();
+        info!(
+            "Control API certificate fingerprint: {}",
+            fingerprint.join(":")
+        );
+    }
+
+    Ok(ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(certs, PrivateKey(key))?)
+}
+
+async fn serve_connection<S>(stream: S, handler: Arc<Handler>)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let service = service_fn(move |request| {
+        let handler = handler.clone();
+        async move { Ok::<_, Infallible>(handler.handle(request).await) }
+    });
+
+    if let Err(error) = Http::new().serve_connection(stream, service).await {
+        debug!("Control API connection failed: {}", error);
+    }
+}
+
+fn respond(status: StatusCode, body: impl Into<Body>) -> Response<Body> {
+    let mut response = Response::new(body.into());
+    *response.status_mut() = status;
+    response
+}
+
+struct Handler {
+    token: String,
+    allowed_origins: Vec<String>,
+    data_dir: PathBuf,
+    state: Arc<SharedState>,
+    history: Arc<Mutex<History>>,
+    tray_tx: mpsc::Sender<TrayMessage>,
+}
+
+impl Handler {
+    /// Returns the response rejecting the request, unless it is authorized.
+    fn reject(&self, request: &Request<Body>) -> Option<Response<Body>> {
+        // Browsers always send the origin with cross-origin requests, which keeps malicious web
+        // pages from talking to the API even before checking the token.
+        if let Some(origin) = request.headers().get(ORIGIN) {
+            if !self
+                .allowed_origins
+                .iter()
+                .any(|allowed| allowed.as_bytes() == origin.as_bytes())
+            {
+                return Some(respond(StatusCode::FORBIDDEN, "Origin not allowed"));
+            }
+        }
+
+        let authorized = request
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map_or(false, |token| {
+                constant_time_eq(token.as_bytes(), self.token.as_bytes())
+            });
+
+        if !authorized {
+            return Some(respond(StatusCode::UNAUTHORIZED, "Invalid token"));
+        }
+
+        None
+    }
+
+    async fn send(&self, message: TrayMessage) -> Response<Body> {
+        match self.tray_tx.send(message).await {
+            Ok(()) => respond(StatusCode::ACCEPTED, ""),
+            Err(_) => respond(StatusCode::SERVICE_UNAVAILABLE, "Shutting down"),
+        }
+    }
+
+    fn json(value: &impl Serialize) -> Response<Body> {
+        match serde_json::to_string(value) {
+            Ok(body) => {
+                let mut response = respond(StatusCode::OK, body);
+                response
+                    .headers_mut()
+                    .insert(CONTENT_TYPE, "application/json".parse().unwrap());
+                response
+            }
+            Err(error) => respond(StatusCode::INTERNAL_SERVER_ERROR, error.to_string()),
+        }
+    }
+
+    async fn history(&self) -> Response<Body> {
+        // Interaction totals are only there when the interactions plugin ran at some point.
+        let interactions = read_to_string(self.data_dir.join("interactions.toml"))
+            .await
+            .ok()
+            .and_then(|contents| toml::from_str::<toml::Table>(&contents).ok())
+            .unwrap_or_default();
+        let shocks: Vec<_> = self
+            .state
+            .recent_events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, event)| event.kind.starts_with("shock_"))
+            .map(|(time, event)| serde_json::json!({ "time": time, "event": event }))
+            .collect();
+        let history = self.history.lock().unwrap();
+
+        Self::json(&serde_json::json!({
+            "traffic": history.traffic,
+            "metrics": history.metrics,
+            "shocks": shocks,
+            "interactions": interactions,
+        }))
+    }
+
+    fn parameters(&self) -> Response<Body> {
+        let parameters: BTreeMap<_, _> = self
+            .state
+            .parameters
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(addr, message)| {
+                (
+                    addr.clone(),
+                    message.args.first().map_or(Value::Null, to_json),
+                )
+            })
+            .collect();
+
+        Self::json(&parameters)
+    }
+
+    async fn send_message(&self, request: Request<Body>) -> Response<Body> {
+        let body = match hyper::body::to_bytes(request.into_body()).await {
+            Ok(body) => body,
+            Err(error) => return respond(StatusCode::BAD_REQUEST, error.to_string()),
+        };
+
+        let request: SendRequest = match serde_json::from_slice(&body) {
+            Ok(request) => request,
+            Err(error) => return respond(StatusCode::BAD_REQUEST, error.to_string()),
+        };
+
+        let Some(arg) = from_json(request.value) else {
+            return respond(StatusCode::BAD_REQUEST, "Unsupported value");
+        };
+
+        self.send(TrayMessage::Send(OscMessage {
+            addr: request.address,
+            args: vec![arg],
+        }))
+        .await
+    }
+
+    fn status(&self) -> Response<Body> {
+        let status = Status {
+            version: crate::VERSION.to_string(),
+            plugins_running: self.state.plugins_running.load(Ordering::Relaxed),
+            output_paused: self.state.output_paused.load(Ordering::Relaxed),
+            disabled_plugins: self.state.disabled_plugins.lock().unwrap().clone(),
+            sent: self.state.stats.sent(),
+            received: self.state.stats.received(),
+            round_trip_ms: self.state.stats.round_trip_ms(),
+            world: self.state.world.lock().unwrap().clone(),
+        };
+
+        Self::json(&status)
+    }
+
+    async fn handle(&self, request: Request<Body>) -> Response<Body> {
+        // The dashboard page itself holds no data, it authenticates its own requests. Upgrades to
+        // other protocols like WebSocket are authorized on every path.
+        if request.method() == Method::GET
+            && request.uri().path() == "/"
+            && !request.headers().contains_key(UPGRADE)
+        {
+            let mut response = respond(StatusCode::OK, DASHBOARD);
+            response
+                .headers_mut()
+                .insert(CONTENT_TYPE, "text/html; charset=utf-8".parse().unwrap());
+            return response;
+        }
+
+        if let Some(response) = self.reject(&request) {
+            return response;
+        }
+
+        #[cfg(feature = "chatbot")]
+        if request.method() == Method::POST && request.uri().path() == "/chatbot" {
+            return match hyper::body::to_bytes(request.into_body()).await {
+                Ok(body) => {
+                    self.state.commands.send(Command::new(
+                        "chatbot",
+                        plugins::chatbot::PROMPT_COMMAND,
+                        String::from_utf8_lossy(&body),
+                    ));
+                    respond(StatusCode::ACCEPTED, "")
+                }
+                Err(error) => respond(StatusCode::BAD_REQUEST, error.to_string()),
+            };
+        }
+
+        if request.method() == Method::POST && request.uri().path() == "/send" {
+            return self.send_message(request).await;
+        }
+
+        if request.method() == Method::POST
+            && matches!(request.uri().path(), "/freeze" | "/unfreeze")
+        {
+            let frozen = request.uri().path() == "/freeze";
+
+            return match hyper::body::to_bytes(request.into_body()).await {
+                Ok(body) => {
+                    let pattern = String::from_utf8_lossy(&body);
+
+                    if pattern.trim().is_empty() {
+                        return respond(StatusCode::BAD_REQUEST, "Missing address pattern");
+                    }
+
+                    self.state.set_frozen(pattern.trim(), frozen);
+                    respond(StatusCode::NO_CONTENT, "")
+                }
+                Err(error) => respond(StatusCode::BAD_REQUEST, error.to_string()),
+            };
+        }
+
+        let path = request.uri().path();
+
+        if let Some((name, action)) = path
+            .strip_prefix("/plugins/")
+            .and_then(|rest| rest.split_once('/'))
+        {
+            let enabled = match (request.method(), action) {
+                (&Method::POST, "enable") => true,
+                (&Method::POST, "disable") => false,
+                _ => return respond(StatusCode::NOT_FOUND, "Not found"),
+            };
+
+            return self
+                .send(TrayMessage::SetPluginEnabled(name.to_string(), enabled))
+                .await;
+        }
+
+        match (request.method(), path) {
+            (&Method::GET, "/status") => self.status(),
+            (&Method::GET, "/history") => self.history().await,
+            (&Method::GET, "/parameters") => self.parameters(),
+            (&Method::GET, "/frozen") => Self::json(&*self.state.frozen.lock().unwrap()),
+            (&Method::GET, "/schema") => Self::json(&crate::config::schema()),
+            (&Method::GET, "/metrics") => Self::json(&Metrics {
+                channels: self.state.channels.snapshot(),
+                plugins: self.state.accounting.snapshot(),
+            }),
+            (&Method::POST, "/reload") => self.send(TrayMessage::ReloadPlugins).await,
+            (&Method::POST, "/pause") => self.send(TrayMessage::SetPaused(true)).await,
+            (&Method::POST, "/resume") => self.send(TrayMessage::SetPaused(false)).await,
+            _ => respond(StatusCode::NOT_FOUND, "Not found"),
+        }
+    }
+}
+
+/// HTTP API for controlling the manager remotely, protected by a bearer token.
+pub struct ControlServer {
+    config: ControlConfig,
+    data_dir: PathBuf,
+    state: Arc<SharedState>,
+    tray_tx: mpsc::Sender<TrayMessage>,
+}
+
+impl ControlServer {
+    pub fn new(
+        config: &ControlConfig,
+        data_dir: &Path,
+        state: Arc<SharedState>,
+        tray_tx: mpsc::Sender<TrayMessage>,
+    ) -> Self {
+        Self {
+            config: config.clone(),
+            data_dir: data_dir.to_path_buf(),
+            state,
+            tray_tx,
+        }
+    }
+
+    async fn token(&self) -> Result<String> {
+        match self.config.token.trim() {
+            "" => load_token(&self.data_dir.join("control-token")).await,
+            token => Ok(token.to_string()),
+        }
+    }
+
+    async fn serve(&self, token: String) -> Result<()> {
+        let history = Arc::new(Mutex::new(History::default()));
+        let handler = Arc::new(Handler {
+            token,
+            allowed_origins: self.config.allowed_origins.clone(),
+            data_dir: self.data_dir.clone(),
+            state: self.state.clone(),
+            history: history.clone(),
+            tray_tx: self.tray_tx.clone(),
+        });
+
+        let acceptor = if self.config.tls {
+            let tls_config = load_tls_config(&self.config, &self.data_dir).await?;
+            Some(TlsAcceptor::from(Arc::new(tls_config)))
+        } else {
+            None
+        };
+
+        let address = SocketAddr::new(self.config.listen_address, self.config.port);
+
+        if !address.ip().is_loopback() && acceptor.is_none() {
+            warn!(
+                "Control API is reachable from other devices on {} without TLS",
+                address
+            );
+        }
+
+        let listener = TcpListener::bind(address)
+            .await
+            .with_context(|| format!("Failed to listen on {}", address))?;
+        info!("Control API listening on {}", address);
+
+        // The sampler runs within the API, so it stops together with it.
+        let sampler = sample(self.state.clone(), history);
+        tokio::pin!(sampler);
+
+        loop {
+            let accepted = select! {
+                () = &mut sampler => bail!("Dashboard sampler stopped unexpectedly"),
+                accepted = listener.accept() => accepted,
+            };
+
+            // Errors like running out of file descriptors pass, so they do not stop the API.
+            let (stream, peer) = match accepted {
+                Ok(connection) => connection,
+                Err(error) => {
+                    warn!("Failed to accept a control API connection: {}", error);
+                    sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+            };
+            let handler = handler.clone();
+            let acceptor = acceptor.clone();
+
+            spawn(async move {
+                match acceptor {
+                    Some(acceptor) => match acceptor.accept(stream).await {
+                        Ok(stream) => serve_connection(stream, handler).await,
+                        Err(error) => debug!("TLS handshake with {} failed: {}", peer, error),
+                    },
+                    None => serve_connection(stream, handler).await,
+                }
+            });
+        }
+    }
+
+    pub async fn run(self, subsys: SubsystemHandle) -> Result<()> {
+        if !self.config.enabled {
+            subsys.on_shutdown_requested().await;
+            return Ok(());
+        }
+
+        let token = match self.token().await {
+            Ok(token) => token,
+            Err(error) => {
+                error!("Not starting the control API: {:#}", error);
+                subsys.on_shutdown_requested().await;
+                return Ok(());
+            }
+        };
+
+        match (self.serve(token).cancel_on_shutdown(&subsys)).await {
+            Ok(Ok(())) => subsys.request_shutdown(),
+            Ok(Err(error)) => return Err(error),
+            Err(CancelledByShutdown) => {}
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,234 @@
+use crate::state::SharedState;
+use crate::tray::TrayMessage;
+use anyhow::{bail, Context, Result};
+use async_osc::{OscMessage, OscType};
+use dbus::arg::{ArgType, RefArg, Variant};
+use dbus::channel::{MatchingReceiver, Sender};
+use dbus::message::MatchRule;
+use dbus::MethodErr;
+use dbus_crossroads::Crossroads;
+use log::{info, warn};
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use tokio::select;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, mpsc};
+use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
+
+const NAME: &str = "org.vrcoscmanager";
+const PATH: &str = "/org/vrcoscmanager";
+
+struct Handler {
+    state: Arc<SharedState>,
+    tray_tx: mpsc::Sender<TrayMessage>,
+}
+
+impl Handler {
+    fn send(&self, message: TrayMessage) -> Result<(), MethodErr> {
+        self.tray_tx
+            .try_send(message)
+            .map_err(|error| MethodErr::failed(&error))
+    }
+}
+
+fn to_arg(value: &Variant<Box<dyn RefArg>>) -> Option<OscType> {
+    let value = &value.0;
+
+    match value.arg_type() {
+        ArgType::Boolean => value.as_i64().map(|value| OscType::Bool(value != 0)),
+        ArgType::Double => value.as_f64().map(|value| OscType::Float(value as f32)),
+        ArgType::String => value
+            .as_str()
+            .map(|value| OscType::String(value.to_string())),
+        _ => value
+            .as_i64()
+            .and_then(|value| i32::try_from(value).ok())
+            .map(OscType::Int),
+    }
+}
+
+fn to_variant(arg: &OscType) -> Option<Variant<Box<dyn RefArg>>> {
+    let value: Box<dyn RefArg> = match arg {
+        OscType::Bool(value) => Box::new(*value),
+        OscType::Int(value) => Box::new(*value),
+        OscType::Float(value) => Box::new(*value as f64),
+        OscType::String(value) => Box::new(value.clone()),
+        _ => return None,
+    };
+
+    Some(Variant(value))
+}
+
+/// Session bus service for desktop integrations, e.g. shell extensions showing avatar state.
+pub struct DbusService {
+    enabled: bool,
+    rx: broadcast::Receiver<Arc<OscMessage>>,
+    state: Arc<SharedState>,
+    tray_tx: mpsc::Sender<TrayMessage>,
+}
+
+impl DbusService {
+    pub fn new(
+        enabled: bool,
+        rx: broadcast::Receiver<Arc<OscMessage>>,
+        state: Arc<SharedState>,
+        tray_tx: mpsc::Sender<TrayMessage>,
+    ) -> Self {
+        Self {
+            enabled,
+            rx,
+            state,
+            tray_tx,
+        }
+    }
+
+    async fn serve(&mut self) -> Result<()> {
+        let (resource, connection) = dbus_tokio::connection::new_session_sync()
+            .context("Failed to connect to the session bus")?;
+        let mut resource = tokio::spawn(resource);
+
+        connection
+            .request_name(NAME, false, true, true)
+            .await
+            .with_context(|| format!("Failed to acquire the bus name {}", NAME))?;
+
+        let mut crossroads = Crossroads::new();
+        let mut parameter_changed = None;
+
+        let interface = crossroads.register(NAME, |builder| {
+            builder.method(
+                "Send",
+                ("address", "value"),
+                (),
+                |_, handler: &mut Handler, (address, value): (String, Variant<Box<dyn RefArg>>)| {
+                    let arg = to_arg(&value)
+                        .ok_or_else(|| MethodErr::invalid_arg("Unsupported value type"))?;
+
+                    handler.send(TrayMessage::Send(OscMessage {
+                        addr: address,
+                        args: vec![arg],
+                    }))
+                },
+            );
+            builder.method("Reload", (), (), |_, handler: &mut Handler, ()| {
+                handler.send(TrayMessage::ReloadPlugins)
+            });
+            builder.method(
+                "SetPaused",
+                ("paused",),
+                (),
+                |_, handler: &mut Handler, (paused,): (bool,)| {
+                    handler.send(TrayMessage::SetPaused(paused))
+                },
+            );
+            builder.method(
+                "SetPluginEnabled",
+                ("plugin", "enabled"),
+                (),
+                |_, handler: &mut Handler, (plugin, enabled): (String, bool)| {
+                    handler.send(TrayMessage::SetPluginEnabled(plugin, enabled))
+                },
+            );
+            builder.method(
+                "Status",
+                (),
+                ("plugins_running", "output_paused", "sent", "received"),
+                |_, handler: &mut Handler, ()| {
+                    let state = &handler.state;
+
+                    Ok((
+                        state.plugins_running.load(Ordering::Relaxed),
+                        state.output_paused.load(Ordering::Relaxed),
+                        state.stats.sent(),
+                        state.stats.received(),
+                    ))
+                },
+            );
+            parameter_changed = Some(
+                builder
+                    .signal::<(String, Variant<Box<dyn RefArg>>), _>(
+                        "ParameterChanged",
+                        ("address", "value"),
+                    )
+                    .msg_fn(),
+            );
+        });
+        let parameter_changed = parameter_changed.unwrap();
+
+        crossroads.insert(
+            PATH,
+            &[interface],
+            Handler {
+                state: self.state.clone(),
+                tray_tx: self.tray_tx.clone(),
+            },
+        );
+
+        connection.start_receive(
+            MatchRule::new_method_call(),
+            Box::new(move |message, connection| {
+                let _ = crossroads.handle_message(message, connection);
+                true
+            }),
+        );
+
+        info!("D-Bus service registered as {}", NAME);
+
+        // VRChat repeats some parameters without them changing, which would only flood the bus.
+        let mut last_values: HashMap<String, OscType> = HashMap::new();
+        let path = PATH.into();
+
+        loop {
+            select! {
+                result = &mut resource => {
+                    bail!("Lost connection to the session bus: {:?}", result);
+                }
+                result = self.rx.recv() => {
+                    let message = match result {
+                        Ok(message) => message,
+                        Err(RecvError::Lagged(skipped)) => {
+                            warn!(
+                                "D-Bus service lagging behind, {} messages have been dropped",
+                                skipped
+                            );
+                            continue;
+                        }
+                        Err(RecvError::Closed) => bail!("Message receiver died unexpectedly"),
+                    };
+
+                    let Some(arg) = message.args.first() else {
+                        continue;
+                    };
+
+                    if last_values.get(&message.addr) == Some(arg) {
+                        continue;
+                    }
+
+                    if let Some(value) = to_variant(arg) {
+                        let _ = connection.send(parameter_changed(&path, &(message.addr.clone(), value)));
+                    }
+
+                    last_values.insert(message.addr.clone(), arg.clone());
+                }
+            }
+        }
+    }
+
+    pub async fn run(mut self, subsys: SubsystemHandle) -> Result<()> {
+        if !self.enabled {
+            // Unread messages would pile up in the receiver otherwise.
+            drop(self);
+            subsys.on_shutdown_requested().await;
+            return Ok(());
+        }
+
+        match (self.serve().cancel_on_shutdown(&subsys)).await {
+            Ok(Ok(())) => subsys.request_shutdown(),
+            Ok(Err(error)) => return Err(error),
+            Err(CancelledByShutdown) => {}
+        }
+
+        Ok(())
+    }
+}
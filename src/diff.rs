@@ -0,0 +1,176 @@
+use crate::recording::{read_recording, Argument, Direction, Record};
+use anyhow::Result;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+/// Rates are only reported as different when they deviate by more than this fraction.
+const RATE_TOLERANCE: f64 = 0.25;
+
+#[derive(Default)]
+struct AddressStats {
+    count: usize,
+    range: Option<(f64, f64)>,
+    strings: BTreeSet<String>,
+}
+
+struct Session {
+    duration: f64,
+    addresses: BTreeMap<(Direction, String), AddressStats>,
+}
+
+impl Session {
+    fn new(records: &[Record]) -> Self {
+        let start = records
+            .iter()
+            .map(|record| record.time)
+            .fold(f64::MAX, f64::min);
+        let end = records
+            .iter()
+            .map(|record| record.time)
+            .fold(f64::MIN, f64::max);
+        let mut addresses: BTreeMap<(Direction, String), AddressStats> = BTreeMap::new();
+
+        for record in records {
+            let stats = addresses
+                .entry((record.direction, record.addr.clone()))
+                .or_default();
+            stats.count += 1;
+
+            for arg in &record.args {
+                if let Argument::String(value) = arg {
+                    stats.strings.insert(value.clone());
+                }
+
+                if let Some(value) = arg.as_f64() {
+                    stats.range = Some(match stats.range {
+                        Some((min, max)) => (min.min(value), max.max(value)),
+                        None => (value, value),
+                    });
+                }
+            }
+        }
+
+        Self {
+            duration: if end > start { end - start } else { 0. },
+            addresses,
+        }
+    }
+
+    fn rate(&self, stats: &AddressStats) -> Option<f64> {
+        if self.duration > 0. {
+            Some(stats.count as f64 / self.duration)
+        } else {
+            None
+        }
+    }
+}
+
+fn describe(direction: Direction, addr: &str) -> String {
+    match direction {
+        Direction::Incoming => format!("<- {}", addr),
+        Direction::Outgoing => format!("-> {}", addr),
+    }
+}
+
+pub async fn run(first_path: &Path, second_path: &Path) -> Result<()> {
+    let first = Session::new(&read_recording(first_path).await?);
+    let second = Session::new(&read_recording(second_path).await?);
+
+    println!(
+        "Comparing {} ({:.1}s) with {} ({:.1}s)",
+        first_path.display(),
+        first.duration,
+        second_path.display(),
+        second.duration
+    );
+
+    let mut only_first = vec![];
+    let mut only_second = vec![];
+    let mut changed = vec![];
+
+    for ((direction, addr), first_stats) in &first.addresses {
+        let second_stats = match second.addresses.get(&(*direction, addr.clone())) {
+            Some(stats) => stats,
+            None => {
+                only_first.push(describe(*direction, addr));
+                continue;
+            }
+        };
+
+        let mut differences = vec![];
+
+        if first_stats.range != second_stats.range {
+            let format_range = |range: Option<(f64, f64)>| match range {
+                Some((min, max)) => format!("{:.3}..{:.3}", min, max),
+                None => "none".to_string(),
+            };
+
+            differences.push(format!(
+                "value range {} vs {}",
+                format_range(first_stats.range),
+                format_range(second_stats.range)
+            ));
+        }
+
+        for value in first_stats.strings.difference(&second_stats.strings) {
+            differences.push(format!("value {:?} only in first", value));
+        }
+
+        for value in second_stats.strings.difference(&first_stats.strings) {
+            differences.push(format!("value {:?} only in second", value));
+        }
+
+        if let (Some(first_rate), Some(second_rate)) =
+            (first.rate(first_stats), second.rate(second_stats))
+        {
+            if (first_rate - second_rate).abs() > first_rate.max(second_rate) * RATE_TOLERANCE {
+                differences.push(format!("rate {:.2}/s vs {:.2}/s", first_rate, second_rate));
+            }
+        }
+
+        if !differences.is_empty() {
+            changed.push((describe(*direction, addr), differences));
+        }
+    }
+
+    for (direction, addr) in second.addresses.keys() {
+        if !first.addresses.contains_key(&(*direction, addr.clone())) {
+            only_second.push(describe(*direction, addr));
+        }
+    }
+
+    if only_first.is_empty() && only_second.is_empty() && changed.is_empty() {
+        println!("No differences found");
+        return Ok(());
+    }
+
+    if !only_first.is_empty() {
+        println!("\nOnly in {}:", first_path.display());
+
+        for addr in only_first {
+            println!("  {}", addr);
+        }
+    }
+
+    if !only_second.is_empty() {
+        println!("\nOnly in {}:", second_path.display());
+
+        for addr in only_second {
+            println!("  {}", addr);
+        }
+    }
+
+    if !changed.is_empty() {
+        println!("\nChanged:");
+
+        for (addr, differences) in changed {
+            println!("  {}", addr);
+
+            for difference in differences {
+                println!("    {}", difference);
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,206 @@
+use crate::StatusEvent;
+use anyhow::{bail, Result};
+use log::{debug, error, info};
+use mdns_sd::{ServiceDaemon, ServiceEvent};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use tokio::fs::{create_dir_all, read_to_string, write};
+use tokio::select;
+use tokio::sync::{mpsc, watch};
+use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
+
+/// Service type VRChat advertises its OSCQuery server under.
+pub const OSCQUERY_SERVICE: &str = "_oscjson._tcp.local.";
+
+/// Prefix of the instance names of VRChat clients, as opposed to other OSCQuery applications.
+const VRCHAT_INSTANCE_PREFIX: &str = "VRChat-Client-";
+
+/// VRChat clients found on the local network, by their instance name, with the address of their
+/// OSCQuery server.
+pub type Clients = HashMap<String, SocketAddr>;
+
+/// Instance part of the full name of an mDNS service.
+fn instance_name(fullname: &str) -> &str {
+    fullname.split('.').next().unwrap_or_default()
+}
+
+/// Discovers VRChat clients on the local network, most notably Quest headsets, for OSCQuery to
+/// look up their ports. With target selection, it also keeps the send target pointed at the
+/// selected one, even when it gets assigned a different IP.
+pub struct Discovery {
+    daemon: Option<ServiceDaemon>,
+    configured_host: String,
+    path: PathBuf,
+    selection_rx: mpsc::Receiver<String>,
+    status_tx: mpsc::Sender<StatusEvent>,
+    /// Send target to update, unless target selection is disabled.
+    target_tx: Option<watch::Sender<String>>,
+    clients_tx: watch::Sender<Clients>,
+    clients: Clients,
+    selected: String,
+}
+
+impl Discovery {
+    /// Browses through the daemon shared with OSCQuery, which is `None` while neither of them is
+    /// enabled.
+    pub fn new(
+        daemon: Option<ServiceDaemon>,
+        configured_host: String,
+        data_dir: PathBuf,
+        selection_rx: mpsc::Receiver<String>,
+        status_tx: mpsc::Sender<StatusEvent>,
+        target_tx: Option<watch::Sender<String>>,
+        clients_tx: watch::Sender<Clients>,
+    ) -> Self {
+        Self {
+            daemon,
+            configured_host,
+            path: data_dir.join("send-target.txt"),
+            selection_rx,
+            status_tx,
+            target_tx,
+            clients_tx,
+            clients: HashMap::new(),
+            selected: String::new(),
+        }
+    }
+
+    fn update_target(&self) {
+        let Some(target_tx) = &self.target_tx else {
+            return;
+        };
+
+        let host = match self.clients.get(&self.selected) {
+            Some(address) => address.ip().to_string(),
+            None => self.configured_host.clone(),
+        };
+
+        target_tx.send_if_modified(|target| {
+            if *target == host {
+                return false;
+            }
+
+            *target = host;
+            true
+        });
+    }
+
+    async fn select_target(&mut self, name: String) -> Result<()> {
+        info!(
+            "Selected send target {}",
+            if name.is_empty() {
+                "from config"
+            } else {
+                &name
+            }
+        );
+
+        self.selected = name;
+        self.update_target();
+
+        if let Some(parent) = self.path.parent() {
+            create_dir_all(parent).await?;
+        }
+
+        write(&self.path, &self.selected).await?;
+        Ok(())
+    }
+
+    async fn resolved(&mut self, name: String, address: SocketAddr) -> Result<()> {
+        match self.clients.insert(name.clone(), address) {
+            Some(previous) if previous == address => return Ok(()),
+            Some(_) => info!("VRChat client {} moved to {}", name, address.ip()),
+            None => {
+                info!("Discovered VRChat client {} at {}", name, address.ip());
+
+                if self.target_tx.is_some() {
+                    self.status_tx
+                        .send(StatusEvent::TargetDiscovered(name.clone()))
+                        .await?;
+                }
+            }
+        }
+
+        self.clients_tx.send_replace(self.clients.clone());
+
+        if name == self.selected {
+            self.update_target();
+        }
+
+        Ok(())
+    }
+
+    /// A client which went away, e.g. a Quest which was turned off, is no target anymore until it
+    /// shows up again.
+    fn removed(&mut self, name: &str) {
+        if self.clients.remove(name).is_none() {
+            return;
+        }
+
+        info!("VRChat client {} went away", name);
+        self.clients_tx.send_replace(self.clients.clone());
+
+        if name == self.selected {
+            self.update_target();
+        }
+    }
+
+    async fn discover(&mut self, daemon: ServiceDaemon) -> Result<()> {
+        self.selected = read_to_string(&self.path)
+            .await
+            .map(|selected| selected.trim().to_string())
+            .unwrap_or_default();
+
+        let events = daemon.browse(OSCQUERY_SERVICE)?;
+
+        loop {
+            select! {
+                event = events.recv_async() => {
+                    match event {
+                        Ok(ServiceEvent::ServiceResolved(info)) => {
+                            let name = instance_name(info.get_fullname());
+
+                            if !name.starts_with(VRCHAT_INSTANCE_PREFIX) {
+                                continue;
+                            }
+
+                            if let Some(address) = info.get_addresses().iter().next() {
+                                let address = SocketAddr::from((*address, info.get_port()));
+                                self.resolved(name.to_string(), address).await?;
+                            }
+                        }
+                        Ok(ServiceEvent::ServiceRemoved(_, fullname)) => {
+                            self.removed(instance_name(&fullname));
+                        }
+                        Ok(event) => debug!("Ignoring mDNS event {:?}", event),
+                        Err(_) => break,
+                    }
+                }
+                Some(name) = self.selection_rx.recv() => {
+                    if let Err(error) = self.select_target(name).await {
+                        error!("Failed to persist send target: {:#}", error);
+                    }
+                }
+            }
+        }
+
+        let _ = daemon.shutdown();
+        bail!("mDNS browser stopped unexpectedly");
+    }
+
+    pub async fn run(mut self, subsys: SubsystemHandle) -> Result<()> {
+        let Some(daemon) = self.daemon.take() else {
+            subsys.on_shutdown_requested().await;
+            return Ok(());
+        };
+
+        match (self.discover(daemon).cancel_on_shutdown(&subsys)).await {
+            Ok(Ok(())) => subsys.request_shutdown(),
+            Ok(Err(error)) => return Err(error),
+            Err(CancelledByShutdown) => {}
+        }
+
+        Ok(())
+    }
+}
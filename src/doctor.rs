@@ -0,0 +1,295 @@
+use crate::avatar;
+use crate::config::{config_path, parse_config, Config};
+use anyhow::Result;
+use directories::BaseDirs;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, UdpSocket};
+use std::path::Path;
+use sysinfo::{ProcessRefreshKind, RefreshKind, System, SystemExt};
+use tokio::fs::read_to_string;
+
+enum Status {
+    Ok,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Status::Ok => write!(f, "ok"),
+            Status::Warning => write!(f, "warn"),
+            Status::Error => write!(f, "fail"),
+        }
+    }
+}
+
+struct Report {
+    problems: usize,
+}
+
+impl Report {
+    fn new() -> Self {
+        Self { problems: 0 }
+    }
+
+    fn check(&mut self, status: Status, name: &str, detail: impl fmt::Display) {
+        if !matches!(status, Status::Ok) {
+            self.problems += 1;
+        }
+
+        println!("[{:>4}] {}: {}", status, name, detail);
+    }
+
+    fn hint(&self, hint: &str) {
+        println!("       {}", hint);
+    }
+}
+
+async fn check_config(report: &mut Report) -> Option<Config> {
+    let path = match config_path() {
+        Ok(path) => path,
+        Err(error) => {
+            report.check(Status::Error, "Config", format!("{:#}", error));
+            return None;
+        }
+    };
+
+    let contents = match read_to_string(&path).await {
+        Ok(contents) => contents,
+        Err(_) => {
+            report.check(
+                Status::Warning,
+                "Config",
+                format!("{} does not exist, defaults are used", path.display()),
+            );
+            return Some(Config::default());
+        }
+    };
+
+    match parse_config(&contents) {
+        Ok(config) => {
+            report.check(Status::Ok, "Config", path.display());
+            Some(config)
+        }
+        Err(error) => {
+            report.check(
+                Status::Error,
+                "Config",
+                format!("{} is invalid: {:#}", path.display(), error),
+            );
+            None
+        }
+    }
+}
+
+fn port_in_use(address: IpAddr, port: u16) -> bool {
+    UdpSocket::bind((address, port)).is_err()
+}
+
+fn check_ports(report: &mut Report, config: &Config, vrchat_running: bool) {
+    if !config.osc.listen_address.is_loopback() {
+        report.check(
+            Status::Warning,
+            "Listen address",
+            format!(
+                "{} is reachable from other devices",
+                config.osc.listen_address
+            ),
+        );
+        report.hint("Use allow lists in [osc.filter] to restrict who can trigger plugins");
+    }
+
+    if port_in_use(config.osc.listen_address, config.osc.receive_port) {
+        report.check(
+            Status::Error,
+            "Receive port",
+            format!("{} is already in use", config.osc.receive_port),
+        );
+        report.hint("Another OSC application or instance of VRC OSC Manager is listening on it");
+    } else {
+        report.check(
+            Status::Ok,
+            "Receive port",
+            format!("{} is available", config.osc.receive_port),
+        );
+    }
+
+    // VRChat listens on the send port while OSC is enabled, so finding it in use is a good sign.
+    if !config.osc.sends_locally() {
+        report.check(
+            Status::Warning,
+            "VRChat OSC",
+            format!("cannot be probed on remote target {}", config.osc.send_host),
+        );
+    } else if port_in_use(IpAddr::V4(Ipv4Addr::LOCALHOST), config.osc.send_port) {
+        report.check(
+            Status::Ok,
+            "VRChat OSC",
+            format!("something is listening on port {}", config.osc.send_port),
+        );
+    } else if vrchat_running {
+        report.check(
+            Status::Error,
+            "VRChat OSC",
+            format!("nothing is listening on port {}", config.osc.send_port),
+        );
+        report.hint("Enable OSC in the action menu of VRChat under Options > OSC");
+
+        #[cfg(target_os = "windows")]
+        report.hint("Make sure your firewall does not block VRChat from listening on local ports");
+    } else {
+        report.check(
+            Status::Warning,
+            "VRChat OSC",
+            "cannot be probed, as VRChat is not running",
+        );
+    }
+}
+
+pub fn vrchat_running() -> bool {
+    let mut sys = System::new();
+    sys.refresh_specifics(RefreshKind::new().with_processes(ProcessRefreshKind::new()));
+    sys.processes_by_name("VRChat").next().is_some()
+}
+
+async fn check_osc_setting(report: &mut Report, vrchat_running: bool) {
+    match crate::vrchat_settings::osc_enabled().await {
+        Ok(Some(true)) => report.check(Status::Ok, "VRChat OSC setting", "enabled"),
+        Ok(Some(false)) => {
+            report.check(Status::Error, "VRChat OSC setting", "disabled");
+
+            if vrchat_running {
+                report.hint("Enable OSC in the action menu of VRChat under Options > OSC");
+            } else {
+                report.hint("Run vrc-osc-manager enable-osc or enable OSC in the action menu of VRChat under Options > OSC");
+            }
+        }
+        Ok(None) => report.check(
+            Status::Warning,
+            "VRChat OSC setting",
+            "not found, VRChat may not have been started on this machine yet",
+        ),
+        Err(error) => report.check(
+            Status::Warning,
+            "VRChat OSC setting",
+            format!("{:#}", error),
+        ),
+    }
+}
+
+fn check_avatar_configs(report: &mut Report) {
+    let osc_dir = match BaseDirs::new() {
+        Some(base_dirs) => avatar::vrchat_osc_dir(&base_dirs),
+        None => {
+            report.check(
+                Status::Warning,
+                "Avatar OSC configs",
+                "home directory unknown",
+            );
+            return;
+        }
+    };
+
+    match avatar::config_paths(&osc_dir).map(|paths| paths.len()) {
+        Ok(0) => {
+            report.check(
+                Status::Warning,
+                "Avatar OSC configs",
+                format!("none found in {}", osc_dir.display()),
+            );
+            report.hint("VRChat generates them when switching into an avatar while OSC is enabled");
+        }
+        Ok(count) => report.check(
+            Status::Ok,
+            "Avatar OSC configs",
+            format!("{} found in {}", count, osc_dir.display()),
+        ),
+        Err(_) => report.check(
+            Status::Warning,
+            "Avatar OSC configs",
+            format!("{} does not exist", osc_dir.display()),
+        ),
+    }
+}
+
+#[cfg(feature = "pishock")]
+fn check_pishock(report: &mut Report, config: &Config) {
+    let pishock = &config.pishock;
+    let missing: Vec<_> = [
+        ("username", &pishock.username),
+        ("api_key", &pishock.api_key),
+        ("code", &pishock.code),
+    ]
+    .into_iter()
+    .filter(|(_, value)| value.trim().is_empty())
+    .map(|(key, _)| key)
+    .collect();
+
+    if !missing.is_empty() {
+        report.check(
+            Status::Error,
+            "PiShock credentials",
+            format!("missing {}", missing.join(", ")),
+        );
+    } else if !(1..=15).contains(&pishock.duration) {
+        report.check(
+            Status::Warning,
+            "PiShock credentials",
+            format!("duration {} will be clamped to 1-15", pishock.duration),
+        );
+    } else {
+        report.check(Status::Ok, "PiShock credentials", "complete");
+    }
+}
+
+fn check_plugins(report: &mut Report, config: &Config) {
+    #[cfg(feature = "pishock")]
+    if config.plugin_enabled("pishock") {
+        check_pishock(report, config);
+    }
+}
+
+pub async fn run(data_dir: &Path) -> Result<()> {
+    println!("VRC OSC Manager v{}", crate::VERSION);
+    println!("OS: {} ({})", std::env::consts::OS, std::env::consts::ARCH);
+    println!("Data directory: {}", data_dir.display());
+    println!();
+
+    let mut report = Report::new();
+    let vrchat_running = vrchat_running();
+
+    report.check(
+        if vrchat_running {
+            Status::Ok
+        } else {
+            Status::Warning
+        },
+        "VRChat",
+        if vrchat_running {
+            "running"
+        } else {
+            "not running"
+        },
+    );
+
+    check_osc_setting(&mut report, vrchat_running).await;
+
+    if let Some(config) = check_config(&mut report).await {
+        check_ports(&mut report, &config, vrchat_running);
+        check_plugins(&mut report, &config);
+    }
+
+    check_avatar_configs(&mut report);
+
+    println!();
+    println!(
+        "{}",
+        match report.problems {
+            0 => "No problems found".to_string(),
+            problems => format!("{} potential problem(s) found", problems),
+        }
+    );
+
+    Ok(())
+}
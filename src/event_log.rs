@@ -0,0 +1,151 @@
+use crate::config::EventLogConfig;
+use crate::state::SharedState;
+use anyhow::{bail, Context, Result};
+use file_rotate::compression::Compression;
+use file_rotate::suffix::AppendCount;
+use file_rotate::{ContentLimit, FileRotate};
+use log::{debug, warn};
+use serde::Serialize;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::fs::create_dir_all;
+use tokio::select;
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::task::spawn_blocking;
+use tokio::time::timeout;
+use tokio_graceful_shutdown::SubsystemHandle;
+use vrc_osc_manager_core::{Event, Events};
+
+/// Time given to the plugins to record stopping once shutting down.
+const DRAIN_TIME: Duration = Duration::from_millis(500);
+
+#[derive(Serialize)]
+struct Line<'a> {
+    time: String,
+    #[serde(flatten)]
+    event: &'a Event,
+}
+
+/// Writes the events on a blocking thread, handing back the file for the next ones.
+async fn append(
+    mut file: FileRotate<AppendCount>,
+    events: Vec<Event>,
+) -> Result<FileRotate<AppendCount>> {
+    spawn_blocking(move || {
+        for event in events {
+            let mut line = serde_json::to_vec(&Line {
+                time: chrono::Local::now().to_rfc3339(),
+                event: &event,
+            })?;
+            line.push(b'\n');
+            file.write_all(&line)?;
+        }
+
+        file.flush()?;
+        Ok::<_, anyhow::Error>(file)
+    })
+    .await?
+}
+
+/// Writes the events of the manager and its plugins as JSON lines to a rotating file.
+pub struct EventLog {
+    config: EventLogConfig,
+    path: PathBuf,
+    state: Arc<SharedState>,
+    tx: mpsc::Sender<Event>,
+    rx: mpsc::Receiver<Event>,
+}
+
+impl EventLog {
+    pub fn new(config: &EventLogConfig, data_dir: &Path, state: Arc<SharedState>) -> Self {
+        let (tx, rx) = mpsc::channel(128);
+
+        Self {
+            config: config.clone(),
+            path: config
+                .path
+                .clone()
+                .unwrap_or_else(|| data_dir.join("logs/events.jsonl")),
+            state,
+            tx,
+            rx,
+        }
+    }
+
+    /// Handle recording into this log, which drops events while it is disabled. The dashboard
+    /// shows the recent ones either way.
+    pub fn events(&self) -> Events {
+        let state = self.state.clone();
+        let tx = self.config.enabled.then(|| self.tx.clone());
+
+        // Events are dropped rather than holding up whoever records them when the file cannot
+        // keep up.
+        Events::new(move |event| {
+            state.remember_event(&event);
+
+            if let Some(tx) = &tx {
+                if let Err(TrySendError::Full(event)) = tx.try_send(event) {
+                    warn!(
+                        "Event log lagging behind, dropped {} event of {}",
+                        event.kind, event.source
+                    );
+                }
+            }
+        })
+    }
+
+    async fn write(&mut self, subsys: &SubsystemHandle) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            create_dir_all(dir)
+                .await
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+
+        let mut file = FileRotate::new(
+            &self.path,
+            AppendCount::new(self.config.max_files),
+            ContentLimit::BytesSurpassed(self.config.max_size_kb * 1024),
+            Compression::None,
+            #[cfg(unix)]
+            None,
+        );
+
+        debug!("Writing events to {}", self.path.display());
+
+        loop {
+            let event = select! {
+                event = self.rx.recv() => event,
+                _ = subsys.on_shutdown_requested() => break,
+            };
+
+            let Some(event) = event else {
+                bail!("Event channel closed unexpectedly");
+            };
+
+            file = append(file, vec![event]).await?;
+        }
+
+        // Plugins stop along with the event log, so what they record while doing so is still
+        // waited for.
+        let mut events = vec![];
+
+        while let Ok(Some(event)) = timeout(DRAIN_TIME, self.rx.recv()).await {
+            events.push(event);
+        }
+
+        append(file, events).await?;
+        Ok(())
+    }
+
+    pub async fn run(mut self, subsys: SubsystemHandle) -> Result<()> {
+        if !self.config.enabled {
+            subsys.on_shutdown_requested().await;
+            return Ok(());
+        }
+
+        self.write(&subsys).await
+    }
+}
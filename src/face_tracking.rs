@@ -0,0 +1,151 @@
+use crate::config::{FaceParameterConfig, FaceTrackingConfig};
+use crate::rules;
+use anyhow::{bail, Result};
+use async_osc::{OscMessage, OscPacket, OscSocket, OscType};
+use log::{info, warn};
+use std::collections::HashMap;
+use tokio::sync::mpsc;
+use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
+use tokio_stream::StreamExt;
+use vrc_osc_manager_core::OutgoingMessage;
+
+impl FaceParameterConfig {
+    fn process(&self, value: f32, previous: Option<f32>) -> f32 {
+        let value = value * self.gain + self.offset;
+        let value = self.clamp_min.map_or(value, |min| value.max(min));
+        let value = self.clamp_max.map_or(value, |max| value.min(max));
+        let smoothing = self.smoothing.clamp(0., 0.99);
+
+        match previous {
+            Some(previous) => previous * smoothing + value * (1. - smoothing),
+            None => value,
+        }
+    }
+}
+
+/// Tunes face tracking parameters on their way to VRChat, using the profile of the current avatar
+/// or the one without an avatar as fallback.
+pub struct FaceProfile {
+    config: FaceTrackingConfig,
+    avatar: Option<String>,
+    smoothed: HashMap<String, f32>,
+}
+
+impl FaceProfile {
+    pub fn new(config: &FaceTrackingConfig) -> Self {
+        Self {
+            config: config.clone(),
+            avatar: None,
+            smoothed: HashMap::new(),
+        }
+    }
+
+    fn parameters(&self, avatar: Option<&str>) -> &[FaceParameterConfig] {
+        let profiles = &self.config.profiles;
+
+        profiles
+            .iter()
+            .find(|profile| profile.avatar.is_some() && profile.avatar.as_deref() == avatar)
+            .or_else(|| profiles.iter().find(|profile| profile.avatar.is_none()))
+            .map_or(&[], |profile| profile.parameters.as_slice())
+    }
+
+    /// Returns the processed message together with its mirrored copy, if any.
+    pub fn apply(&mut self, mut message: OscMessage, avatar: Option<&str>) -> Vec<OscMessage> {
+        if self.config.profiles.is_empty() {
+            return vec![message];
+        }
+
+        if self.avatar.as_deref() != avatar {
+            self.avatar = avatar.map(str::to_string);
+            self.smoothed.clear();
+        }
+
+        let Some(parameter) = self
+            .parameters(avatar)
+            .iter()
+            .find(|parameter| rules::matches(&parameter.address, &message.addr))
+            .cloned()
+        else {
+            return vec![message];
+        };
+
+        let Some(OscType::Float(value)) = message.args.first_mut() else {
+            return vec![message];
+        };
+
+        *value = parameter.process(*value, self.smoothed.get(&message.addr).copied());
+        self.smoothed.insert(message.addr.clone(), *value);
+        let processed = *value;
+
+        match parameter.mirror {
+            Some(mirror) => {
+                let mirrored = OscMessage {
+                    addr: mirror,
+                    args: vec![OscType::Float(if parameter.mirror_invert {
+                        -processed
+                    } else {
+                        processed
+                    })],
+                };
+                vec![message, mirrored]
+            }
+            None => vec![message],
+        }
+    }
+}
+
+/// Accepts face tracking data from VRCFaceTracking or similar on a port of its own, so it passes
+/// through the profile before reaching VRChat.
+pub struct FaceTrackingInput {
+    config: FaceTrackingConfig,
+    tx: mpsc::Sender<OutgoingMessage>,
+}
+
+impl FaceTrackingInput {
+    pub fn new(config: &FaceTrackingConfig, tx: mpsc::Sender<OutgoingMessage>) -> Self {
+        Self {
+            config: config.clone(),
+            tx,
+        }
+    }
+
+    async fn receive(&self, port: u16) -> Result<()> {
+        let address = self.config.listen_address;
+        let mut socket = OscSocket::bind((address, port)).await?;
+        info!("Receiving face tracking on {}:{}", address, port);
+
+        while let Some(packet) = socket.next().await {
+            let packet = match packet {
+                Ok((packet, _)) => packet,
+                Err(error) => {
+                    warn!("Dropped undecodable face tracking packet: {}", error);
+                    continue;
+                }
+            };
+
+            if let OscPacket::Message(message) = packet {
+                self.tx
+                    .send(OutgoingMessage::new("face_tracking", message))
+                    .await?;
+            }
+        }
+
+        bail!("Face tracking stream closed unexpectedly");
+    }
+
+    pub async fn run(self, subsys: SubsystemHandle) -> Result<()> {
+        let Some(port) = self.config.port else {
+            subsys.on_shutdown_requested().await;
+            return Ok(());
+        };
+
+        match (self.receive(port).cancel_on_shutdown(&subsys)).await {
+            Ok(Ok(())) => subsys.request_shutdown(),
+            Ok(Err(error)) => return Err(error),
+            Err(CancelledByShutdown) => {}
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,177 @@
+use crate::config::FloodConfig;
+use log::warn;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+const WINDOW: Duration = Duration::from_secs(1);
+
+/// Counts messages within fixed one second windows and blocks sources and addresses exceeding
+/// their cap for a while.
+pub struct FloodGuard {
+    per_source: u32,
+    per_address: u32,
+    block_duration: Duration,
+    window_start: Instant,
+    source_counts: HashMap<IpAddr, u32>,
+    address_counts: HashMap<String, u32>,
+    blocked_sources: HashMap<IpAddr, Instant>,
+    blocked_addresses: HashMap<String, Instant>,
+}
+
+impl FloodGuard {
+    pub fn new(config: &FloodConfig) -> Self {
+        Self {
+            per_source: config.per_source,
+            per_address: config.per_address,
+            block_duration: Duration::from_secs(config.block_seconds),
+            window_start: Instant::now(),
+            source_counts: HashMap::new(),
+            address_counts: HashMap::new(),
+            blocked_sources: HashMap::new(),
+            blocked_addresses: HashMap::new(),
+        }
+    }
+
+    fn roll_window(&mut self, now: Instant) {
+        if now.duration_since(self.window_start) < WINDOW {
+            return;
+        }
+
+        self.window_start = now;
+        self.source_counts.clear();
+        self.address_counts.clear();
+        self.blocked_sources.retain(|_, until| *until > now);
+        self.blocked_addresses.retain(|_, until| *until > now);
+    }
+
+    /// Whether a message from the source to the address should be let through. A cap of zero
+    /// disables the respective check.
+    pub fn allow(&mut self, source: IpAddr, addr: &str) -> bool {
+        self.allow_at(source, addr, Instant::now())
+    }
+
+    fn allow_at(&mut self, source: IpAddr, addr: &str, now: Instant) -> bool {
+        self.roll_window(now);
+
+        if self.blocked_sources.contains_key(&source) || self.blocked_addresses.contains_key(addr) {
+            return false;
+        }
+
+        if self.per_source > 0 {
+            let count = self.source_counts.entry(source).or_insert(0);
+            *count += 1;
+
+            if *count > self.per_source {
+                warn!(
+                    "{} exceeded {} messages per second, dropping its messages for {}s",
+                    source,
+                    self.per_source,
+                    self.block_duration.as_secs()
+                );
+                self.blocked_sources
+                    .insert(source, now + self.block_duration);
+                return false;
+            }
+        }
+
+        if self.per_address > 0 {
+            let count = self.address_counts.entry(addr.to_string()).or_insert(0);
+            *count += 1;
+
+            if *count > self.per_address {
+                warn!(
+                    "{} exceeded {} messages per second, dropping its messages for {}s",
+                    addr,
+                    self.per_address,
+                    self.block_duration.as_secs()
+                );
+                self.blocked_addresses
+                    .insert(addr.to_string(), now + self.block_duration);
+                return false;
+            }
+        }
+
+        true
+    }
+
+    pub fn flooded(&self) -> bool {
+        !self.blocked_sources.is_empty() || !self.blocked_addresses.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    const SOURCE: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    const OTHER_SOURCE: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 20));
+
+    fn guard(per_source: u32, per_address: u32) -> FloodGuard {
+        FloodGuard::new(&FloodConfig {
+            per_source,
+            per_address,
+            block_seconds: 10,
+        })
+    }
+
+    #[test]
+    fn blocks_sources_exceeding_their_cap() {
+        let mut guard = guard(3, 0);
+        let now = guard.window_start;
+
+        for index in 0..3 {
+            assert!(guard.allow_at(SOURCE, &format!("/{}", index), now));
+        }
+
+        assert!(!guard.flooded());
+        assert!(!guard.allow_at(SOURCE, "/3", now));
+        assert!(guard.flooded());
+        assert!(guard.allow_at(OTHER_SOURCE, "/3", now));
+    }
+
+    #[test]
+    fn blocks_addresses_exceeding_their_cap() {
+        let mut guard = guard(0, 2);
+        let now = guard.window_start;
+
+        assert!(guard.allow_at(SOURCE, "/a", now));
+        assert!(guard.allow_at(OTHER_SOURCE, "/a", now));
+        assert!(!guard.allow_at(SOURCE, "/a", now));
+        assert!(guard.allow_at(SOURCE, "/b", now));
+    }
+
+    #[test]
+    fn counts_within_one_second_windows() {
+        let mut guard = guard(2, 0);
+        let now = guard.window_start;
+
+        assert!(guard.allow_at(SOURCE, "/a", now));
+        assert!(guard.allow_at(SOURCE, "/a", now));
+        assert!(guard.allow_at(SOURCE, "/a", now + WINDOW));
+        assert!(guard.allow_at(SOURCE, "/a", now + WINDOW));
+    }
+
+    #[test]
+    fn keeps_blocking_for_the_block_duration() {
+        let mut guard = guard(1, 0);
+        let now = guard.window_start;
+
+        assert!(guard.allow_at(SOURCE, "/a", now));
+        assert!(!guard.allow_at(SOURCE, "/a", now));
+        assert!(!guard.allow_at(SOURCE, "/a", now + Duration::from_secs(5)));
+        assert!(guard.allow_at(SOURCE, "/a", now + Duration::from_secs(11)));
+        assert!(!guard.flooded());
+    }
+
+    #[test]
+    fn zero_disables_the_caps() {
+        let mut guard = guard(0, 0);
+        let now = guard.window_start;
+
+        for _ in 0..10_000 {
+            assert!(guard.allow_at(SOURCE, "/a", now));
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use crate::avatar::{self, ParameterType};
+use crate::config::load_config;
+use crate::rules;
+use anyhow::{bail, Result};
+use async_osc::{OscMessage, OscSocket, OscType};
+use clap::ValueEnum;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::time::Duration;
+use tokio::time::{interval, Instant};
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum FuzzMode {
+    /// Step every parameter through its range, one after another
+    Sweep,
+    /// Send random values to random parameters
+    Random,
+}
+
+pub struct FuzzOptions<'a> {
+    pub avatar: Option<&'a str>,
+    pub mode: FuzzMode,
+    pub rate: f64,
+    pub steps: u32,
+    pub duration: Option<u64>,
+    pub filter: Option<&'a str>,
+}
+
+/// Upper limit of the rate, as shorter intervals are no longer kept by the timer anyway.
+const MAX_RATE: f64 = 1000.;
+
+/// Maps a fraction between 0 and 1 onto the range VRChat accepts for the type.
+fn value(kind: ParameterType, fraction: f32) -> OscType {
+    match kind {
+        ParameterType::Bool => OscType::Bool(fraction >= 0.5),
+        ParameterType::Int => OscType::Int((fraction * 255.).round() as i32),
+        ParameterType::Float => OscType::Float(fraction * 2. - 1.),
+    }
+}
+
+/// Sends values to every input parameter of an avatar, to shake out broken animator transitions.
+pub async fn run(options: FuzzOptions<'_>) -> Result<()> {
+    let config = load_config().await?;
+    let avatar = avatar::load(options.avatar).await?;
+
+    let inputs: Vec<_> = avatar
+        .parameters
+        .iter()
+        .filter_map(|parameter| parameter.input.clone())
+        .filter(|input| {
+            options
+                .filter
+                .map_or(true, |pattern| rules::matches(pattern, &input.address))
+        })
+        .collect();
+
+    if inputs.is_empty() {
+        bail!("Avatar {} has no matching input parameters", avatar.name);
+    }
+
+    if options.rate.is_nan() || options.rate <= 0. {
+        bail!("Rate has to be positive");
+    }
+
+    let rate = options.rate.min(MAX_RATE);
+
+    println!(
+        "Fuzzing {} parameters of {} ({}) at {} messages per second, press Ctrl+C to stop",
+        inputs.len(),
+        avatar.name,
+        avatar.id,
+        rate
+    );
+
+    let socket = OscSocket::bind("0.0.0.0:0").await?;
+    socket
+        .connect((config.osc.send_host.as_str(), config.osc.send_port))
+        .await?;
+
+    let deadline = options
+        .duration
+        .map(|seconds| Instant::now() + Duration::from_secs(seconds));
+    let mut ticks = interval(Duration::from_secs_f64(1. / rate));
+    let mut random = StdRng::from_entropy();
+    let steps = options.steps.max(2);
+    let mut sent: u64 = 0;
+
+    loop {
+        ticks.tick().await;
+
+        if deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+            break;
+        }
+
+        let (input, arg) = match options.mode {
+            FuzzMode::Random => {
+                let input = &inputs[random.gen_range(0..inputs.len())];
+                (input, value(input.kind, random.gen()))
+            }
+            FuzzMode::Sweep => {
+                let position = sent as usize;
+                let input = &inputs[position / steps as usize % inputs.len()];
+                let step = (position % steps as usize) as f32 / (steps - 1) as f32;
+
+                if position % steps as usize == 0 {
+                    println!("Sweeping {}", input.address);
+                }
+
+                (input, value(input.kind, step))
+            }
+        };
+
+        socket
+            .send(OscMessage {
+                addr: input.address.clone(),
+                args: vec![arg],
+            })
+            .await?;
+        sent += 1;
+    }
+
+    println!("Sent {} messages", sent);
+    Ok(())
+}
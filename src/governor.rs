@@ -0,0 +1,90 @@
+use crate::config::GovernorConfig;
+use anyhow::Result;
+use log::{debug, info};
+use std::time::Duration;
+use sysinfo::{Pid, ProcessExt, ProcessRefreshKind, System, SystemExt};
+use tokio::sync::{mpsc, watch};
+use tokio::time::sleep;
+use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
+use vrc_osc_manager_core::OutgoingMessage;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Throttles low priority plugins while the manager uses too much CPU or the send queue backs up,
+/// so it never contributes to frame drops in VR.
+pub struct Governor {
+    config: GovernorConfig,
+    sender_tx: mpsc::Sender<OutgoingMessage>,
+    throttled_tx: watch::Sender<bool>,
+}
+
+impl Governor {
+    pub fn new(
+        config: &GovernorConfig,
+        sender_tx: mpsc::Sender<OutgoingMessage>,
+        throttled_tx: watch::Sender<bool>,
+    ) -> Self {
+        Self {
+            config: config.clone(),
+            sender_tx,
+            throttled_tx,
+        }
+    }
+
+    async fn govern(&self) -> Result<()> {
+        let pid = Pid::from(std::process::id() as usize);
+        let mut sys = System::new();
+        let mut throttled = false;
+
+        loop {
+            // CPU usage is measured between two refreshes, so the first one always reads zero.
+            sys.refresh_process_specifics(pid, ProcessRefreshKind::new().with_cpu());
+            let cpu = sys.process(pid).map_or(0., |process| process.cpu_usage());
+            let queue_depth = self.sender_tx.max_capacity() - self.sender_tx.capacity();
+            debug!(
+                "Governor sees {:.1}% CPU and {} queued messages",
+                cpu, queue_depth
+            );
+
+            // Load has to drop to half the limits before lifting the throttle, so it does not flap.
+            let overloaded = if throttled {
+                cpu > self.config.max_cpu_percent / 2.
+                    || queue_depth > self.config.max_queue_depth / 2
+            } else {
+                cpu > self.config.max_cpu_percent || queue_depth > self.config.max_queue_depth
+            };
+
+            if overloaded != throttled {
+                throttled = overloaded;
+                info!(
+                    "{} low priority plugins ({:.1}% CPU, {} queued messages)",
+                    if throttled {
+                        "Throttling"
+                    } else {
+                        "Unthrottling"
+                    },
+                    cpu,
+                    queue_depth
+                );
+                self.throttled_tx.send_replace(throttled);
+            }
+
+            sleep(CHECK_INTERVAL).await;
+        }
+    }
+
+    pub async fn run(self, subsys: SubsystemHandle) -> Result<()> {
+        if !self.config.enabled {
+            subsys.on_shutdown_requested().await;
+            return Ok(());
+        }
+
+        match (self.govern().cancel_on_shutdown(&subsys)).await {
+            Ok(Ok(())) => subsys.request_shutdown(),
+            Ok(Err(error)) => return Err(error),
+            Err(CancelledByShutdown) => {}
+        }
+
+        Ok(())
+    }
+}
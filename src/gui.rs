@@ -0,0 +1,497 @@
+use crate::config::{config_path, lookup, section_mut, Config};
+#[cfg(feature = "installer")]
+use crate::installer::{self, Listing};
+use crate::plugin_logs::STATES_FILE;
+use crate::plugins;
+use anyhow::{anyhow, Context, Result};
+use eframe::egui;
+use std::fs::{read_dir, read_to_string, write};
+use std::path::{Path, PathBuf};
+#[cfg(feature = "installer")]
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use toml_edit::{value, Array, Document, Item};
+use vrc_osc_manager_core::{addresses, ConfigField, ConfigFieldKind};
+
+enum FieldValue {
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    Text(String),
+}
+
+struct FieldState {
+    field: ConfigField,
+    value: FieldValue,
+}
+
+impl FieldState {
+    fn new(field: ConfigField, current: Option<&toml::Value>) -> Self {
+        let value = match field.kind {
+            ConfigFieldKind::Bool => {
+                FieldValue::Bool(current.and_then(toml::Value::as_bool).unwrap_or_default())
+            }
+            ConfigFieldKind::Integer => FieldValue::Integer(
+                current
+                    .and_then(toml::Value::as_integer)
+                    .unwrap_or_default(),
+            ),
+            ConfigFieldKind::Float => {
+                FieldValue::Float(current.and_then(toml::Value::as_float).unwrap_or_default())
+            }
+            ConfigFieldKind::String
+            | ConfigFieldKind::Secret
+            | ConfigFieldKind::Choice(_)
+            | ConfigFieldKind::Address => FieldValue::Text(
+                current
+                    .and_then(toml::Value::as_str)
+                    .unwrap_or_default()
+                    .to_string(),
+            ),
+        };
+
+        Self { field, value }
+    }
+
+    fn describe(&self) -> String {
+        match (&self.field.kind, &self.value) {
+            (ConfigFieldKind::Secret, FieldValue::Text(text)) if !text.is_empty() => {
+                "********".to_string()
+            }
+            (_, FieldValue::Bool(value)) => value.to_string(),
+            (_, FieldValue::Integer(value)) => value.to_string(),
+            (_, FieldValue::Float(value)) => value.to_string(),
+            (_, FieldValue::Text(text)) => format!("{:?}", text),
+        }
+    }
+
+    fn show(&mut self, ui: &mut egui::Ui, id: &str) {
+        match (&self.field.kind, &mut self.value) {
+            (ConfigFieldKind::Choice(options), FieldValue::Text(text)) => {
+                egui::ComboBox::from_id_source(id)
+                    .selected_text(text.clone())
+                    .show_ui(ui, |ui| {
+                        for option in options.iter() {
+                            ui.selectable_value(text, option.to_string(), *option);
+                        }
+                    });
+            }
+            (ConfigFieldKind::Secret, FieldValue::Text(text)) => {
+                ui.add(egui::TextEdit::singleline(text).password(true));
+            }
+            (ConfigFieldKind::Address, FieldValue::Text(text)) => {
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(text);
+                    let suggestions: Vec<_> = addresses::completions(text).collect();
+
+                    ui.menu_button("Standard addresses", |ui| {
+                        for entry in suggestions {
+                            if ui
+                                .button(entry.address)
+                                .on_hover_text(format!(
+                                    "{} ({})",
+                                    entry.description,
+                                    entry.signature()
+                                ))
+                                .clicked()
+                            {
+                                *text = entry.address.to_string();
+                                ui.close_menu();
+                            }
+                        }
+                    });
+                });
+            }
+            (_, FieldValue::Bool(value)) => {
+                ui.checkbox(value, "");
+            }
+            (_, FieldValue::Integer(value)) => {
+                ui.add(egui::DragValue::new(value));
+            }
+            (_, FieldValue::Float(value)) => {
+                ui.add(egui::DragValue::new(value).speed(0.01));
+            }
+            (_, FieldValue::Text(text)) => {
+                ui.text_edit_singleline(text);
+            }
+        }
+    }
+
+    fn store(&self, section: &mut Item) {
+        section[self.field.key] = match &self.value {
+            FieldValue::Bool(current) => value(*current),
+            FieldValue::Integer(current) => value(*current),
+            FieldValue::Float(current) => value(*current),
+            FieldValue::Text(current) => value(current.as_str()),
+        };
+    }
+}
+
+struct PluginState {
+    name: &'static str,
+    section: String,
+    enabled: bool,
+    fields: Vec<FieldState>,
+    /// Most recent log records of the plugin, mirrored into the data directory by the manager.
+    log: String,
+    /// What the plugin is doing in the running manager, if there is one.
+    state: Option<String>,
+}
+
+impl PluginState {
+    fn summary(&self) -> String {
+        if self.fields.is_empty() {
+            return "No editable options".to_string();
+        }
+
+        self.fields
+            .iter()
+            .map(|field| format!("{}: {}", field.field.label, field.describe()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+fn installed_plugins(data_dir: &Path) -> Vec<String> {
+    let mut installed: Vec<String> = match read_dir(data_dir.join("plugins")) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .collect(),
+        Err(_) => vec![],
+    };
+
+    installed.sort();
+    installed
+}
+
+/// Plugins offered by the plugin index, which are fetched and installed in the background.
+#[cfg(feature = "installer")]
+struct IndexBrowser {
+    config: Config,
+    data_dir: PathBuf,
+    runtime: tokio::runtime::Handle,
+    listings: Option<Result<Vec<Listing>, String>>,
+    loading: Option<mpsc::Receiver<Result<Vec<Listing>, String>>>,
+    installing: Option<(String, mpsc::Receiver<Result<(), String>>)>,
+    status: Option<String>,
+}
+
+#[cfg(feature = "installer")]
+impl IndexBrowser {
+    fn new(config: Config, data_dir: &Path) -> Self {
+        Self {
+            config,
+            data_dir: data_dir.to_path_buf(),
+            runtime: tokio::runtime::Handle::current(),
+            listings: None,
+            loading: None,
+            installing: None,
+            status: None,
+        }
+    }
+
+    fn fetch(&mut self) {
+        let (tx, rx) = mpsc::channel();
+        let config = self.config.clone();
+
+        self.runtime.spawn(async move {
+            let listings = installer::listings(&config).await;
+            let _ = tx.send(listings.map_err(|error| format!("{:#}", error)));
+        });
+
+        self.loading = Some(rx);
+    }
+
+    fn install(&mut self, name: &str) {
+        let (tx, rx) = mpsc::channel();
+        let config = self.config.clone();
+        let data_dir = self.data_dir.clone();
+        let plugin = name.to_string();
+
+        self.runtime.spawn(async move {
+            let result = installer::install(&config, &data_dir, &plugin).await;
+            let _ = tx.send(result.map_err(|error| format!("{:#}", error)));
+        });
+
+        self.installing = Some((name.to_string(), rx));
+        self.status = Some(format!("Installing {}", name));
+    }
+
+    /// Shows the index, returning whether a plugin was installed.
+    fn show(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut installed = false;
+
+        if let Some(listings) = self.loading.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            self.listings = Some(listings);
+            self.loading = None;
+        }
+
+        if let Some(result) = self
+            .installing
+            .as_ref()
+            .and_then(|(_, rx)| rx.try_recv().ok())
+        {
+            let (name, _) = self.installing.take().unwrap();
+            installed = result.is_ok();
+            self.status = Some(match result {
+                Ok(()) => format!("Installed {}", name),
+                Err(error) => format!("Failed to install {}: {}", name, error),
+            });
+        }
+
+        ui.heading("Plugin index");
+
+        if self.loading.is_some() {
+            ui.label("Fetching the plugin index");
+        } else if ui.button("Fetch plugin index").clicked() {
+            self.fetch();
+        }
+
+        let mut install = None;
+
+        match &self.listings {
+            Some(Ok(listings)) => {
+                for listing in listings {
+                    ui.horizontal(|ui| {
+                        ui.strong(format!("{} {}", listing.name, listing.version));
+
+                        if !listing.available {
+                            ui.label("Not available for this platform");
+                        } else if ui
+                            .add_enabled(self.installing.is_none(), egui::Button::new("Install"))
+                            .clicked()
+                        {
+                            install = Some(listing.name.clone());
+                        }
+                    });
+                    ui.label(listing.description.as_str());
+
+                    if listing.permissions.is_empty() {
+                        ui.label("Declares no permissions");
+                    } else {
+                        ui.label(format!("Declares to {}", listing.permissions.join(", ")));
+                    }
+                }
+            }
+            Some(Err(error)) => {
+                ui.label(format!("Failed to fetch the plugin index: {}", error));
+            }
+            None => {}
+        }
+
+        if let Some(name) = install {
+            self.install(&name);
+        }
+
+        if let Some(status) = &self.status {
+            ui.label(status.as_str());
+        }
+
+        ui.separator();
+        installed
+    }
+}
+
+struct PluginManager {
+    #[cfg(feature = "installer")]
+    index: IndexBrowser,
+    path: PathBuf,
+    document: Document,
+    plugins: Vec<PluginState>,
+    installed: Vec<String>,
+    data_dir: PathBuf,
+    status: Option<String>,
+    log_dir: PathBuf,
+    logs_read: Option<Instant>,
+}
+
+impl PluginManager {
+    fn load(data_dir: &Path) -> Result<Self> {
+        let path = config_path()?;
+        let toml_config = read_to_string(&path).unwrap_or_default();
+        let document: Document = toml_config
+            .parse()
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        let config: Config = toml::from_str(&toml_config)?;
+        let effective = toml::Value::try_from(&config)?;
+        let config = Arc::new(config);
+
+        let plugins = plugins::builtin(&config)
+            .into_iter()
+            .map(|plugin| {
+                let section = plugin.config_section();
+                let current = lookup(&effective, &section);
+
+                PluginState {
+                    name: plugin.name(),
+                    enabled: config.plugin_enabled(plugin.name()),
+                    fields: plugin
+                        .config_fields()
+                        .into_iter()
+                        .map(|field| {
+                            FieldState::new(
+                                field,
+                                current.and_then(|current| current.get(field.key)),
+                            )
+                        })
+                        .collect(),
+                    section,
+                    log: String::new(),
+                    state: None,
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            #[cfg(feature = "installer")]
+            index: IndexBrowser::new((*config).clone(), data_dir),
+            path,
+            document,
+            plugins,
+            installed: installed_plugins(data_dir),
+            data_dir: data_dir.to_path_buf(),
+            status: None,
+            log_dir: data_dir.join("logs/plugins"),
+            logs_read: None,
+        })
+    }
+
+    fn read_logs(&mut self) {
+        let states: toml::Table = read_to_string(self.log_dir.join(STATES_FILE))
+            .ok()
+            .and_then(|states| toml::from_str(&states).ok())
+            .unwrap_or_default();
+
+        for plugin in &mut self.plugins {
+            plugin.log = read_to_string(self.log_dir.join(format!("{}.log", plugin.name)))
+                .unwrap_or_default();
+            plugin.state = states
+                .get(plugin.name)
+                .and_then(toml::Value::as_str)
+                .map(str::to_string);
+        }
+
+        self.logs_read = Some(Instant::now());
+    }
+
+    fn save(&mut self) -> Result<()> {
+        let mut disabled_plugins = Array::new();
+
+        for plugin in &self.plugins {
+            if !plugin.enabled {
+                disabled_plugins.push(plugin.name);
+            }
+
+            let section = section_mut(&mut self.document, &plugin.section);
+
+            for field in &plugin.fields {
+                field.store(section);
+            }
+        }
+
+        self.document["disabled_plugins"] = value(disabled_plugins);
+
+        let toml_config = self.document.to_string();
+        toml::from_str::<Config>(&toml_config).context("Invalid configuration")?;
+        write(&self.path, toml_config)
+            .with_context(|| format!("Failed to write {}", self.path.display()))?;
+
+        Ok(())
+    }
+}
+
+impl eframe::App for PluginManager {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        if self
+            .logs_read
+            .map_or(true, |read| read.elapsed() >= Duration::from_secs(1))
+        {
+            self.read_logs();
+        }
+
+        ctx.request_repaint_after(Duration::from_secs(1));
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                for plugin in &mut self.plugins {
+                    ui.horizontal(|ui| {
+                        ui.heading(plugin.name);
+                        ui.checkbox(&mut plugin.enabled, "Enabled");
+                        ui.label(plugin.state.as_deref().unwrap_or("manager not running"));
+                    });
+                    ui.label(plugin.summary());
+
+                    if !plugin.fields.is_empty() {
+                        ui.collapsing(format!("Edit {}", plugin.name), |ui| {
+                            egui::Grid::new(plugin.name).num_columns(2).show(ui, |ui| {
+                                for field in &mut plugin.fields {
+                                    ui.label(field.field.label);
+                                    field.show(
+                                        ui,
+                                        &format!("{}.{}", plugin.section, field.field.key),
+                                    );
+                                    ui.end_row();
+                                }
+                            });
+                        });
+                    }
+
+                    ui.collapsing(format!("Log of {}", plugin.name), |ui| {
+                        if plugin.log.is_empty() {
+                            ui.label("Nothing logged yet");
+                            return;
+                        }
+
+                        egui::ScrollArea::vertical()
+                            .id_source(format!("{} log", plugin.name))
+                            .max_height(200.)
+                            .stick_to_bottom(true)
+                            .show(ui, |ui| ui.monospace(plugin.log.as_str()));
+                    });
+
+                    ui.separator();
+                }
+
+                #[cfg(feature = "installer")]
+                if self.index.show(ui) {
+                    self.installed = installed_plugins(&self.data_dir);
+                }
+
+                if !self.installed.is_empty() {
+                    ui.heading("Installed plugin files");
+                    ui.label("These are not loaded by this version of the manager yet.");
+
+                    for file in &self.installed {
+                        ui.label(file.as_str());
+                    }
+
+                    ui.separator();
+                }
+
+                if ui.button("Save").clicked() {
+                    self.status = Some(match self.save() {
+                        Ok(()) => "Saved, reload plugins from the tray to apply".to_string(),
+                        Err(error) => format!("Failed to save: {:#}", error),
+                    });
+                }
+
+                if let Some(status) = &self.status {
+                    ui.label(status.as_str());
+                }
+            });
+        });
+    }
+}
+
+pub fn run(data_dir: &Path) -> Result<()> {
+    let manager = PluginManager::load(data_dir)?;
+
+    eframe::run_native(
+        "VRC OSC Manager - Plugins",
+        eframe::NativeOptions::default(),
+        Box::new(|_| Box::new(manager)),
+    )
+    .map_err(|error| anyhow!("Failed to open plugin manager: {}", error))
+}
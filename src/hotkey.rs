@@ -0,0 +1,117 @@
+use crate::config::HotkeyConfig;
+use crate::tray::TrayMessage;
+use anyhow::{Context, Result};
+use global_hotkey::hotkey::HotKey;
+use global_hotkey::{GlobalHotKeyEvent, GlobalHotKeyManager};
+use log::{info, warn};
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use tokio_graceful_shutdown::SubsystemHandle;
+
+/// Registers the desktop wide hotkeys, which stay registered once the process holds them, so they
+/// are handled on a thread of their own.
+pub struct Hotkeys {
+    config: HotkeyConfig,
+    tray_tx: mpsc::Sender<TrayMessage>,
+}
+
+impl Hotkeys {
+    pub fn new(config: &HotkeyConfig, tray_tx: mpsc::Sender<TrayMessage>) -> Self {
+        Self {
+            config: config.clone(),
+            tray_tx,
+        }
+    }
+
+    pub async fn run(self, subsys: SubsystemHandle) -> Result<()> {
+        if self.config.paste.is_empty() {
+            info!("No hotkey configured");
+            subsys.on_shutdown_requested().await;
+            return Ok(());
+        }
+
+        let hotkey: HotKey = self
+            .config
+            .paste
+            .parse()
+            .with_context(|| format!("Invalid hotkey {}", self.config.paste))?;
+        let (registered_tx, registered_rx) = oneshot::channel();
+        let tray_tx = self.tray_tx;
+
+        std::thread::spawn(move || listen(hotkey, registered_tx, tray_tx));
+
+        registered_rx
+            .await
+            .context("Hotkey thread stopped")?
+            .with_context(|| format!("Failed to register hotkey {}", self.config.paste))?;
+        info!(
+            "Sending the clipboard to the chatbox on {}",
+            self.config.paste
+        );
+
+        subsys.on_shutdown_requested().await;
+        Ok(())
+    }
+}
+
+/// The manager has to stay on the thread it was created on, which on Windows also has to handle
+/// the messages of its window.
+fn listen(
+    hotkey: HotKey,
+    registered_tx: oneshot::Sender<Result<()>>,
+    tray_tx: mpsc::Sender<TrayMessage>,
+) {
+    let manager = match GlobalHotKeyManager::new() {
+        Ok(manager) => manager,
+        Err(error) => {
+            let _ = registered_tx.send(Err(error.into()));
+            return;
+        }
+    };
+
+    if let Err(error) = manager.register(hotkey) {
+        let _ = registered_tx.send(Err(error.into()));
+        return;
+    }
+
+    let _ = registered_tx.send(Ok(()));
+
+    loop {
+        #[cfg(target_os = "windows")]
+        dispatch_messages();
+
+        match GlobalHotKeyEvent::receiver().recv_timeout(Duration::from_millis(50)) {
+            Ok(event) if event.id == hotkey.id() => {
+                // The manager is gone once the tray stops listening, so the thread ends with it.
+                if tray_tx.blocking_send(TrayMessage::SendClipboard).is_err() {
+                    break;
+                }
+            }
+            Ok(_) => {}
+            Err(error) if error.is_disconnected() => {
+                warn!("Hotkey events stopped");
+                break;
+            }
+            Err(_) => {}
+        }
+    }
+
+    let _ = manager.unregister(hotkey);
+}
+
+#[cfg(target_os = "windows")]
+fn dispatch_messages() {
+    use windows::Win32::Foundation::HWND;
+    use windows::Win32::UI::WindowsAndMessaging::{
+        DispatchMessageW, PeekMessageW, TranslateMessage, MSG, PM_REMOVE,
+    };
+
+    let mut message = MSG::default();
+
+    unsafe {
+        while PeekMessageW(&mut message, HWND(0), 0, 0, PM_REMOVE).as_bool() {
+            TranslateMessage(&message);
+            DispatchMessageW(&message);
+        }
+    }
+}
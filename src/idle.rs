@@ -0,0 +1,92 @@
+use crate::config::IdleConfig;
+use crate::rules;
+use crate::StatusEvent;
+use anyhow::{bail, Result};
+use async_osc::OscMessage;
+use log::{info, warn};
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, mpsc, watch};
+use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
+
+/// Follows the AFK parameter VRChat reports, which switches the plugins into idle mode.
+pub struct IdleDetector {
+    config: IdleConfig,
+    rx: broadcast::Receiver<Arc<OscMessage>>,
+    idle_tx: watch::Sender<bool>,
+    status_tx: mpsc::Sender<StatusEvent>,
+}
+
+impl IdleDetector {
+    pub fn new(
+        config: &IdleConfig,
+        rx: broadcast::Receiver<Arc<OscMessage>>,
+        idle_tx: watch::Sender<bool>,
+        status_tx: mpsc::Sender<StatusEvent>,
+    ) -> Self {
+        Self {
+            config: config.clone(),
+            rx,
+            idle_tx,
+            status_tx,
+        }
+    }
+
+    async fn detect(&mut self) -> Result<()> {
+        loop {
+            let message = match self.rx.recv().await {
+                Ok(message) => message,
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!(
+                        "Idle detector lagging behind, {} messages have been dropped",
+                        skipped
+                    );
+                    continue;
+                }
+                Err(RecvError::Closed) => bail!("Message receiver died unexpectedly"),
+            };
+
+            if message.addr != self.config.parameter {
+                continue;
+            }
+
+            let Some(value) = rules::value(&message) else {
+                continue;
+            };
+
+            let idle = value != 0.;
+
+            if idle != *self.idle_tx.borrow() {
+                info!(
+                    "{}",
+                    if idle {
+                        "Entering idle mode"
+                    } else {
+                        "Leaving idle mode"
+                    }
+                );
+                self.idle_tx.send_replace(idle);
+                self.status_tx.send(StatusEvent::Idle(idle)).await?;
+            }
+        }
+    }
+
+    pub async fn run(mut self, subsys: SubsystemHandle) -> Result<()> {
+        if !self.config.enabled {
+            // Unread messages would pile up in the receiver, while idle mode has to stay readable.
+            let Self { rx, idle_tx, .. } = self;
+            drop(rx);
+            subsys.on_shutdown_requested().await;
+            drop(idle_tx);
+            return Ok(());
+        }
+
+        match (self.detect().cancel_on_shutdown(&subsys)).await {
+            Ok(Ok(())) => subsys.request_shutdown(),
+            Ok(Err(error)) => return Err(error),
+            Err(CancelledByShutdown) => {}
+        }
+
+        Ok(())
+    }
+}
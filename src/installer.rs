@@ -0,0 +1,309 @@
+use crate::config::Config;
+use crate::plugins;
+use anyhow::{bail, Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::env::consts::{ARCH, DLL_EXTENSION, OS};
+use std::path::Path;
+use std::sync::Arc;
+use tokio::fs::{create_dir_all, write};
+
+/// What an out-of-tree plugin declares to need in the plugin index. Installed plugins are not
+/// loaded yet, so nothing is granted or enforced, these are only listed for the user.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+struct Permissions {
+    network: bool,
+    filesystem: bool,
+    /// Address patterns the plugin sends to, where `*` matches any sequence of characters.
+    osc_write: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Index {
+    plugins: Vec<IndexEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IndexEntry {
+    name: String,
+    version: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    permissions: Permissions,
+    /// Name of the publisher whose key signed the artifacts.
+    #[serde(default)]
+    publisher: Option<String>,
+    artifacts: Vec<Artifact>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ArtifactKind {
+    Dylib,
+    Wasm,
+}
+
+#[derive(Debug, Deserialize)]
+struct Artifact {
+    kind: ArtifactKind,
+    #[serde(default)]
+    target: Option<String>,
+    url: String,
+    sha256: String,
+    /// Base64 encoded ed25519 signature of the artifact.
+    #[serde(default)]
+    signature: Option<String>,
+}
+
+fn host_target() -> String {
+    format!("{}-{}", ARCH, OS)
+}
+
+async fn fetch_index(config: &Config) -> Result<Index> {
+    let url = &config.plugin_index.url;
+
+    if url.is_empty() {
+        bail!("No plugin index configured, set `url` in the `[plugin_index]` section");
+    }
+
+    let index = reqwest::get(url)
+        .await
+        .with_context(|| format!("Failed to fetch plugin index from {}", url))?
+        .error_for_status()?
+        .json()
+        .await
+        .context("Failed to parse plugin index")?;
+
+    Ok(index)
+}
+
+/// Picks a native library built for the current platform, falling back to a WASM build.
+fn select_artifact(entry: &IndexEntry) -> Option<&Artifact> {
+    let target = host_target();
+
+    entry
+        .artifacts
+        .iter()
+        .find(|artifact| {
+            artifact.kind == ArtifactKind::Dylib
+                && artifact.target.as_deref() == Some(target.as_str())
+        })
+        .or_else(|| {
+            entry
+                .artifacts
+                .iter()
+                .find(|artifact| artifact.kind == ArtifactKind::Wasm)
+        })
+}
+
+fn verify_signature(key: &str, signature: &str, bytes: &[u8]) -> Result<()> {
+    let key: [u8; 32] = STANDARD
+        .decode(key)?
+        .try_into()
+        .ok()
+        .context("Public keys have to be 32 bytes long")?;
+    let signature: [u8; 64] = STANDARD
+        .decode(signature)?
+        .try_into()
+        .ok()
+        .context("Signatures have to be 64 bytes long")?;
+
+    VerifyingKey::from_bytes(&key)?.verify(bytes, &Signature::from_bytes(&signature))?;
+    Ok(())
+}
+
+/// Checks that a trusted publisher signed the artifact. An invalid signature always refuses the
+/// artifact, while missing ones are accepted in developer mode.
+fn verify(config: &Config, entry: &IndexEntry, artifact: &Artifact, bytes: &[u8]) -> Result<()> {
+    let problem = match (&entry.publisher, &artifact.signature) {
+        (Some(publisher), Some(signature)) => match config.plugin_index.publishers.get(publisher) {
+            Some(key) => {
+                return verify_signature(key, signature, bytes).with_context(|| {
+                    format!(
+                        "Signature of {} by {} is invalid, refusing to install",
+                        artifact.url, publisher
+                    )
+                });
+            }
+            None => format!("its publisher {} is not trusted", publisher),
+        },
+        _ => "it is not signed".to_string(),
+    };
+
+    if !config.plugin_index.developer_mode {
+        bail!(
+            "Refusing to install {} as {}. Add the key of the publisher to `publishers` in the \
+            `[plugin_index]` section, or enable `developer_mode` to install it anyway",
+            entry.name,
+            problem
+        );
+    }
+
+    println!(
+        "Warning: installing {} although {}, as developer mode is enabled",
+        entry.name, problem
+    );
+    Ok(())
+}
+
+fn describe(permissions: &Permissions) -> Vec<String> {
+    let mut lines = vec![];
+
+    if permissions.network {
+        lines.push("access the network".to_string());
+    }
+
+    if permissions.filesystem {
+        lines.push("access files outside its data directory".to_string());
+    }
+
+    for pattern in &permissions.osc_write {
+        lines.push(format!("send OSC messages to {}", pattern));
+    }
+
+    lines
+}
+
+/// Plugin offered by the index, as shown when browsing it.
+pub struct Listing {
+    pub name: String,
+    pub version: String,
+    pub description: String,
+    /// Permissions the plugin declares, described for the user.
+    pub permissions: Vec<String>,
+    /// Whether there is an artifact for this platform.
+    pub available: bool,
+}
+
+pub async fn listings(config: &Config) -> Result<Vec<Listing>> {
+    let index = fetch_index(config).await?;
+
+    Ok(index
+        .plugins
+        .iter()
+        .map(|entry| Listing {
+            name: entry.name.clone(),
+            version: entry.version.clone(),
+            description: entry.description.clone(),
+            permissions: describe(&entry.permissions),
+            available: select_artifact(entry).is_some(),
+        })
+        .collect())
+}
+
+pub async fn list(config: &Config) -> Result<()> {
+    for listing in listings(config).await? {
+        let available = if listing.available {
+            ""
+        } else {
+            " (not available for this platform)"
+        };
+
+        println!(
+            "{} {}{}\n  {}",
+            listing.name, listing.version, available, listing.description
+        );
+    }
+
+    Ok(())
+}
+
+/// Installs a plugin from the index.
+pub async fn install(config: &Config, data_dir: &Path, name: &str) -> Result<()> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        bail!("Invalid plugin name: {}", name);
+    }
+
+    // Plugins are configured and addressed by name, so one posing as a built-in one would take its
+    // place.
+    if plugins::builtin(&Arc::new(config.clone()))
+        .iter()
+        .any(|plugin| plugin.name() == name)
+    {
+        bail!("Plugin {} clashes with a built-in plugin", name);
+    }
+
+    let index = fetch_index(config).await?;
+    let entry = index
+        .plugins
+        .iter()
+        .find(|entry| entry.name == name)
+        .with_context(|| format!("Plugin {} not found in plugin index", name))?;
+    let artifact = select_artifact(entry).with_context(|| {
+        format!(
+            "Plugin {} has no artifact for {} or WASM",
+            name,
+            host_target()
+        )
+    })?;
+
+    let permissions = describe(&entry.permissions);
+
+    if permissions.is_empty() {
+        println!("{} declares no permissions", name);
+    } else {
+        println!("{} declares to {}", name, permissions.join(", "));
+    }
+
+    println!(
+        "Downloading {} {} from {}",
+        entry.name, entry.version, artifact.url
+    );
+
+    let bytes = reqwest::get(&artifact.url)
+        .await
+        .with_context(|| format!("Failed to download {}", artifact.url))?
+        .error_for_status()?
+        .bytes()
+        .await?;
+
+    let checksum = format!("{:x}", Sha256::digest(&bytes));
+
+    if checksum != artifact.sha256.to_lowercase() {
+        bail!(
+            "Checksum mismatch for {}: expected {}, got {}",
+            artifact.url,
+            artifact.sha256,
+            checksum
+        );
+    }
+
+    verify(config, entry, artifact, &bytes)?;
+
+    let plugins_dir = data_dir.join("plugins");
+    create_dir_all(&plugins_dir)
+        .await
+        .with_context(|| format!("Failed to create {}", plugins_dir.display()))?;
+
+    let extension = match artifact.kind {
+        ArtifactKind::Dylib => DLL_EXTENSION,
+        ArtifactKind::Wasm => "wasm",
+    };
+    let path = plugins_dir.join(format!("{}.{}", entry.name, extension));
+
+    write(&path, &bytes)
+        .await
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+
+    println!(
+        "Installed {} {} to {}",
+        entry.name,
+        entry.version,
+        path.display()
+    );
+    println!(
+        "This version of the manager does not load installed plugins yet, so {} does not run",
+        entry.name
+    );
+
+    Ok(())
+}
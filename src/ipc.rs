@@ -0,0 +1,252 @@
+use crate::config::IpcConfig;
+use crate::state::SharedState;
+use crate::tray::TrayMessage;
+use anyhow::{bail, Context, Result};
+use async_osc::{OscMessage, OscType};
+use log::{debug, info, warn};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::spawn;
+use tokio::sync::mpsc;
+use tokio::time::sleep;
+use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
+use vrc_osc_manager_core::addresses;
+
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+#[cfg(windows)]
+use tokio::net::windows::named_pipe::ServerOptions;
+#[cfg(unix)]
+use tokio::net::UnixListener;
+
+fn format_arg(arg: &OscType) -> String {
+    match arg {
+        OscType::Bool(value) => value.to_string(),
+        OscType::Int(value) => value.to_string(),
+        OscType::Float(value) => value.to_string(),
+        OscType::String(value) => format!("{:?}", value),
+        other => format!("{:?}", other),
+    }
+}
+
+struct Handler {
+    state: Arc<SharedState>,
+    tray_tx: mpsc::Sender<TrayMessage>,
+}
+
+impl Handler {
+    fn status(&self) -> String {
+        format!(
+            "plugins={} output={} sent={} received={}",
+            if self.state.plugins_running.load(Ordering::Relaxed) {
+                "running"
+            } else {
+                "stopped"
+            },
+            if self.state.output_paused.load(Ordering::Relaxed) {
+                "paused"
+            } else {
+                "active"
+            },
+            self.state.stats.sent(),
+            self.state.stats.received(),
+        )
+    }
+
+    /// Executes a single command, returning the text following the `ok` of the response.
+    async fn execute(&self, line: &str) -> Result<String> {
+        let (command, argument) = line.split_once(' ').unwrap_or((line, ""));
+        let argument = argument.trim();
+
+        let message = match command {
+            "status" => return Ok(self.status()),
+            "get" => {
+                let parameters = self.state.parameters.lock().unwrap();
+                let message = parameters
+                    .get(argument)
+                    .with_context(|| format!("No value received for {}", argument))?;
+                let values: Vec<String> = message.args.iter().map(format_arg).collect();
+                return Ok(values.join(" "));
+            }
+            "send" => {
+                let Some((addr, value)) = argument.split_once(' ') else {
+                    bail!("Usage: send <address> <value>");
+                };
+
+                TrayMessage::Send(OscMessage {
+                    addr: addr.to_string(),
+                    args: vec![addresses::parse_argument(value.trim())],
+                })
+            }
+            "reload" => TrayMessage::ReloadPlugins,
+            #[cfg(feature = "clipboard")]
+            "paste" => TrayMessage::SendClipboard,
+            "pause" => TrayMessage::SetPaused(true),
+            "resume" => TrayMessage::SetPaused(false),
+            "enable" | "disable" if !argument.is_empty() => {
+                TrayMessage::SetPluginEnabled(argument.to_string(), command == "enable")
+            }
+            _ => bail!("Unknown command {}", command),
+        };
+
+        self.tray_tx
+            .send(message)
+            .await
+            .ok()
+            .context("Shutting down")?;
+        Ok(String::new())
+    }
+
+    /// Answers every line with either `ok`, followed by the result if any, or `error` and a reason.
+    async fn serve<S>(&self, stream: S) -> Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let (reader, mut writer) = tokio::io::split(stream);
+        let mut lines = BufReader::new(reader).lines();
+
+        while let Some(line) = lines.next_line().await? {
+            let line = line.trim();
+
+            if line.is_empty() {
+                continue;
+            }
+
+            let response = match self.execute(line).await {
+                Ok(result) if result.is_empty() => "ok\n".to_string(),
+                Ok(result) => format!("ok {}\n", result),
+                Err(error) => format!("error {:#}\n", error),
+            };
+
+            writer.write_all(response.as_bytes()).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Line based control interface for local scripts, listening on a Unix domain socket on Linux and
+/// a named pipe on Windows, so no network port has to be opened.
+pub struct IpcServer {
+    config: IpcConfig,
+    data_dir: PathBuf,
+    state: Arc<SharedState>,
+    tray_tx: mpsc::Sender<TrayMessage>,
+}
+
+impl IpcServer {
+    pub fn new(
+        config: &IpcConfig,
+        data_dir: &Path,
+        state: Arc<SharedState>,
+        tray_tx: mpsc::Sender<TrayMessage>,
+    ) -> Self {
+        Self {
+            config: config.clone(),
+            data_dir: data_dir.to_path_buf(),
+            state,
+            tray_tx,
+        }
+    }
+
+    fn handler(&self) -> Arc<Handler> {
+        Arc::new(Handler {
+            state: self.state.clone(),
+            tray_tx: self.tray_tx.clone(),
+        })
+    }
+
+    #[cfg(unix)]
+    async fn listen(&self) -> Result<()> {
+        let path = if self.config.path.is_empty() {
+            self.data_dir.join("ipc.sock")
+        } else {
+            PathBuf::from(&self.config.path)
+        };
+
+        // A socket left behind by a crashed instance would keep the bind from succeeding.
+        let _ = tokio::fs::remove_file(&path).await;
+
+        let listener = UnixListener::bind(&path)
+            .with_context(|| format!("Failed to listen on {}", path.display()))?;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+        info!("IPC interface listening on {}", path.display());
+
+        let handler = self.handler();
+
+        loop {
+            // Errors like running out of file descriptors pass, so they do not stop the interface.
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(error) => {
+                    warn!("Failed to accept an IPC connection: {}", error);
+                    sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+            };
+            let handler = handler.clone();
+
+            spawn(async move {
+                if let Err(error) = handler.serve(stream).await {
+                    debug!("IPC connection failed: {}", error);
+                }
+            });
+        }
+    }
+
+    #[cfg(windows)]
+    async fn listen(&self) -> Result<()> {
+        let path = if self.config.path.is_empty() {
+            r"\\.\pipe\vrc-osc-manager".to_string()
+        } else {
+            self.config.path.clone()
+        };
+
+        let mut server = ServerOptions::new()
+            .first_pipe_instance(true)
+            .create(&path)
+            .with_context(|| format!("Failed to create pipe {}", path))?;
+        info!("IPC interface listening on {}", path);
+
+        let handler = self.handler();
+
+        loop {
+            if let Err(error) = server.connect().await {
+                warn!("Failed to accept an IPC connection: {}", error);
+                sleep(Duration::from_millis(100)).await;
+                server = ServerOptions::new().create(&path)?;
+                continue;
+            }
+
+            // Every client gets its own pipe instance, so the next one has to be created before
+            // the connected one is handed off.
+            let client = server;
+            server = ServerOptions::new().create(&path)?;
+            let handler = handler.clone();
+
+            spawn(async move {
+                if let Err(error) = handler.serve(client).await {
+                    debug!("IPC connection failed: {}", error);
+                }
+            });
+        }
+    }
+
+    pub async fn run(self, subsys: SubsystemHandle) -> Result<()> {
+        if !self.config.enabled {
+            subsys.on_shutdown_requested().await;
+            return Ok(());
+        }
+
+        match (self.listen().cancel_on_shutdown(&subsys)).await {
+            Ok(Ok(())) => subsys.request_shutdown(),
+            Ok(Err(error)) => return Err(error),
+            Err(CancelledByShutdown) => {}
+        }
+
+        Ok(())
+    }
+}
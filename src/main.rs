@@ -3,41 +3,129 @@
     windows_subsystem = "windows"
 )]
 
+mod accounting;
+mod avatar;
+mod backpressure;
+mod bitpack;
+mod chatbox;
+#[cfg(feature = "clipboard")]
+mod clipboard;
+mod cluster;
 mod config;
+mod config_watcher;
+#[cfg(feature = "control")]
+mod console;
+#[cfg(feature = "control")]
+mod control;
+#[cfg(all(target_os = "linux", feature = "dbus_service"))]
+mod dbus_service;
+mod diff;
+#[cfg(feature = "discovery")]
+mod discovery;
+mod doctor;
+mod event_log;
+mod face_tracking;
+mod flood;
+mod fuzz;
+mod governor;
+#[cfg(feature = "gui")]
+mod gui;
+#[cfg(feature = "hotkey")]
+mod hotkey;
+mod idle;
+#[cfg(feature = "installer")]
+mod installer;
+#[cfg(feature = "ipc")]
+mod ipc;
+#[cfg(feature = "menu_settings")]
+mod menu_settings;
+mod merge;
+mod monitor;
 mod osc;
+#[cfg(feature = "oscquery")]
+mod oscquery;
+mod overrides;
+mod param_docs;
+mod ping;
+mod plugin_logs;
 mod plugins;
+mod proxy;
+mod reachability;
+mod recording;
+#[cfg(feature = "control")]
+mod remote;
+mod reset;
+mod rules;
+mod schedule;
+#[cfg(any(feature = "control", feature = "donations"))]
+mod secret;
+mod self_test;
+mod session;
+mod state;
+mod stats;
+#[cfg(feature = "steam_presence")]
+mod steam_presence;
+#[cfg(target_os = "windows")]
+mod toast;
 mod tray;
+#[cfg(feature = "tui")]
+mod tui;
+#[cfg(feature = "vrchat_api")]
+mod vrchat_api;
+mod vrchat_log;
+mod vrchat_settings;
+mod watchdog;
 
+use crate::accounting::Measured;
 use crate::config::{load_config, Config};
+use crate::state::{PluginState, SharedState};
 use crate::tray::TrayMessage;
 use anyhow::{bail, Context, Result};
 use async_osc::OscMessage;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use directories::BaseDirs;
 use file_rotate::compression::Compression;
 use file_rotate::suffix::{AppendTimestamp, FileLimit};
 use file_rotate::{ContentLimit, FileRotate, TimeFrequency};
-use log::{debug, error, info, LevelFilter};
-use simplelog::{ColorChoice, CombinedLogger, TermLogger, TerminalMode, WriteLogger};
+use log::{debug, error, info, warn, LevelFilter};
+use simplelog::{ColorChoice, CombinedLogger, SharedLogger, TermLogger, TerminalMode, WriteLogger};
+use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::time::Duration;
 use sysinfo::{ProcessRefreshKind, RefreshKind, System, SystemExt};
 use tokio::select;
-use tokio::sync::{broadcast, mpsc};
-use tokio::time::sleep;
+use tokio::sync::{broadcast, mpsc, watch};
+use tokio::task::spawn_blocking;
+use tokio::time::{interval, sleep};
 use tokio_graceful_shutdown::{
     errors::CancelledByShutdown, FutureExt, NestedSubsystem, SubsystemHandle, Toplevel,
 };
+use vrc_osc_manager_core::{
+    arming, Activity, ChatboxQueue, DeviceStatus, Events, InstanceWatcher, Notification,
+    NotificationAction, Notifier, OscSender, OutgoingMessage, ParameterAccess, Plugin,
+    PluginContext, Readiness, VrchatLogEvent, MANAGER,
+};
 
 struct VrChatActivity {
     tx: mpsc::Sender<bool>,
+    /// Whether VRChat is running, `None` until checked first.
+    running_tx: watch::Sender<Option<bool>>,
     disabled: bool,
 }
 
 impl VrChatActivity {
-    fn new(tx: mpsc::Sender<bool>, disabled: bool) -> Self {
-        Self { tx, disabled }
+    fn new(
+        tx: mpsc::Sender<bool>,
+        running_tx: watch::Sender<Option<bool>>,
+        disabled: bool,
+    ) -> Self {
+        Self {
+            tx,
+            running_tx,
+            disabled,
+        }
     }
 
     async fn check(&self) -> Result<()> {
@@ -49,6 +137,8 @@ impl VrChatActivity {
             debug!("Checking if VRChat is running");
             sys.refresh_specifics(refresh_kind);
             let running = sys.processes_by_name("VRChat").next().is_some();
+            self.running_tx
+                .send_if_modified(|value| value.replace(running) != Some(running));
 
             if running != vrchat_running {
                 vrchat_running = running;
@@ -66,6 +156,7 @@ impl VrChatActivity {
 
     async fn run(self, subsys: SubsystemHandle) -> Result<()> {
         if self.disabled {
+            self.running_tx.send_replace(Some(true));
             self.tx.send(true).await?;
             subsys.on_shutdown_requested().await;
             return Ok(());
@@ -81,115 +172,1013 @@ impl VrChatActivity {
     }
 }
 
+/// Manager command of the notification action reloading the plugins.
+const RELOAD: &str = "reload";
+
+struct Notifications {
+    rx: mpsc::Receiver<Notification>,
+    state: Arc<SharedState>,
+    tray_tx: mpsc::Sender<TrayMessage>,
+}
+
+impl Notifications {
+    fn new(
+        rx: mpsc::Receiver<Notification>,
+        state: Arc<SharedState>,
+        tray_tx: mpsc::Sender<TrayMessage>,
+    ) -> Self {
+        Self { rx, state, tray_tx }
+    }
+
+    async fn show(&mut self) -> Result<()> {
+        let (action_tx, mut action_rx) = mpsc::unbounded_channel::<vrc_osc_manager_core::Command>();
+
+        #[cfg(target_os = "windows")]
+        let mut toasts = toast::Toasts::new(action_tx)?;
+        #[cfg(not(target_os = "windows"))]
+        drop(action_tx);
+
+        loop {
+            select! {
+                notification = self.rx.recv() => {
+                    let Some(notification) = notification else {
+                        bail!("Notification channel closed unexpectedly");
+                    };
+
+                    info!("{}: {}", notification.title, notification.body);
+
+                    #[cfg(target_os = "windows")]
+                    if let Err(error) = toasts.show(&notification) {
+                        log::warn!("Failed to show notification: {:#}", error);
+                    }
+                }
+                Some(command) = action_rx.recv() => {
+                    if command.plugin != MANAGER {
+                        self.state.commands.send(command);
+                        continue;
+                    }
+
+                    match command.name {
+                        RELOAD => self.tray_tx.send(TrayMessage::ReloadPlugins).await?,
+                        arming::ARM => self.tray_tx.send(TrayMessage::SetArmed(true)).await?,
+                        arming::DISARM => self.tray_tx.send(TrayMessage::SetArmed(false)).await?,
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+
+    async fn run(mut self, subsys: SubsystemHandle) -> Result<()> {
+        match (self.show().cancel_on_shutdown(&subsys)).await {
+            Ok(Ok(())) => subsys.request_shutdown(),
+            Ok(Err(error)) => return Err(error),
+            Err(CancelledByShutdown) => {}
+        }
+
+        Ok(())
+    }
+}
+
+/// Events shown in the tray, reported to the launcher by other subsystems.
+pub enum StatusEvent {
+    PluginFailed(&'static str),
+    PluginStarting(&'static str, bool),
+    TargetReachable(bool),
+    Flooded(bool),
+    Congested(bool),
+    LinkVerified(bool),
+    DeviceConnected(&'static str, bool),
+    Idle(bool),
+    WorldChanged(String),
+    PlayerJoined(String),
+    #[cfg(feature = "discovery")]
+    TargetDiscovered(String),
+}
+
+/// Handles shared by all plugins, cloned into every plugin subsystem.
+#[derive(Clone)]
+struct PluginEnvironment {
+    data_dir: PathBuf,
+    receiver_tx: broadcast::Sender<Arc<OscMessage>>,
+    sender_tx: mpsc::Sender<OutgoingMessage>,
+    notifier: Notifier,
+    activity: Activity,
+    throttled: watch::Receiver<bool>,
+    chatbox_queue: ChatboxQueue,
+    status_tx: mpsc::Sender<StatusEvent>,
+    state: Arc<SharedState>,
+    instance: InstanceWatcher,
+    vrchat_log_tx: broadcast::Sender<VrchatLogEvent>,
+    events: Events,
+}
+
+/// Sends the values parameters are reset to once their plugin stops.
+async fn send_resets(
+    sender_tx: &mpsc::Sender<OutgoingMessage>,
+    source: &'static str,
+    messages: Vec<OscMessage>,
+) {
+    for message in messages {
+        let _ = sender_tx.send(OutgoingMessage::new(source, message)).await;
+    }
+}
+
+/// Starts or stops a single plugin, while the other plugins keep running.
+pub enum PluginControl {
+    /// Starts the plugin, or restarts it if it is running, with the given config.
+    Start(String, Arc<Config>),
+    Stop(String),
+}
+
+async fn start_plugin(
+    subsys: &SubsystemHandle,
+    mut plugin: Box<dyn Plugin>,
+    config: &Config,
+    environment: &PluginEnvironment,
+) -> NestedSubsystem {
+    let name = plugin.name();
+    let plugin_config = config.plugin_config(name);
+    let required = plugin.required() || config.plugin_required(name);
+    let lazy = plugin.lazy();
+    let mut resets = config.reset_messages(Some(name));
+    let usage = environment.state.accounting.usage(name);
+    let context = PluginContext {
+        osc: OscSender::new(environment.sender_tx.clone(), name),
+        receiver: environment.state.channels.relay(
+            &format!("Plugin {}", name),
+            environment.receiver_tx.subscribe(),
+            Some(usage.clone()),
+            plugin.parameters(&plugin_config).iter().any(|parameter| {
+                matches!(
+                    parameter.access,
+                    ParameterAccess::Read | ParameterAccess::ReadWrite
+                )
+            }),
+        ),
+        config: plugin_config.clone(),
+        data_dir: environment.data_dir.clone(),
+        notifier: environment.notifier.clone(),
+        activity: if config.governor.low_priority.iter().any(|low| low == name) {
+            environment
+                .activity
+                .clone()
+                .throttled_by(environment.throttled.clone(), config.governor.throttle)
+        } else {
+            environment.activity.clone()
+        },
+        chatbox_queue: environment.chatbox_queue.clone(),
+        device: {
+            let status_tx = environment.status_tx.clone();
+            DeviceStatus::new(name, move |connected| {
+                let status_tx = status_tx.clone();
+                tokio::spawn(async move {
+                    let _ = status_tx
+                        .send(StatusEvent::DeviceConnected(name, connected))
+                        .await;
+                });
+            })
+        },
+        instance: environment.instance.clone(),
+        vrchat_log: environment.vrchat_log_tx.subscribe(),
+        heartbeat: environment.state.heartbeats.heartbeat(name),
+        readiness: {
+            let status_tx = environment.status_tx.clone();
+            Readiness::new(move || {
+                let status_tx = status_tx.clone();
+                tokio::spawn(async move {
+                    let _ = status_tx
+                        .send(StatusEvent::PluginStarting(name, false))
+                        .await;
+                });
+            })
+        },
+        metrics: environment.state.metrics.clone(),
+        events: environment.events.with_source(name),
+        commands: environment.state.commands.subscribe(name),
+        arming: environment.state.arming.clone(),
+    };
+    let readiness = context.readiness.clone();
+    let status_tx = environment.status_tx.clone();
+    let chatbox_queue = environment.chatbox_queue.clone();
+    let heartbeats = environment.state.heartbeats.clone();
+    let sender_tx = environment.sender_tx.clone();
+    let events = context.events.clone();
+
+    let _ = status_tx
+        .send(StatusEvent::PluginStarting(name, true))
+        .await;
+
+    // A failing plugin is reported instead of taking down the entire application, unless the
+    // setup cannot do without it. Plugins set up in the background, so one which takes long
+    // to initialize does not hold up the others.
+    subsys.start(name, move |subsys| async move {
+        let setup = spawn_blocking(move || {
+            let result = plugin.setup(&plugin_config);
+            (plugin, plugin_config, result)
+        });
+        let (plugin, plugin_config) = match setup.cancel_on_shutdown(&subsys).await {
+            Ok(joined) => match joined? {
+                (plugin, plugin_config, Ok(())) => (plugin, plugin_config),
+                (_, _, Err(error)) => {
+                    readiness.set_ready();
+
+                    if required {
+                        return Err(
+                            error.context(format!("Required plugin {} failed to set up", name))
+                        );
+                    }
+
+                    error!("Plugin {} failed to set up: {:#}", name, error);
+                    events.record("plugin_failed", vec![("error", format!("{:#}", error))]);
+                    let _ = status_tx.send(StatusEvent::PluginFailed(name)).await;
+                    return Ok(());
+                }
+            },
+            Err(CancelledByShutdown) => {
+                readiness.set_ready();
+                return Ok(());
+            }
+        };
+
+        resets.extend(plugin.reset_messages(&plugin_config));
+
+        if !lazy {
+            readiness.set_ready();
+        }
+
+        events.record("plugin_started", vec![]);
+        let result = Measured::new(plugin.run(context, subsys), usage).await;
+        // A plugin stopping before it got ready is not starting anymore either.
+        readiness.set_ready();
+        send_resets(&sender_tx, name, resets).await;
+        chatbox_queue.set_line(name, "");
+        heartbeats.remove(name);
+
+        if let Err(error) = result {
+            events.record("plugin_failed", vec![("error", format!("{:#}", error))]);
+
+            if required {
+                return Err(error.context(format!("Required plugin {} failed", name)));
+            }
+
+            error!("Plugin {} failed: {:#}", name, error);
+            let _ = status_tx.send(StatusEvent::PluginFailed(name)).await;
+        } else {
+            events.record("plugin_stopped", vec![]);
+        }
+
+        Ok::<(), anyhow::Error>(())
+    })
+}
+
+/// Whether an enabled plugin may run right now, as it can be held back by its schedule or while
+/// the user is idle. Plugins held back are tracked, so they are not shown as running.
+fn may_run(
+    config: &Config,
+    schedules: &schedule::Schedules,
+    idle: bool,
+    state: &SharedState,
+    name: &str,
+) -> bool {
+    let held = if !schedules.allows(config.plugin_schedule(name)) {
+        info!("Plugin {} is outside of its schedule", name);
+        true
+    } else if idle
+        && config
+            .idle
+            .pause_plugins
+            .iter()
+            .any(|paused| paused == name)
+    {
+        info!("Plugin {} is paused while idle", name);
+        true
+    } else {
+        false
+    };
+
+    let mut held_plugins = state.held_plugins.lock().unwrap();
+    held_plugins.retain(|other| other != name);
+
+    if held {
+        held_plugins.push(name.to_string());
+    }
+
+    !held
+}
+
+/// Hands a message to the plugins subsystem without waiting for it, as it may just be shutting down
+/// a plugin.
+fn send_control(control_tx: &mpsc::Sender<PluginControl>, control: PluginControl) {
+    if let Err(error) = control_tx.try_send(control) {
+        warn!("Failed to start or stop plugin: {}", error);
+    }
+}
+
 async fn run_plugins(
     subsys: SubsystemHandle,
-    config: Arc<Config>,
-    data_dir: PathBuf,
-    receiver_tx: broadcast::Sender<OscMessage>,
-    sender_tx: mpsc::Sender<OscMessage>,
+    mut config: Arc<Config>,
+    environment: PluginEnvironment,
+    idle: bool,
+    mut control_rx: mpsc::Receiver<PluginControl>,
 ) -> Result<()> {
-    #[cfg(feature = "watch")]
-    {
-        let sender_tx = sender_tx.clone();
-        subsys.start("PluginWatch", |subsys| {
-            plugins::watch::Watch::new(sender_tx).run(subsys)
-        });
+    let mut schedules = schedule::Schedules::new(&config.schedules);
+    let mut running = HashMap::new();
+
+    for plugin in plugins::builtin(&config) {
+        let name = plugin.name();
+
+        if !config.plugin_enabled(name) {
+            info!("Plugin {} is disabled", name);
+            continue;
+        }
+
+        if !may_run(&config, &schedules, idle, &environment.state, name) {
+            continue;
+        }
+
+        running.insert(
+            name,
+            start_plugin(&subsys, plugin, &config, &environment).await,
+        );
     }
 
-    #[cfg(feature = "pishock")]
-    {
-        let sender_tx = sender_tx.clone();
-        let receiver_rx = receiver_tx.subscribe();
-        subsys.start("PluginPiShock", |subsys| {
-            plugins::pishock::PiShock::new(sender_tx, receiver_rx, config, data_dir).run(subsys)
-        });
+    environment.state.plugins_subscribed.notify_one();
+
+    loop {
+        let control = select! {
+            _ = subsys.on_shutdown_requested() => return Ok(()),
+            Some(control) = control_rx.recv() => control,
+        };
+
+        match control {
+            PluginControl::Start(name, current) => {
+                config = current;
+                schedules = schedule::Schedules::new(&config.schedules);
+
+                let Some(plugin) = plugins::builtin(&config)
+                    .into_iter()
+                    .find(|plugin| plugin.name() == name)
+                else {
+                    warn!("Cannot start unknown plugin {}", name);
+                    continue;
+                };
+
+                // A plugin which failed or stopped on its own is still around until it is
+                // started again.
+                if let Some(plugin_subsys) = running.remove(plugin.name()) {
+                    subsys.perform_partial_shutdown(plugin_subsys).await?;
+                }
+
+                if !config.plugin_enabled(plugin.name()) {
+                    info!("Plugin {} is disabled", name);
+                    continue;
+                }
+
+                if !may_run(&config, &schedules, idle, &environment.state, plugin.name()) {
+                    continue;
+                }
+
+                info!("Starting plugin {}", name);
+                running.insert(
+                    plugin.name(),
+                    start_plugin(&subsys, plugin, &config, &environment).await,
+                );
+            }
+            PluginControl::Stop(name) => {
+                environment
+                    .state
+                    .held_plugins
+                    .lock()
+                    .unwrap()
+                    .retain(|held| *held != name);
+
+                if let Some(plugin_subsys) = running.remove(name.as_str()) {
+                    info!("Stopping plugin {}", name);
+                    subsys.perform_partial_shutdown(plugin_subsys).await?;
+                }
+            }
+        }
     }
+}
 
-    subsys.on_shutdown_requested().await;
+/// The plugin manager runs in its own process, as windowing libraries want to own the main thread
+/// and cannot recreate their event loop once the window was closed.
+#[cfg(feature = "gui")]
+fn open_plugin_manager() -> Result<()> {
+    tokio::process::Command::new(std::env::current_exe()?)
+        .arg("manage-plugins")
+        .spawn()?;
     Ok(())
 }
 
 struct Launcher {
     rx: mpsc::Receiver<bool>,
     config: Arc<Config>,
-    data_dir: PathBuf,
-    receiver_tx: broadcast::Sender<OscMessage>,
-    sender_tx: mpsc::Sender<OscMessage>,
+    environment: PluginEnvironment,
+    status_rx: mpsc::Receiver<StatusEvent>,
+    state: Arc<SharedState>,
+    tray_tx: mpsc::Sender<TrayMessage>,
+    tray_rx: mpsc::Receiver<TrayMessage>,
     dark_mode_icons: bool,
+    headless: bool,
+    idle: bool,
+    vrchat_running: bool,
+    session: Option<session::Session>,
+    /// Plugins whose device went away while they are running.
+    missing_devices: Vec<&'static str>,
+    /// Whether the plugins are forced to run or stay stopped, overriding the activity detector.
+    forced_plugins: Option<bool>,
+    /// Starts and stops single plugins while the plugins run.
+    plugin_control: Option<mpsc::Sender<PluginControl>>,
+    #[cfg(feature = "discovery")]
+    selection_tx: mpsc::Sender<String>,
 }
 
 impl Launcher {
+    #[allow(clippy::too_many_arguments)]
     fn new(
         rx: mpsc::Receiver<bool>,
         config: Arc<Config>,
-        data_dir: PathBuf,
-        receiver_tx: broadcast::Sender<OscMessage>,
-        sender_tx: mpsc::Sender<OscMessage>,
+        environment: PluginEnvironment,
+        status_rx: mpsc::Receiver<StatusEvent>,
+        state: Arc<SharedState>,
+        tray_tx: mpsc::Sender<TrayMessage>,
+        tray_rx: mpsc::Receiver<TrayMessage>,
         dark_mode_icons: bool,
+        headless: bool,
+        #[cfg(feature = "discovery")] selection_tx: mpsc::Sender<String>,
     ) -> Self {
+        *state.disabled_plugins.lock().unwrap() = config.disabled_plugins.clone();
+
         Self {
             rx,
             config,
-            data_dir,
-            receiver_tx,
-            sender_tx,
+            environment,
+            status_rx,
+            state,
+            tray_tx,
+            tray_rx,
             dark_mode_icons,
+            headless,
+            idle: false,
+            vrchat_running: false,
+            session: None,
+            missing_devices: vec![],
+            forced_plugins: None,
+            plugin_control: None,
+            #[cfg(feature = "discovery")]
+            selection_tx,
         }
     }
 
+    fn start_plugins(&mut self, subsys: &SubsystemHandle) -> NestedSubsystem {
+        self.state.plugins_running.store(true, Ordering::Relaxed);
+        self.state.failed_plugins.lock().unwrap().clear();
+        self.state.starting_plugins.lock().unwrap().clear();
+        self.state.held_plugins.lock().unwrap().clear();
+        let config = self.config.clone();
+        let environment = self.environment.clone();
+        let idle = self.idle;
+        // Room for a message to every plugin, so the launcher never waits on a partial shutdown.
+        let (control_tx, control_rx) = mpsc::channel(64);
+        self.plugin_control = Some(control_tx);
+
+        subsys.start("Plugins", move |subsys| {
+            run_plugins(subsys, config, environment, idle, control_rx)
+        })
+    }
+
+    fn set_config(&mut self, config: Config) {
+        *self.state.disabled_plugins.lock().unwrap() = config.disabled_plugins.clone();
+        self.config = Arc::new(config);
+    }
+
+    async fn restart_plugins(
+        &mut self,
+        subsys: &SubsystemHandle,
+        plugin_subsys: NestedSubsystem,
+        tray: &mut tray::Tray,
+    ) -> Result<NestedSubsystem> {
+        subsys.perform_partial_shutdown(plugin_subsys).await?;
+        tray.set_plugin_error(false)?;
+        self.clear_missing_devices(tray)?;
+        Ok(self.start_plugins(subsys))
+    }
+
+    fn clear_missing_devices(&mut self, tray: &mut tray::Tray) -> Result<()> {
+        self.missing_devices.clear();
+        tray.set_device_missing(false)
+    }
+
+    /// Names of the plugins whose schedule is currently active, as any change requires a restart.
+    fn scheduled_plugins(&self) -> Vec<String> {
+        let schedules = schedule::Schedules::new(&self.config.schedules);
+        let mut names: Vec<_> = self
+            .config
+            .plugin_schedules
+            .iter()
+            .filter(|(_, schedule)| schedules.is_active(schedule))
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Starts or stops the plugins, depending on whether VRChat is running unless overridden.
+    async fn update_plugins(
+        &mut self,
+        subsys: &SubsystemHandle,
+        maybe_plugin_subsys: Option<NestedSubsystem>,
+        tray: &mut tray::Tray,
+    ) -> Result<Option<NestedSubsystem>> {
+        let active = self.forced_plugins.unwrap_or(self.vrchat_running);
+
+        match maybe_plugin_subsys {
+            None if active => {
+                info!("Starting plugins");
+                tray.set_running(true)?;
+                self.state.resync.notify_one();
+                Ok(Some(self.start_plugins(subsys)))
+            }
+            Some(plugin_subsys) if !active => {
+                info!("Stopping plugins");
+                tray.set_running(false)?;
+
+                self.plugin_control = None;
+                subsys.perform_partial_shutdown(plugin_subsys).await?;
+                self.state.plugins_running.store(false, Ordering::Relaxed);
+                self.state.starting_plugins.lock().unwrap().clear();
+                self.state.held_plugins.lock().unwrap().clear();
+                tray.set_plugins_starting(false)?;
+                tray.set_plugin_error(false)?;
+                self.clear_missing_devices(tray)?;
+                self.send_resets().await;
+                Ok(None)
+            }
+            maybe_plugin_subsys => Ok(maybe_plugin_subsys),
+        }
+    }
+
+    /// Resets the parameters declared for no plugin in particular, once all plugins stopped.
+    async fn send_resets(&self) {
+        send_resets(
+            &self.environment.sender_tx,
+            "resets",
+            self.config.reset_messages(None),
+        )
+        .await;
+    }
+
+    async fn summarize(&self, session: session::Session) {
+        let config = &self.config.session;
+
+        if !config.summary && !config.notify {
+            return;
+        }
+
+        let summary = session.finish(&self.state);
+        info!("Session ended: {}", summary.brief());
+
+        if config.summary {
+            if let Err(error) = summary.append(&self.environment.data_dir).await {
+                error!("Failed to write session summary: {:#}", error);
+            }
+        }
+
+        if config.notify {
+            let _ = self
+                .environment
+                .notifier
+                .notify("Session summary", summary.brief())
+                .await;
+        }
+    }
+
+    /// Enables or disables a plugin until the config is reloaded. While the plugins run, just this
+    /// plugin is started or stopped, which also starts it again after it failed.
+    async fn set_plugin_enabled(
+        &mut self,
+        name: &str,
+        enabled: bool,
+        tray: &mut tray::Tray,
+    ) -> Result<()> {
+        info!(
+            "{} plugin {}",
+            if enabled { "Enabling" } else { "Disabling" },
+            name
+        );
+        let mut config = (*self.config).clone();
+        config.disabled_plugins.retain(|disabled| disabled != name);
+
+        if !enabled {
+            config.disabled_plugins.push(name.to_string());
+        }
+
+        self.set_config(config);
+
+        let Some(control_tx) = &self.plugin_control else {
+            return Ok(());
+        };
+
+        if enabled {
+            let mut failed_plugins = self.state.failed_plugins.lock().unwrap();
+            failed_plugins.retain(|failed| failed != name);
+            let any_failed = !failed_plugins.is_empty();
+            drop(failed_plugins);
+            tray.set_plugin_error(any_failed)?;
+        }
+
+        let control = if enabled {
+            PluginControl::Start(name.to_string(), self.config.clone())
+        } else {
+            PluginControl::Stop(name.to_string())
+        };
+        send_control(control_tx, control);
+        Ok(())
+    }
+
+    fn set_paused(&self, tray: &mut tray::Tray, paused: bool) -> Result<()> {
+        self.state.output_paused.store(paused, Ordering::Relaxed);
+        info!("Output {}", if paused { "paused" } else { "resumed" });
+        tray.set_paused(paused)
+    }
+
+    async fn set_armed(&self, armed: bool) {
+        self.state.arming.set_armed(armed);
+        info!("Shocks {}", if armed { "armed" } else { "disarmed" });
+
+        let (title, body) = if armed {
+            ("Shocks armed", "Shocks are sent until disarmed again")
+        } else {
+            ("Shocks disarmed", "Shocks are ignored until armed again")
+        };
+        let _ = self
+            .environment
+            .notifier
+            .notify_with_actions(title, body, vec![arming::action(!armed)])
+            .await;
+    }
+
     async fn wait(&mut self, subsys: &SubsystemHandle) -> Result<()> {
-        let (tray_tx, mut tray_rx) = mpsc::channel(4);
-        let mut tray = tray::Tray::new(tray_tx, self.dark_mode_icons)?;
+        let mut tray = tray::Tray::new(
+            self.tray_tx.clone(),
+            self.dark_mode_icons,
+            &self.config.tray.icon_theme,
+            self.config.tray.live_status,
+            self.headless,
+        )?;
+        if !self.config.osc.freeze.is_empty() {
+            tray.add_entry("Freeze/unfreeze parameters", TrayMessage::ToggleFreeze)?;
+        }
+
+        #[cfg(feature = "pishock")]
+        if self.config.plugin_enabled("pishock") {
+            tray.add_entry("Arm/disarm shocks", TrayMessage::ToggleArmed)?;
+        }
+
+        for bookmark in &self.config.tray.bookmarks {
+            tray.add_entry(
+                &bookmark.name,
+                TrayMessage::SendBookmark(bookmark.name.clone()),
+            )?;
+        }
+
+        if self.config.tray.plugin_toggles {
+            for plugin in plugins::builtin(&self.config) {
+                tray.add_plugin_entry(plugin.name(), self.state.plugin_state(plugin.name()))?;
+            }
+        }
+
+        #[cfg(feature = "scenes")]
+        if self.config.plugin_enabled("scenes") {
+            for name in plugins::scenes::scene_names(&self.config) {
+                tray.add_entry(
+                    &format!("Save scene {}", name),
+                    TrayMessage::SaveScene(name.clone()),
+                )?;
+                tray.add_entry(
+                    &format!("Recall scene {}", name),
+                    TrayMessage::RecallScene(name),
+                )?;
+            }
+        }
+
+        #[cfg(feature = "macros")]
+        if self.config.plugin_enabled("macros") {
+            for name in plugins::macros::recorded_names(&self.config) {
+                tray.add_entry(
+                    &format!("Record/stop macro {}", name),
+                    TrayMessage::RecordMacro(name.clone()),
+                )?;
+                tray.add_entry(
+                    &format!("Play macro {}", name),
+                    TrayMessage::PlayMacro(name),
+                )?;
+            }
+        }
+
+        #[cfg(feature = "discovery")]
+        if self.config.osc.discovery && self.config.cluster.role != config::ClusterRole::Secondary {
+            tray.add_send_targets()?;
+        }
+
+        tray.add_exit()?;
+
         let mut maybe_plugin_subsys: Option<NestedSubsystem> = None;
+        let mut traffic_interval = interval(Duration::from_millis(500));
+        let mut last_traffic = 0;
+        let mut schedule_interval = interval(Duration::from_secs(30));
+        let mut scheduled_plugins = self.scheduled_plugins();
 
         loop {
             select! {
-                Some(message) = tray_rx.recv() => {
+                Some(message) = self.tray_rx.recv() => {
                     match message {
                         TrayMessage::ReloadPlugins => {
                             info!("Reloading plugins");
-                            self.config = Arc::new(load_config().await?);
 
-                            if let Some(plugin_subsys) = maybe_plugin_subsys {
-                                subsys.perform_partial_shutdown(plugin_subsys).await?;
+                            let previous = self.config.clone();
 
-                                let config = self.config.clone();
-                                let receiver_tx = self.receiver_tx.clone();
-                                let sender_tx = self.sender_tx.clone();
-                                let data_dir = self.data_dir.clone();
+                            match load_config().await {
+                                Ok(config) => {
+                                    self.set_config(config);
+                                    tray.set_config_error(false)?;
+                                }
+                                Err(error) => {
+                                    error!("Failed to reload config, keeping the previous one: {:#}", error);
+                                    tray.set_config_error(true)?;
+                                    continue;
+                                }
+                            }
 
-                                maybe_plugin_subsys = Some(subsys.start("Plugins", move |subsys| {
-                                    run_plugins(subsys, config, data_dir, receiver_tx, sender_tx)
-                                }));
+                            // Restarting every plugin for a change within a single plugin section, e.g.
+                            // from the avatar menu, would needlessly reset the state of the others.
+                            match (&self.plugin_control, config::changed_plugins(&self.config, &previous)) {
+                                (Some(control_tx), Some(changed)) if maybe_plugin_subsys.is_some() => {
+                                    for name in changed {
+                                        let control = if self.config.plugin_enabled(name) {
+                                            PluginControl::Start(name.to_string(), self.config.clone())
+                                        } else {
+                                            PluginControl::Stop(name.to_string())
+                                        };
+                                        send_control(control_tx, control);
+                                    }
+                                }
+                                _ => {
+                                    if let Some(plugin_subsys) = maybe_plugin_subsys {
+                                        maybe_plugin_subsys = Some(self.restart_plugins(subsys, plugin_subsys, &mut tray).await?);
+                                    }
+                                }
                             }
                         }
+                        TrayMessage::RestartPlugin(name) => {
+                            if let Some(control_tx) = &self.plugin_control {
+                                info!("Restarting plugin {}", name);
+                                send_control(control_tx, PluginControl::Start(name.to_string(), self.config.clone()));
+                            }
+                        }
+                        #[cfg(any(
+                            feature = "control",
+                            feature = "dbus_service",
+                            feature = "ipc",
+                            feature = "tui"
+                        ))]
+                        TrayMessage::SetPluginEnabled(name, enabled) => {
+                            self.set_plugin_enabled(&name, enabled, &mut tray).await?;
+                        }
+                        TrayMessage::TogglePlugin(name) => {
+                            let enabled = matches!(
+                                self.state.plugin_state(name),
+                                PluginState::Failed | PluginState::Disabled
+                            );
+                            self.set_plugin_enabled(name, enabled, &mut tray).await?;
+                        }
+                        #[cfg(feature = "gui")]
+                        TrayMessage::ManagePlugins => {
+                            info!("Opening plugin manager");
+
+                            if let Err(error) = open_plugin_manager() {
+                                error!("Failed to open plugin manager: {:#}", error);
+                            }
+                        }
+                        TrayMessage::TogglePause => {
+                            let paused = !self.state.output_paused.load(Ordering::Relaxed);
+                            self.set_paused(&mut tray, paused)?;
+                        }
+                        TrayMessage::ToggleForcedPlugins => {
+                            // Forcing always goes against the detector, so that the click has an
+                            // effect, and a second click hands control back to it.
+                            self.forced_plugins = match self.forced_plugins {
+                                Some(_) => None,
+                                None => Some(!self.vrchat_running),
+                            };
+
+                            match self.forced_plugins {
+                                Some(true) => info!("Forcing plugins on"),
+                                Some(false) => info!("Forcing plugins off"),
+                                None => info!("Plugins follow whether VRChat is running again"),
+                            }
+
+                            maybe_plugin_subsys = self.update_plugins(subsys, maybe_plugin_subsys, &mut tray).await?;
+                        }
+                        #[cfg(any(feature = "control", feature = "dbus_service", feature = "ipc"))]
+                        TrayMessage::SetPaused(paused) => {
+                            self.set_paused(&mut tray, paused)?;
+                        }
+                        #[cfg(any(feature = "control", feature = "dbus_service", feature = "ipc"))]
+                        TrayMessage::Send(message) => {
+                            let _ = self.environment.sender_tx.send(OutgoingMessage::new("control", message)).await;
+                        }
+                        TrayMessage::SetArmed(armed) => self.set_armed(armed).await,
+                        TrayMessage::ToggleArmed => {
+                            let armed = !self.state.arming.is_armed();
+                            self.set_armed(armed).await;
+                        }
+                        #[cfg(feature = "clipboard")]
+                        TrayMessage::CopyPluginLogs => {
+                            let text = self.state.plugin_logs.text();
+
+                            if text.is_empty() {
+                                info!("No plugin logged anything yet");
+                            } else {
+                                clipboard::copy(text);
+                            }
+                        }
+                        #[cfg(feature = "clipboard")]
+                        TrayMessage::SendClipboard => match clipboard::paste().await {
+                            Ok(text) if !text.trim().is_empty() => {
+                                use vrc_osc_manager_core::chatbox::{Chatbox, MAX_LENGTH};
+                                let text = text.trim();
+
+                                if text.chars().count() > MAX_LENGTH {
+                                    warn!("Clipboard holds more than {} characters, sending only the start", MAX_LENGTH);
+                                }
+
+                                // Blocked words are masked by the chatbox scheduler, as for every other message.
+                                Chatbox::new(
+                                    OscSender::new(self.environment.sender_tx.clone(), "clipboard"),
+                                    self.environment.chatbox_queue.clone(),
+                                )
+                                .send(text, false);
+                            }
+                            Ok(_) => info!("Clipboard holds no text to send"),
+                            Err(error) => warn!("Failed to read the clipboard: {:#}", error),
+                        },
+                        TrayMessage::ToggleFreeze => {
+                            let frozen = !self.config.osc.freeze.iter().any(|pattern| {
+                                self.state.frozen.lock().unwrap().contains(pattern)
+                            });
+                            info!("{} parameters", if frozen { "Freezing" } else { "Unfreezing" });
+
+                            for pattern in &self.config.osc.freeze {
+                                self.state.set_frozen(pattern, frozen);
+                            }
+                        }
+                        TrayMessage::SendBookmark(name) => {
+                            // Entries stay in the menu after reloading, even if their bookmark
+                            // was removed from the config since.
+                            let Some(bookmark) = self.config.tray.bookmarks.iter().find(|bookmark| bookmark.name == name) else {
+                                warn!("Bookmark {} no longer exists, restart to update the tray menu", name);
+                                continue;
+                            };
+
+                            info!("Sending bookmark {}", name);
+                            let message = OscMessage {
+                                addr: bookmark.address.clone(),
+                                args: vec![bookmark.value.to_osc()],
+                            };
+                            let _ = self.environment.sender_tx.send(OutgoingMessage::new("bookmarks", message)).await;
+                        }
+                        #[cfg(feature = "discovery")]
+                        TrayMessage::SelectTarget(name) => {
+                            let _ = self.selection_tx.send(name).await;
+                        }
+                        #[cfg(feature = "scenes")]
+                        TrayMessage::SaveScene(name) => {
+                            self.state.commands.send(vrc_osc_manager_core::Command::new("scenes", plugins::scenes::SAVE, name));
+                        }
+                        #[cfg(feature = "scenes")]
+                        TrayMessage::RecallScene(name) => {
+                            self.state.commands.send(vrc_osc_manager_core::Command::new("scenes", plugins::scenes::RECALL, name));
+                        }
+                        #[cfg(feature = "macros")]
+                        TrayMessage::RecordMacro(name) => {
+                            self.state.commands.send(vrc_osc_manager_core::Command::new("macros", plugins::macros::RECORD, name));
+                        }
+                        #[cfg(feature = "macros")]
+                        TrayMessage::PlayMacro(name) => {
+                            self.state.commands.send(vrc_osc_manager_core::Command::new("macros", plugins::macros::PLAY, name));
+                        }
                         TrayMessage::Exit => {
                             subsys.request_shutdown();
                         }
                     }
                 }
                 Some(vrchat_running) = self.rx.recv() => {
-                    if vrchat_running {
-                        if maybe_plugin_subsys.is_none() {
-                            info!("Starting plugins");
-                            tray.set_running(true)?;
-
-                            let config = self.config.clone();
-                            let receiver_tx = self.receiver_tx.clone();
-                            let sender_tx = self.sender_tx.clone();
-                            let data_dir = self.data_dir.clone();
-
-                            maybe_plugin_subsys = Some(subsys.start("Plugins", move |subsys| {
-                                run_plugins(subsys, config, data_dir, receiver_tx, sender_tx)
-                            }));
-                        }
+                    if vrchat_running && !self.vrchat_running {
+                        self.session = Some(session::Session::start(&self.state));
                     } else if !vrchat_running {
-                        if let Some(plugin_subsys) = maybe_plugin_subsys {
-                            info!("Stopping plugins");
-                            tray.set_running(false)?;
+                        *self.state.world.lock().unwrap() = None;
 
-                            subsys.perform_partial_shutdown(plugin_subsys).await?;
-                            maybe_plugin_subsys = None;
+                        if let Some(session) = self.session.take() {
+                            self.summarize(session).await;
+                        }
+                    }
+
+                    self.vrchat_running = vrchat_running;
+                    maybe_plugin_subsys = self.update_plugins(subsys, maybe_plugin_subsys, &mut tray).await?;
+                }
+                Some(event) = self.status_rx.recv() => {
+                    match event {
+                        StatusEvent::PluginFailed(name) => {
+                            self.state.failed_plugins.lock().unwrap().push(name.to_string());
+
+                            if let Some(session) = &mut self.session {
+                                session.record_failure(name);
+                            }
+                            tray.set_plugin_error(true)?;
+
+                            let reload = NotificationAction::new("Reload", vrc_osc_manager_core::Command::new(MANAGER, RELOAD, ""));
+                            let _ = self.environment.notifier.notify_with_actions(
+                                "Plugin failed",
+                                format!("The {} plugin stopped, see the log for details", name),
+                                vec![reload],
+                            ).await;
+                        }
+                        StatusEvent::PluginStarting(name, starting) => {
+                            let mut starting_plugins = self.state.starting_plugins.lock().unwrap();
+                            let was_starting = starting_plugins.iter().any(|other| other == name);
+                            starting_plugins.retain(|other| other != name);
+
+                            if starting {
+                                starting_plugins.push(name.to_string());
+                            } else if was_starting {
+                                info!("Plugin {} is ready", name);
+                            }
+
+                            let any_starting = !starting_plugins.is_empty();
+                            drop(starting_plugins);
+                            tray.set_plugins_starting(any_starting)?;
+                        }
+                        StatusEvent::TargetReachable(reachable) => tray.set_target_unreachable(!reachable)?,
+                        StatusEvent::Flooded(flooded) => tray.set_flooded(flooded)?,
+                        StatusEvent::Congested(congested) => tray.set_congested(congested)?,
+                        StatusEvent::LinkVerified(verified) => tray.set_link_failed(!verified)?,
+                        StatusEvent::DeviceConnected(name, connected) => {
+                            self.missing_devices.retain(|missing| *missing != name);
+
+                            if !connected {
+                                self.missing_devices.push(name);
+                                let _ = self.environment.notifier.notify(
+                                    "Device disconnected",
+                                    format!("The device of the {} plugin is gone, it will be picked up again once it returns", name),
+                                ).await;
+                            }
+
+                            tray.set_device_missing(!self.missing_devices.is_empty())?;
+                        }
+                        StatusEvent::Idle(idle) => {
+                            self.idle = idle;
+
+                            if !self.config.idle.pause_plugins.is_empty() {
+                                if let Some(plugin_subsys) = maybe_plugin_subsys {
+                                    maybe_plugin_subsys = Some(self.restart_plugins(subsys, plugin_subsys, &mut tray).await?);
+                                }
+                            }
+                        }
+                        StatusEvent::WorldChanged(name) => {
+                            info!("Entered world {}", name);
+
+                            if let Some(session) = &mut self.session {
+                                session.record_world(&name);
+                            }
+
+                            *self.state.world.lock().unwrap() = Some(name);
+                        }
+                        StatusEvent::PlayerJoined(name) => {
+                            if let Some(session) = &mut self.session {
+                                session.record_player(&name);
+                            }
+                        }
+                        #[cfg(feature = "discovery")]
+                        StatusEvent::TargetDiscovered(name) => tray.add_send_target(&name)?,
+                    }
+                }
+                _ = traffic_interval.tick() => {
+                    let traffic = self.state.stats.sent() + self.state.stats.received();
+                    tray.tick_traffic(traffic != last_traffic)?;
+                    last_traffic = traffic;
+                }
+                _ = schedule_interval.tick() => {
+                    let active = self.scheduled_plugins();
+
+                    if active != scheduled_plugins {
+                        scheduled_plugins = active;
+
+                        if let Some(plugin_subsys) = maybe_plugin_subsys {
+                            info!("Schedules changed, restarting plugins");
+                            maybe_plugin_subsys = Some(self.restart_plugins(subsys, plugin_subsys, &mut tray).await?);
                         }
                     }
                 }
@@ -197,6 +1186,8 @@ impl Launcher {
                     bail!("Select yielded an unexpected result while waiting for activity message")
                 }
             }
+
+            tray.update_plugin_entries(|name| self.state.plugin_state(name))?;
         }
     }
 
@@ -204,15 +1195,170 @@ impl Launcher {
         match (self.wait(&subsys).cancel_on_shutdown(&subsys)).await {
             Ok(Ok(())) => subsys.request_shutdown(),
             Ok(Err(error)) => return Err(error),
-            Err(CancelledByShutdown) => {}
+            Err(CancelledByShutdown) => {
+                // The sender keeps going for a moment after shutting down, to send these.
+                if self.state.plugins_running.load(Ordering::Relaxed) {
+                    self.send_resets().await;
+                }
+            }
         }
 
         Ok(())
     }
 }
 
+#[derive(Subcommand)]
+enum Command {
+    /// Compare two recorded OSC sessions
+    Diff {
+        /// Path to the first recording
+        first: PathBuf,
+
+        /// Path to the second recording
+        second: PathBuf,
+    },
+
+    /// Open an interactive prompt for sending messages, watching parameters and toggling plugins
+    #[cfg(feature = "control")]
+    Console {
+        /// Base URL of the control API
+        #[arg(default_value = "http://127.0.0.1:9002")]
+        url: String,
+
+        /// Token of the control API, read from the data directory when omitted
+        #[arg(long, env = "VRC_OSC_MANAGER_TOKEN")]
+        token: Option<String>,
+
+        /// Certificate to trust, usually the control-cert.pem of the instance
+        #[arg(long)]
+        cert: Option<PathBuf>,
+    },
+
+    /// Print the JSON Schema of the config file
+    ConfigSchema,
+
+    /// Check the environment for common problems and print a report for bug reports
+    Doctor,
+
+    /// Turn on OSC in the settings of VRChat, which has to be closed meanwhile
+    EnableOsc,
+
+    /// Send values to every input parameter of an avatar, to test its animator
+    FuzzParams {
+        /// ID of the avatar or path to its OSC config, defaulting to the avatar used last
+        avatar: Option<String>,
+
+        /// Whether to sweep through the parameters or pick values at random
+        #[arg(long, value_enum, default_value_t = fuzz::FuzzMode::Random)]
+        mode: fuzz::FuzzMode,
+
+        /// Messages sent per second
+        #[arg(long, default_value_t = 10.)]
+        rate: f64,
+
+        /// Values each parameter is stepped through when sweeping
+        #[arg(long, default_value_t = 8)]
+        steps: u32,
+
+        /// Seconds after which to stop, running until interrupted otherwise
+        #[arg(long)]
+        duration: Option<u64>,
+
+        /// Pattern the parameter addresses have to match, e.g. /avatar/parameters/Hat*
+        #[arg(long)]
+        filter: Option<String>,
+    },
+
+    /// Open the plugin manager window
+    #[cfg(feature = "gui")]
+    ManagePlugins,
+
+    /// Print a report of the parameters of an avatar and which plugins read or write them
+    ParamDocs {
+        /// ID of the avatar or path to its OSC config, defaulting to the avatar used last
+        avatar: Option<String>,
+
+        /// Format of the report
+        #[arg(long, value_enum, default_value_t = param_docs::DocsFormat::Markdown)]
+        format: param_docs::DocsFormat,
+    },
+
+    /// Control another instance through its control API
+    #[cfg(feature = "control")]
+    Remote {
+        /// Base URL of the control API, e.g. https://192.168.1.10:9002
+        url: String,
+
+        /// Token of the control API
+        #[arg(long, env = "VRC_OSC_MANAGER_TOKEN")]
+        token: String,
+
+        /// Certificate to trust, usually the control-cert.pem of the remote instance
+        #[arg(long)]
+        cert: Option<PathBuf>,
+
+        #[command(subcommand)]
+        command: RemoteCommand,
+    },
+
+    /// Manage plugins from the plugin index
+    #[cfg(feature = "installer")]
+    Plugin {
+        #[command(subcommand)]
+        command: PluginCommand,
+    },
+
+    /// Log in to the VRChat API and store the session in the keyring
+    #[cfg(feature = "vrchat_api")]
+    VrchatLogin,
+}
+
+#[cfg(feature = "control")]
+#[derive(Subcommand)]
+enum RemoteCommand {
+    /// Show the status of the remote instance
+    Status,
+
+    /// Reload the config and restart the plugins
+    Reload,
+
+    /// Pause the output
+    Pause,
+
+    /// Resume the output
+    Resume,
+
+    /// Enable a plugin until the config is reloaded
+    Enable {
+        /// Name of the plugin
+        plugin: String,
+    },
+
+    /// Disable a plugin until the config is reloaded
+    Disable {
+        /// Name of the plugin
+        plugin: String,
+    },
+}
+
+#[cfg(feature = "installer")]
+#[derive(Subcommand)]
+enum PluginCommand {
+    /// List all plugins available in the plugin index
+    List,
+
+    /// Download a plugin from the plugin index into the plugins directory
+    Install {
+        /// Name of the plugin in the plugin index
+        name: String,
+    },
+}
+
 #[derive(Parser)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Use icons optimized for dark mode
     #[arg(long, default_value_t = false)]
     dark_mode_icons: bool,
@@ -224,6 +1370,15 @@ struct Args {
     /// Enable debug logging
     #[arg(long, default_value_t = false)]
     debug: bool,
+
+    /// Feed the incoming messages of a recording to the plugins instead of waiting for VRChat
+    #[arg(long)]
+    replay: Option<PathBuf>,
+
+    /// Show a dashboard in the terminal instead of the tray icon
+    #[cfg(feature = "tui")]
+    #[arg(long, default_value_t = false)]
+    tui: bool,
 }
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -234,6 +1389,85 @@ async fn main() -> Result<()> {
 
     let base_dirs = BaseDirs::new().context("Base directories not available")?;
     let data_dir = base_dirs.data_dir().join("vrc-osc-manager");
+
+    if let Some(command) = &args.command {
+        return match command {
+            Command::Diff { first, second } => diff::run(first, second).await,
+            #[cfg(feature = "control")]
+            Command::Console { url, token, cert } => {
+                console::run(url, token.as_deref(), cert.as_deref(), &data_dir).await
+            }
+            Command::ConfigSchema => {
+                println!("{}", serde_json::to_string_pretty(&config::schema())?);
+                Ok(())
+            }
+            Command::Doctor => doctor::run(&data_dir).await,
+            Command::EnableOsc => {
+                if doctor::vrchat_running() {
+                    bail!("Close VRChat first, it overwrites its settings when closing");
+                }
+
+                vrchat_settings::enable_osc().await?;
+                println!("OSC is enabled in VRChat");
+                Ok(())
+            }
+            Command::FuzzParams {
+                avatar,
+                mode,
+                rate,
+                steps,
+                duration,
+                filter,
+            } => {
+                fuzz::run(fuzz::FuzzOptions {
+                    avatar: avatar.as_deref(),
+                    mode: *mode,
+                    rate: *rate,
+                    steps: *steps,
+                    duration: *duration,
+                    filter: filter.as_deref(),
+                })
+                .await
+            }
+            #[cfg(feature = "control")]
+            Command::Remote {
+                url,
+                token,
+                cert,
+                command,
+            } => {
+                let action = match command {
+                    RemoteCommand::Status => remote::RemoteAction::Status,
+                    RemoteCommand::Reload => remote::RemoteAction::Reload,
+                    RemoteCommand::Pause => remote::RemoteAction::Pause,
+                    RemoteCommand::Resume => remote::RemoteAction::Resume,
+                    RemoteCommand::Enable { plugin } => remote::RemoteAction::Enable(plugin),
+                    RemoteCommand::Disable { plugin } => remote::RemoteAction::Disable(plugin),
+                };
+
+                remote::run(url, token, cert.as_deref(), action).await
+            }
+            #[cfg(feature = "gui")]
+            Command::ManagePlugins => gui::run(&data_dir),
+            Command::ParamDocs { avatar, format } => {
+                param_docs::run(avatar.as_deref(), *format).await
+            }
+            #[cfg(feature = "installer")]
+            Command::Plugin { command } => {
+                let config = load_config().await?;
+
+                match command {
+                    PluginCommand::List => installer::list(&config).await,
+                    PluginCommand::Install { name } => {
+                        installer::install(&config, &data_dir, name).await
+                    }
+                }
+            }
+            #[cfg(feature = "vrchat_api")]
+            Command::VrchatLogin => vrchat_api::login().await,
+        };
+    }
+
     let log_dir = data_dir.join("logs/log");
 
     let log_file = FileRotate::new(
@@ -251,15 +1485,35 @@ async fn main() -> Result<()> {
         LevelFilter::Info
     };
 
-    CombinedLogger::init(vec![
-        TermLogger::new(
+    #[cfg(feature = "tui")]
+    let headless = args.tui;
+    #[cfg(not(feature = "tui"))]
+    let headless = false;
+
+    let plugin_logs = plugin_logs::PluginLogs::new(log_filter);
+    let mut loggers: Vec<Box<dyn SharedLogger>> = vec![
+        WriteLogger::new(log_filter, simplelog::Config::default(), log_file),
+        Box::new(plugin_logs.clone()),
+    ];
+
+    // While the TUI owns the terminal, log output would garble it, so it only collects errors.
+    #[cfg(feature = "tui")]
+    let log_buffer = tui::LogBuffer::new();
+    #[cfg(feature = "tui")]
+    if args.tui {
+        loggers.push(Box::new(log_buffer.clone()));
+    }
+
+    if !headless {
+        loggers.push(TermLogger::new(
             log_filter,
             simplelog::Config::default(),
             TerminalMode::Mixed,
             ColorChoice::Auto,
-        ),
-        WriteLogger::new(log_filter, simplelog::Config::default(), log_file),
-    ])?;
+        ));
+    }
+
+    CombinedLogger::init(loggers)?;
 
     info!("Starting VRC OSC Manager v{}", VERSION);
 
@@ -268,32 +1522,362 @@ async fn main() -> Result<()> {
 
     let (sender_tx, sender_rx) = mpsc::channel(128);
     let (receiver_tx, _) = broadcast::channel(128);
-    let launcher_receiver_tx = receiver_tx.clone();
+    let (notification_tx, notification_rx) = mpsc::channel(16);
+    let (status_tx, status_rx) = mpsc::channel(8);
+    let (tray_tx, tray_rx) = mpsc::channel(4);
+    let state = Arc::new(SharedState {
+        plugin_logs: plugin_logs.clone(),
+        ..SharedState::default()
+    });
+    let plugin_log_writer = plugin_logs::PluginLogWriter::new(
+        plugin_logs,
+        data_dir.join("logs/plugins"),
+        state.clone(),
+        plugins::builtin(&config)
+            .iter()
+            .map(|plugin| plugin.name())
+            .collect(),
+    );
+    state.channels.watch("Outgoing queue", &sender_tx);
+    state
+        .channels
+        .watch_broadcast("Received messages", &receiver_tx);
+    state.channels.watch("Notification queue", &notification_tx);
+    state.channels.watch("Status events", &status_tx);
+    state.channels.watch("Tray commands", &tray_tx);
+    let send_config = config.send_config();
+    let (target_tx, target_rx) = watch::channel(send_config.send_host.clone());
+
+    #[cfg(feature = "discovery")]
+    let select_targets =
+        config.osc.discovery && config.cluster.role != config::ClusterRole::Secondary;
+    #[cfg(feature = "oscquery")]
+    let advertise = config.osc.oscquery && config.cluster.role != config::ClusterRole::Secondary;
+
+    // Discovery and OSCQuery share a single mDNS daemon, where discovery browses for the VRChat
+    // clients OSCQuery asks for their ports.
+    #[cfg(feature = "discovery")]
+    let mdns_daemon = {
+        #[allow(unused_mut)]
+        let mut enabled = select_targets;
+
+        #[cfg(feature = "oscquery")]
+        {
+            enabled |= advertise;
+        }
+
+        if enabled {
+            Some(mdns_sd::ServiceDaemon::new()?)
+        } else {
+            None
+        }
+    };
+    #[cfg(feature = "discovery")]
+    let (clients_tx, clients_rx) = watch::channel(discovery::Clients::new());
+    #[cfg(feature = "discovery")]
+    let (selection_tx, selection_rx) = mpsc::channel(4);
+    #[cfg(feature = "discovery")]
+    let discovery = discovery::Discovery::new(
+        mdns_daemon.clone(),
+        config.osc.send_host.clone(),
+        data_dir.clone(),
+        selection_rx,
+        status_tx.clone(),
+        select_targets.then_some(target_tx),
+        clients_tx,
+    );
+    #[cfg(not(feature = "discovery"))]
+    drop(target_tx);
+    #[cfg(all(feature = "discovery", not(feature = "oscquery")))]
+    drop(clients_rx);
+
+    let (port_tx, port_rx) = watch::channel(send_config.send_port);
+
+    #[cfg(feature = "oscquery")]
+    let (receive_port_tx, receive_port_rx) = watch::channel(config.osc.receive_port);
+    #[cfg(feature = "oscquery")]
+    let oscquery = oscquery::OscQuery::new(
+        mdns_daemon.filter(|_| advertise),
+        &config.osc,
+        receive_port_rx,
+        target_rx.clone(),
+        port_tx,
+        clients_rx,
+    );
+    #[cfg(not(feature = "oscquery"))]
+    drop(port_tx);
+
+    #[cfg(feature = "control")]
+    let control =
+        control::ControlServer::new(&config.control, &data_dir, state.clone(), tray_tx.clone());
+
+    #[cfg(all(target_os = "linux", feature = "dbus_service"))]
+    let dbus_service = dbus_service::DbusService::new(
+        config.dbus.enabled,
+        state
+            .channels
+            .relay("D-Bus service", receiver_tx.subscribe(), None, false),
+        state.clone(),
+        tray_tx.clone(),
+    );
 
-    let send_port = config.osc.send_port;
-    let receive_port = config.osc.receive_port;
+    #[cfg(feature = "ipc")]
+    let ipc = ipc::IpcServer::new(&config.ipc, &data_dir, state.clone(), tray_tx.clone());
 
-    let result = Toplevel::new()
+    #[cfg(feature = "hotkey")]
+    let hotkeys = hotkey::Hotkeys::new(&config.hotkey, tray_tx.clone());
+
+    let cluster = cluster::ClusterListener::new(&config.cluster, sender_tx.clone());
+    let proxy = proxy::Proxy::new(
+        &config.proxy,
+        state
+            .channels
+            .relay("Proxy", receiver_tx.subscribe(), None, false),
+        sender_tx.clone(),
+    );
+    let config_watcher =
+        config_watcher::ConfigWatcher::new(&config.config_watcher, tray_tx.clone());
+    #[cfg(feature = "menu_settings")]
+    let menu_settings = menu_settings::MenuSettings::new(
+        &config,
+        state
+            .channels
+            .relay("Menu settings", receiver_tx.subscribe(), None, false),
+        sender_tx.clone(),
+        tray_tx.clone(),
+    );
+    let face_tracking_input =
+        face_tracking::FaceTrackingInput::new(&config.face_tracking, sender_tx.clone());
+    let chatbox_queue = ChatboxQueue::new();
+    let chatbox_scheduler =
+        chatbox::ChatboxScheduler::new(&config.chatbox, chatbox_queue.clone(), sender_tx.clone());
+    let (throttled_tx, throttled_rx) = watch::channel(false);
+    let governor = governor::Governor::new(&config.governor, sender_tx.clone(), throttled_tx);
+    let (idle_tx, idle_rx) = watch::channel(false);
+    let idle_detector = idle::IdleDetector::new(
+        &config.idle,
+        state
+            .channels
+            .relay("Idle detector", receiver_tx.subscribe(), None, false),
+        idle_tx,
+        status_tx.clone(),
+    );
+
+    // Joining a full instance logs a line for every player and their avatar at once.
+    let (instance_tx, instance_rx) = watch::channel(None);
+    let (vrchat_log_tx, _) = broadcast::channel(1024);
+    let (vrchat_running_tx, vrchat_running_rx) = watch::channel(None);
+    let vrchat_log = vrchat_log::VrchatLog::new(
+        instance_tx,
+        vrchat_running_rx,
+        vrchat_log_tx.clone(),
+        status_tx.clone(),
+        state.clone(),
+    );
+
+    // The log and the poller share the client, so they share its cache of names as well.
+    #[cfg(feature = "vrchat_api")]
+    let vrchat_api_client = if config.vrchat_api.enabled {
+        vrchat_api::VrchatApi::from_keyring(&data_dir)
+            .map_err(|error| warn!("Not using the VRChat API: {:#}", error))
+            .ok()
+    } else {
+        None
+    };
+
+    #[cfg(feature = "vrchat_api")]
+    let vrchat_log = match vrchat_api_client.clone() {
+        Some(api) => vrchat_log.resolve_names(api),
+        None => vrchat_log,
+    };
+
+    let event_log = event_log::EventLog::new(&config.event_log, &data_dir, state.clone());
+    let monitor = monitor::Monitor::new(&config.monitor, &data_dir);
+    // A replay stands in for VRChat, and a secondary never sees VRChat as it runs on the machine of
+    // the primary, so the plugins have to run without it.
+    let disable_activity_check = args.disable_activity_check
+        || args.replay.is_some()
+        || config.cluster.role == config::ClusterRole::Secondary;
+    let replay = monitor::Replay::new(args.replay.clone(), receiver_tx.clone(), state.clone());
+
+    let watchdog = watchdog::Watchdog::new(
+        &config.watchdog,
+        state.clone(),
+        tray_tx.clone(),
+        Notifier::new(notification_tx.clone()),
+    );
+    let self_test = self_test::SelfTest::new(
+        &config.self_test,
+        state
+            .channels
+            .relay("Self-test", receiver_tx.subscribe(), None, false),
+        sender_tx.clone(),
+        Notifier::new(notification_tx.clone()),
+        status_tx.clone(),
+    );
+
+    #[cfg(feature = "steam_presence")]
+    let steam_presence = steam_presence::SteamPresence::new(
+        &config.steam_presence,
+        state.clone(),
+        instance_rx.clone(),
+        status_tx.clone(),
+    );
+
+    #[cfg(feature = "vrchat_api")]
+    let vrchat_api = vrchat_api::VrchatApiPoller::new(
+        &config.vrchat_api,
+        vrchat_api_client,
+        sender_tx.clone(),
+        status_tx.clone(),
+        state.clone(),
+        instance_rx.clone(),
+    );
+
+    let ping = ping::Ping::new(
+        &config.ping,
+        state
+            .channels
+            .relay("Ping", receiver_tx.subscribe(), None, false),
+        sender_tx.clone(),
+        state.clone(),
+    );
+
+    // The most common reason for nothing happening at all is OSC being turned off in VRChat.
+    if let Ok(Some(false)) = vrchat_settings::osc_enabled().await {
+        warn!("OSC is disabled in VRChat, enable it in the action menu under Options > OSC");
+        let _ = Notifier::new(notification_tx.clone())
+            .notify(
+                "OSC is disabled in VRChat",
+                "Enable it in the action menu under Options > OSC, or run vrc-osc-manager enable-osc while VRChat is closed",
+            )
+            .await;
+    }
+
+    let environment = PluginEnvironment {
+        data_dir,
+        receiver_tx: receiver_tx.clone(),
+        sender_tx,
+        notifier: Notifier::new(notification_tx),
+        activity: Activity::new(idle_rx, config.idle.slowdown),
+        throttled: throttled_rx,
+        chatbox_queue,
+        status_tx: status_tx.clone(),
+        state: state.clone(),
+        instance: instance_rx,
+        vrchat_log_tx,
+        events: event_log.events(),
+    };
+    let notifications = Notifications::new(notification_rx, state.clone(), tray_tx.clone());
+
+    let schedules = schedule::Schedules::new(&config.schedules);
+    let sender = osc::Sender::new(
+        sender_rx,
+        target_rx.clone(),
+        port_rx.clone(),
+        &send_config,
+        &config.face_tracking,
+        schedules.clone(),
+        monitor.tap(),
+        state.clone(),
+    );
+    let receiver = osc::Receiver::new(
+        receiver_tx,
+        &config.receive_config(),
+        schedules,
+        monitor.tap(),
+        state.clone(),
+        status_tx.clone(),
+    );
+    #[cfg(feature = "oscquery")]
+    let receiver = if advertise {
+        receiver.any_port(receive_port_tx)
+    } else {
+        receiver
+    };
+    let backpressure = backpressure::BackpressureMonitor::new(state.clone(), status_tx.clone());
+    let reachability = reachability::ReachabilityCheck::new(target_rx, port_rx, status_tx);
+    #[cfg(feature = "tui")]
+    let tui = tui::Tui::new(&config, state.clone(), log_buffer, tray_tx.clone());
+
+    let toplevel = Toplevel::new()
         .start("VrChatActivity", move |subsys| {
-            VrChatActivity::new(tx, args.disable_activity_check).run(subsys)
+            VrChatActivity::new(tx, vrchat_running_tx, disable_activity_check).run(subsys)
         })
         .start("Launcher", move |subsys| {
             Launcher::new(
                 rx,
                 config,
-                data_dir,
-                launcher_receiver_tx,
-                sender_tx,
+                environment,
+                status_rx,
+                state,
+                tray_tx,
+                tray_rx,
                 args.dark_mode_icons,
+                headless,
+                #[cfg(feature = "discovery")]
+                selection_tx,
             )
             .run(subsys)
         })
-        .start("OscSender", move |subsys| {
-            osc::Sender::new(sender_rx, send_port).run(subsys)
-        })
-        .start("OscReceiver", move |subsys| {
-            osc::Receiver::new(receiver_tx, receive_port).run(subsys)
+        .start("Notifications", move |subsys| notifications.run(subsys))
+        .start("OscSender", move |subsys| sender.run(subsys))
+        .start("OscReceiver", move |subsys| receiver.run(subsys))
+        .start("Reachability", move |subsys| reachability.run(subsys))
+        .start("Backpressure", move |subsys| backpressure.run(subsys))
+        .start("SelfTest", move |subsys| self_test.run(subsys))
+        .start("Ping", move |subsys| ping.run(subsys))
+        .start("PluginLogs", move |subsys| plugin_log_writer.run(subsys))
+        .start("EventLog", move |subsys| event_log.run(subsys))
+        .start("Monitor", move |subsys| monitor.run(subsys))
+        .start("Replay", move |subsys| replay.run(subsys))
+        .start("Cluster", move |subsys| cluster.run(subsys))
+        .start("Proxy", move |subsys| proxy.run(subsys))
+        .start("FaceTracking", move |subsys| {
+            face_tracking_input.run(subsys)
         })
+        .start("IdleDetector", move |subsys| idle_detector.run(subsys))
+        .start("VrchatLog", move |subsys| vrchat_log.run(subsys))
+        .start("Governor", move |subsys| governor.run(subsys))
+        .start("Chatbox", move |subsys| chatbox_scheduler.run(subsys))
+        .start("ConfigWatcher", move |subsys| config_watcher.run(subsys))
+        .start("Watchdog", move |subsys| watchdog.run(subsys));
+
+    #[cfg(feature = "discovery")]
+    let toplevel = toplevel.start("Discovery", move |subsys| discovery.run(subsys));
+
+    #[cfg(feature = "oscquery")]
+    let toplevel = toplevel.start("OscQuery", move |subsys| oscquery.run(subsys));
+
+    #[cfg(feature = "control")]
+    let toplevel = toplevel.start("Control", move |subsys| control.run(subsys));
+
+    #[cfg(feature = "ipc")]
+    let toplevel = toplevel.start("Ipc", move |subsys| ipc.run(subsys));
+
+    #[cfg(feature = "hotkey")]
+    let toplevel = toplevel.start("Hotkeys", move |subsys| hotkeys.run(subsys));
+
+    #[cfg(feature = "steam_presence")]
+    let toplevel = toplevel.start("SteamPresence", move |subsys| steam_presence.run(subsys));
+
+    #[cfg(feature = "vrchat_api")]
+    let toplevel = toplevel.start("VrchatApi", move |subsys| vrchat_api.run(subsys));
+
+    #[cfg(feature = "menu_settings")]
+    let toplevel = toplevel.start("MenuSettings", move |subsys| menu_settings.run(subsys));
+
+    #[cfg(all(target_os = "linux", feature = "dbus_service"))]
+    let toplevel = toplevel.start("DbusService", move |subsys| dbus_service.run(subsys));
+
+    #[cfg(feature = "tui")]
+    let toplevel = if args.tui {
+        toplevel.start("Tui", move |subsys| tui.run(subsys))
+    } else {
+        toplevel
+    };
+
+    let result = toplevel
         .catch_signals()
         .handle_shutdown_requests(Duration::from_millis(1000))
         .await;
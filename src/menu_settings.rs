@@ -0,0 +1,265 @@
+//! Binds plugin options to avatar parameters, so they can be changed from the radial menu in game
+//! and are written back to the config file.
+
+use crate::config::{config_path, lookup, section_mut, Config, MenuSettingsConfig};
+use crate::plugins;
+use crate::rules;
+use crate::tray::TrayMessage;
+use anyhow::{bail, Context, Result};
+use async_osc::{OscMessage, OscType};
+use log::{info, warn};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::fs::{read_to_string, write};
+use tokio::select;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, mpsc};
+use tokio::time::{sleep_until, Instant};
+use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
+use toml_edit::{value, Document};
+use vrc_osc_manager_core::addresses::AVATAR_CHANGE;
+use vrc_osc_manager_core::{ConfigFieldKind, OutgoingMessage};
+
+/// Time without changes before they are written, as radial puppets send values while dragging.
+const SETTLE: Duration = Duration::from_secs(1);
+
+struct Binding {
+    address: String,
+    section: String,
+    key: &'static str,
+    kind: ConfigFieldKind,
+    range: Option<(f64, f64)>,
+}
+
+impl Binding {
+    /// Config value for the value of a received message.
+    fn config_value(&self, message: &OscMessage) -> Option<toml::Value> {
+        let value = match self.range {
+            Some((min, max)) => rules::value(message)?.clamp(min, max),
+            None => rules::value(message)?,
+        };
+
+        match self.kind {
+            ConfigFieldKind::Bool => Some(toml::Value::Boolean(value != 0.)),
+            ConfigFieldKind::Integer => Some(toml::Value::Integer(value.round() as i64)),
+            ConfigFieldKind::Float => Some(toml::Value::Float(value)),
+            ConfigFieldKind::Choice(options) => options
+                .get(value.round() as usize)
+                .map(|option| toml::Value::String(option.to_string())),
+            ConfigFieldKind::String | ConfigFieldKind::Secret | ConfigFieldKind::Address => None,
+        }
+    }
+
+    /// Message reporting a config value to the avatar.
+    fn message(&self, current: &toml::Value) -> Option<OscMessage> {
+        let arg = match (self.kind, current) {
+            (ConfigFieldKind::Bool, toml::Value::Boolean(current)) => OscType::Bool(*current),
+            (ConfigFieldKind::Integer, toml::Value::Integer(current)) => {
+                OscType::Int(*current as i32)
+            }
+            (ConfigFieldKind::Float, toml::Value::Float(current)) => {
+                OscType::Float(*current as f32)
+            }
+            (ConfigFieldKind::Choice(options), toml::Value::String(current)) => {
+                OscType::Int(options.iter().position(|option| option == current)? as i32)
+            }
+            _ => return None,
+        };
+
+        Some(OscMessage {
+            addr: self.address.clone(),
+            args: vec![arg],
+        })
+    }
+}
+
+pub struct MenuSettings {
+    config: MenuSettingsConfig,
+    bindings: Vec<Binding>,
+    rx: broadcast::Receiver<Arc<OscMessage>>,
+    sender_tx: mpsc::Sender<OutgoingMessage>,
+    tray_tx: mpsc::Sender<TrayMessage>,
+    /// Whether the config watcher applies written changes already.
+    watched: bool,
+}
+
+impl MenuSettings {
+    pub fn new(
+        config: &Arc<Config>,
+        rx: broadcast::Receiver<Arc<OscMessage>>,
+        sender_tx: mpsc::Sender<OutgoingMessage>,
+        tray_tx: mpsc::Sender<TrayMessage>,
+    ) -> Self {
+        let prefix = &config.menu_settings.prefix;
+        let bindings = plugins::builtin(config)
+            .into_iter()
+            .flat_map(|plugin| {
+                let name = plugin.name();
+                let section = plugin.config_section();
+
+                plugin
+                    .config_fields()
+                    .into_iter()
+                    .filter(|field| field.menu)
+                    .map(move |field| Binding {
+                        address: format!("{}/{}/{}", prefix, name, field.key),
+                        section: section.clone(),
+                        key: field.key,
+                        kind: field.kind,
+                        range: field.range,
+                    })
+            })
+            .collect();
+
+        Self {
+            config: config.menu_settings.clone(),
+            bindings,
+            rx,
+            sender_tx,
+            tray_tx,
+            watched: config.config_watcher.enabled,
+        }
+    }
+
+    /// Reads the config file, which is the only place changes of other subsystems end up in.
+    async fn read() -> Result<(String, toml::Value)> {
+        let path = config_path()?;
+        let toml_config = read_to_string(&path)
+            .await
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let config: Config = toml::from_str(&toml_config)?;
+
+        Ok((toml_config, toml::Value::try_from(&config)?))
+    }
+
+    /// Reports the current values to the avatar, so the menu shows them.
+    async fn sync(&self) -> Result<()> {
+        let (_, effective) = Self::read().await?;
+
+        for binding in &self.bindings {
+            let Some(message) = lookup(&effective, &binding.section)
+                .and_then(|section| section.get(binding.key))
+                .and_then(|current| binding.message(current))
+            else {
+                continue;
+            };
+
+            self.sender_tx
+                .send(OutgoingMessage::new("menu_settings", message))
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn store(&self, changes: BTreeMap<usize, toml::Value>) -> Result<()> {
+        let (toml_config, effective) = Self::read().await?;
+        let mut document: Document = toml_config.parse()?;
+        let mut changed = false;
+
+        for (index, new) in changes {
+            let binding = &self.bindings[index];
+
+            if lookup(&effective, &binding.section).and_then(|section| section.get(binding.key))
+                == Some(&new)
+            {
+                continue;
+            }
+
+            info!(
+                "Setting {}.{} to {} from the avatar menu",
+                binding.section, binding.key, new
+            );
+            let item = match new {
+                toml::Value::Boolean(new) => value(new),
+                toml::Value::Integer(new) => value(new),
+                toml::Value::Float(new) => value(new),
+                toml::Value::String(new) => value(new),
+                _ => continue,
+            };
+            section_mut(&mut document, &binding.section)[binding.key] = item;
+            changed = true;
+        }
+
+        if !changed {
+            return Ok(());
+        }
+
+        let toml_config = document.to_string();
+        toml::from_str::<Config>(&toml_config).context("Invalid configuration")?;
+        let path = config_path()?;
+        write(&path, toml_config)
+            .await
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+
+        if !self.watched {
+            self.tray_tx.send(TrayMessage::ReloadPlugins).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn handle(&mut self) -> Result<()> {
+        let mut changes = BTreeMap::new();
+        let mut deadline = None;
+
+        loop {
+            select! {
+                message = self.rx.recv() => {
+                    let message = match message {
+                        Ok(message) => message,
+                        Err(RecvError::Lagged(skipped)) => {
+                            warn!("Menu settings lagging behind, {} messages have been dropped", skipped);
+                            continue;
+                        }
+                        Err(RecvError::Closed) => bail!("Message receiver died unexpectedly"),
+                    };
+
+                    if message.addr == AVATAR_CHANGE {
+                        if let Err(error) = self.sync().await {
+                            warn!("Failed to report menu settings: {:#}", error);
+                        }
+
+                        continue;
+                    }
+
+                    for (index, binding) in self.bindings.iter().enumerate() {
+                        if binding.address != message.addr {
+                            continue;
+                        }
+
+                        if let Some(new) = binding.config_value(&message) {
+                            changes.insert(index, new);
+                            deadline = Some(Instant::now() + SETTLE);
+                        }
+                    }
+                }
+                _ = sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                    deadline = None;
+
+                    if let Err(error) = self.store(std::mem::take(&mut changes)).await {
+                        warn!("Failed to store menu settings: {:#}", error);
+                    }
+                }
+            }
+        }
+    }
+
+    pub async fn run(mut self, subsys: SubsystemHandle) -> Result<()> {
+        if !self.config.enabled || self.bindings.is_empty() {
+            // Unread messages would pile up in the receiver.
+            drop(self.rx);
+            subsys.on_shutdown_requested().await;
+            return Ok(());
+        }
+
+        match (self.handle().cancel_on_shutdown(&subsys)).await {
+            Ok(Ok(())) => subsys.request_shutdown(),
+            Ok(Err(error)) => return Err(error),
+            Err(CancelledByShutdown) => {}
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,94 @@
+use crate::config::{MergeConfig, MergePolicy};
+use crate::rules;
+use async_osc::{OscMessage, OscType};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// A value a plugin wrote recently.
+struct Write {
+    source: &'static str,
+    arg: OscType,
+    value: Option<f64>,
+    at: Instant,
+}
+
+/// Merges the values plugins write to the same address according to the configured policies,
+/// instead of whichever came last winning.
+pub struct Merger {
+    policies: Vec<MergeConfig>,
+    writes: HashMap<String, Vec<Write>>,
+}
+
+impl Merger {
+    pub fn new(policies: Vec<MergeConfig>) -> Self {
+        Self {
+            policies,
+            writes: HashMap::new(),
+        }
+    }
+
+    fn rank(policy: &MergeConfig, source: &str) -> usize {
+        policy
+            .priority
+            .iter()
+            .position(|name| name == source)
+            .unwrap_or(policy.priority.len())
+    }
+
+    /// Returns the message to send in place of the one written by the source, if any.
+    pub fn apply(&mut self, source: &'static str, message: OscMessage) -> Option<OscMessage> {
+        let Some(policy) = self
+            .policies
+            .iter()
+            .find(|policy| rules::matches(&policy.address, &message.addr))
+        else {
+            return Some(message);
+        };
+
+        if policy.policy == MergePolicy::LastWriter {
+            return Some(message);
+        }
+
+        let Some(arg) = message.args.first().cloned() else {
+            return Some(message);
+        };
+
+        let now = Instant::now();
+        let window = Duration::from_millis(policy.window_ms);
+        let writes = self.writes.entry(message.addr.clone()).or_default();
+        writes.retain(|write| write.source != source && now.duration_since(write.at) < window);
+        writes.push(Write {
+            source,
+            value: rules::value(&message),
+            arg,
+            at: now,
+        });
+
+        if policy.policy == MergePolicy::Priority {
+            let rank = Self::rank(policy, source);
+            let outranked = writes
+                .iter()
+                .any(|write| Self::rank(policy, write.source) < rank);
+            return (!outranked).then_some(message);
+        }
+
+        let numeric = writes
+            .iter()
+            .filter_map(|write| Some((write, write.value?)));
+        let winner = if policy.policy == MergePolicy::Max {
+            numeric.max_by(|(_, a), (_, b)| a.total_cmp(b))
+        } else {
+            numeric.min_by(|(_, a), (_, b)| a.total_cmp(b))
+        };
+
+        // Values without a number cannot be compared and are passed on as they are.
+        let Some((winner, _)) = winner else {
+            return Some(message);
+        };
+
+        Some(OscMessage {
+            addr: message.addr,
+            args: vec![winner.arg.clone()],
+        })
+    }
+}
@@ -0,0 +1,176 @@
+use crate::config::MonitorConfig;
+use crate::recording::{read_recording, Direction, Record};
+use crate::state::SharedState;
+use anyhow::{bail, Context, Result};
+use async_osc::OscMessage;
+use log::info;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::fs::{create_dir_all, File};
+use tokio::io::AsyncWriteExt;
+use tokio::select;
+use tokio::sync::{broadcast, mpsc};
+use tokio::time::{sleep_until, Instant};
+use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
+
+/// Hands the messages going over the wire to the monitor, dropping them while it is disabled.
+#[derive(Clone)]
+pub struct Tap {
+    start: Instant,
+    tx: Option<mpsc::Sender<Record>>,
+}
+
+impl Tap {
+    pub fn record(&self, direction: Direction, message: &OscMessage) {
+        let Some(tx) = &self.tx else {
+            return;
+        };
+
+        // Records are dropped rather than holding up the traffic when the file cannot keep up.
+        let _ = tx.try_send(Record::new(
+            self.start.elapsed().as_secs_f64(),
+            direction,
+            message,
+        ));
+    }
+}
+
+/// Writes all incoming and outgoing messages to a recording, one JSON object per line.
+pub struct Monitor {
+    enabled: bool,
+    path: PathBuf,
+    tap: Tap,
+    rx: mpsc::Receiver<Record>,
+}
+
+impl Monitor {
+    pub fn new(config: &MonitorConfig, data_dir: &Path) -> Self {
+        let (tx, rx) = mpsc::channel(1024);
+        let path = config.path.clone().unwrap_or_else(|| {
+            data_dir.join(format!(
+                "recordings/{}.jsonl",
+                chrono::Local::now().format("%Y-%m-%d_%H-%M-%S")
+            ))
+        });
+
+        Self {
+            enabled: config.enabled,
+            path,
+            tap: Tap {
+                start: Instant::now(),
+                tx: config.enabled.then_some(tx),
+            },
+            rx,
+        }
+    }
+
+    pub fn tap(&self) -> Tap {
+        self.tap.clone()
+    }
+
+    async fn write(&mut self, subsys: &SubsystemHandle) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            create_dir_all(dir)
+                .await
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+
+        let mut file = File::create(&self.path)
+            .await
+            .with_context(|| format!("Failed to create {}", self.path.display()))?;
+        info!("Recording OSC traffic to {}", self.path.display());
+
+        loop {
+            let record = select! {
+                record = self.rx.recv() => record,
+                _ = subsys.on_shutdown_requested() => break,
+            };
+
+            let Some(record) = record else {
+                bail!("Monitor channel closed unexpectedly");
+            };
+
+            file.write_all(&record.to_line()?).await?;
+        }
+
+        // The file buffers writes, so the end of the recording would be lost without flushing.
+        while let Ok(record) = self.rx.try_recv() {
+            file.write_all(&record.to_line()?).await?;
+        }
+
+        file.flush().await?;
+        Ok(())
+    }
+
+    pub async fn run(mut self, subsys: SubsystemHandle) -> Result<()> {
+        if !self.enabled {
+            subsys.on_shutdown_requested().await;
+            return Ok(());
+        }
+
+        self.write(&subsys).await
+    }
+}
+
+/// Feeds the incoming messages of a recording to the plugins at their original timing, in place of
+/// VRChat.
+pub struct Replay {
+    path: Option<PathBuf>,
+    tx: broadcast::Sender<Arc<OscMessage>>,
+    state: Arc<SharedState>,
+}
+
+impl Replay {
+    pub fn new(
+        path: Option<PathBuf>,
+        tx: broadcast::Sender<Arc<OscMessage>>,
+        state: Arc<SharedState>,
+    ) -> Self {
+        Self { path, tx, state }
+    }
+
+    async fn replay(&self, path: &Path) -> Result<()> {
+        let records = read_recording(path).await?;
+
+        // Messages sent before the plugins subscribed would be lost.
+        self.state.plugins_subscribed.notified().await;
+
+        info!("Replaying {}", path.display());
+        let start = Instant::now();
+
+        for record in records
+            .iter()
+            .filter(|record| record.direction == Direction::Incoming)
+        {
+            sleep_until(start + Duration::from_secs_f64(record.time.max(0.))).await;
+            let _ = self.tx.send(Arc::new(record.to_message()));
+        }
+
+        info!("Replay of {} finished", path.display());
+        Ok(())
+    }
+
+    pub async fn run(self, subsys: SubsystemHandle) -> Result<()> {
+        let Some(path) = self.path.clone() else {
+            subsys.on_shutdown_requested().await;
+            return Ok(());
+        };
+
+        // Messages of VRChat would mix with the replayed ones, so only the replay reaches the
+        // plugins until it is done.
+        self.state.replaying.store(true, Ordering::Relaxed);
+        let result = (self.replay(&path).cancel_on_shutdown(&subsys)).await;
+        self.state.replaying.store(false, Ordering::Relaxed);
+
+        match result {
+            // The plugins keep running afterwards, so what they did can still be looked at.
+            Ok(Ok(())) => subsys.on_shutdown_requested().await,
+            Ok(Err(error)) => return Err(error),
+            Err(CancelledByShutdown) => {}
+        }
+
+        Ok(())
+    }
+}
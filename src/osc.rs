@@ -1,62 +1,1016 @@
-use anyhow::{bail, Result};
-use async_osc::{OscMessage, OscPacket, OscSocket};
-use tokio::sync::{broadcast, mpsc};
+use crate::bitpack;
+use crate::config::{
+    BatchConfig, DownstreamConfig, FaceTrackingConfig, FilterConfig, KeepaliveConfig, OscConfig,
+    RouteConfig, RuleConfig, RuleDirection,
+};
+use crate::face_tracking::FaceProfile;
+use crate::flood::FloodGuard;
+use crate::merge::Merger;
+use crate::monitor::Tap;
+use crate::overrides::Overrides;
+use crate::recording::Direction;
+use crate::reset::ResetDetector;
+use crate::rules::{self, Rules};
+use crate::schedule::Schedules;
+use crate::state::SharedState;
+use crate::StatusEvent;
+use anyhow::{bail, Context, Result};
+use async_osc::{OscBundle, OscMessage, OscPacket, OscSocket, OscType};
+use log::{debug, error, info, warn};
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::select;
+use tokio::sync::{broadcast, mpsc, watch};
+use tokio::time::{interval, sleep, sleep_until, timeout_at, Instant};
 use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
 use tokio_stream::StreamExt;
+use vrc_osc_manager_core::addresses::AVATAR_CHANGE;
+use vrc_osc_manager_core::{heartbeat, Command, OutgoingMessage, ALL, RESYNC};
+
+/// Number of steps VRChat uses to sync a float in the range of -1.0 to 1.0 over the network.
+const SYNCED_FLOAT_STEPS: f32 = 127.;
+
+/// Time the sender keeps going after shutting down, so parameters are reset by the stopping
+/// plugins before the application exits.
+const TEARDOWN: Duration = Duration::from_millis(500);
+
+/// Names the sender and receiver report their heartbeat under.
+pub const SENDER: &str = "Sender";
+pub const RECEIVER: &str = "Receiver";
+
+/// Rounds floats to a grid of steps and drops messages which would not change the rounded value,
+/// either on selected outgoing addresses to the grid VRChat can actually sync, or on all messages
+/// passed on to a destination taking fewer updates.
+struct Quantizer {
+    /// Addresses rounded, all of them when `None`.
+    addresses: Option<HashSet<String>>,
+    steps: f32,
+    /// Whether values are limited to the range of synced floats first.
+    synced: bool,
+    last_steps: HashMap<String, i32>,
+}
+
+impl Quantizer {
+    fn new(addresses: Vec<String>) -> Self {
+        Self {
+            addresses: Some(addresses.into_iter().collect()),
+            steps: SYNCED_FLOAT_STEPS,
+            synced: true,
+            last_steps: HashMap::new(),
+        }
+    }
+
+    /// Rounds all floats to the given number of steps per unit.
+    fn steps(steps: u32) -> Self {
+        Self {
+            addresses: None,
+            steps: steps.max(1) as f32,
+            synced: false,
+            last_steps: HashMap::new(),
+        }
+    }
+
+    fn apply(&mut self, message: &mut OscMessage) -> bool {
+        if let Some(addresses) = &self.addresses {
+            if !addresses.contains(&message.addr) {
+                return true;
+            }
+        }
+
+        let value = match message.args.as_mut_slice() {
+            [OscType::Float(value)] => value,
+            _ => return true,
+        };
+
+        if self.synced {
+            *value = value.clamp(-1., 1.);
+        }
+
+        let step = (*value * self.steps).round() as i32;
+        *value = step as f32 / self.steps;
+
+        self.last_steps.insert(message.addr.clone(), step) != Some(step)
+    }
+
+    /// Forgets the steps sent so far, so the next value of each address goes out again.
+    fn reset(&mut self) {
+        self.last_steps.clear();
+    }
+}
+
+/// Transformations of the messages sent to a single destination, on top of the processing all
+/// messages go through.
+struct Transform {
+    rules: Rules,
+    quantizer: Option<Quantizer>,
+}
+
+impl Transform {
+    /// Rules for the other direction never apply to the destination, which is warned about as
+    /// these are likely meant for the messages it gets.
+    fn new(
+        destination: &str,
+        rules: &[RuleConfig],
+        direction: RuleDirection,
+        float_steps: Option<u32>,
+        schedules: Schedules,
+    ) -> Self {
+        for rule in rules
+            .iter()
+            .filter(|rule| rule.direction != direction && rule.direction != RuleDirection::Both)
+        {
+            warn!(
+                "Rule for {} of {} is for {:?} messages, which never reach it",
+                rule.address, destination, rule.direction
+            );
+        }
+
+        Self {
+            rules: Rules::new(rules, direction, schedules),
+            quantizer: float_steps.map(Quantizer::steps),
+        }
+    }
+
+    fn apply(&mut self, message: OscMessage) -> Vec<OscMessage> {
+        let quantizer = &mut self.quantizer;
+
+        self.rules
+            .apply(message)
+            .into_iter()
+            .filter_map(|mut message| match quantizer {
+                Some(quantizer) => quantizer.apply(&mut message).then_some(message),
+                None => Some(message),
+            })
+            .collect()
+    }
+}
+
+/// Drops outgoing messages repeating the last value sent to their address, as plugins sending on
+/// every tick would otherwise flood VRChat with redundant traffic.
+struct Deduplicator {
+    enabled: bool,
+    exempt: Vec<String>,
+    last_args: HashMap<String, Vec<OscType>>,
+}
+
+impl Deduplicator {
+    fn new(enabled: bool, exempt: Vec<String>) -> Self {
+        Self {
+            enabled,
+            exempt,
+            last_args: HashMap::new(),
+        }
+    }
+
+    fn apply(&mut self, message: &OscMessage) -> bool {
+        if !self.enabled
+            || self
+                .exempt
+                .iter()
+                .any(|pattern| rules::matches(pattern, &message.addr))
+        {
+            return true;
+        }
+
+        if self.last_args.get(&message.addr) == Some(&message.args) {
+            return false;
+        }
+
+        self.last_args
+            .insert(message.addr.clone(), message.args.clone());
+        true
+    }
+
+    /// Forgets the values sent so far, so a new target receives the next value of each address.
+    fn reset(&mut self) {
+        self.last_args.clear();
+    }
+}
+
+/// Remembers the last message sent to each keepalive address together with its destination.
+struct Keepalive {
+    addresses: Vec<String>,
+    interval: Duration,
+    last_messages: HashMap<String, (Option<String>, OscMessage)>,
+}
+
+impl Keepalive {
+    fn new(config: &KeepaliveConfig) -> Self {
+        Self {
+            addresses: config.addresses.clone(),
+            interval: Duration::from_secs(config.interval_seconds.max(1)),
+            last_messages: HashMap::new(),
+        }
+    }
+
+    fn record(&mut self, destination: Option<&str>, message: &OscMessage) {
+        if self
+            .addresses
+            .iter()
+            .any(|pattern| rules::matches(pattern, &message.addr))
+        {
+            self.last_messages.insert(
+                message.addr.clone(),
+                (destination.map(str::to_string), message.clone()),
+            );
+        }
+    }
+}
+
+/// Picks the destination of outgoing messages, the first matching route wins.
+struct Route {
+    config: RouteConfig,
+    transform: Transform,
+}
+
+struct Router {
+    routes: Vec<Route>,
+    schedules: Schedules,
+}
+
+impl Router {
+    fn new(routes: Vec<RouteConfig>, schedules: Schedules) -> Self {
+        Self {
+            routes: routes
+                .into_iter()
+                .map(|config| Route {
+                    transform: Transform::new(
+                        &config.destination,
+                        &config.rules,
+                        RuleDirection::Outbound,
+                        config.float_steps,
+                        schedules.clone(),
+                    ),
+                    config,
+                })
+                .collect(),
+            schedules,
+        }
+    }
+
+    /// Index of the route the message is sent along, if any.
+    fn find(&self, source: &str, addr: &str) -> Option<usize> {
+        self.routes.iter().position(|route| {
+            route
+                .config
+                .plugin
+                .as_ref()
+                .map_or(true, |plugin| plugin == source)
+                && route
+                    .config
+                    .address_prefix
+                    .as_ref()
+                    .map_or(true, |prefix| addr.starts_with(prefix.as_str()))
+                && self.schedules.allows(route.config.schedule.as_deref())
+        })
+    }
+
+    fn destination(&self, route: Option<usize>) -> Option<&str> {
+        route.map(|route| self.routes[route].config.destination.as_str())
+    }
+}
+
+/// Sockets connected to the destinations of routes. The main socket is connected to the send
+/// target, and sending elsewhere through a connected socket fails on some platforms.
+#[derive(Default)]
+struct RouteSockets {
+    sockets: HashMap<String, OscSocket>,
+}
+
+impl RouteSockets {
+    async fn connect(destination: &str) -> Result<OscSocket> {
+        let socket = OscSocket::bind("0.0.0.0:0").await?;
+        socket.connect(destination).await?;
+        Ok(socket)
+    }
+
+    async fn send(
+        &mut self,
+        socket: &OscSocket,
+        destination: Option<&str>,
+        packet: impl Into<OscPacket>,
+    ) {
+        // We ignore failure of sending, as a proper shutdown will be handled by the launcher.
+        let Some(destination) = destination else {
+            let _ = socket.send(packet).await;
+            return;
+        };
+
+        if !self.sockets.contains_key(destination) {
+            match Self::connect(destination).await {
+                Ok(route_socket) => {
+                    self.sockets.insert(destination.to_string(), route_socket);
+                }
+                Err(error) => {
+                    warn!("Failed to connect to {}: {:#}", destination, error);
+                    return;
+                }
+            }
+        }
+
+        let _ = self.sockets[destination].send(packet).await;
+    }
+}
+
+/// Holds back outgoing messages until the batch is full or its oldest message waited long enough.
+struct Batch {
+    max_latency: Duration,
+    max_messages: usize,
+    pending: Vec<(Option<String>, OscMessage)>,
+    deadline: Option<Instant>,
+}
+
+impl Batch {
+    fn new(config: &BatchConfig) -> Self {
+        Self {
+            max_latency: Duration::from_millis(config.max_latency_ms),
+            max_messages: config.max_messages.max(1),
+            pending: vec![],
+            deadline: None,
+        }
+    }
+
+    fn enabled(&self) -> bool {
+        !self.max_latency.is_zero() && self.max_messages > 1
+    }
+
+    /// Queues the message and tells whether the batch is full.
+    fn push(&mut self, destination: Option<&str>, message: OscMessage) -> bool {
+        if self.pending.is_empty() {
+            self.deadline = Some(Instant::now() + self.max_latency);
+        }
+
+        self.pending
+            .push((destination.map(str::to_string), message));
+        self.pending.len() >= self.max_messages
+    }
+
+    /// Takes the pending messages, grouped into packets per destination in their original order.
+    fn take(&mut self) -> Vec<(Option<String>, OscPacket)> {
+        self.deadline = None;
+        let mut groups: Vec<(Option<String>, Vec<OscMessage>)> = vec![];
+
+        for (destination, message) in self.pending.drain(..) {
+            match groups.iter_mut().find(|(other, _)| *other == destination) {
+                Some((_, messages)) => messages.push(message),
+                None => groups.push((destination, vec![message])),
+            }
+        }
+
+        groups
+            .into_iter()
+            .map(|(destination, mut messages)| {
+                let packet = if messages.len() == 1 {
+                    OscPacket::Message(messages.remove(0))
+                } else {
+                    OscPacket::Bundle(OscBundle {
+                        // A time tag of 1 means to process the bundle immediately.
+                        timetag: OscType::Time(0, 1),
+                        content: messages.into_iter().map(OscPacket::Message).collect(),
+                    })
+                };
+
+                (destination, packet)
+            })
+            .collect()
+    }
+}
 
 pub struct Sender {
-    rx: mpsc::Receiver<OscMessage>,
-    port: u16,
+    rx: mpsc::Receiver<OutgoingMessage>,
+    target: watch::Receiver<String>,
+    port: watch::Receiver<u16>,
+    quantizer: Quantizer,
+    deduplicator: Deduplicator,
+    keepalive: Keepalive,
+    batch: Batch,
+    merger: Merger,
+    overrides: Overrides,
+    face_profile: FaceProfile,
+    bitpack_encoder: bitpack::Encoder,
+    rules: Rules,
+    router: Router,
+    route_sockets: RouteSockets,
+    tap: Tap,
+    state: Arc<SharedState>,
 }
 
 impl Sender {
-    pub fn new(rx: mpsc::Receiver<OscMessage>, port: u16) -> Self {
-        Self { rx, port }
+    pub fn new(
+        rx: mpsc::Receiver<OutgoingMessage>,
+        target: watch::Receiver<String>,
+        port: watch::Receiver<u16>,
+        config: &OscConfig,
+        face_tracking: &FaceTrackingConfig,
+        schedules: Schedules,
+        tap: Tap,
+        state: Arc<SharedState>,
+    ) -> Self {
+        Self {
+            rx,
+            target,
+            port,
+            quantizer: Quantizer::new(config.quantize_addresses.clone()),
+            deduplicator: Deduplicator::new(config.deduplicate, config.deduplicate_exempt.clone()),
+            keepalive: Keepalive::new(&config.keepalive),
+            batch: Batch::new(&config.batch),
+            merger: Merger::new(config.merge.clone()),
+            overrides: Overrides::new(),
+            face_profile: FaceProfile::new(face_tracking),
+            bitpack_encoder: bitpack::Encoder::new(&config.bitpack),
+            rules: Rules::new(&config.rules, RuleDirection::Outbound, schedules.clone()),
+            router: Router::new(config.routes.clone(), schedules),
+            route_sockets: RouteSockets::default(),
+            tap,
+            state,
+        }
     }
 
-    async fn send(&mut self) -> Result<()> {
-        let socket = OscSocket::bind("127.0.0.1:0").await?;
-        socket.connect(("127.0.0.1", self.port)).await?;
+    async fn forward(&mut self, socket: &OscSocket, outgoing: OutgoingMessage) {
+        if self.state.output_paused.load(Ordering::Relaxed) {
+            return;
+        }
 
-        while let Some(message) = self.rx.recv().await {
-            // We ignore failure of sending, as a proper shutdown will be handled by the launcher.
-            let _ = socket.send(message).await;
+        let Some(outgoing) = self.overrides.apply(outgoing, &self.state) else {
+            return;
+        };
+
+        let Some(message) = self.merger.apply(outgoing.source, outgoing.message) else {
+            return;
+        };
+
+        let avatar = self.state.avatar.lock().unwrap().clone();
+        let messages: Vec<_> = self
+            .face_profile
+            .apply(message, avatar.as_deref())
+            .into_iter()
+            .flat_map(|message| self.rules.apply(message))
+            .flat_map(|message| match self.bitpack_encoder.encode(&message) {
+                Some(messages) => messages,
+                None => vec![message],
+            })
+            .collect();
+
+        for mut message in messages {
+            // Messages sent by hand still go through, e.g. to adjust a frozen parameter.
+            if outgoing.source != "control" && self.state.is_frozen(&message.addr) {
+                continue;
+            }
+
+            if !self.quantizer.apply(&mut message) || !self.deduplicator.apply(&message) {
+                continue;
+            }
+
+            let route = self.router.find(outgoing.source, &message.addr);
+            let messages = match route {
+                Some(route) => self.router.routes[route].transform.apply(message),
+                None => vec![message],
+            };
+
+            for message in messages {
+                let destination = self.router.destination(route);
+                self.keepalive.record(destination, &message);
+                self.state.accounting.record_sent(outgoing.source);
+                self.tap.record(Direction::Outgoing, &message);
+
+                if self.batch.enabled() {
+                    if self.batch.push(destination, message) {
+                        self.flush(socket).await;
+                    }
+
+                    continue;
+                }
+
+                self.route_sockets.send(socket, destination, message).await;
+                self.state.stats.record_sent();
+            }
+        }
+    }
+
+    /// Sends the batched messages, one packet per destination.
+    async fn flush(&mut self, socket: &OscSocket) {
+        let count = self.batch.pending.len();
+
+        for (destination, packet) in self.batch.take() {
+            self.route_sockets
+                .send(socket, destination.as_deref(), packet)
+                .await;
+        }
+
+        for _ in 0..count {
+            self.state.stats.record_sent();
+        }
+    }
+
+    /// Sends the keepalive messages again, bypassing the processing they already went through.
+    async fn resend_keepalive(&mut self, socket: &OscSocket) {
+        if self.state.output_paused.load(Ordering::Relaxed) {
+            return;
+        }
+
+        for (destination, message) in self.keepalive.last_messages.values() {
+            self.tap.record(Direction::Outgoing, message);
+            self.route_sockets
+                .send(socket, destination.as_deref(), message.clone())
+                .await;
+            self.state.stats.record_sent();
+        }
+    }
+
+    async fn send(&mut self, socket: &OscSocket) -> Result<()> {
+        let host = self.target.borrow_and_update().clone();
+        let port = *self.port.borrow_and_update();
+        socket.connect((host.as_str(), port)).await?;
+        let mut keepalive_interval = interval(self.keepalive.interval);
+        let heartbeat = self.state.heartbeats.heartbeat(SENDER);
+        let mut heartbeat_interval = interval(heartbeat::INTERVAL);
+
+        loop {
+            let deadline = self.batch.deadline;
+            let override_deadline = self.overrides.deadline();
+
+            select! {
+                message = self.rx.recv() => match message {
+                    Some(message) => {
+                        self.forward(socket, message).await;
+
+                        // Drain whatever queued up meanwhile, so it can share a batch.
+                        if self.batch.enabled() {
+                            while let Ok(message) = self.rx.try_recv() {
+                                self.forward(socket, message).await;
+                            }
+                        }
+                    }
+                    None => {
+                        self.flush(socket).await;
+                        break;
+                    }
+                },
+                _ = sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                    self.flush(socket).await;
+                }
+                _ = sleep_until(override_deadline.unwrap_or_else(Instant::now)), if override_deadline.is_some() => {
+                    for restore in self.overrides.expire() {
+                        self.forward(socket, restore).await;
+                    }
+                }
+                Ok(()) = self.target.changed() => self.retarget(socket).await,
+                Ok(()) = self.port.changed() => self.retarget(socket).await,
+                _ = keepalive_interval.tick() => self.resend_keepalive(socket).await,
+                _ = heartbeat_interval.tick() => heartbeat.beat(),
+                // Notified on avatar changes and resets as well, after which the avatar needs every
+                // value again, even those already sent before.
+                _ = self.state.resync.notified() => {
+                    self.quantizer.reset();
+                    self.deduplicator.reset();
+                    debug!("Resending {} keepalive values", self.keepalive.last_messages.len());
+                    self.resend_keepalive(socket).await;
+                }
+            }
         }
 
         bail!("Sender stream closed unexpectedly");
     }
 
+    async fn retarget(&mut self, socket: &OscSocket) {
+        let host = self.target.borrow_and_update().clone();
+        let port = *self.port.borrow_and_update();
+
+        match socket.connect((host.as_str(), port)).await {
+            Ok(()) => {
+                info!("Sending to {}:{}", host, port);
+                self.quantizer.reset();
+                self.deduplicator.reset();
+            }
+            Err(error) => error!(
+                "Failed to switch send target to {}:{}: {}",
+                host, port, error
+            ),
+        }
+    }
+
+    /// Forwards what the stopping subsystems send while shutting down.
+    async fn teardown(&mut self, socket: &OscSocket) {
+        let deadline = Instant::now() + TEARDOWN;
+
+        while let Ok(Some(message)) = timeout_at(deadline, self.rx.recv()).await {
+            self.forward(socket, message).await;
+        }
+
+        self.flush(socket).await;
+    }
+
+    /// Starts over on a new socket whenever the watchdog finds the sender stuck.
+    async fn supervise(&mut self, socket: &mut OscSocket) -> Result<()> {
+        let heartbeat = self.state.heartbeats.heartbeat(SENDER);
+
+        loop {
+            select! {
+                result = self.send(socket) => return result,
+                _ = heartbeat.stalled() => {}
+            }
+
+            warn!("Sender stopped responding, starting over on a new socket");
+            *socket = OscSocket::bind("0.0.0.0:0").await?;
+        }
+    }
+
     pub async fn run(mut self, subsys: SubsystemHandle) -> Result<()> {
-        match (self.send().cancel_on_shutdown(&subsys)).await {
+        let mut socket = OscSocket::bind("0.0.0.0:0").await?;
+
+        match (self.supervise(&mut socket).cancel_on_shutdown(&subsys)).await {
             Ok(Ok(())) => subsys.request_shutdown(),
             Ok(Err(error)) => return Err(error),
-            Err(CancelledByShutdown) => {}
+            Err(CancelledByShutdown) => self.teardown(&socket).await,
         }
 
         Ok(())
     }
 }
 
+/// Enforces the allow and deny lists on incoming messages.
+struct Filter {
+    config: FilterConfig,
+}
+
+impl Filter {
+    fn new(config: FilterConfig) -> Self {
+        Self { config }
+    }
+
+    fn allows_source(&self, source: IpAddr) -> bool {
+        let config = &self.config;
+
+        !config.deny_sources.contains(&source)
+            && (config.allow_sources.is_empty() || config.allow_sources.contains(&source))
+    }
+
+    fn allows(&self, source: IpAddr, addr: &str) -> bool {
+        let config = &self.config;
+
+        self.allows_source(source)
+            && !config
+                .deny_addresses
+                .iter()
+                .any(|pattern| rules::matches(pattern, addr))
+            && (config.allow_addresses.is_empty()
+                || config
+                    .allow_addresses
+                    .iter()
+                    .any(|pattern| rules::matches(pattern, addr)))
+    }
+}
+
+/// Another OSC application, together with the state of its transformations.
+struct Downstream {
+    config: DownstreamConfig,
+    destination: SocketAddr,
+    transform: Transform,
+}
+
+impl Downstream {
+    /// Resolves the destination once, as it is sent to for every packet. Fails when it does not
+    /// resolve.
+    fn new(config: DownstreamConfig, schedules: Schedules) -> Result<Self> {
+        let destination = config
+            .destination
+            .to_socket_addrs()?
+            .next()
+            .with_context(|| format!("{} resolves to no address", config.destination))?;
+
+        Ok(Self {
+            transform: Transform::new(
+                &config.destination,
+                &config.rules,
+                RuleDirection::Inbound,
+                config.float_steps,
+                schedules,
+            ),
+            config,
+            destination,
+        })
+    }
+
+    fn wants(&self, addr: &str) -> bool {
+        self.config.address_prefixes.is_empty()
+            || self
+                .config
+                .address_prefixes
+                .iter()
+                .any(|prefix| addr.starts_with(prefix.as_str()))
+    }
+
+    fn transform(&mut self, packet: &OscPacket) -> Vec<OscPacket> {
+        match packet {
+            OscPacket::Message(message) => {
+                if !self.wants(&message.addr) {
+                    return vec![];
+                }
+
+                self.transform
+                    .apply(message.clone())
+                    .into_iter()
+                    .map(OscPacket::Message)
+                    .collect()
+            }
+            OscPacket::Bundle(bundle) => {
+                let content: Vec<_> = bundle
+                    .content
+                    .iter()
+                    .flat_map(|packet| self.transform(packet))
+                    .collect();
+
+                if content.is_empty() {
+                    return vec![];
+                }
+
+                vec![OscPacket::Bundle(OscBundle {
+                    timetag: bundle.timetag.clone(),
+                    content,
+                })]
+            }
+        }
+    }
+}
+
+/// Passes incoming packets on to other OSC applications, reduced to the messages each of them is
+/// interested in and transformed for each of them.
+struct Forwarder {
+    downstream: Vec<Downstream>,
+}
+
+impl Forwarder {
+    fn new(downstream: Vec<DownstreamConfig>, schedules: Schedules) -> Self {
+        Self {
+            downstream: downstream
+                .into_iter()
+                .filter_map(|config| {
+                    let destination = config.destination.clone();
+                    Downstream::new(config, schedules.clone())
+                        .map_err(|error| {
+                            warn!("Not forwarding to {}: {:#}", destination, error);
+                        })
+                        .ok()
+                })
+                .collect(),
+        }
+    }
+
+    async fn forward(&mut self, socket: &OscSocket, packet: &OscPacket) {
+        for downstream in &mut self.downstream {
+            for packet in downstream.transform(packet) {
+                // An application which is not running is no reason to hold up the others.
+                if let Err(error) = socket.send_to(packet, downstream.destination).await {
+                    debug!("Failed to forward to {}: {}", downstream.destination, error);
+                }
+            }
+        }
+    }
+}
+
+/// Freezes the parameters matching a pattern given as string, or the configured ones with a bool.
+const FREEZE: &str = "/vrc-osc-manager/freeze";
+
+/// Unfreezes the parameters matching a pattern given as string.
+const UNFREEZE: &str = "/vrc-osc-manager/unfreeze";
+
 pub struct Receiver {
-    tx: broadcast::Sender<OscMessage>,
+    tx: broadcast::Sender<Arc<OscMessage>>,
+    address: IpAddr,
     port: u16,
+    /// Reports the port when falling back to a free one, unless the configured one is required.
+    port_tx: Option<watch::Sender<u16>>,
+    bitpack_decoder: bitpack::Decoder,
+    rules: Rules,
+    filter: Filter,
+    forwarder: Forwarder,
+    flood_guard: FloodGuard,
+    flooded: bool,
+    reset_detector: ResetDetector,
+    settle: Duration,
+    freeze: Vec<String>,
+    tap: Tap,
+    state: Arc<SharedState>,
+    status_tx: mpsc::Sender<StatusEvent>,
 }
 
 impl Receiver {
-    pub fn new(tx: broadcast::Sender<OscMessage>, port: u16) -> Self {
-        Self { tx, port }
+    pub fn new(
+        tx: broadcast::Sender<Arc<OscMessage>>,
+        config: &OscConfig,
+        schedules: Schedules,
+        tap: Tap,
+        state: Arc<SharedState>,
+        status_tx: mpsc::Sender<StatusEvent>,
+    ) -> Self {
+        Self {
+            tx,
+            address: config.listen_address,
+            port: config.receive_port,
+            port_tx: None,
+            bitpack_decoder: bitpack::Decoder::new(&config.bitpack),
+            rules: Rules::new(&config.rules, RuleDirection::Inbound, schedules.clone()),
+            filter: Filter::new(config.filter.clone()),
+            forwarder: Forwarder::new(config.downstream.clone(), schedules),
+            flood_guard: FloodGuard::new(&config.flood),
+            flooded: false,
+            reset_detector: ResetDetector::new(&config.reset_detection),
+            settle: Duration::from_millis(config.reset_detection.settle_ms),
+            freeze: config.freeze.clone(),
+            tap,
+            state,
+            status_tx,
+        }
+    }
+
+    /// Lets the receiver fall back to any free port when another application holds the configured
+    /// one already, as VRChat finds the port through OSCQuery either way.
+    #[cfg(feature = "oscquery")]
+    pub fn any_port(mut self, port_tx: watch::Sender<u16>) -> Self {
+        self.port_tx = Some(port_tx);
+        self
+    }
+
+    /// Binds the receive port. Once fallen back to a free port, binding again after a stall keeps
+    /// it, so VRChat does not have to be told about another one.
+    async fn bind(&mut self) -> Result<OscSocket> {
+        let error = match OscSocket::bind((self.address, self.port)).await {
+            Ok(socket) => return Ok(socket),
+            Err(error) => error,
+        };
+
+        let Some(port_tx) = &self.port_tx else {
+            return Err(error.into());
+        };
+
+        let socket = OscSocket::bind((self.address, 0)).await?;
+        let port = socket.local_addr()?.port();
+        warn!(
+            "Port {} is in use, receiving on {} instead",
+            self.port, port
+        );
+        self.port = port;
+        port_tx.send_replace(port);
+        Ok(socket)
+    }
+
+    fn check_flood(&mut self, source: IpAddr, addr: &str) -> bool {
+        let allowed = self.flood_guard.allow(source, addr);
+        let flooded = self.flood_guard.flooded();
+
+        if flooded != self.flooded {
+            self.flooded = flooded;
+            // The tray only reflects the state, so a full channel is no reason to hold up traffic.
+            let _ = self.status_tx.try_send(StatusEvent::Flooded(flooded));
+        }
+
+        allowed
+    }
+
+    /// Waits for the rest of the reset to arrive, as values pushed in between would be overwritten.
+    fn schedule_resync(&self) {
+        info!("Avatar reset detected, resyncing parameters");
+        let state = self.state.clone();
+        let settle = self.settle;
+
+        tokio::spawn(async move {
+            sleep(settle).await;
+            state.resync.notify_one();
+            state.commands.send(Command::new(ALL, RESYNC, ""));
+        });
+    }
+
+    /// Handles messages freezing parameters, which can also be renamed avatar parameters.
+    fn handle_freeze(&self, message: &OscMessage) -> bool {
+        let frozen = match message.addr.as_str() {
+            FREEZE => true,
+            UNFREEZE => false,
+            _ => return false,
+        };
+
+        match message.args.first() {
+            Some(OscType::String(pattern)) if !pattern.trim().is_empty() => {
+                info!(
+                    "{} {}",
+                    if frozen { "Freezing" } else { "Unfreezing" },
+                    pattern
+                );
+                self.state.set_frozen(pattern, frozen);
+            }
+            Some(OscType::Bool(value)) if frozen => {
+                info!(
+                    "{} parameters",
+                    if *value { "Freezing" } else { "Unfreezing" }
+                );
+
+                for pattern in &self.freeze {
+                    self.state.set_frozen(pattern, *value);
+                }
+            }
+            _ => warn!("Ignored {} without a pattern", message.addr),
+        }
+
+        true
+    }
+
+    /// Hands the message to the plugins and remembers it as the last value of its address.
+    fn publish(&self, message: OscMessage) {
+        self.tap.record(Direction::Incoming, &message);
+        let message = Arc::new(message);
+        let mut parameters = self.state.parameters.lock().unwrap();
+
+        // Addresses repeat all the time, so only new ones allocate a key.
+        match parameters.get_mut(&message.addr) {
+            Some(last) => *last = message.clone(),
+            None => {
+                parameters.insert(message.addr.clone(), message.clone());
+            }
+        }
+
+        drop(parameters);
+        let _ = self.tx.send(message);
     }
 
     async fn receive(&mut self) -> Result<()> {
-        let mut socket = OscSocket::bind(("127.0.0.1", self.port)).await?;
+        let mut socket = self.bind().await?;
+        // Errors about unreachable applications would otherwise end up on the receiving socket.
+        let forward_socket = if self.forwarder.downstream.is_empty() {
+            None
+        } else {
+            Some(OscSocket::bind("0.0.0.0:0").await?)
+        };
+        let heartbeat = self.state.heartbeats.heartbeat(RECEIVER);
+        let mut heartbeat_interval = interval(heartbeat::INTERVAL);
 
-        while let Some(packet) = socket.next().await {
-            let (packet, _) = packet?;
+        loop {
+            let packet = select! {
+                packet = socket.next() => packet,
+                _ = heartbeat_interval.tick() => {
+                    heartbeat.beat();
+                    continue;
+                }
+            };
+            let Some(packet) = packet else {
+                break;
+            };
+            let (packet, peer) = packet?;
+
+            if self.state.replaying.load(Ordering::Relaxed) {
+                continue;
+            }
+
+            if let Some(forward_socket) = &forward_socket {
+                if self.filter.allows_source(peer.ip()) {
+                    self.forwarder.forward(forward_socket, &packet).await;
+                }
+            }
 
             match packet {
                 OscPacket::Bundle(_) => {}
                 OscPacket::Message(message) => {
-                    let _ = self.tx.send(message);
+                    if !self.filter.allows(peer.ip(), &message.addr) {
+                        debug!("Filtered {} from {}", message.addr, peer);
+                        continue;
+                    }
+
+                    if !self.check_flood(peer.ip(), &message.addr) {
+                        continue;
+                    }
+
+                    // Only avatar changes which passed the filter and the flood guard resync, so
+                    // no other source can keep triggering resyncs.
+                    if message.addr == AVATAR_CHANGE {
+                        if let Some(OscType::String(avatar)) = message.args.first() {
+                            *self.state.avatar.lock().unwrap() = Some(avatar.clone());
+                            self.state.stats.record_avatar_change();
+                        }
+
+                        self.state.resync.notify_one();
+                    }
+
+                    // Only what reaches the plugins is counted, dropped messages would skew the
+                    // statistics.
+                    self.state.stats.record_received();
+
+                    if self.reset_detector.record(&message) {
+                        self.schedule_resync();
+                    }
+
+                    for message in self.rules.apply(message) {
+                        if self.handle_freeze(&message) {
+                            continue;
+                        }
+
+                        let decoded = self.bitpack_decoder.decode(&message);
+                        self.publish(message);
+
+                        if let Some(decoded) = decoded {
+                            self.publish(decoded);
+                        }
+                    }
                 }
             }
         }
@@ -64,8 +1018,40 @@ impl Receiver {
         bail!("Receiver stream closed unexpectedly");
     }
 
+    /// Binds the socket again whenever the watchdog finds the receiver stuck.
+    async fn supervise(&mut self) -> Result<()> {
+        let heartbeat = self.state.heartbeats.heartbeat(RECEIVER);
+
+        loop {
+            select! {
+                result = self.receive() => return result,
+                _ = heartbeat.stalled() => {}
+            }
+
+            warn!("Receiver stopped responding, binding its socket again");
+        }
+    }
+
     pub async fn run(mut self, subsys: SubsystemHandle) -> Result<()> {
-        match (self.receive().cancel_on_shutdown(&subsys)).await {
+        if !self.address.is_loopback() {
+            warn!(
+                "Listening for OSC on {}, every device able to reach it can control your plugins",
+                self.address
+            );
+        }
+
+        for downstream in &self.forwarder.downstream {
+            let destination = downstream.destination;
+
+            if destination.port() == self.port && destination.ip().is_loopback() {
+                warn!(
+                    "Forwarding to {}, where the manager receives itself, loops messages",
+                    destination
+                );
+            }
+        }
+
+        match (self.supervise().cancel_on_shutdown(&subsys)).await {
             Ok(Ok(())) => subsys.request_shutdown(),
             Ok(Err(error)) => return Err(error),
             Err(CancelledByShutdown) => {}
@@ -74,3 +1060,71 @@ impl Receiver {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn float(addr: &str, value: f32) -> OscMessage {
+        OscMessage {
+            addr: addr.to_string(),
+            args: vec![OscType::Float(value)],
+        }
+    }
+
+    #[test]
+    fn quantizes_to_synced_steps() {
+        let mut quantizer = Quantizer::new(vec!["/avatar/parameters/Value".to_string()]);
+
+        let mut message = float("/avatar/parameters/Value", 0.5);
+        assert!(quantizer.apply(&mut message));
+        assert_eq!(message, float("/avatar/parameters/Value", 64. / 127.));
+
+        let mut message = float("/avatar/parameters/Value", 2.);
+        assert!(quantizer.apply(&mut message));
+        assert_eq!(message, float("/avatar/parameters/Value", 1.));
+
+        let mut message = float("/avatar/parameters/Other", 0.5);
+        assert!(quantizer.apply(&mut message));
+        assert_eq!(message, float("/avatar/parameters/Other", 0.5));
+    }
+
+    #[test]
+    fn quantizer_drops_unchanged_steps_until_reset() {
+        let mut quantizer = Quantizer::new(vec!["/avatar/parameters/Value".to_string()]);
+
+        assert!(quantizer.apply(&mut float("/avatar/parameters/Value", 0.5)));
+        assert!(!quantizer.apply(&mut float("/avatar/parameters/Value", 0.501)));
+        assert!(quantizer.apply(&mut float("/avatar/parameters/Value", 0.6)));
+
+        quantizer.reset();
+        assert!(quantizer.apply(&mut float("/avatar/parameters/Value", 0.6)));
+    }
+
+    #[test]
+    fn deduplicator_drops_repeats_until_reset() {
+        let mut deduplicator =
+            Deduplicator::new(true, vec!["/avatar/parameters/Exempt*".to_string()]);
+
+        assert!(deduplicator.apply(&float("/avatar/parameters/Value", 0.5)));
+        assert!(!deduplicator.apply(&float("/avatar/parameters/Value", 0.5)));
+        assert!(deduplicator.apply(&float("/avatar/parameters/Value", 0.6)));
+        assert!(deduplicator.apply(&float("/avatar/parameters/Other", 0.6)));
+
+        deduplicator.reset();
+        assert!(deduplicator.apply(&float("/avatar/parameters/Value", 0.6)));
+        assert!(!deduplicator.apply(&float("/avatar/parameters/Value", 0.6)));
+    }
+
+    #[test]
+    fn deduplicator_passes_exempt_addresses_and_when_disabled() {
+        let mut deduplicator =
+            Deduplicator::new(true, vec!["/avatar/parameters/Exempt*".to_string()]);
+        assert!(deduplicator.apply(&float("/avatar/parameters/Exempt", 1.)));
+        assert!(deduplicator.apply(&float("/avatar/parameters/Exempt", 1.)));
+
+        let mut deduplicator = Deduplicator::new(false, vec![]);
+        assert!(deduplicator.apply(&float("/avatar/parameters/Value", 1.)));
+        assert!(deduplicator.apply(&float("/avatar/parameters/Value", 1.)));
+    }
+}
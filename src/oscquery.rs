@@ -0,0 +1,228 @@
+use crate::config::{is_local_host, OscConfig};
+use crate::discovery::{Clients, OSCQUERY_SERVICE};
+use anyhow::{bail, Context, Result};
+use hyper::header::CONTENT_TYPE;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, Server};
+use log::{info, warn};
+use mdns_sd::{ServiceDaemon, ServiceInfo};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use tokio::select;
+use tokio::sync::watch;
+use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
+
+const OSC_SERVICE: &str = "_osc._udp.local.";
+
+#[derive(Debug, Deserialize)]
+struct HostInfo {
+    #[serde(rename = "OSC_PORT")]
+    osc_port: u16,
+}
+
+/// Answers the queries VRChat sends to find out where to send OSC messages to, and which of them.
+fn respond(request: &Request<Body>, address: Ipv4Addr, receive_port: u16) -> Response<Body> {
+    let body = if request.uri().query() == Some("HOST_INFO") {
+        json!({
+            "NAME": "VRC OSC Manager",
+            "OSC_IP": address.to_string(),
+            "OSC_PORT": receive_port,
+            "OSC_TRANSPORT": "UDP",
+            "EXTENSIONS": { "ACCESS": true, "VALUE": true },
+        })
+    } else {
+        // VRChat only sends the parts of its traffic whose root appears in the tree.
+        let node = |path: &str| json!({ "FULL_PATH": path, "ACCESS": 2 });
+        json!({
+            "FULL_PATH": "/",
+            "ACCESS": 0,
+            "CONTENTS": {
+                "avatar": node("/avatar"),
+                "tracking": node("/tracking"),
+            },
+        })
+    };
+
+    let mut response = Response::new(Body::from(body.to_string()));
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, "application/json".parse().unwrap());
+    response
+}
+
+/// Advertises the manager through OSCQuery, so VRChat sends to it whatever port it ended up on,
+/// and asks the VRChat clients found by discovery which port they listen on in turn.
+pub struct OscQuery {
+    daemon: Option<ServiceDaemon>,
+    address: Ipv4Addr,
+    /// Port the receiver ended up on, which may change when it has to fall back to a free one.
+    receive_port: watch::Receiver<u16>,
+    send_port: u16,
+    target: watch::Receiver<String>,
+    port_tx: watch::Sender<u16>,
+    clients: watch::Receiver<Clients>,
+}
+
+impl OscQuery {
+    /// Registers through the daemon shared with discovery, which is `None` while OSCQuery is
+    /// disabled.
+    pub fn new(
+        daemon: Option<ServiceDaemon>,
+        config: &OscConfig,
+        receive_port: watch::Receiver<u16>,
+        target: watch::Receiver<String>,
+        port_tx: watch::Sender<u16>,
+        clients: watch::Receiver<Clients>,
+    ) -> Self {
+        Self {
+            daemon,
+            // Advertising the unspecified address would leave VRChat without a way to reach us.
+            address: match config.listen_address {
+                IpAddr::V4(address) if !address.is_unspecified() => address,
+                _ => Ipv4Addr::LOCALHOST,
+            },
+            receive_port,
+            send_port: config.send_port,
+            target,
+            port_tx,
+            clients,
+        }
+    }
+
+    /// Whether the client runs on the host messages are sent to. A local send target has to be
+    /// matched through the addresses of this machine, which are the only ones a socket binds to.
+    fn is_target(&self, address: IpAddr) -> bool {
+        let host = self.target.borrow().clone();
+
+        if is_local_host(&host) {
+            UdpSocket::bind((address, 0)).is_ok()
+        } else {
+            host == address.to_string()
+        }
+    }
+
+    async fn query(client: &reqwest::Client, address: SocketAddr) -> Result<u16> {
+        let info: HostInfo = client
+            .get(format!("http://{}/?HOST_INFO", address))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(info.osc_port)
+    }
+
+    /// Points the sender at the port of the VRChat client on the send target, going back to the
+    /// configured one while there is none, e.g. once the client went away.
+    async fn update_port(&self, client: &reqwest::Client) {
+        let target = self
+            .clients
+            .borrow()
+            .iter()
+            .find(|(_, address)| self.is_target(address.ip()))
+            .map(|(name, address)| (name.clone(), *address));
+
+        let (port, source) = match target {
+            Some((name, address)) => match Self::query(client, address).await {
+                Ok(port) => (port, name),
+                Err(error) => {
+                    warn!("Failed to query {} through OSCQuery: {:#}", name, error);
+                    return;
+                }
+            },
+            None => (self.send_port, "send_port".to_string()),
+        };
+
+        self.port_tx.send_if_modified(|current| {
+            if *current == port {
+                return false;
+            }
+
+            info!("Sending OSC to port {} from {}", port, source);
+            *current = port;
+            true
+        });
+    }
+
+    fn register(&self, daemon: &ServiceDaemon, service: &str, port: u16) -> Result<()> {
+        let name = format!("VRC-OSC-Manager-{}", std::process::id());
+        let host_name = format!("{}.local.", name);
+
+        daemon.register(ServiceInfo::new(
+            service,
+            &name,
+            &host_name,
+            self.address.to_string().as_str(),
+            port,
+            HashMap::<String, String>::new(),
+        )?)?;
+        Ok(())
+    }
+
+    async fn advertise(&mut self, daemon: ServiceDaemon) -> Result<()> {
+        let address = self.address;
+        let receive_port = self.receive_port.clone();
+        let server = Server::try_bind(&SocketAddr::from((self.address, 0)))
+            .context("Failed to start the OSCQuery server")?
+            .serve(make_service_fn(move |_| {
+                let receive_port = receive_port.clone();
+
+                async move {
+                    Ok::<_, Infallible>(service_fn(move |request| {
+                        let receive_port = *receive_port.borrow();
+
+                        async move { Ok::<_, Infallible>(respond(&request, address, receive_port)) }
+                    }))
+                }
+            }));
+        let http_port = server.local_addr().port();
+
+        self.register(&daemon, OSCQUERY_SERVICE, http_port)?;
+        let receive_port = *self.receive_port.borrow_and_update();
+        self.register(&daemon, OSC_SERVICE, receive_port)?;
+        info!(
+            "Advertising OSC on {}:{} through OSCQuery on port {}",
+            self.address, receive_port, http_port
+        );
+
+        let client = reqwest::Client::new();
+        self.update_port(&client).await;
+        tokio::pin!(server);
+
+        loop {
+            select! {
+                result = &mut server => {
+                    result?;
+                    break;
+                }
+                Ok(()) = self.receive_port.changed() => {
+                    let receive_port = *self.receive_port.borrow_and_update();
+                    self.register(&daemon, OSC_SERVICE, receive_port)?;
+                    info!("Advertising OSC on {}:{} instead", self.address, receive_port);
+                }
+                Ok(()) = self.clients.changed() => self.update_port(&client).await,
+                Ok(()) = self.target.changed() => self.update_port(&client).await,
+            }
+        }
+
+        bail!("OSCQuery stopped unexpectedly");
+    }
+
+    pub async fn run(mut self, subsys: SubsystemHandle) -> Result<()> {
+        let Some(daemon) = self.daemon.take() else {
+            subsys.on_shutdown_requested().await;
+            return Ok(());
+        };
+
+        match (self.advertise(daemon).cancel_on_shutdown(&subsys)).await {
+            Ok(Ok(())) => subsys.request_shutdown(),
+            Ok(Err(error)) => return Err(error),
+            Err(CancelledByShutdown) => {}
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,110 @@
+use crate::state::SharedState;
+use async_osc::{OscMessage, OscType};
+use std::collections::HashMap;
+use tokio::time::Instant;
+use vrc_osc_manager_core::OutgoingMessage;
+
+/// An override of an address, with the write restoring what it replaced.
+struct Override {
+    until: Instant,
+    restore: Option<OutgoingMessage>,
+}
+
+/// Applies time-limited overrides, so plugins setting a parameter for a moment do not have to keep
+/// track of its previous value and of other plugins overriding it meanwhile.
+#[derive(Default)]
+pub struct Overrides {
+    active: HashMap<String, Override>,
+    /// Last regular write per address, which an override restores once it expires.
+    last: HashMap<String, OutgoingMessage>,
+}
+
+impl Overrides {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The value to restore when no plugin wrote to the address yet, which is the one VRChat
+    /// reported last, or else the neutral value of the type of the override.
+    fn previous(&self, outgoing: &OutgoingMessage, state: &SharedState) -> Option<OutgoingMessage> {
+        let addr = &outgoing.message.addr;
+
+        if let Some(last) = self.last.get(addr) {
+            return Some(last.clone());
+        }
+
+        let message = match state.parameters.lock().unwrap().get(addr) {
+            Some(received) => (**received).clone(),
+            None => OscMessage {
+                addr: addr.clone(),
+                args: vec![match outgoing.message.args.first()? {
+                    OscType::Bool(_) => OscType::Bool(false),
+                    OscType::Int(_) => OscType::Int(0),
+                    OscType::Float(_) => OscType::Float(0.),
+                    _ => return None,
+                }],
+            },
+        };
+
+        Some(OutgoingMessage::new(outgoing.source, message))
+    }
+
+    /// Returns the message to send, unless a running override holds back the write.
+    pub fn apply(
+        &mut self,
+        outgoing: OutgoingMessage,
+        state: &SharedState,
+    ) -> Option<OutgoingMessage> {
+        let addr = outgoing.message.addr.clone();
+
+        let Some(duration) = outgoing.restore_after else {
+            if let Some(active) = self.active.get_mut(&addr) {
+                active.restore = Some(outgoing);
+                return None;
+            }
+
+            self.last.insert(addr, outgoing.clone());
+            return Some(outgoing);
+        };
+
+        // A newer override replaces the running one, but still restores what was there before.
+        let restore = match self.active.remove(&addr) {
+            Some(active) => active.restore,
+            None => self.previous(&outgoing, state),
+        };
+
+        self.active.insert(
+            addr,
+            Override {
+                until: Instant::now() + duration,
+                restore,
+            },
+        );
+
+        Some(OutgoingMessage {
+            restore_after: None,
+            ..outgoing
+        })
+    }
+
+    /// When the next override expires.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.active.values().map(|active| active.until).min()
+    }
+
+    /// Ends the expired overrides, returning the writes restoring the previous values.
+    pub fn expire(&mut self) -> Vec<OutgoingMessage> {
+        let now = Instant::now();
+        let expired: Vec<_> = self
+            .active
+            .iter()
+            .filter(|(_, active)| active.until <= now)
+            .map(|(addr, _)| addr.clone())
+            .collect();
+
+        expired
+            .into_iter()
+            .filter_map(|addr| self.active.remove(&addr)?.restore)
+            .collect()
+    }
+}
@@ -0,0 +1,319 @@
+use crate::avatar::{self, AvatarConfig, Parameter};
+use crate::config::{load_config, Config};
+use crate::plugins;
+use crate::rules;
+use anyhow::Result;
+use clap::ValueEnum;
+use std::fmt::Write;
+use std::sync::Arc;
+use vrc_osc_manager_core::{addresses, ParameterAccess, ParameterUsage};
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum DocsFormat {
+    Markdown,
+    Html,
+}
+
+struct Row {
+    name: String,
+    address: String,
+    kind: String,
+    direction: &'static str,
+    plugins: Vec<String>,
+    processing: Vec<String>,
+}
+
+/// A standard address of VRChat used by a plugin, which never shows up on the avatar.
+struct StandardUse {
+    address: String,
+    signature: String,
+    plugin: String,
+}
+
+/// Parameters of the avatar, the addresses plugins use but the avatar lacks, and the standard
+/// addresses plugins use.
+type Report = (Vec<Row>, Vec<(String, String)>, Vec<StandardUse>);
+
+fn access_name(access: ParameterAccess) -> &'static str {
+    match access {
+        ParameterAccess::Read => "reads",
+        ParameterAccess::Write => "writes",
+        ParameterAccess::ReadWrite => "reads and writes",
+    }
+}
+
+fn parameter_address(parameter: &Parameter) -> String {
+    parameter
+        .input
+        .as_ref()
+        .or(parameter.output.as_ref())
+        .map_or_else(
+            || addresses::parameter_address(&parameter.name),
+            |endpoint| endpoint.address.clone(),
+        )
+}
+
+/// Describes how the manager itself processes messages on the address, through the OSC section.
+fn processing(config: &Config, address: &str) -> Vec<String> {
+    let osc = &config.osc;
+    let mut processing = vec![];
+
+    for rule in &osc.rules {
+        if rules::matches(&rule.address, address) {
+            processing.push(format!("rule on `{}`", rule.address));
+        }
+    }
+
+    for pack in &osc.bitpack {
+        if address == addresses::parameter_address(&pack.parameter) {
+            processing.push(format!("packed into {} bits", pack.bits.len()));
+        } else if pack
+            .bits
+            .iter()
+            .any(|bit| address == addresses::parameter_address(bit))
+        {
+            processing.push(format!("bit of `{}`", pack.parameter));
+        }
+    }
+
+    for route in &osc.routes {
+        if route
+            .address_prefix
+            .as_ref()
+            .map_or(false, |prefix| address.starts_with(prefix.as_str()))
+        {
+            processing.push(format!("routed to {}", route.destination));
+        }
+    }
+
+    if osc
+        .keepalive
+        .addresses
+        .iter()
+        .any(|pattern| rules::matches(pattern, address))
+    {
+        processing.push("kept alive".to_string());
+    }
+
+    processing
+}
+
+fn rows(config: &Config, avatar: &AvatarConfig) -> Report {
+    let usages: Vec<(&'static str, ParameterUsage)> = plugins::builtin(&Arc::new(config.clone()))
+        .into_iter()
+        .filter(|plugin| config.plugin_enabled(plugin.name()))
+        .flat_map(|plugin| {
+            let name = plugin.name();
+            plugin
+                .parameters(&config.plugin_config(name))
+                .into_iter()
+                .map(move |usage| (name, usage))
+        })
+        .collect();
+
+    let rows: Vec<Row> = avatar
+        .parameters
+        .iter()
+        .map(|parameter| {
+            let address = parameter_address(parameter);
+            let mut plugins: Vec<String> = usages
+                .iter()
+                .filter(|(_, usage)| rules::matches(&usage.address, &address))
+                .map(|(name, usage)| format!("{} {}", name, access_name(usage.access)))
+                .collect();
+            plugins.dedup();
+
+            Row {
+                name: parameter.name.clone(),
+                kind: parameter
+                    .kind()
+                    .map_or("unknown", |kind| kind.name())
+                    .to_string(),
+                direction: match (&parameter.input, &parameter.output) {
+                    (Some(_), Some(_)) => "in/out",
+                    (Some(_), None) => "in",
+                    (None, Some(_)) => "out",
+                    (None, None) => "",
+                },
+                processing: processing(config, &address),
+                address,
+                plugins,
+            }
+        })
+        .collect();
+
+    // Parameters a plugin expects but the avatar lacks usually point to a typo or a missing
+    // prefab.
+    let mut missing: Vec<(String, String)> = usages
+        .iter()
+        .filter(|(_, usage)| {
+            addresses::lookup(&usage.address).is_none()
+                && !rows
+                    .iter()
+                    .any(|row| rules::matches(&usage.address, &row.address))
+        })
+        .map(|(name, usage)| (usage.address.clone(), name.to_string()))
+        .collect();
+    missing.sort();
+    missing.dedup();
+
+    let mut standard: Vec<StandardUse> = usages
+        .iter()
+        .filter_map(|(name, usage)| {
+            addresses::lookup(&usage.address).map(|entry| StandardUse {
+                address: usage.address.clone(),
+                signature: entry.signature(),
+                plugin: format!("{} {}", name, access_name(usage.access)),
+            })
+        })
+        .collect();
+    standard.sort_by(|a, b| (&a.address, &a.plugin).cmp(&(&b.address, &b.plugin)));
+    standard.dedup_by(|a, b| a.address == b.address && a.plugin == b.plugin);
+
+    (rows, missing, standard)
+}
+
+fn markdown(
+    avatar: &AvatarConfig,
+    rows: &[Row],
+    missing: &[(String, String)],
+    standard: &[StandardUse],
+) -> String {
+    let mut output = String::new();
+    let _ = writeln!(output, "# {}\n\nAvatar ID: `{}`\n", avatar.name, avatar.id);
+    let _ = writeln!(
+        output,
+        "| Parameter | Type | Direction | Plugins | Processing |"
+    );
+    let _ = writeln!(
+        output,
+        "|-----------|------|-----------|---------|------------|"
+    );
+
+    for row in rows {
+        let _ = writeln!(
+            output,
+            "| `{}` | {} | {} | {} | {} |",
+            row.name,
+            row.kind,
+            row.direction,
+            row.plugins.join(", "),
+            row.processing.join(", ")
+        );
+    }
+
+    if !missing.is_empty() {
+        let _ = writeln!(output, "\n## Used by plugins, but missing on the avatar\n");
+
+        for (address, plugin) in missing {
+            let _ = writeln!(output, "- `{}` ({})", address, plugin);
+        }
+    }
+
+    if !standard.is_empty() {
+        let _ = writeln!(
+            output,
+            "\n## Standard addresses of VRChat used by plugins\n"
+        );
+
+        for usage in standard {
+            let _ = writeln!(
+                output,
+                "- `{}` ({}), {}",
+                usage.address, usage.signature, usage.plugin
+            );
+        }
+    }
+
+    output
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn html(
+    avatar: &AvatarConfig,
+    rows: &[Row],
+    missing: &[(String, String)],
+    standard: &[StandardUse],
+) -> String {
+    let mut output = String::new();
+    let _ = write!(
+        output,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{0}</title>\n\
+        <style>body {{ font-family: sans-serif; }} table {{ border-collapse: collapse; }} \
+        td, th {{ border: 1px solid #ccc; padding: 4px 8px; text-align: left; }}</style>\n\
+        </head>\n<body>\n<h1>{0}</h1>\n<p>Avatar ID: <code>{1}</code></p>\n<table>\n\
+        <tr><th>Parameter</th><th>Type</th><th>Direction</th><th>Plugins</th><th>Processing</th></tr>\n",
+        escape(&avatar.name),
+        escape(&avatar.id)
+    );
+
+    for row in rows {
+        let _ = writeln!(
+            output,
+            "<tr><td><code>{}</code></td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            escape(&row.name),
+            row.kind,
+            row.direction,
+            escape(&row.plugins.join(", ")),
+            escape(&row.processing.join(", ")).replace('`', "")
+        );
+    }
+
+    output.push_str("</table>\n");
+
+    if !missing.is_empty() {
+        output.push_str("<h2>Used by plugins, but missing on the avatar</h2>\n<ul>\n");
+
+        for (address, plugin) in missing {
+            let _ = writeln!(
+                output,
+                "<li><code>{}</code> ({})</li>",
+                escape(address),
+                plugin
+            );
+        }
+
+        output.push_str("</ul>\n");
+    }
+
+    if !standard.is_empty() {
+        output.push_str("<h2>Standard addresses of VRChat used by plugins</h2>\n<ul>\n");
+
+        for usage in standard {
+            let _ = writeln!(
+                output,
+                "<li><code>{}</code> ({}), {}</li>",
+                escape(&usage.address),
+                usage.signature,
+                usage.plugin
+            );
+        }
+
+        output.push_str("</ul>\n");
+    }
+
+    output.push_str("</body>\n</html>\n");
+    output
+}
+
+/// Prints a report of the parameters of an avatar and how the enabled plugins use them.
+pub async fn run(avatar: Option<&str>, format: DocsFormat) -> Result<()> {
+    let config = load_config().await?;
+    let avatar = avatar::load(avatar).await?;
+    let (rows, missing, standard) = rows(&config, &avatar);
+
+    print!(
+        "{}",
+        match format {
+            DocsFormat::Markdown => markdown(&avatar, &rows, &missing, &standard),
+            DocsFormat::Html => html(&avatar, &rows, &missing, &standard),
+        }
+    );
+
+    Ok(())
+}
@@ -0,0 +1,121 @@
+use crate::config::PingConfig;
+use crate::rules;
+use crate::state::SharedState;
+use anyhow::{bail, Result};
+use async_osc::{OscMessage, OscType};
+use log::{debug, warn};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::select;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc;
+use tokio::time::{interval, Instant, MissedTickBehavior};
+use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
+use vrc_osc_manager_core::{OscReceiver, OutgoingMessage};
+
+/// Measures the latency of the whole chain from the manager through VRChat and the avatar back,
+/// to tell apart a slow link from slow plugins, e.g. when streaming VR from another machine.
+pub struct Ping {
+    config: PingConfig,
+    rx: OscReceiver,
+    sender_tx: mpsc::Sender<OutgoingMessage>,
+    state: Arc<SharedState>,
+}
+
+impl Ping {
+    pub fn new(
+        config: &PingConfig,
+        rx: OscReceiver,
+        sender_tx: mpsc::Sender<OutgoingMessage>,
+        state: Arc<SharedState>,
+    ) -> Self {
+        Self {
+            config: config.clone(),
+            rx,
+            sender_tx,
+            state,
+        }
+    }
+
+    async fn send(&self, addr: &str, value: i32) -> Result<()> {
+        self.sender_tx
+            .send(OutgoingMessage::new(
+                "ping",
+                OscMessage {
+                    addr: addr.to_string(),
+                    args: vec![OscType::Int(value)],
+                },
+            ))
+            .await?;
+        Ok(())
+    }
+
+    async fn measure(&mut self) -> Result<()> {
+        let mut interval = interval(Duration::from_secs(self.config.interval_seconds.max(1)));
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        // Avatars sync ints as a single byte, where zero is left out as it is the default value.
+        let mut counter = 0;
+        let mut pending: Option<Instant> = None;
+
+        loop {
+            select! {
+                _ = interval.tick() => {
+                    if pending.is_some() {
+                        debug!("Ping {} got lost", counter);
+                        self.state.stats.record_round_trip(None);
+                    }
+
+                    counter = counter % 255 + 1;
+                    pending = Some(Instant::now());
+                    self.send(&self.config.send_parameter, counter).await?;
+                }
+                message = self.rx.recv() => {
+                    let message = match message {
+                        Ok(message) => message,
+                        Err(RecvError::Lagged(skipped)) => {
+                            warn!("Ping lagging behind, {} messages have been dropped", skipped);
+                            continue;
+                        }
+                        Err(RecvError::Closed) => bail!("Message receiver died unexpectedly"),
+                    };
+
+                    if message.addr != self.config.receive_parameter
+                        || rules::value(&message) != Some(counter as f64)
+                    {
+                        continue;
+                    }
+
+                    let Some(sent_at) = pending.take() else {
+                        continue;
+                    };
+
+                    let round_trip = sent_at.elapsed();
+                    debug!("Ping {} took {} ms", counter, round_trip.as_millis());
+                    self.state.stats.record_round_trip(Some(round_trip));
+
+                    if let Some(latency_parameter) = &self.config.latency_parameter {
+                        self.send(latency_parameter, round_trip.as_millis().min(255) as i32)
+                            .await?;
+                    }
+                }
+            }
+        }
+    }
+
+    pub async fn run(mut self, subsys: SubsystemHandle) -> Result<()> {
+        if !self.config.enabled {
+            // Unread messages would pile up in the receiver.
+            drop(self);
+            subsys.on_shutdown_requested().await;
+            return Ok(());
+        }
+
+        match (self.measure().cancel_on_shutdown(&subsys)).await {
+            Ok(Ok(())) => subsys.request_shutdown(),
+            Ok(Err(error)) => return Err(error),
+            Err(CancelledByShutdown) => {}
+        }
+
+        Ok(())
+    }
+}
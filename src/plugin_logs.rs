@@ -0,0 +1,235 @@
+use crate::state::SharedState;
+use anyhow::Result;
+use log::{warn, LevelFilter, Log, Metadata, Record};
+use simplelog::SharedLogger;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt::{self, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::fs::{create_dir_all, remove_dir_all, remove_file, write};
+use tokio::time::interval;
+use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
+
+/// Number of log records kept per plugin.
+const CAPACITY: usize = 200;
+
+/// Module the built-in plugins live in, each in a module named after the plugin.
+const PLUGIN_TARGET: &str = "vrc_osc_manager::plugins::";
+
+/// Modules next to the plugins which are not plugins themselves, logging through [`target`].
+const SHARED_MODULES: &[&str] = &["actions"];
+
+#[derive(Default)]
+struct Records {
+    plugins: BTreeMap<String, VecDeque<String>>,
+    /// Plugins which logged since their records were last written to disk.
+    changed: BTreeSet<String>,
+}
+
+/// Log target attributing the records of helpers shared between plugins, like the actions, to the
+/// plugin using them instead of the module of the helper.
+pub fn target(plugin: &str) -> String {
+    format!("{}{}", PLUGIN_TARGET, plugin)
+}
+
+/// Logger keeping the most recent records of every built-in plugin. It logs at the same level as
+/// the log files, as a lower level would have every debug record of the application formatted.
+#[derive(Clone)]
+pub struct PluginLogs {
+    records: Arc<Mutex<Records>>,
+    level: LevelFilter,
+}
+
+impl Default for PluginLogs {
+    fn default() -> Self {
+        Self::new(LevelFilter::Info)
+    }
+}
+
+impl fmt::Debug for PluginLogs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let records = self.records.lock().unwrap();
+        f.debug_list().entries(records.plugins.keys()).finish()
+    }
+}
+
+impl PluginLogs {
+    pub fn new(level: LevelFilter) -> Self {
+        Self {
+            records: Arc::default(),
+            level,
+        }
+    }
+
+    fn plugin(target: &str) -> Option<&str> {
+        target
+            .strip_prefix(PLUGIN_TARGET)
+            .and_then(|module| module.split("::").next())
+            .filter(|module| !SHARED_MODULES.contains(module))
+    }
+
+    /// Records of all plugins which logged anything, one section per plugin.
+    pub fn text(&self) -> String {
+        let records = self.records.lock().unwrap();
+        let mut text = String::new();
+
+        for (name, lines) in &records.plugins {
+            let _ = writeln!(text, "== {} ==", name);
+
+            for line in lines {
+                let _ = writeln!(text, "{}", line);
+            }
+
+            text.push('\n');
+        }
+
+        text
+    }
+
+    /// Takes the records of the plugins which logged since the last call.
+    fn take_changed(&self) -> Vec<(String, String)> {
+        let mut records = self.records.lock().unwrap();
+        let changed = std::mem::take(&mut records.changed);
+
+        changed
+            .into_iter()
+            .map(|name| {
+                let mut text = String::new();
+
+                for line in &records.plugins[&name] {
+                    let _ = writeln!(text, "{}", line);
+                }
+
+                (name, text)
+            })
+            .collect()
+    }
+}
+
+impl Log for PluginLogs {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level && Self::plugin(metadata.target()).is_some()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let Some(name) = Self::plugin(record.target()) else {
+            return;
+        };
+
+        let mut records = self.records.lock().unwrap();
+        let lines = records.plugins.entry(name.to_string()).or_default();
+
+        if lines.len() == CAPACITY {
+            lines.pop_front();
+        }
+
+        lines.push_back(format!(
+            "{} [{}] {}",
+            chrono::Local::now().format("%H:%M:%S"),
+            record.level(),
+            record.args()
+        ));
+
+        if !records.changed.contains(name) {
+            records.changed.insert(name.to_string());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+impl SharedLogger for PluginLogs {
+    fn level(&self) -> LevelFilter {
+        self.level
+    }
+
+    fn config(&self) -> Option<&simplelog::Config> {
+        None
+    }
+
+    fn as_log(self: Box<Self>) -> Box<dyn Log> {
+        Box::new(*self)
+    }
+}
+
+/// File in the log directory holding what each plugin is doing, one `name = "state"` per line.
+pub const STATES_FILE: &str = "states.toml";
+
+/// Mirrors the records of every plugin into a file of its own, where the plugin manager shows
+/// them, as it runs in a separate process. The states of the plugins are mirrored as well.
+pub struct PluginLogWriter {
+    logs: PluginLogs,
+    dir: PathBuf,
+    state: Arc<SharedState>,
+    plugins: Vec<&'static str>,
+}
+
+impl PluginLogWriter {
+    pub fn new(
+        logs: PluginLogs,
+        dir: PathBuf,
+        state: Arc<SharedState>,
+        plugins: Vec<&'static str>,
+    ) -> Self {
+        Self {
+            logs,
+            dir,
+            state,
+            plugins,
+        }
+    }
+
+    fn states(&self) -> String {
+        self.plugins
+            .iter()
+            .map(|name| format!("{} = {:?}\n", name, self.state.plugin_state(name).label()))
+            .collect()
+    }
+
+    async fn write(&self) -> Result<()> {
+        // Records of the previous run would otherwise show up as if they were current.
+        let _ = remove_dir_all(&self.dir).await;
+        create_dir_all(&self.dir).await?;
+        let mut interval = interval(Duration::from_secs(1));
+        let mut written_states = String::new();
+
+        loop {
+            interval.tick().await;
+            let states = self.states();
+
+            if states != written_states {
+                let path = self.dir.join(STATES_FILE);
+
+                match write(&path, &states).await {
+                    Ok(()) => written_states = states,
+                    Err(error) => warn!("Failed to write {}: {}", path.display(), error),
+                }
+            }
+
+            for (name, text) in self.logs.take_changed() {
+                let path = self.dir.join(format!("{}.log", name));
+
+                if let Err(error) = write(&path, text).await {
+                    warn!("Failed to write {}: {}", path.display(), error);
+                }
+            }
+        }
+    }
+
+    pub async fn run(self, subsys: SubsystemHandle) -> Result<()> {
+        match (self.write().cancel_on_shutdown(&subsys)).await {
+            Ok(Ok(())) => subsys.request_shutdown(),
+            Ok(Err(error)) => return Err(error),
+            Err(CancelledByShutdown) => {}
+        }
+
+        // Nothing runs anymore once the manager stopped.
+        let _ = remove_file(self.dir.join(STATES_FILE)).await;
+        Ok(())
+    }
+}
@@ -0,0 +1,225 @@
+//! Building blocks shared by the plugins automating OSC through the config file.
+
+use crate::plugin_logs;
+use crate::rules;
+use async_osc::{OscMessage, OscType};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::select;
+use tokio::time::sleep;
+use tokio_util::sync::CancellationToken;
+use vrc_osc_manager_core::addresses::parameter_address;
+use vrc_osc_manager_core::{OscSender, ParameterUsage};
+
+/// A value written as plain TOML bool, integer, float or string.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Value {
+    Bool(bool),
+    Int(i32),
+    Float(f32),
+    String(String),
+}
+
+impl Value {
+    pub fn from_osc(arg: &OscType) -> Option<Self> {
+        match arg {
+            OscType::Bool(value) => Some(Value::Bool(*value)),
+            OscType::Int(value) => Some(Value::Int(*value)),
+            OscType::Float(value) => Some(Value::Float(*value)),
+            OscType::String(value) => Some(Value::String(value.clone())),
+            _ => None,
+        }
+    }
+
+    pub fn to_osc(&self) -> OscType {
+        match self {
+            Value::Bool(value) => OscType::Bool(*value),
+            Value::Int(value) => OscType::Int(*value),
+            Value::Float(value) => OscType::Float(*value),
+            Value::String(value) => OscType::String(value.clone()),
+        }
+    }
+
+    /// The neutral value of the same type, used when resetting a parameter.
+    pub fn zero(&self) -> Self {
+        match self {
+            Value::Bool(_) => Value::Bool(false),
+            Value::Int(_) => Value::Int(0),
+            Value::Float(_) => Value::Float(0.),
+            Value::String(_) => Value::String(String::new()),
+        }
+    }
+
+    fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Bool(value) => Some(if *value { 1. } else { 0. }),
+            Value::Int(value) => Some(*value as f64),
+            Value::Float(value) => Some(*value as f64),
+            Value::String(_) => None,
+        }
+    }
+}
+
+/// Matches incoming messages on an address, which may contain `*` wildcards, whose value equals the
+/// given one or lies within `min` and `max`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Condition {
+    pub address: String,
+    pub equals: Option<Value>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+impl Condition {
+    pub fn usage(&self) -> ParameterUsage {
+        ParameterUsage::read(&self.address)
+    }
+
+    pub fn matches_address(&self, addr: &str) -> bool {
+        rules::matches(&parameter_address(&self.address), addr)
+    }
+
+    pub fn is_met(&self, message: &OscMessage) -> bool {
+        if !self.matches_address(&message.addr) {
+            return false;
+        }
+
+        let value = message.args.first().and_then(Value::from_osc);
+
+        if let Some(equals) = &self.equals {
+            let equal = match (equals.as_f64(), value.as_ref().and_then(Value::as_f64)) {
+                (Some(expected), Some(actual)) => (expected - actual).abs() < f64::EPSILON,
+                _ => value.as_ref() == Some(equals),
+            };
+
+            if !equal {
+                return false;
+            }
+        }
+
+        let number = rules::value(message);
+
+        self.min
+            .map_or(true, |min| number.map_or(false, |value| value >= min))
+            && self
+                .max
+                .map_or(true, |max| number.map_or(false, |value| value <= max))
+    }
+}
+
+/// Sends a value after an optional delay, and resets it again after `reset_after_ms` when given.
+/// With `restore_after_ms`, the manager restores the previous value instead.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Action {
+    pub address: String,
+    pub value: Value,
+    #[serde(default)]
+    pub delay_ms: u64,
+    pub reset_after_ms: Option<u64>,
+    pub reset_value: Option<Value>,
+    pub restore_after_ms: Option<u64>,
+}
+
+impl Action {
+    pub fn usage(&self) -> ParameterUsage {
+        ParameterUsage::write(&self.address)
+    }
+
+    pub async fn send(&self, tx: &OscSender, value: &Value) {
+        let _ = tx
+            .send(OscMessage {
+                addr: parameter_address(&self.address),
+                args: vec![value.to_osc()],
+            })
+            .await;
+    }
+
+    /// Sends the value of the action, as override when it restores the previous value.
+    async fn set(&self, tx: &OscSender) {
+        debug!(
+            target: &plugin_logs::target(tx.source()),
+            "Setting {} to {:?}",
+            self.address,
+            self.value
+        );
+
+        let Some(restore_after_ms) = self.restore_after_ms else {
+            return self.send(tx, &self.value).await;
+        };
+
+        let _ = tx
+            .send_for(
+                OscMessage {
+                    addr: parameter_address(&self.address),
+                    args: vec![self.value.to_osc()],
+                },
+                Duration::from_millis(restore_after_ms),
+            )
+            .await;
+    }
+
+    async fn reset(&self, tx: &OscSender) {
+        if let Some(reset_after_ms) = self.reset_after_ms {
+            sleep(Duration::from_millis(reset_after_ms)).await;
+            let reset_value = self
+                .reset_value
+                .clone()
+                .unwrap_or_else(|| self.value.zero());
+            debug!(
+                target: &plugin_logs::target(tx.source()),
+                "Resetting {} to {:?}",
+                self.address,
+                reset_value
+            );
+            self.send(tx, &reset_value).await;
+        }
+    }
+
+    async fn perform(&self, tx: &OscSender) {
+        sleep(Duration::from_millis(self.delay_ms)).await;
+        self.set(tx).await;
+        self.reset(tx).await;
+    }
+}
+
+/// Spawns a task performing the action, which is aborted once the token is cancelled.
+fn spawn<F, Fut>(tx: &OscSender, action: &Action, token: &CancellationToken, perform: F)
+where
+    F: FnOnce(Action, OscSender) -> Fut + Send + 'static,
+    Fut: std::future::Future<Output = ()> + Send + 'static,
+{
+    let action = action.clone();
+    let tx = tx.clone();
+    let token = token.clone();
+
+    tokio::spawn(async move {
+        select! {
+            _ = perform(action, tx) => {}
+            _ = token.cancelled() => {}
+        }
+    });
+}
+
+/// Performs all actions concurrently in the background, until they are done or the token is
+/// cancelled.
+pub fn perform_all(tx: &OscSender, actions: &[Action], token: &CancellationToken) {
+    for action in actions {
+        spawn(tx, action, token, |action, tx| async move {
+            action.perform(&tx).await
+        });
+    }
+}
+
+/// Performs the actions one after another, with each delay counting from the previous action.
+/// Resets do not hold up the sequence and happen in the background instead.
+pub async fn perform_sequence(tx: &OscSender, actions: &[Action], token: &CancellationToken) {
+    for action in actions {
+        sleep(Duration::from_millis(action.delay_ms)).await;
+        action.set(tx).await;
+        spawn(tx, action, token, |action, tx| async move {
+            action.reset(&tx).await
+        });
+    }
+}
@@ -0,0 +1,161 @@
+use crate::plugins::actions::Condition;
+use anyhow::{bail, Result};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::select;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::{sleep_until, Instant};
+use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
+use vrc_osc_manager_core::check_config;
+use vrc_osc_manager_core::{
+    Notifier, OscReceiver, ParameterUsage, Plugin, PluginConfig, PluginContext, PluginFuture,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Alert {
+    name: String,
+    when: Condition,
+    #[serde(default)]
+    for_seconds: u64,
+    message: Option<String>,
+    repeat_seconds: Option<u64>,
+}
+
+impl Alert {
+    fn body(&self, addr: &str) -> String {
+        match &self.message {
+            Some(message) => message.clone(),
+            None if self.for_seconds > 0 => {
+                format!("{} has been met for {} seconds", addr, self.for_seconds)
+            }
+            None => format!("{} has been met", addr),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+struct AlertsConfig {
+    alerts: Vec<Alert>,
+}
+
+/// When the condition of an alert started being met on an address, and when it was last notified.
+struct Pending {
+    since: Instant,
+    notified: Option<Instant>,
+}
+
+impl Pending {
+    fn due(&self, alert: &Alert) -> Option<Instant> {
+        match self.notified {
+            None => Some(self.since + Duration::from_secs(alert.for_seconds)),
+            Some(notified) => alert
+                .repeat_seconds
+                .map(|repeat| notified + Duration::from_secs(repeat.max(1))),
+        }
+    }
+}
+
+/// Notifies the user when incoming messages keep meeting a condition for a while, as a lightweight
+/// monitor of the avatar state.
+#[derive(Default)]
+pub struct Alerts;
+
+impl Alerts {
+    pub fn new() -> Self {
+        Self
+    }
+
+    async fn handle(config: AlertsConfig, notifier: Notifier, mut rx: OscReceiver) -> Result<()> {
+        let mut pending: Vec<HashMap<String, Pending>> =
+            (0..config.alerts.len()).map(|_| HashMap::new()).collect();
+
+        loop {
+            let deadline = config
+                .alerts
+                .iter()
+                .zip(&pending)
+                .flat_map(|(alert, pending)| pending.values().filter_map(|entry| entry.due(alert)))
+                .min();
+
+            select! {
+                message = rx.recv() => {
+                    let message = match message {
+                        Ok(message) => message,
+                        Err(RecvError::Lagged(skipped)) => {
+                            warn!("Alerts lagging behind, {} messages have been dropped", skipped);
+                            continue;
+                        }
+                        Err(RecvError::Closed) => bail!("Message receiver died unexpectedly"),
+                    };
+
+                    for (alert, pending) in config.alerts.iter().zip(pending.iter_mut()) {
+                        if !alert.when.matches_address(&message.addr) {
+                            continue;
+                        }
+
+                        if !alert.when.is_met(&message) {
+                            pending.remove(&message.addr);
+                        } else if !pending.contains_key(&message.addr) {
+                            pending.insert(message.addr.clone(), Pending {
+                                since: Instant::now(),
+                                notified: None,
+                            });
+                        }
+                    }
+                }
+                _ = sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                    let now = Instant::now();
+
+                    for (alert, pending) in config.alerts.iter().zip(pending.iter_mut()) {
+                        for (addr, entry) in pending.iter_mut() {
+                            if entry.due(alert).map_or(true, |due| due > now) {
+                                continue;
+                            }
+
+                            info!("Alert {} raised by {}", alert.name, addr);
+                            entry.notified = Some(now);
+                            notifier.notify(&alert.name, alert.body(addr)).await?;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Plugin for Alerts {
+    fn name(&self) -> &'static str {
+        "alerts"
+    }
+
+    check_config!(AlertsConfig);
+
+    fn parameters(&self, config: &PluginConfig) -> Vec<ParameterUsage> {
+        let config: AlertsConfig = config.parse().unwrap_or_default();
+        config
+            .alerts
+            .iter()
+            .map(|alert| alert.when.usage())
+            .collect()
+    }
+
+    fn run(self: Box<Self>, context: PluginContext, subsys: SubsystemHandle) -> PluginFuture {
+        Box::pin(async move {
+            let config: AlertsConfig = context.config.parse()?;
+
+            match (Self::handle(config, context.notifier, context.receiver)
+                .cancel_on_shutdown(&subsys))
+            .await
+            {
+                Ok(Ok(())) => subsys.request_shutdown(),
+                Ok(Err(error)) => return Err(error),
+                Err(CancelledByShutdown) => {}
+            }
+
+            Ok(())
+        })
+    }
+}
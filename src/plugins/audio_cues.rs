@@ -0,0 +1,207 @@
+use anyhow::{bail, Context, Result};
+use log::{debug, info, warn};
+use rodio::cpal::traits::{DeviceTrait, HostTrait};
+use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::oneshot;
+use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
+use vrc_osc_manager_core::addresses::parameter_address;
+use vrc_osc_manager_core::check_config;
+use vrc_osc_manager_core::{
+    OscReceiver, ParameterUsage, Plugin, PluginConfig, PluginContext, PluginFuture, Trigger,
+    TriggerEvent,
+};
+
+fn default_volume() -> f32 {
+    1.
+}
+
+fn default_threshold() -> f64 {
+    0.5
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Cue {
+    parameter: String,
+    /// Sound file to play, relative to the data directory unless absolute.
+    file: PathBuf,
+    #[serde(default = "default_volume")]
+    volume: f32,
+    #[serde(default)]
+    cooldown_ms: u64,
+    #[serde(default = "default_threshold")]
+    threshold: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+struct AudioCuesConfig {
+    /// Name of the output device, using the default one when not set.
+    device: Option<String>,
+    cues: Vec<Cue>,
+}
+
+fn output_stream(device: Option<&str>) -> Result<(OutputStream, OutputStreamHandle)> {
+    let Some(name) = device else {
+        return Ok(OutputStream::try_default()?);
+    };
+
+    let host = rodio::cpal::default_host();
+    let Some(device) = host.output_devices()?.find(|device| {
+        device
+            .name()
+            .map_or(false, |device_name| device_name == name)
+    }) else {
+        let names: Vec<String> = host
+            .output_devices()?
+            .filter_map(|device| device.name().ok())
+            .collect();
+        bail!(
+            "Output device {} not found, available are: {}",
+            name,
+            names.join(", ")
+        );
+    };
+
+    Ok(OutputStream::try_from_device(&device)?)
+}
+
+fn play(handle: &OutputStreamHandle, path: &Path, volume: f32) -> Result<()> {
+    let file = File::open(path)?;
+    let sink = Sink::try_new(handle)?;
+    sink.set_volume(volume);
+    sink.append(Decoder::new(BufReader::new(file))?);
+    sink.detach();
+    Ok(())
+}
+
+/// Plays sounds on a thread of its own, as the output stream cannot be moved between threads. The
+/// thread ends once the returned sender is dropped.
+async fn start_player(device: Option<String>) -> Result<mpsc::Sender<(PathBuf, f32)>> {
+    let (tx, rx) = mpsc::channel::<(PathBuf, f32)>();
+    let (ready_tx, ready_rx) = oneshot::channel();
+
+    std::thread::spawn(move || {
+        let (_stream, handle) = match output_stream(device.as_deref()) {
+            Ok(stream) => {
+                let _ = ready_tx.send(Ok(()));
+                stream
+            }
+            Err(error) => {
+                let _ = ready_tx.send(Err(error));
+                return;
+            }
+        };
+
+        for (path, volume) in rx {
+            if let Err(error) = play(&handle, &path, volume) {
+                warn!("Failed to play {}: {:#}", path.display(), error);
+            }
+        }
+    });
+
+    ready_rx
+        .await
+        .context("Audio output stopped unexpectedly")??;
+    Ok(tx)
+}
+
+/// Plays local sound files when parameters trigger, e.g. a chime when someone boops the avatar
+/// while you are looking at another monitor.
+#[derive(Default)]
+pub struct AudioCues;
+
+impl AudioCues {
+    pub fn new() -> Self {
+        Self
+    }
+
+    async fn handle(config: AudioCuesConfig, data_dir: PathBuf, mut rx: OscReceiver) -> Result<()> {
+        let mut cues = vec![];
+
+        for cue in config.cues {
+            let path = data_dir.join(&cue.file);
+
+            if !path.is_file() {
+                bail!("Sound file {} does not exist", path.display());
+            }
+
+            let trigger =
+                Trigger::new(cue.threshold).cooldown(Duration::from_millis(cue.cooldown_ms));
+            cues.push((parameter_address(&cue.parameter), path, cue.volume, trigger));
+        }
+
+        let player = start_player(config.device).await?;
+        info!("Playing {} audio cues", cues.len());
+
+        loop {
+            let message = match rx.recv().await {
+                Ok(message) => message,
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!(
+                        "Audio cues lagging behind, {} messages have been dropped",
+                        skipped
+                    );
+                    continue;
+                }
+                Err(RecvError::Closed) => bail!("Message receiver died unexpectedly"),
+            };
+
+            let Some(arg) = message.args.first() else {
+                continue;
+            };
+
+            for (addr, path, volume, trigger) in &mut cues {
+                if *addr != message.addr || trigger.update(arg) != Some(TriggerEvent::Pressed) {
+                    continue;
+                }
+
+                debug!("Playing {} for {}", path.display(), addr);
+                player
+                    .send((path.clone(), *volume))
+                    .ok()
+                    .context("Audio output stopped unexpectedly")?;
+            }
+        }
+    }
+}
+
+impl Plugin for AudioCues {
+    fn name(&self) -> &'static str {
+        "audio_cues"
+    }
+
+    check_config!(AudioCuesConfig);
+
+    fn parameters(&self, config: &PluginConfig) -> Vec<ParameterUsage> {
+        let config: AudioCuesConfig = config.parse().unwrap_or_default();
+        config
+            .cues
+            .iter()
+            .map(|cue| ParameterUsage::read(&cue.parameter))
+            .collect()
+    }
+
+    fn run(self: Box<Self>, context: PluginContext, subsys: SubsystemHandle) -> PluginFuture {
+        Box::pin(async move {
+            let config: AudioCuesConfig = context.config.parse()?;
+
+            match (Self::handle(config, context.data_dir, context.receiver)
+                .cancel_on_shutdown(&subsys))
+            .await
+            {
+                Ok(Ok(())) => subsys.request_shutdown(),
+                Ok(Err(error)) => return Err(error),
+                Err(CancelledByShutdown) => {}
+            }
+
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,284 @@
+use anyhow::{bail, Result};
+use async_osc::OscType;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+use tokio::select;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::{sleep, sleep_until};
+use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
+use vrc_osc_manager_core::chatbox::MAX_LENGTH;
+use vrc_osc_manager_core::check_config;
+use vrc_osc_manager_core::{Chatbox, Commands, OscReceiver, Plugin, PluginContext, PluginFuture};
+
+/// Address speech to text tools can send their transcriptions to as string argument.
+pub const PROMPT: &str = "/vrc-osc-manager/chatbot/prompt";
+
+/// Command the control API hands its prompts over with.
+pub const PROMPT_COMMAND: &str = "prompt";
+
+/// How often to check whether the previous chunk left the chatbox queue yet.
+const PENDING_POLL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct ChatbotConfig {
+    endpoint: String,
+    api_key: String,
+    model: String,
+    system_prompt: String,
+    max_tokens: u32,
+    max_response_chars: usize,
+    min_interval_seconds: u64,
+    chunk_interval_ms: u64,
+    timeout_seconds: u64,
+}
+
+impl Default for ChatbotConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: "".to_string(),
+            api_key: "".to_string(),
+            model: "gpt-4o-mini".to_string(),
+            system_prompt:
+                "You are a friendly companion in VRChat. Answer in one or two short sentences."
+                    .to_string(),
+            max_tokens: 150,
+            max_response_chars: 432,
+            min_interval_seconds: 10,
+            chunk_interval_ms: 4000,
+            timeout_seconds: 30,
+        }
+    }
+}
+
+/// Splits streamed text into chunks fitting the chatbox, breaking at whitespace where possible.
+struct Chunker {
+    buffer: String,
+}
+
+impl Chunker {
+    fn new() -> Self {
+        Self {
+            buffer: String::new(),
+        }
+    }
+
+    fn push(&mut self, text: &str) -> Vec<String> {
+        self.buffer.push_str(text);
+        let mut chunks = vec![];
+
+        while let Some((limit, _)) = self.buffer.char_indices().nth(MAX_LENGTH) {
+            let split = match self.buffer[..limit].rfind(char::is_whitespace) {
+                Some(split) if split > 0 => split,
+                _ => limit,
+            };
+
+            chunks.push(self.buffer[..split].trim().to_string());
+            self.buffer = self.buffer[split..].trim_start().to_string();
+        }
+
+        chunks
+    }
+
+    fn finish(self) -> Option<String> {
+        let rest = self.buffer.trim();
+        (!rest.is_empty()).then(|| rest.to_string())
+    }
+}
+
+/// Posts chunks to the chatbox, leaving enough time in between for each one to be read.
+struct Poster {
+    chatbox: Chatbox,
+    interval: Duration,
+    next: Instant,
+    remaining: usize,
+}
+
+impl Poster {
+    async fn post(&mut self, chunk: &str) -> bool {
+        if self.remaining == 0 {
+            return false;
+        }
+
+        let chunk: String = chunk.chars().take(self.remaining).collect();
+        self.remaining -= chunk.chars().count();
+
+        sleep_until(self.next.into()).await;
+
+        // The chatbox queue only holds one message per plugin, so a chunk still waiting there
+        // would be replaced by the next one when other plugins keep the queue busy.
+        while self.chatbox.is_pending() {
+            sleep(PENDING_POLL).await;
+        }
+
+        self.chatbox.send(&chunk, false);
+        self.next = Instant::now() + self.interval;
+        true
+    }
+}
+
+/// Answers prompts through an OpenAI compatible chat completions endpoint in the chatbox. The
+/// response is streamed, so the first chunk shows up before the model is done.
+#[derive(Default)]
+pub struct Chatbot;
+
+impl Chatbot {
+    pub fn new() -> Self {
+        Self
+    }
+
+    async fn answer(
+        config: &ChatbotConfig,
+        client: &reqwest::Client,
+        poster: &mut Poster,
+        text: &str,
+    ) -> Result<()> {
+        let mut request = client.post(&config.endpoint).json(&json!({
+            "model": config.model,
+            "max_tokens": config.max_tokens,
+            "stream": true,
+            "messages": [
+                {"role": "system", "content": config.system_prompt},
+                {"role": "user", "content": text},
+            ],
+        }));
+
+        if !config.api_key.is_empty() {
+            request = request.bearer_auth(&config.api_key);
+        }
+
+        let mut response = request.send().await?.error_for_status()?;
+        let mut chunker = Chunker::new();
+        let mut pending = String::new();
+
+        // The stream consists of server-sent events, each line carrying a delta of the answer.
+        'stream: while let Some(bytes) = response.chunk().await? {
+            pending.push_str(&String::from_utf8_lossy(&bytes));
+
+            while let Some(end) = pending.find('\n') {
+                let line = pending[..end].trim().to_string();
+                pending.drain(..=end);
+
+                let Some(data) = line.strip_prefix("data:").map(str::trim) else {
+                    continue;
+                };
+
+                if data == "[DONE]" {
+                    break 'stream;
+                }
+
+                let event: Value = serde_json::from_str(data)?;
+
+                if let Some(delta) = event["choices"][0]["delta"]["content"].as_str() {
+                    for chunk in chunker.push(delta) {
+                        if !poster.post(&chunk).await {
+                            break 'stream;
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(chunk) = chunker.finish() {
+            poster.post(&chunk).await;
+        }
+
+        Ok(())
+    }
+
+    async fn handle(
+        config: ChatbotConfig,
+        chatbox: Chatbox,
+        mut rx: OscReceiver,
+        mut commands: Commands,
+    ) -> Result<()> {
+        // Covers the whole streamed answer, so a stalled endpoint cannot hold up later prompts.
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_seconds))
+            .build()?;
+        let min_interval = Duration::from_secs(config.min_interval_seconds);
+        let mut last_prompt: Option<Instant> = None;
+        let mut poster = Poster {
+            chatbox,
+            interval: Duration::from_millis(config.chunk_interval_ms),
+            next: Instant::now(),
+            remaining: 0,
+        };
+
+        loop {
+            let text = select! {
+                message = rx.recv() => match message {
+                    Ok(message) => match (message.addr.as_str(), message.args.first()) {
+                        (PROMPT, Some(OscType::String(text))) => text.clone(),
+                        _ => continue,
+                    },
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!(
+                            "Chatbot lagging behind, {} messages have been dropped",
+                            skipped
+                        );
+                        continue;
+                    }
+                    Err(RecvError::Closed) => bail!("Message receiver died unexpectedly"),
+                },
+                command = commands.recv() => match command.name {
+                    PROMPT_COMMAND => command.argument,
+                    _ => continue,
+                },
+            };
+
+            if last_prompt.map_or(false, |last| last.elapsed() < min_interval) {
+                debug!("Ignoring prompt within {:?} of the last one", min_interval);
+                continue;
+            }
+
+            last_prompt = Some(Instant::now());
+            info!("Answering prompt: {}", text);
+            poster.remaining = config.max_response_chars;
+
+            let _ = poster.chatbox.set_typing(true).await;
+
+            // A failed request should not take down the plugin, the next prompt may work again.
+            if let Err(error) = Self::answer(&config, &client, &mut poster, &text).await {
+                warn!("Failed to answer prompt: {:#}", error);
+            }
+
+            let _ = poster.chatbox.set_typing(false).await;
+        }
+    }
+}
+
+impl Plugin for Chatbot {
+    fn name(&self) -> &'static str {
+        "chatbot"
+    }
+
+    check_config!(ChatbotConfig);
+
+    fn run(self: Box<Self>, context: PluginContext, subsys: SubsystemHandle) -> PluginFuture {
+        Box::pin(async move {
+            let config: ChatbotConfig = context.config.parse()?;
+
+            if config.endpoint.is_empty() {
+                info!("No endpoint configured for the chatbot");
+                subsys.on_shutdown_requested().await;
+                return Ok(());
+            }
+
+            let chatbox = context.chatbox();
+
+            match (Self::handle(config, chatbox, context.receiver, context.commands)
+                .cancel_on_shutdown(&subsys))
+            .await
+            {
+                Ok(Ok(())) => subsys.request_shutdown(),
+                Ok(Err(error)) => return Err(error),
+                Err(CancelledByShutdown) => {}
+            }
+
+            Ok(())
+        })
+    }
+}
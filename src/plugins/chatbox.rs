@@ -0,0 +1,267 @@
+use anyhow::Result;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::select;
+use tokio::time::{interval, sleep, sleep_until, Instant};
+use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
+use vrc_osc_manager_core::chatbox::MAX_LENGTH;
+use vrc_osc_manager_core::check_config;
+use vrc_osc_manager_core::{
+    heartbeat, Chatbox, ChatboxQueue, ConfigField, ConfigFieldKind, Heartbeat, Instance,
+    InstanceWatcher, Plugin, PluginContext, PluginFuture,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct ChatboxPluginConfig {
+    /// Plugins whose lines come first, in this order, followed by the lines of all others.
+    order: Vec<String>,
+    /// Line showing the current world, where {world} is replaced with its name.
+    world: Option<String>,
+    separator: String,
+    /// Seconds after which an unchanged message is sent again, as VRChat hides it eventually.
+    refresh_seconds: u64,
+    /// Seconds a message sent by a plugin on its own, like an answer of the chatbot, stays up
+    /// before the lines are shown again.
+    hold_seconds: u64,
+    /// Whether the typing indicator shows up before a changed message.
+    typing_indicator: bool,
+    typing_ms: u64,
+}
+
+impl Default for ChatboxPluginConfig {
+    fn default() -> Self {
+        Self {
+            order: vec![],
+            world: None,
+            separator: "\n".to_string(),
+            refresh_seconds: 25,
+            hold_seconds: 10,
+            typing_indicator: false,
+            typing_ms: 1000,
+        }
+    }
+}
+
+/// Combines the lines in order. Lines which do not fit anymore are left out as a whole, rather
+/// than cutting off the message in the middle of one, while a first line too long on its own is
+/// truncated.
+fn compose(
+    config: &ChatboxPluginConfig,
+    mut lines: Vec<(&'static str, String)>,
+    instance: Option<&Instance>,
+) -> String {
+    lines.sort_by_key(|(source, _)| {
+        config
+            .order
+            .iter()
+            .position(|name| name == source)
+            .unwrap_or(usize::MAX)
+    });
+
+    let world = config.world.as_ref().and_then(|template| {
+        instance.map(|instance| template.replace("{world}", &instance.world_name))
+    });
+
+    let mut text = String::new();
+
+    for line in world
+        .into_iter()
+        .chain(lines.into_iter().map(|(_, text)| text))
+    {
+        if text.is_empty() {
+            text = line.chars().take(MAX_LENGTH).collect();
+            continue;
+        }
+
+        let combined = format!("{}{}{}", text, config.separator, line);
+
+        if combined.chars().count() <= MAX_LENGTH {
+            text = combined;
+        } else {
+            debug!("Leaving out {:?}, which does not fit anymore", line);
+        }
+    }
+
+    text
+}
+
+/// Combines the chatbox lines of all plugins, like the time, the song playing and the heart rate,
+/// into a single message, as VRChat only shows one at a time.
+#[derive(Default)]
+pub struct ChatboxPlugin;
+
+impl ChatboxPlugin {
+    pub fn new() -> Self {
+        Self
+    }
+
+    async fn show(
+        config: ChatboxPluginConfig,
+        chatbox: Chatbox,
+        queue: ChatboxQueue,
+        mut instance: InstanceWatcher,
+        heartbeat: Heartbeat,
+    ) -> Result<()> {
+        let refresh = Duration::from_secs(config.refresh_seconds.max(1));
+        let hold = Duration::from_secs(config.hold_seconds);
+        let mut shown = String::new();
+        let mut refresh_at = Instant::now() + refresh;
+        let mut heartbeat_interval = interval(heartbeat::INTERVAL);
+
+        loop {
+            // Messages plugins send on their own replace the lines in the chatbox, so these are
+            // sent again once the message had time to be read.
+            if let Some(held_until) = queue
+                .direct_sent()
+                .map(|sent| Instant::from_std(sent) + hold)
+                .filter(|held_until| *held_until > Instant::now())
+            {
+                shown.clear();
+
+                select! {
+                    _ = sleep_until(held_until) => {}
+                    _ = heartbeat_interval.tick() => heartbeat.beat(),
+                }
+
+                continue;
+            }
+
+            let text = compose(
+                &config,
+                queue.lines(),
+                instance.borrow_and_update().as_ref(),
+            );
+            let changed = text != shown;
+
+            if changed || (!text.is_empty() && Instant::now() >= refresh_at) {
+                if changed && config.typing_indicator && !text.is_empty() {
+                    chatbox.set_typing(true).await?;
+                    sleep(Duration::from_millis(config.typing_ms)).await;
+                    chatbox.set_typing(false).await?;
+                }
+
+                debug!("Showing {:?}", text);
+                chatbox.send_lines(&text);
+                shown = text;
+                refresh_at = Instant::now() + refresh;
+            }
+
+            select! {
+                _ = queue.wait_lines() => {}
+                Ok(()) = instance.changed() => {}
+                _ = sleep_until(refresh_at) => {}
+                _ = heartbeat_interval.tick() => heartbeat.beat(),
+            }
+        }
+    }
+}
+
+impl Plugin for ChatboxPlugin {
+    fn name(&self) -> &'static str {
+        "chatbox"
+    }
+
+    check_config!(ChatboxPluginConfig);
+
+    fn config_fields(&self) -> Vec<ConfigField> {
+        vec![
+            ConfigField::new("separator", "Separator", ConfigFieldKind::String),
+            ConfigField::new(
+                "typing_indicator",
+                "Typing indicator",
+                ConfigFieldKind::Bool,
+            )
+            .menu(),
+        ]
+    }
+
+    fn run(self: Box<Self>, context: PluginContext, subsys: SubsystemHandle) -> PluginFuture {
+        Box::pin(async move {
+            let config: ChatboxPluginConfig = context.config.parse()?;
+            let chatbox = context.chatbox();
+            let queue = context.chatbox_queue.clone();
+            queue.set_composed(true);
+
+            let result = (Self::show(
+                config,
+                chatbox,
+                context.chatbox_queue,
+                context.instance,
+                context.heartbeat,
+            )
+            .cancel_on_shutdown(&subsys))
+            .await;
+            queue.set_composed(false);
+
+            match result {
+                Ok(Ok(())) => subsys.request_shutdown(),
+                Ok(Err(error)) => return Err(error),
+                Err(CancelledByShutdown) => {}
+            }
+
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn composes_the_lines_in_order() {
+        let config = ChatboxPluginConfig {
+            order: vec!["media".to_string()],
+            ..Default::default()
+        };
+        let lines = vec![
+            ("time", "12:00".to_string()),
+            ("media", "Song".to_string()),
+            ("heart_rate", "80 bpm".to_string()),
+        ];
+
+        assert_eq!(compose(&config, lines, None), "Song\n12:00\n80 bpm");
+    }
+
+    #[test]
+    fn starts_with_the_world() {
+        let config = ChatboxPluginConfig {
+            world: Some("In {world}".to_string()),
+            separator: " | ".to_string(),
+            ..Default::default()
+        };
+        let instance = Instance::parse("wrld_1234:5678", "The Black Cat").unwrap();
+        let lines = vec![("time", "12:00".to_string())];
+
+        assert_eq!(
+            compose(&config, lines.clone(), Some(&instance)),
+            "In The Black Cat | 12:00"
+        );
+        assert_eq!(compose(&config, lines, None), "12:00");
+    }
+
+    #[test]
+    fn leaves_out_lines_which_do_not_fit() {
+        let config = ChatboxPluginConfig::default();
+        let lines = vec![
+            ("first", "a".repeat(100)),
+            ("second", "b".repeat(50)),
+            ("third", "c".repeat(10)),
+        ];
+
+        assert_eq!(
+            compose(&config, lines, None),
+            format!("{}\n{}", "a".repeat(100), "c".repeat(10))
+        );
+    }
+
+    #[test]
+    fn truncates_a_first_line_too_long() {
+        let config = ChatboxPluginConfig::default();
+        let lines = vec![("first", "a".repeat(200)), ("second", "b".to_string())];
+
+        assert_eq!(compose(&config, lines, None), "a".repeat(MAX_LENGTH));
+    }
+}
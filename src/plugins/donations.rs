@@ -0,0 +1,409 @@
+use crate::chatbox::mask;
+use crate::config::Config;
+use crate::plugins::actions::{perform_all, Action};
+use crate::secret::constant_time_eq;
+use anyhow::{bail, Context, Result};
+use hyper::body::HttpBody;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use tokio::select;
+use tokio::sync::mpsc;
+use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
+use tokio_util::sync::CancellationToken;
+use vrc_osc_manager_core::check_config;
+use vrc_osc_manager_core::{
+    Chatbox, ConfigField, ConfigFieldKind, Events, OscSender, ParameterUsage, Plugin, PluginConfig,
+    PluginContext, PluginFuture,
+};
+
+/// Largest webhook body accepted, far above what Ko-fi or Streamlabs send for a donation.
+const MAX_BODY_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Tier {
+    /// Smallest amount reaching this tier, regardless of the currency.
+    min_amount: f64,
+    #[serde(default)]
+    actions: Vec<Action>,
+    /// Chatbox message, where {name}, {amount}, {currency} and {message} are replaced.
+    chatbox: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct DonationsConfig {
+    listen_address: IpAddr,
+    port: u16,
+    /// Token shown in the webhook settings of Ko-fi, which requests have to carry.
+    kofi_verification_token: Option<String>,
+    /// Secret the forwarded Streamlabs events have to carry in the X-Secret header.
+    streamlabs_secret: Option<String>,
+    /// Reactions to donations, of which the highest tier reached is performed.
+    tiers: Vec<Tier>,
+}
+
+impl Default for DonationsConfig {
+    fn default() -> Self {
+        Self {
+            listen_address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: 8095,
+            kofi_verification_token: None,
+            streamlabs_secret: None,
+            tiers: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Donation {
+    platform: &'static str,
+    name: String,
+    amount: f64,
+    currency: String,
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct KofiForm {
+    data: String,
+}
+
+#[derive(Deserialize)]
+struct KofiData {
+    verification_token: String,
+    #[serde(rename = "type")]
+    kind: String,
+    from_name: String,
+    #[serde(default)]
+    message: Option<String>,
+    amount: String,
+    currency: String,
+}
+
+#[derive(Deserialize)]
+struct StreamlabsEvent {
+    #[serde(rename = "type")]
+    kind: String,
+    message: Vec<StreamlabsDonation>,
+}
+
+#[derive(Deserialize)]
+struct StreamlabsDonation {
+    name: String,
+    amount: serde_json::Value,
+    currency: String,
+    #[serde(default)]
+    message: Option<String>,
+}
+
+/// Parses a Ko-fi webhook, which posts its JSON as `data` field of a form.
+fn parse_kofi(config: &DonationsConfig, body: &[u8]) -> Result<Option<Donation>> {
+    let Some(token) = &config.kofi_verification_token else {
+        bail!("Ko-fi is not configured");
+    };
+
+    let form: KofiForm = serde_urlencoded::from_bytes(body)?;
+    let data: KofiData = serde_json::from_str(&form.data)?;
+
+    if !constant_time_eq(data.verification_token.as_bytes(), token.as_bytes()) {
+        bail!("Invalid verification token");
+    }
+
+    // Subscriptions and shop orders are paid as well, but are no donations to react to.
+    if data.kind != "Donation" {
+        debug!("Ignoring Ko-fi {}", data.kind);
+        return Ok(None);
+    }
+
+    Ok(Some(Donation {
+        platform: "Ko-fi",
+        name: data.from_name,
+        amount: data.amount.parse().context("Invalid amount")?,
+        currency: data.currency,
+        message: data.message.unwrap_or_default(),
+    }))
+}
+
+/// Parses a donation event of the Streamlabs socket API, as forwarded by a relay.
+fn parse_streamlabs(
+    config: &DonationsConfig,
+    secret: Option<&str>,
+    body: &[u8],
+) -> Result<Vec<Donation>> {
+    let Some(expected) = &config.streamlabs_secret else {
+        bail!("Streamlabs is not configured");
+    };
+
+    if !secret.map_or(false, |secret| {
+        constant_time_eq(secret.as_bytes(), expected.as_bytes())
+    }) {
+        bail!("Invalid secret");
+    }
+
+    let event: StreamlabsEvent = serde_json::from_slice(body)?;
+
+    if event.kind != "donation" {
+        debug!("Ignoring Streamlabs {}", event.kind);
+        return Ok(vec![]);
+    }
+
+    event
+        .message
+        .into_iter()
+        .map(|donation| {
+            // The amount arrives as number or as string, depending on the event.
+            let amount = match &donation.amount {
+                serde_json::Value::Number(amount) => amount.as_f64(),
+                serde_json::Value::String(amount) => amount.parse().ok(),
+                _ => None,
+            };
+
+            Ok(Donation {
+                platform: "Streamlabs",
+                name: donation.name,
+                amount: amount.context("Invalid amount")?,
+                currency: donation.currency,
+                message: donation.message.unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+/// Reads the body of a webhook, failing once it grows beyond what Ko-fi or Streamlabs ever send.
+async fn read_body(mut body: Body) -> Result<Vec<u8>, StatusCode> {
+    let mut bytes = vec![];
+
+    while let Some(chunk) = body.data().await {
+        bytes.extend_from_slice(&chunk.map_err(|_| StatusCode::BAD_REQUEST)?);
+
+        if bytes.len() > MAX_BODY_SIZE {
+            return Err(StatusCode::PAYLOAD_TOO_LARGE);
+        }
+    }
+
+    Ok(bytes)
+}
+
+fn respond(status: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = status;
+    response
+}
+
+async fn handle_request(
+    config: &DonationsConfig,
+    request: Request<Body>,
+    donation_tx: &mpsc::Sender<Donation>,
+) -> Response<Body> {
+    if request.method() != Method::POST {
+        return respond(StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    let path = request.uri().path().to_string();
+    let secret = request
+        .headers()
+        .get("x-secret")
+        .and_then(|secret| secret.to_str().ok())
+        .map(str::to_string);
+    let body = match read_body(request.into_body()).await {
+        Ok(body) => body,
+        Err(status) => return respond(status),
+    };
+
+    let donations = match path.as_str() {
+        "/kofi" => parse_kofi(config, &body).map(|donation| donation.into_iter().collect()),
+        "/streamlabs" => parse_streamlabs(config, secret.as_deref(), &body),
+        _ => return respond(StatusCode::NOT_FOUND),
+    };
+
+    match donations {
+        Ok(donations) => {
+            for donation in donations {
+                let _ = donation_tx.send(donation).await;
+            }
+
+            respond(StatusCode::OK)
+        }
+        Err(error) => {
+            warn!("Rejected webhook on {}: {:#}", path, error);
+            respond(StatusCode::BAD_REQUEST)
+        }
+    }
+}
+
+/// Reacts to donations received through Ko-fi and Streamlabs webhooks on the avatar and in the
+/// chatbox, depending on the amount.
+pub struct Donations {
+    /// Blocked words of the chatbox, in lowercase.
+    blocked_words: Vec<String>,
+}
+
+impl Donations {
+    pub fn new(config: Arc<Config>) -> Self {
+        Self {
+            blocked_words: config
+                .chatbox
+                .blocked_words
+                .iter()
+                .map(|word| word.to_lowercase())
+                .collect(),
+        }
+    }
+
+    fn react(
+        &self,
+        config: &DonationsConfig,
+        donation: &Donation,
+        tx: &OscSender,
+        chatbox: &Chatbox,
+        token: &CancellationToken,
+    ) {
+        let Some(tier) = config
+            .tiers
+            .iter()
+            .filter(|tier| donation.amount >= tier.min_amount)
+            .max_by(|a, b| a.min_amount.total_cmp(&b.min_amount))
+        else {
+            return;
+        };
+
+        perform_all(tx, &tier.actions, token);
+
+        // Donors choose their name and message freely, so these are masked on their own before
+        // ending up next to the text of the template.
+        if let Some(template) = &tier.chatbox {
+            chatbox.send(
+                &template
+                    .replace("{name}", &mask(&donation.name, &self.blocked_words))
+                    .replace("{amount}", &format!("{:.2}", donation.amount))
+                    .replace("{currency}", &donation.currency)
+                    .replace("{message}", &mask(&donation.message, &self.blocked_words)),
+                true,
+            );
+        }
+    }
+
+    async fn serve(
+        &self,
+        config: DonationsConfig,
+        tx: OscSender,
+        chatbox: Chatbox,
+        events: Events,
+        token: CancellationToken,
+    ) -> Result<()> {
+        if config.kofi_verification_token.is_none() && config.streamlabs_secret.is_none() {
+            bail!(
+                "Set kofi_verification_token or streamlabs_secret in the [plugins.donations] section"
+            );
+        }
+
+        let (donation_tx, mut donation_rx) = mpsc::channel(16);
+        let address = SocketAddr::new(config.listen_address, config.port);
+        let service_config = config.clone();
+        let server = Server::try_bind(&address)
+            .with_context(|| format!("Failed to listen on {}", address))?
+            .serve(make_service_fn(move |_| {
+                let config = service_config.clone();
+                let donation_tx = donation_tx.clone();
+
+                async move {
+                    Ok::<_, Infallible>(service_fn(move |request| {
+                        let config = config.clone();
+                        let donation_tx = donation_tx.clone();
+
+                        async move {
+                            Ok::<_, Infallible>(
+                                handle_request(&config, request, &donation_tx).await,
+                            )
+                        }
+                    }))
+                }
+            }));
+        info!("Listening for donation webhooks on {}", address);
+        tokio::pin!(server);
+
+        loop {
+            let donation = select! {
+                result = &mut server => {
+                    result?;
+                    bail!("Donation webhook server stopped unexpectedly");
+                }
+                Some(donation) = donation_rx.recv() => donation,
+            };
+
+            info!(
+                "{} donated {:.2} {} on {}",
+                donation.name, donation.amount, donation.currency, donation.platform
+            );
+            events.record(
+                "donation",
+                vec![
+                    ("platform", donation.platform.to_string()),
+                    ("name", donation.name.clone()),
+                    ("amount", donation.amount.to_string()),
+                    ("currency", donation.currency.clone()),
+                ],
+            );
+            self.react(&config, &donation, &tx, &chatbox, &token);
+        }
+    }
+}
+
+impl Plugin for Donations {
+    fn name(&self) -> &'static str {
+        "donations"
+    }
+
+    check_config!(DonationsConfig);
+
+    fn config_fields(&self) -> Vec<ConfigField> {
+        vec![
+            ConfigField::new("port", "Port", ConfigFieldKind::Integer),
+            ConfigField::new(
+                "kofi_verification_token",
+                "Ko-fi verification token",
+                ConfigFieldKind::Secret,
+            ),
+            ConfigField::new(
+                "streamlabs_secret",
+                "Streamlabs secret",
+                ConfigFieldKind::Secret,
+            ),
+        ]
+    }
+
+    fn parameters(&self, config: &PluginConfig) -> Vec<ParameterUsage> {
+        let config: DonationsConfig = config.parse().unwrap_or_default();
+        config
+            .tiers
+            .iter()
+            .flat_map(|tier| tier.actions.iter().map(Action::usage))
+            .collect()
+    }
+
+    fn run(self: Box<Self>, context: PluginContext, subsys: SubsystemHandle) -> PluginFuture {
+        Box::pin(async move {
+            let config: DonationsConfig = context.config.parse()?;
+            let chatbox = context.chatbox();
+            let token = CancellationToken::new();
+            let _guard = token.clone().drop_guard();
+
+            match (self
+                .serve(config, context.osc, chatbox, context.events, token)
+                .cancel_on_shutdown(&subsys))
+            .await
+            {
+                Ok(Ok(())) => subsys.request_shutdown(),
+                Ok(Err(error)) => return Err(error),
+                Err(CancelledByShutdown) => {}
+            }
+
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,195 @@
+use anyhow::{bail, Result};
+use async_osc::{OscPacket, OscSocket, OscType};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr};
+use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
+use tokio_stream::StreamExt;
+use vrc_osc_manager_core::check_config;
+use vrc_osc_manager_core::{
+    addresses, OscSender, ParameterUsage, Plugin, PluginConfig, PluginContext, PluginFuture,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Range {
+    /// Gaze directions, normalized to -1.0 to 1.0.
+    Signed,
+    /// Eyelid openness, normalized to 0.0 to 1.0.
+    Unsigned,
+}
+
+/// Maps the raw values EyeTrackVR sends on an address from their calibrated `min` and `max` to
+/// the normalized range of the parameter.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ParameterConfig {
+    input: String,
+    output: String,
+    range: Range,
+    #[serde(default)]
+    min: Option<f32>,
+    #[serde(default)]
+    max: Option<f32>,
+    #[serde(default)]
+    invert: bool,
+}
+
+impl ParameterConfig {
+    fn new(name: &str, range: Range) -> Self {
+        Self {
+            input: addresses::parameter_address(name),
+            output: name.to_string(),
+            range,
+            min: None,
+            max: None,
+            invert: false,
+        }
+    }
+
+    fn normalize(&self, raw: f32) -> f32 {
+        let (low, high) = match self.range {
+            Range::Signed => (-1., 1.),
+            Range::Unsigned => (0., 1.),
+        };
+        let min = self.min.unwrap_or(low);
+        let max = self.max.unwrap_or(high);
+
+        let position = if max > min {
+            ((raw - min) / (max - min)).clamp(0., 1.)
+        } else {
+            0.
+        };
+        let position = if self.invert { 1. - position } else { position };
+
+        low + position * (high - low)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct EyeTrackingConfig {
+    listen_address: IpAddr,
+    /// Nothing is received until a port is set, so no port is taken up unless asked for.
+    port: Option<u16>,
+    smoothing: f32,
+    parameters: Vec<ParameterConfig>,
+}
+
+impl Default for EyeTrackingConfig {
+    fn default() -> Self {
+        Self {
+            listen_address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: None,
+            smoothing: 0.5,
+            parameters: vec![
+                ParameterConfig::new("LeftEyeX", Range::Signed),
+                ParameterConfig::new("RightEyeX", Range::Signed),
+                ParameterConfig::new("EyesY", Range::Signed),
+                ParameterConfig::new("LeftEyeLid", Range::Unsigned),
+                ParameterConfig::new("RightEyeLid", Range::Unsigned),
+            ],
+        }
+    }
+}
+
+/// Receives eye tracking data from EyeTrackVR on a port of its own, and forwards it to VRChat
+/// calibrated and smoothed.
+#[derive(Default)]
+pub struct EyeTracking;
+
+impl EyeTracking {
+    pub fn new() -> Self {
+        Self
+    }
+
+    async fn forward(config: EyeTrackingConfig, port: u16, tx: OscSender) -> Result<()> {
+        let mut socket = OscSocket::bind((config.listen_address, port)).await?;
+        info!(
+            "Receiving eye tracking on {}:{}",
+            config.listen_address, port
+        );
+
+        let smoothing = config.smoothing.clamp(0., 0.99);
+        let parameters: HashMap<_, _> = config
+            .parameters
+            .iter()
+            .map(|parameter| (parameter.input.as_str(), parameter))
+            .collect();
+        let mut smoothed: HashMap<String, f32> = HashMap::new();
+
+        while let Some(packet) = socket.next().await {
+            let packet = match packet {
+                Ok((packet, _)) => packet,
+                Err(error) => {
+                    warn!("Dropped undecodable eye tracking packet: {}", error);
+                    continue;
+                }
+            };
+
+            let OscPacket::Message(message) = packet else {
+                continue;
+            };
+
+            let Some(parameter) = parameters.get(message.addr.as_str()) else {
+                continue;
+            };
+
+            let raw = match message.args.first() {
+                Some(OscType::Float(value)) => *value,
+                Some(OscType::Double(value)) => *value as f32,
+                _ => continue,
+            };
+
+            let target = parameter.normalize(raw);
+            let value = match smoothed.get(&parameter.output) {
+                Some(previous) => previous * smoothing + target * (1. - smoothing),
+                None => target,
+            };
+            smoothed.insert(parameter.output.clone(), value);
+
+            tx.send_parameter(&parameter.output, OscType::Float(value))
+                .await?;
+        }
+
+        bail!("Eye tracking stream closed unexpectedly");
+    }
+}
+
+impl Plugin for EyeTracking {
+    fn name(&self) -> &'static str {
+        "eye_tracking"
+    }
+
+    check_config!(EyeTrackingConfig);
+
+    fn parameters(&self, config: &PluginConfig) -> Vec<ParameterUsage> {
+        // The inputs are addresses of the eye tracker, not of the avatar.
+        let config: EyeTrackingConfig = config.parse().unwrap_or_default();
+        config
+            .parameters
+            .iter()
+            .map(|parameter| ParameterUsage::write(&parameter.output))
+            .collect()
+    }
+
+    fn run(self: Box<Self>, context: PluginContext, subsys: SubsystemHandle) -> PluginFuture {
+        Box::pin(async move {
+            let config: EyeTrackingConfig = context.config.parse()?;
+
+            let Some(port) = config.port else {
+                info!("No port configured for eye tracking");
+                subsys.on_shutdown_requested().await;
+                return Ok(());
+            };
+
+            match (Self::forward(config, port, context.osc).cancel_on_shutdown(&subsys)).await {
+                Ok(Ok(())) => subsys.request_shutdown(),
+                Ok(Err(error)) => return Err(error),
+                Err(CancelledByShutdown) => {}
+            }
+
+            Ok(())
+        })
+    }
+}
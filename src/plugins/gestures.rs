@@ -0,0 +1,245 @@
+use crate::plugins::actions::{perform_all, Action};
+use anyhow::{bail, Result};
+use async_osc::OscType;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::error::RecvError;
+use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
+use tokio_util::sync::CancellationToken;
+use vrc_osc_manager_core::check_config;
+use vrc_osc_manager_core::{
+    OscReceiver, OscSender, ParameterUsage, Plugin, PluginConfig, PluginContext, PluginFuture,
+};
+
+const GESTURE_LEFT: &str = "/avatar/parameters/GestureLeft";
+const GESTURE_RIGHT: &str = "/avatar/parameters/GestureRight";
+
+/// Number of hand states kept around, which bounds the length of combos.
+const HISTORY: usize = 32;
+
+/// Hand gestures in the order VRChat numbers them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Gesture {
+    Neutral,
+    Fist,
+    HandOpen,
+    FingerPoint,
+    Victory,
+    RockNRoll,
+    HandGun,
+    ThumbsUp,
+}
+
+impl Gesture {
+    fn from_osc(value: &OscType) -> Option<Self> {
+        let OscType::Int(value) = value else {
+            return None;
+        };
+
+        Some(match value {
+            0 => Gesture::Neutral,
+            1 => Gesture::Fist,
+            2 => Gesture::HandOpen,
+            3 => Gesture::FingerPoint,
+            4 => Gesture::Victory,
+            5 => Gesture::RockNRoll,
+            6 => Gesture::HandGun,
+            7 => Gesture::ThumbsUp,
+            _ => return None,
+        })
+    }
+}
+
+/// A step is reached when the given hands show their gesture, a hand left out may show anything.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Step {
+    left: Option<Gesture>,
+    right: Option<Gesture>,
+}
+
+impl Step {
+    fn matches(&self, state: &HandState) -> bool {
+        self.left.map_or(true, |left| left == state.left)
+            && self.right.map_or(true, |right| right == state.right)
+    }
+}
+
+fn default_within_ms() -> u64 {
+    1000
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ComboConfig {
+    name: String,
+    steps: Vec<Step>,
+    #[serde(default = "default_within_ms")]
+    within_ms: u64,
+    then: Vec<Action>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+struct GesturesConfig {
+    combos: Vec<ComboConfig>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct HandState {
+    left: Gesture,
+    right: Gesture,
+    at: Instant,
+}
+
+impl ComboConfig {
+    /// Whether the steps were reached in order within the time window, ending with the latest
+    /// state. Other gestures in between, like going back to neutral, are ignored.
+    fn is_completed(&self, history: &VecDeque<HandState>) -> bool {
+        let Some(latest) = history.back() else {
+            return false;
+        };
+
+        let window = Duration::from_millis(self.within_ms);
+        let mut steps = self.steps.iter().rev();
+
+        match steps.next() {
+            Some(last) if last.matches(latest) => {}
+            _ => return false,
+        }
+
+        let mut step = steps.next();
+
+        for state in history.iter().rev().skip(1) {
+            let Some(current) = step else {
+                break;
+            };
+
+            if latest.at.duration_since(state.at) > window {
+                return false;
+            }
+
+            if current.matches(state) {
+                step = steps.next();
+            }
+        }
+
+        step.is_none()
+    }
+}
+
+/// Detects combos and sequences of hand gestures, giving extra buttons without taking up slots in
+/// the expression menu.
+#[derive(Default)]
+pub struct Gestures;
+
+impl Gestures {
+    pub fn new() -> Self {
+        Self
+    }
+
+    async fn handle(
+        config: GesturesConfig,
+        tx: OscSender,
+        mut rx: OscReceiver,
+        token: CancellationToken,
+    ) -> Result<()> {
+        let mut state = HandState {
+            left: Gesture::Neutral,
+            right: Gesture::Neutral,
+            at: Instant::now(),
+        };
+        let mut history: VecDeque<HandState> = VecDeque::with_capacity(HISTORY);
+
+        loop {
+            let message = match rx.recv().await {
+                Ok(message) => message,
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!(
+                        "Gestures lagging behind, {} messages have been dropped",
+                        skipped
+                    );
+                    continue;
+                }
+                Err(RecvError::Closed) => bail!("Message receiver died unexpectedly"),
+            };
+
+            let Some(gesture) = message.args.first().and_then(Gesture::from_osc) else {
+                continue;
+            };
+
+            let previous = state;
+
+            match message.addr.as_str() {
+                GESTURE_LEFT => state.left = gesture,
+                GESTURE_RIGHT => state.right = gesture,
+                _ => continue,
+            }
+
+            if state.left == previous.left && state.right == previous.right {
+                continue;
+            }
+
+            state.at = Instant::now();
+
+            if history.len() == HISTORY {
+                history.pop_front();
+            }
+
+            history.push_back(state);
+
+            if let Some(combo) = config
+                .combos
+                .iter()
+                .find(|combo| combo.is_completed(&history))
+            {
+                info!("Gesture combo {} detected", combo.name);
+                perform_all(&tx, &combo.then, &token);
+                history.clear();
+            }
+        }
+    }
+}
+
+impl Plugin for Gestures {
+    fn name(&self) -> &'static str {
+        "gestures"
+    }
+
+    check_config!(GesturesConfig);
+
+    fn parameters(&self, config: &PluginConfig) -> Vec<ParameterUsage> {
+        let config: GesturesConfig = config.parse().unwrap_or_default();
+        let mut usages = vec![
+            ParameterUsage::read(GESTURE_LEFT),
+            ParameterUsage::read(GESTURE_RIGHT),
+        ];
+        usages.extend(
+            config
+                .combos
+                .iter()
+                .flat_map(|combo| combo.then.iter().map(Action::usage)),
+        );
+        usages
+    }
+
+    fn run(self: Box<Self>, context: PluginContext, subsys: SubsystemHandle) -> PluginFuture {
+        Box::pin(async move {
+            let config: GesturesConfig = context.config.parse()?;
+            let token = CancellationToken::new();
+            let _guard = token.clone().drop_guard();
+
+            match (Self::handle(config, context.osc, context.receiver, token)
+                .cancel_on_shutdown(&subsys))
+            .await
+            {
+                Ok(Ok(())) => subsys.request_shutdown(),
+                Ok(Err(error)) => return Err(error),
+                Err(CancelledByShutdown) => {}
+            }
+
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,310 @@
+use anyhow::{bail, Context, Result};
+use async_osc::{OscMessage, OscType};
+use btleplug::api::bleuuid::uuid_from_u16;
+use btleplug::api::{Central, Manager as _, Peripheral as _, ScanFilter};
+use btleplug::platform::{Adapter, Manager, Peripheral};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::time::{sleep, timeout};
+use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
+use tokio_stream::StreamExt;
+use uuid::Uuid;
+use vrc_osc_manager_core::addresses::parameter_address;
+use vrc_osc_manager_core::check_config;
+use vrc_osc_manager_core::{
+    Chatbox, ConfigField, ConfigFieldKind, DeviceStatus, Metrics, OscSender, ParameterUsage,
+    Plugin, PluginConfig, PluginContext, PluginFuture,
+};
+
+const HEART_RATE_SERVICE: Uuid = uuid_from_u16(0x180D);
+const HEART_RATE_MEASUREMENT: Uuid = uuid_from_u16(0x2A37);
+
+/// Time given to monitors to show up in a scan.
+const SCAN_TIME: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct HeartRateConfig {
+    /// Name or address of the monitor. The first one found is used otherwise.
+    device: Option<String>,
+    /// Int parameter receiving the heart rate in beats per minute.
+    bpm_parameter: Option<String>,
+    /// Float parameter receiving the heart rate as fraction of `max_bpm`.
+    percent_parameter: Option<String>,
+    max_bpm: u16,
+    /// Weight of the previous value when smoothing, from 0 for none to just below 1.
+    smoothing: f64,
+    /// Seconds without a reading after which the monitor counts as gone, e.g. once the strap
+    /// lost contact.
+    timeout_seconds: u64,
+    /// Chatbox line, where {bpm} is replaced with the heart rate.
+    chatbox: Option<String>,
+}
+
+impl Default for HeartRateConfig {
+    fn default() -> Self {
+        Self {
+            device: None,
+            bpm_parameter: Some("HR".to_string()),
+            percent_parameter: Some("HRPercent".to_string()),
+            max_bpm: 200,
+            smoothing: 0.5,
+            timeout_seconds: 10,
+            chatbox: None,
+        }
+    }
+}
+
+/// Decodes a heart rate measurement, whose first bit of flags tells whether the value takes one
+/// or two bytes.
+fn parse_measurement(data: &[u8]) -> Option<u16> {
+    let flags = *data.first()?;
+
+    if flags & 1 == 0 {
+        data.get(1).map(|bpm| *bpm as u16)
+    } else {
+        Some(u16::from_le_bytes([*data.get(1)?, *data.get(2)?]))
+    }
+}
+
+/// Messages bringing the parameters back to no heart rate.
+fn resting(config: &HeartRateConfig) -> Vec<OscMessage> {
+    let bpm = config.bpm_parameter.iter().map(|parameter| OscMessage {
+        addr: parameter_address(parameter),
+        args: vec![OscType::Int(0)],
+    });
+    let percent = config.percent_parameter.iter().map(|parameter| OscMessage {
+        addr: parameter_address(parameter),
+        args: vec![OscType::Float(0.)],
+    });
+
+    bpm.chain(percent).collect()
+}
+
+/// Publishes the readings of a Bluetooth LE heart rate monitor to avatar parameters, waiting for
+/// the monitor to come back whenever it drops out.
+#[derive(Default)]
+pub struct HeartRate;
+
+impl HeartRate {
+    pub fn new() -> Self {
+        Self
+    }
+
+    async fn matches(config: &HeartRateConfig, peripheral: &Peripheral) -> Result<bool> {
+        let Some(device) = &config.device else {
+            return Ok(true);
+        };
+
+        let properties = peripheral.properties().await?;
+        Ok(properties.map_or(false, |properties| {
+            properties.address.to_string().eq_ignore_ascii_case(device)
+                || properties.local_name.as_deref() == Some(device.as_str())
+        }))
+    }
+
+    /// Scans for the monitor, connects to it and subscribes to its measurements.
+    async fn find(config: &HeartRateConfig, adapter: &Adapter) -> Result<Peripheral> {
+        adapter
+            .start_scan(ScanFilter {
+                services: vec![HEART_RATE_SERVICE],
+            })
+            .await?;
+        sleep(SCAN_TIME).await;
+        let peripherals = adapter.peripherals().await?;
+        adapter.stop_scan().await?;
+
+        for peripheral in peripherals {
+            if !Self::matches(config, &peripheral).await? {
+                continue;
+            }
+
+            if !peripheral.is_connected().await? {
+                // Another monitor might still be around, e.g. when this one is paired elsewhere.
+                if let Err(error) = peripheral.connect().await {
+                    debug!("Failed to connect to {}: {}", peripheral.address(), error);
+                    continue;
+                }
+            }
+
+            peripheral.discover_services().await?;
+            let Some(characteristic) = peripheral
+                .characteristics()
+                .into_iter()
+                .find(|characteristic| characteristic.uuid == HEART_RATE_MEASUREMENT)
+            else {
+                debug!("{} has no heart rate measurement", peripheral.address());
+                let _ = peripheral.disconnect().await;
+                continue;
+            };
+
+            peripheral.subscribe(&characteristic).await?;
+            return Ok(peripheral);
+        }
+
+        match &config.device {
+            Some(device) => bail!("Heart rate monitor {} not found", device),
+            None => bail!("No heart rate monitor found"),
+        }
+    }
+
+    /// Publishes readings until the monitor stops sending them.
+    async fn stream(
+        config: &HeartRateConfig,
+        peripheral: &Peripheral,
+        osc: &OscSender,
+        chatbox: &Chatbox,
+        metrics: &Metrics,
+    ) -> Result<()> {
+        let mut notifications = peripheral.notifications().await?;
+        let mut smoothed: Option<f64> = None;
+        let limit = Duration::from_secs(config.timeout_seconds);
+        // A weight of 1 would never move from the first reading.
+        let smoothing = config.smoothing.clamp(0., 0.99);
+
+        loop {
+            let notification = timeout(limit, notifications.next())
+                .await
+                .with_context(|| format!("No reading for {} seconds", config.timeout_seconds))?
+                .context("Connection closed")?;
+
+            if notification.uuid != HEART_RATE_MEASUREMENT {
+                continue;
+            }
+
+            // Monitors report 0 while they have no contact, which is no heart rate to show.
+            let Some(bpm) = parse_measurement(&notification.value).filter(|bpm| *bpm > 0) else {
+                continue;
+            };
+
+            metrics.record("Heart rate", bpm as f64);
+            let bpm = match smoothed {
+                Some(previous) => previous * smoothing + bpm as f64 * (1. - smoothing),
+                None => bpm as f64,
+            };
+            smoothed = Some(bpm);
+
+            if let Some(parameter) = &config.bpm_parameter {
+                osc.send(OscMessage {
+                    addr: ParameterUsage::write(parameter).address,
+                    args: vec![OscType::Int(bpm.round() as i32)],
+                })
+                .await?;
+            }
+
+            if let Some(parameter) = &config.percent_parameter {
+                let percent = (bpm / config.max_bpm.max(1) as f64).clamp(0., 1.);
+                osc.send(OscMessage {
+                    addr: ParameterUsage::write(parameter).address,
+                    args: vec![OscType::Float(percent as f32)],
+                })
+                .await?;
+            }
+
+            if let Some(template) = &config.chatbox {
+                chatbox.set_line(&template.replace("{bpm}", &format!("{:.0}", bpm)));
+            }
+        }
+    }
+
+    async fn handle(
+        config: HeartRateConfig,
+        adapter: Adapter,
+        device: DeviceStatus,
+        osc: OscSender,
+        chatbox: Chatbox,
+        metrics: Metrics,
+    ) -> Result<()> {
+        let config = &config;
+        let adapter = &adapter;
+
+        loop {
+            let peripheral = device.connect(|| Self::find(config, adapter)).await;
+            info!("Connected to heart rate monitor {}", peripheral.address());
+
+            if let Err(error) = Self::stream(config, &peripheral, &osc, &chatbox, &metrics).await {
+                warn!("Lost heart rate monitor: {:#}", error);
+            }
+
+            device.set_connected(false);
+            chatbox.set_line("");
+
+            for message in resting(config) {
+                osc.send(message).await?;
+            }
+
+            let _ = peripheral.disconnect().await;
+        }
+    }
+}
+
+impl Plugin for HeartRate {
+    fn name(&self) -> &'static str {
+        "heart_rate"
+    }
+
+    check_config!(HeartRateConfig);
+
+    fn config_fields(&self) -> Vec<ConfigField> {
+        vec![
+            ConfigField::new("device", "Device", ConfigFieldKind::String),
+            ConfigField::new("max_bpm", "Maximum BPM", ConfigFieldKind::Integer),
+            ConfigField::new("smoothing", "Smoothing", ConfigFieldKind::Float),
+        ]
+    }
+
+    fn parameters(&self, config: &PluginConfig) -> Vec<ParameterUsage> {
+        let config: HeartRateConfig = config.parse().unwrap_or_default();
+        config
+            .bpm_parameter
+            .iter()
+            .chain(config.percent_parameter.iter())
+            .map(|parameter| ParameterUsage::write(parameter))
+            .collect()
+    }
+
+    /// The avatar would otherwise keep showing the last heart rate.
+    fn reset_messages(&self, config: &PluginConfig) -> Vec<OscMessage> {
+        resting(&config.parse().unwrap_or_default())
+    }
+
+    /// Finding the Bluetooth adapter can take a while, so the plugin reports when it is done.
+    fn lazy(&self) -> bool {
+        true
+    }
+
+    fn run(self: Box<Self>, context: PluginContext, subsys: SubsystemHandle) -> PluginFuture {
+        Box::pin(async move {
+            let config: HeartRateConfig = context.config.parse()?;
+            let chatbox = context.chatbox();
+
+            let manager = Manager::new().await?;
+            let adapter = manager
+                .adapters()
+                .await?
+                .into_iter()
+                .next()
+                .context("No Bluetooth adapter found")?;
+            context.readiness.set_ready();
+
+            match (Self::handle(
+                config,
+                adapter,
+                context.device,
+                context.osc,
+                chatbox,
+                context.metrics,
+            )
+            .cancel_on_shutdown(&subsys))
+            .await
+            {
+                Ok(Ok(())) => subsys.request_shutdown(),
+                Ok(Err(error)) => return Err(error),
+                Err(CancelledByShutdown) => {}
+            }
+
+            Ok(())
+        })
+    }
+}
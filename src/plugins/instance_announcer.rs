@@ -0,0 +1,149 @@
+use anyhow::{bail, Context, Result};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
+use vrc_osc_manager_core::check_config;
+use vrc_osc_manager_core::{
+    Instance, InstanceAccess, InstanceWatcher, Plugin, PluginContext, PluginFuture,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct InstanceAnnouncerConfig {
+    webhook_url: String,
+    /// Types of instances which are announced. Invite instances never are, even when listed.
+    instance_types: Vec<InstanceAccess>,
+    /// Text of the announcement, where {world}, {link}, {type} and {region} are replaced.
+    template: String,
+}
+
+impl Default for InstanceAnnouncerConfig {
+    fn default() -> Self {
+        Self {
+            webhook_url: "".to_string(),
+            instance_types: vec![InstanceAccess::Public, InstanceAccess::GroupPublic],
+            template: "Now in **{world}** ({type}): {link}".to_string(),
+        }
+    }
+}
+
+fn access_name(access: InstanceAccess) -> &'static str {
+    match access {
+        InstanceAccess::Public => "Public",
+        InstanceAccess::FriendsPlus => "Friends+",
+        InstanceAccess::Friends => "Friends",
+        InstanceAccess::InvitePlus => "Invite+",
+        InstanceAccess::Invite => "Invite",
+        InstanceAccess::GroupPublic => "Group Public",
+        InstanceAccess::GroupPlus => "Group+",
+        InstanceAccess::Group => "Group",
+    }
+}
+
+fn announcement(template: &str, instance: &Instance) -> String {
+    template
+        .replace("{world}", &instance.world_name)
+        .replace("{link}", &instance.link())
+        .replace("{type}", access_name(instance.access))
+        .replace("{region}", instance.region.as_deref().unwrap_or("us"))
+}
+
+/// Posts to a Discord webhook whenever a new instance is joined, so a group knows where to find
+/// you.
+#[derive(Default)]
+pub struct InstanceAnnouncer;
+
+impl InstanceAnnouncer {
+    pub fn new() -> Self {
+        Self
+    }
+
+    async fn post(
+        client: &reqwest::Client,
+        config: &InstanceAnnouncerConfig,
+        instance: &Instance,
+    ) -> Result<()> {
+        client
+            .post(&config.webhook_url)
+            .json(&json!({ "content": announcement(&config.template, instance) }))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    async fn announce(
+        config: InstanceAnnouncerConfig,
+        mut instance: InstanceWatcher,
+    ) -> Result<()> {
+        if config.webhook_url.is_empty() {
+            bail!("Set webhook_url in the [plugins.instance_announcer] section");
+        }
+
+        if config
+            .instance_types
+            .iter()
+            .any(|access| access.is_private())
+        {
+            warn!("Invite instances are never announced, ignoring them in instance_types");
+        }
+
+        let client = reqwest::Client::new();
+        // The instance joined before the plugin started is not new anymore.
+        let mut announced = instance.borrow_and_update().clone();
+
+        loop {
+            instance
+                .changed()
+                .await
+                .context("VRChat log watcher stopped")?;
+            let Some(current) = instance.borrow_and_update().clone() else {
+                continue;
+            };
+
+            // Leaving and coming back is not worth another announcement.
+            if announced.as_ref() == Some(&current) {
+                continue;
+            }
+
+            if current.access.is_private() || !config.instance_types.contains(&current.access) {
+                debug!(
+                    "Not announcing {} instance of {}",
+                    access_name(current.access),
+                    current.world_name
+                );
+                continue;
+            }
+
+            match Self::post(&client, &config, &current).await {
+                Ok(()) => info!("Announced instance of {}", current.world_name),
+                Err(error) => warn!("Failed to announce instance: {:#}", error),
+            }
+
+            announced = Some(current);
+        }
+    }
+}
+
+impl Plugin for InstanceAnnouncer {
+    fn name(&self) -> &'static str {
+        "instance_announcer"
+    }
+
+    check_config!(InstanceAnnouncerConfig);
+
+    fn run(self: Box<Self>, context: PluginContext, subsys: SubsystemHandle) -> PluginFuture {
+        Box::pin(async move {
+            let config: InstanceAnnouncerConfig = context.config.parse()?;
+
+            match (Self::announce(config, context.instance).cancel_on_shutdown(&subsys)).await {
+                Ok(Ok(())) => subsys.request_shutdown(),
+                Ok(Err(error)) => return Err(error),
+                Err(CancelledByShutdown) => {}
+            }
+
+            Ok(())
+        })
+    }
+}
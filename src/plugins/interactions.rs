@@ -0,0 +1,293 @@
+use crate::rules;
+use anyhow::{bail, Context, Result};
+use async_osc::OscType;
+use chrono::Local;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio::fs::{create_dir_all, metadata, read_to_string, write};
+use tokio::select;
+use tokio::sync::broadcast::error::RecvError;
+use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
+use vrc_osc_manager_core::addresses::parameter_address;
+use vrc_osc_manager_core::check_config;
+use vrc_osc_manager_core::{
+    Chatbox, Commands, Metrics, OscReceiver, OscSender, ParameterUsage, Plugin, PluginConfig,
+    PluginContext, PluginFuture, Trigger, TriggerEvent, RESYNC,
+};
+
+fn default_threshold() -> f64 {
+    0.5
+}
+
+fn default_hold_ms() -> u64 {
+    1000
+}
+
+fn default_double_tap_ms() -> u64 {
+    400
+}
+
+fn default_cooldown_ms() -> u64 {
+    300
+}
+
+/// A contact receiver to count interactions on. Touching again within `cooldown_ms` of letting go
+/// counts as the same interaction, as OSC does not tell who is touching.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ContactConfig {
+    name: String,
+    parameter: String,
+    #[serde(default = "default_threshold")]
+    threshold: f64,
+    #[serde(default = "default_hold_ms")]
+    hold_ms: u64,
+    #[serde(default = "default_double_tap_ms")]
+    double_tap_ms: u64,
+    #[serde(default = "default_cooldown_ms")]
+    cooldown_ms: u64,
+    count_parameter: Option<String>,
+    chatbox: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+struct InteractionsConfig {
+    contacts: Vec<ContactConfig>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default)]
+#[serde(default)]
+struct Counts {
+    count: u32,
+    holds: u32,
+    double_taps: u32,
+}
+
+/// Daily totals, keyed by date and then by contact.
+type Totals = BTreeMap<String, BTreeMap<String, Counts>>;
+
+async fn load_totals(path: &Path) -> Result<Totals> {
+    if metadata(path).await.is_err() {
+        return Ok(Totals::new());
+    }
+
+    let contents = read_to_string(path)
+        .await
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    Ok(toml::from_str(&contents)?)
+}
+
+struct Contact {
+    trigger: Trigger,
+    touched_at: Option<Instant>,
+    released_at: Option<Instant>,
+}
+
+/// Counts interactions on contact receivers like headpats, detecting holds and double taps, and
+/// keeps daily totals which are exposed as parameters and in the chatbox.
+#[derive(Default)]
+pub struct Interactions;
+
+impl Interactions {
+    pub fn new() -> Self {
+        Self
+    }
+
+    async fn publish(config: &ContactConfig, counts: Counts, tx: &OscSender, chatbox: &Chatbox) {
+        if let Some(parameter) = &config.count_parameter {
+            // Int parameters in VRChat only hold a single byte.
+            let _ = tx
+                .send_parameter(parameter, OscType::Int(counts.count.min(255) as i32))
+                .await;
+        }
+
+        if let Some(template) = &config.chatbox {
+            let text = template
+                .replace("{count}", &counts.count.to_string())
+                .replace("{holds}", &counts.holds.to_string())
+                .replace("{double_taps}", &counts.double_taps.to_string());
+            chatbox.send(&text, false);
+        }
+    }
+
+    async fn handle(
+        config: InteractionsConfig,
+        path: PathBuf,
+        tx: OscSender,
+        chatbox: Chatbox,
+        metrics: Metrics,
+        mut rx: OscReceiver,
+        mut commands: Commands,
+    ) -> Result<()> {
+        let mut totals = load_totals(&path).await?;
+        let mut contacts: Vec<Contact> = config
+            .contacts
+            .iter()
+            .map(|contact_config| Contact {
+                trigger: Trigger::new(contact_config.threshold),
+                touched_at: None,
+                released_at: None,
+            })
+            .collect();
+
+        loop {
+            let message = select! {
+                message = rx.recv() => match message {
+                    Ok(message) => message,
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!(
+                            "Interactions lagging behind, {} messages have been dropped",
+                            skipped
+                        );
+                        continue;
+                    }
+                    Err(RecvError::Closed) => bail!("Message receiver died unexpectedly"),
+                },
+                command = commands.recv() => {
+                    if command.name != RESYNC {
+                        continue;
+                    }
+
+                    let today = Local::now().format("%Y-%m-%d").to_string();
+                    let day = totals.get(&today);
+
+                    for contact_config in &config.contacts {
+                        let counts = day
+                            .and_then(|day| day.get(&contact_config.name))
+                            .copied()
+                            .unwrap_or_default();
+                        Self::publish(contact_config, counts, &tx, &chatbox).await;
+                    }
+
+                    continue;
+                }
+            };
+
+            let today = Local::now().format("%Y-%m-%d").to_string();
+
+            for (contact_config, contact) in config.contacts.iter().zip(contacts.iter_mut()) {
+                if message.addr != parameter_address(&contact_config.parameter) {
+                    continue;
+                }
+
+                let Some(value) = rules::value(&message) else {
+                    continue;
+                };
+
+                let now = Instant::now();
+
+                let Some(event) = contact.trigger.update_at(value, now) else {
+                    continue;
+                };
+                let counts = totals
+                    .entry(today.clone())
+                    .or_default()
+                    .entry(contact_config.name.clone())
+                    .or_default();
+
+                if event == TriggerEvent::Pressed {
+                    let continued = contact.released_at.map_or(false, |released_at| {
+                        now.duration_since(released_at)
+                            < Duration::from_millis(contact_config.cooldown_ms)
+                    });
+                    let double_tap = contact.touched_at.map_or(false, |touched_at| {
+                        now.duration_since(touched_at)
+                            < Duration::from_millis(contact_config.double_tap_ms)
+                    });
+
+                    contact.touched_at = Some(now);
+
+                    if double_tap {
+                        counts.double_taps += 1;
+                    } else if continued {
+                        continue;
+                    } else {
+                        counts.count += 1;
+                    }
+
+                    metrics.count(&contact_config.name);
+                } else {
+                    contact.released_at = Some(now);
+
+                    let held = matches!(
+                        event,
+                        TriggerEvent::Released(duration)
+                            if duration >= Duration::from_millis(contact_config.hold_ms)
+                    );
+
+                    if !held {
+                        continue;
+                    }
+
+                    counts.holds += 1;
+                }
+
+                let counts = *counts;
+                info!(
+                    "{}: {} today, {} held, {} double taps",
+                    contact_config.name, counts.count, counts.holds, counts.double_taps
+                );
+                Self::publish(contact_config, counts, &tx, &chatbox).await;
+
+                if let Some(parent) = path.parent() {
+                    create_dir_all(parent).await?;
+                }
+
+                write(&path, toml::to_string(&totals)?).await?;
+            }
+        }
+    }
+}
+
+impl Plugin for Interactions {
+    fn name(&self) -> &'static str {
+        "interactions"
+    }
+
+    check_config!(InteractionsConfig);
+
+    fn parameters(&self, config: &PluginConfig) -> Vec<ParameterUsage> {
+        let config: InteractionsConfig = config.parse().unwrap_or_default();
+        let mut usages = vec![];
+
+        for contact in &config.contacts {
+            usages.push(ParameterUsage::read(&contact.parameter));
+
+            if let Some(parameter) = &contact.count_parameter {
+                usages.push(ParameterUsage::write(parameter));
+            }
+        }
+
+        usages
+    }
+
+    fn run(self: Box<Self>, context: PluginContext, subsys: SubsystemHandle) -> PluginFuture {
+        Box::pin(async move {
+            let config: InteractionsConfig = context.config.parse()?;
+            let path = context.data_dir.join("interactions.toml");
+            let chatbox = context.chatbox();
+
+            match (Self::handle(
+                config,
+                path,
+                context.osc,
+                chatbox,
+                context.metrics,
+                context.receiver,
+                context.commands,
+            )
+            .cancel_on_shutdown(&subsys))
+            .await
+            {
+                Ok(Ok(())) => subsys.request_shutdown(),
+                Ok(Err(error)) => return Err(error),
+                Err(CancelledByShutdown) => {}
+            }
+
+            Ok(())
+        })
+    }
+}
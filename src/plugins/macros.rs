@@ -0,0 +1,468 @@
+use crate::config::Config;
+use crate::plugins::actions::{perform_sequence, Action, Condition};
+use crate::recording::{read_recording, Direction, Recorder};
+use crate::rules;
+use anyhow::{bail, Result};
+use async_osc::OscMessage;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::select;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep_until, Instant};
+use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
+use tokio_util::sync::CancellationToken;
+use vrc_osc_manager_core::addresses::parameter_address;
+use vrc_osc_manager_core::check_config;
+use vrc_osc_manager_core::{
+    Command, Commands, OscReceiver, OscSender, ParameterUsage, Plugin, PluginConfig, PluginContext,
+    PluginFuture, Trigger, TriggerEvent,
+};
+
+/// Commands the tray sends to record and play recorded macros, carrying the name of the macro.
+/// Recording the macro being recorded stops the recording.
+pub const RECORD: &str = "record";
+pub const PLAY: &str = "play";
+
+fn default_repeat() -> u32 {
+    1
+}
+
+fn default_max_seconds() -> u64 {
+    10
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct MacroConfig {
+    name: String,
+    trigger: Condition,
+    #[serde(default = "default_repeat")]
+    repeat: u32,
+    steps: Vec<Action>,
+}
+
+/// A macro recorded from the parameter changes of the user instead of being configured step by
+/// step.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RecordedConfig {
+    name: String,
+    /// Parameters recorded, which may contain `*` wildcards. All avatar parameters are when empty.
+    #[serde(default)]
+    parameters: Vec<String>,
+    /// Seconds after which the recording stops on its own.
+    #[serde(default = "default_max_seconds")]
+    max_seconds: u64,
+    /// Bool parameter recording while true.
+    record_trigger: Option<String>,
+    /// Bool parameter playing the recording when it becomes true.
+    play_trigger: Option<String>,
+}
+
+impl RecordedConfig {
+    fn records(&self, addr: &str) -> bool {
+        addr.starts_with("/avatar/parameters/")
+            && [&self.record_trigger, &self.play_trigger]
+                .into_iter()
+                .flatten()
+                .all(|trigger| parameter_address(trigger) != addr)
+            && (self.parameters.is_empty()
+                || self
+                    .parameters
+                    .iter()
+                    .any(|parameter| rules::matches(&parameter_address(parameter), addr)))
+    }
+
+    fn path(&self, dir: &Path) -> PathBuf {
+        dir.join(format!("{}.jsonl", self.name))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+struct MacrosConfig {
+    macros: Vec<MacroConfig>,
+    recorded: Vec<RecordedConfig>,
+}
+
+/// Names of the recorded macros, used to populate the tray menu.
+pub fn recorded_names(config: &Config) -> Vec<String> {
+    config
+        .plugin_config("macros")
+        .parse::<MacrosConfig>()
+        .map(|config| {
+            config
+                .recorded
+                .into_iter()
+                .map(|recorded| recorded.name)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Feeds the message into the trigger if it is meant for the trigger parameter.
+fn trigger_event(
+    parameter: &Option<String>,
+    trigger: &mut Trigger,
+    message: &OscMessage,
+) -> Option<TriggerEvent> {
+    if parameter_address(parameter.as_ref()?) != message.addr {
+        return None;
+    }
+
+    trigger.update(message.args.first()?)
+}
+
+/// A recording in progress.
+struct Recording {
+    index: usize,
+    deadline: Instant,
+    recorder: Recorder,
+}
+
+/// The recorded macros, with what is being recorded and played right now.
+struct RecordedMacros {
+    config: Vec<RecordedConfig>,
+    dir: PathBuf,
+    recording: Option<Recording>,
+    playing: Vec<Option<JoinHandle<()>>>,
+    record_triggers: Vec<Trigger>,
+    play_triggers: Vec<Trigger>,
+    tx: OscSender,
+    token: CancellationToken,
+}
+
+impl RecordedMacros {
+    fn new(
+        config: Vec<RecordedConfig>,
+        dir: PathBuf,
+        tx: OscSender,
+        token: CancellationToken,
+    ) -> Self {
+        let playing = config.iter().map(|_| None).collect();
+        let record_triggers = config.iter().map(|_| Trigger::default()).collect();
+        let play_triggers = config.iter().map(|_| Trigger::default()).collect();
+
+        Self {
+            config,
+            dir,
+            recording: None,
+            playing,
+            record_triggers,
+            play_triggers,
+            tx,
+            token,
+        }
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.recording.as_ref().map(|recording| recording.deadline)
+    }
+
+    fn is_recording(&self, index: usize) -> bool {
+        self.recording
+            .as_ref()
+            .map_or(false, |recording| recording.index == index)
+    }
+
+    fn start_recording(&mut self, index: usize) {
+        info!("Recording macro {}", self.config[index].name);
+        self.recording = Some(Recording {
+            index,
+            deadline: Instant::now() + Duration::from_secs(self.config[index].max_seconds.max(1)),
+            recorder: Recorder::new(),
+        });
+    }
+
+    /// Saves the recording in progress. Failing to do so only loses this recording, so it is
+    /// logged instead of stopping the plugin.
+    async fn stop_recording(&mut self) {
+        let Some(recording) = self.recording.take() else {
+            return;
+        };
+        let config = &self.config[recording.index];
+
+        match recording.recorder.save(&config.path(&self.dir)).await {
+            Ok(()) => info!(
+                "Recorded macro {} with {} messages",
+                config.name,
+                recording.recorder.len()
+            ),
+            Err(error) => warn!("Failed to save macro {}: {:#}", config.name, error),
+        }
+    }
+
+    async fn toggle_recording(&mut self, index: usize) {
+        let was_recording = self.is_recording(index);
+        self.stop_recording().await;
+
+        if !was_recording {
+            self.start_recording(index);
+        }
+    }
+
+    fn play(&mut self, index: usize) {
+        let config = &self.config[index];
+
+        if self.is_recording(index) {
+            warn!("Macro {} is still being recorded", config.name);
+            return;
+        }
+
+        if self.playing[index]
+            .as_ref()
+            .map_or(false, |handle| !handle.is_finished())
+        {
+            return;
+        }
+
+        let name = config.name.clone();
+        let path = config.path(&self.dir);
+        let tx = self.tx.clone();
+        let token = self.token.clone();
+
+        self.playing[index] = Some(tokio::spawn(async move {
+            let records = match read_recording(&path).await {
+                Ok(records) => records,
+                Err(error) => {
+                    warn!("Macro {} has not been recorded yet: {:#}", name, error);
+                    return;
+                }
+            };
+
+            info!("Playing recorded macro {}", name);
+            let started = Instant::now();
+
+            select! {
+                _ = async {
+                    for record in records {
+                        sleep_until(started + Duration::from_secs_f64(record.time.max(0.))).await;
+                        let _ = tx.send(record.to_message()).await;
+                    }
+                } => {}
+                _ = token.cancelled() => {}
+            }
+        }));
+    }
+
+    /// Records the message and starts and stops recordings and plays them through their trigger
+    /// parameters.
+    async fn handle_message(&mut self, message: &OscMessage) {
+        if let Some(recording) = &mut self.recording {
+            if self.config[recording.index].records(&message.addr) {
+                recording.recorder.record(Direction::Incoming, message);
+            }
+        }
+
+        for index in 0..self.config.len() {
+            let recorded = &self.config[index];
+            let record = trigger_event(
+                &recorded.record_trigger,
+                &mut self.record_triggers[index],
+                message,
+            );
+            let play = trigger_event(
+                &recorded.play_trigger,
+                &mut self.play_triggers[index],
+                message,
+            );
+            let is_recording = self.is_recording(index);
+
+            match record {
+                Some(TriggerEvent::Released(_)) if is_recording => self.stop_recording().await,
+                Some(TriggerEvent::Pressed) if !is_recording => {
+                    self.stop_recording().await;
+                    self.start_recording(index);
+                }
+                _ if play == Some(TriggerEvent::Pressed) => self.play(index),
+                _ => {}
+            }
+        }
+    }
+
+    async fn handle_command(&mut self, command: Command) {
+        let Some(index) = self
+            .config
+            .iter()
+            .position(|recorded| recorded.name == command.argument)
+        else {
+            warn!(
+                "Macro {} no longer exists, restart to update the tray menu",
+                command.argument
+            );
+            return;
+        };
+
+        match command.name {
+            RECORD => self.toggle_recording(index).await,
+            PLAY => self.play(index),
+            _ => {}
+        }
+    }
+}
+
+/// Plays a timed sequence of messages whenever its trigger parameter starts meeting the condition.
+/// Triggering a macro again while it is still playing has no effect. Recorded macros replay the
+/// parameter changes captured while recording, with the same timing.
+#[derive(Default)]
+pub struct Macros;
+
+impl Macros {
+    pub fn new() -> Self {
+        Self
+    }
+
+    fn play(config: &MacroConfig, tx: &OscSender, token: &CancellationToken) -> JoinHandle<()> {
+        let config = config.clone();
+        let tx = tx.clone();
+        let token = token.clone();
+
+        tokio::spawn(async move {
+            info!("Playing macro {}", config.name);
+
+            select! {
+                _ = async {
+                    for _ in 0..config.repeat {
+                        perform_sequence(&tx, &config.steps, &token).await;
+                    }
+                } => {}
+                _ = token.cancelled() => {}
+            }
+        })
+    }
+
+    async fn handle(
+        config: MacrosConfig,
+        dir: PathBuf,
+        tx: OscSender,
+        mut rx: OscReceiver,
+        mut commands: Commands,
+        token: CancellationToken,
+    ) -> Result<()> {
+        let mut met: Vec<HashSet<String>> = vec![HashSet::new(); config.macros.len()];
+        let mut playing: Vec<Option<JoinHandle<()>>> = config.macros.iter().map(|_| None).collect();
+        let mut recorded = RecordedMacros::new(config.recorded, dir, tx.clone(), token.clone());
+
+        loop {
+            let deadline = recorded.deadline();
+
+            let message = select! {
+                message = rx.recv() => match message {
+                    Ok(message) => message,
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!(
+                            "Macros lagging behind, {} messages have been dropped",
+                            skipped
+                        );
+                        continue;
+                    }
+                    Err(RecvError::Closed) => bail!("Message receiver died unexpectedly"),
+                },
+                command = commands.recv() => {
+                    recorded.handle_command(command).await;
+                    continue;
+                }
+                _ = sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                    recorded.stop_recording().await;
+                    continue;
+                }
+            };
+
+            recorded.handle_message(&message).await;
+
+            for ((macro_config, met), playing) in config
+                .macros
+                .iter()
+                .zip(met.iter_mut())
+                .zip(playing.iter_mut())
+            {
+                if !macro_config.trigger.matches_address(&message.addr) {
+                    continue;
+                }
+
+                if !macro_config.trigger.is_met(&message) {
+                    met.remove(&message.addr);
+                    continue;
+                }
+
+                if !met.insert(message.addr.clone()) {
+                    continue;
+                }
+
+                if playing
+                    .as_ref()
+                    .map_or(false, |handle| !handle.is_finished())
+                {
+                    continue;
+                }
+
+                *playing = Some(Self::play(macro_config, &tx, &token));
+            }
+        }
+    }
+}
+
+impl Plugin for Macros {
+    fn name(&self) -> &'static str {
+        "macros"
+    }
+
+    check_config!(MacrosConfig);
+
+    fn parameters(&self, config: &PluginConfig) -> Vec<ParameterUsage> {
+        let config: MacrosConfig = config.parse().unwrap_or_default();
+        let mut usages = vec![];
+
+        for macro_config in &config.macros {
+            usages.push(macro_config.trigger.usage());
+            usages.extend(macro_config.steps.iter().map(Action::usage));
+        }
+
+        for recorded in &config.recorded {
+            usages.extend(
+                recorded
+                    .record_trigger
+                    .iter()
+                    .chain(&recorded.play_trigger)
+                    .map(|trigger| ParameterUsage::read(trigger)),
+            );
+            usages.extend(
+                recorded
+                    .parameters
+                    .iter()
+                    .map(|parameter| ParameterUsage::write(parameter)),
+            );
+        }
+
+        usages
+    }
+
+    fn run(self: Box<Self>, context: PluginContext, subsys: SubsystemHandle) -> PluginFuture {
+        Box::pin(async move {
+            let config: MacrosConfig = context.config.parse()?;
+            let dir = context.data_dir.join("macros");
+            let token = CancellationToken::new();
+            let _guard = token.clone().drop_guard();
+
+            match (Self::handle(
+                config,
+                dir,
+                context.osc,
+                context.receiver,
+                context.commands,
+                token,
+            )
+            .cancel_on_shutdown(&subsys))
+            .await
+            {
+                Ok(Ok(())) => subsys.request_shutdown(),
+                Ok(Err(error)) => return Err(error),
+                Err(CancelledByShutdown) => {}
+            }
+
+            Ok(())
+        })
+    }
+}
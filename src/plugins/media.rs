@@ -0,0 +1,245 @@
+use anyhow::{bail, Result};
+use async_osc::OscType;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::select;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::spawn_blocking;
+use tokio::time::interval;
+use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
+use vrc_osc_manager_core::check_config;
+use vrc_osc_manager_core::{
+    heartbeat, Chatbox, ConfigField, ConfigFieldKind, Heartbeat, OscReceiver, ParameterUsage,
+    Plugin, PluginConfig, PluginContext, PluginFuture,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct MediaConfig {
+    /// Chatbox line, where {artist} and {title} are replaced with those of the current track.
+    template: String,
+    /// Seconds between two checks of the current track.
+    interval_seconds: u64,
+    /// Bool parameter hiding the track while false, e.g. from a toggle in the avatar menu.
+    parameter: Option<String>,
+}
+
+impl Default for MediaConfig {
+    fn default() -> Self {
+        Self {
+            template: "Now playing: {artist} – {title}".to_string(),
+            interval_seconds: 5,
+            parameter: Some("MediaChatbox".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Track {
+    artist: String,
+    title: String,
+}
+
+impl Track {
+    fn line(&self, template: &str) -> String {
+        template
+            .replace("{artist}", &self.artist)
+            .replace("{title}", &self.title)
+    }
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(target_os = "linux")] {
+        /// Track of the first MPRIS player which is playing.
+        fn current_track() -> Result<Option<Track>> {
+            use dbus::arg::{prop_cast, PropMap};
+            use dbus::blocking::stdintf::org_freedesktop_dbus::Properties;
+            use dbus::blocking::Connection;
+
+            const PLAYER: &str = "org.mpris.MediaPlayer2.Player";
+            let timeout = Duration::from_secs(1);
+            let connection = Connection::new_session()?;
+            let (names,): (Vec<String>,) = connection
+                .with_proxy("org.freedesktop.DBus", "/org/freedesktop/DBus", timeout)
+                .method_call("org.freedesktop.DBus", "ListNames", ())?;
+
+            for name in names
+                .iter()
+                .filter(|name| name.starts_with("org.mpris.MediaPlayer2."))
+            {
+                let player = connection.with_proxy(name, "/org/mpris/MediaPlayer2", timeout);
+
+                match player.get::<String>(PLAYER, "PlaybackStatus") {
+                    Ok(status) if status == "Playing" => {}
+                    _ => continue,
+                }
+
+                let metadata: PropMap = player.get(PLAYER, "Metadata")?;
+                let title = prop_cast::<String>(&metadata, "xesam:title");
+                let artists = prop_cast::<Vec<String>>(&metadata, "xesam:artist");
+
+                return Ok(Some(Track {
+                    artist: artists.map(|artists| artists.join(", ")).unwrap_or_default(),
+                    title: title.cloned().unwrap_or_default(),
+                }));
+            }
+
+            Ok(None)
+        }
+    } else if #[cfg(target_os = "windows")] {
+        /// Track of the media session Windows considers current, while it is playing.
+        fn current_track() -> Result<Option<Track>> {
+            use windows::Media::Control::{
+                GlobalSystemMediaTransportControlsSessionManager as SessionManager,
+                GlobalSystemMediaTransportControlsSessionPlaybackStatus as PlaybackStatus,
+            };
+
+            let manager = SessionManager::RequestAsync()?.get()?;
+            let Ok(session) = manager.GetCurrentSession() else {
+                return Ok(None);
+            };
+
+            if session.GetPlaybackInfo()?.PlaybackStatus()? != PlaybackStatus::Playing {
+                return Ok(None);
+            }
+
+            let properties = session.TryGetMediaPropertiesAsync()?.get()?;
+
+            Ok(Some(Track {
+                artist: properties.Artist()?.to_string(),
+                title: properties.Title()?.to_string(),
+            }))
+        }
+    } else {
+        fn current_track() -> Result<Option<Track>> {
+            bail!("Reading the current track is not supported on this OS");
+        }
+    }
+}
+
+/// Sets the line shown by the chatbox plugin. Without it, the track is sent on its own instead,
+/// again on every check so it does not time out, and cleared once when nothing plays anymore.
+fn show(chatbox: &Chatbox, shown: &mut String, line: String) {
+    if chatbox.is_composed() {
+        chatbox.set_line(&line);
+    } else if !line.is_empty() || !shown.is_empty() {
+        chatbox.send(&line, false);
+    }
+
+    *shown = line;
+}
+
+/// Shows the track currently playing in the chatbox, as read from the media session of the OS.
+#[derive(Default)]
+pub struct Media;
+
+impl Media {
+    pub fn new() -> Self {
+        Self
+    }
+
+    async fn show(
+        config: MediaConfig,
+        chatbox: Chatbox,
+        mut rx: OscReceiver,
+        heartbeat: Heartbeat,
+    ) -> Result<()> {
+        let address = config
+            .parameter
+            .as_ref()
+            .map(|parameter| ParameterUsage::read(parameter).address);
+        let mut check = interval(Duration::from_secs(config.interval_seconds.max(1)));
+        let mut heartbeat_interval = interval(heartbeat::INTERVAL);
+        let mut enabled = true;
+        let mut shown = String::new();
+
+        loop {
+            select! {
+                // Reading the media session is what hangs, which stops the beats as well.
+                _ = heartbeat_interval.tick() => heartbeat.beat(),
+                _ = check.tick() => {
+                    if !enabled {
+                        continue;
+                    }
+
+                    let line = match spawn_blocking(current_track).await? {
+                        Ok(Some(track)) if !track.title.is_empty() => track.line(&config.template),
+                        Ok(_) => "".to_string(),
+                        Err(error) => {
+                            debug!("Failed to read the current track: {:#}", error);
+                            "".to_string()
+                        }
+                    };
+
+                    show(&chatbox, &mut shown, line);
+                }
+                message = rx.recv() => {
+                    let message = match message {
+                        Ok(message) => message,
+                        Err(RecvError::Lagged(skipped)) => {
+                            warn!("Media lagging behind, {} messages have been dropped", skipped);
+                            continue;
+                        }
+                        Err(RecvError::Closed) => bail!("Message receiver died unexpectedly"),
+                    };
+
+                    if address.as_ref() != Some(&message.addr) {
+                        continue;
+                    }
+
+                    if let [OscType::Bool(value)] = message.args[..] {
+                        enabled = value;
+
+                        if !enabled {
+                            show(&chatbox, &mut shown, "".to_string());
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Plugin for Media {
+    fn name(&self) -> &'static str {
+        "media"
+    }
+
+    check_config!(MediaConfig);
+
+    fn config_fields(&self) -> Vec<ConfigField> {
+        vec![ConfigField::new(
+            "template",
+            "Template",
+            ConfigFieldKind::String,
+        )]
+    }
+
+    fn parameters(&self, config: &PluginConfig) -> Vec<ParameterUsage> {
+        let config: MediaConfig = config.parse().unwrap_or_default();
+        config
+            .parameter
+            .iter()
+            .map(|parameter| ParameterUsage::read(parameter))
+            .collect()
+    }
+
+    fn run(self: Box<Self>, context: PluginContext, subsys: SubsystemHandle) -> PluginFuture {
+        Box::pin(async move {
+            let config: MediaConfig = context.config.parse()?;
+            let chatbox = context.chatbox();
+
+            match (Self::show(config, chatbox, context.receiver, context.heartbeat)
+                .cancel_on_shutdown(&subsys))
+            .await
+            {
+                Ok(Ok(())) => subsys.request_shutdown(),
+                Ok(Err(error)) => return Err(error),
+                Err(CancelledByShutdown) => {}
+            }
+
+            Ok(())
+        })
+    }
+}
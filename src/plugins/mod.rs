@@ -1,4 +1,136 @@
+use crate::config::Config;
+use std::sync::Arc;
+use vrc_osc_manager_core::Plugin;
+
+#[cfg(any(
+    feature = "alerts",
+    feature = "audio_cues",
+    feature = "donations",
+    feature = "gestures",
+    feature = "interactions",
+    feature = "macros",
+    feature = "scenes",
+    feature = "state_machine",
+    feature = "triggers",
+    feature = "wake_word",
+    feature = "youtube_chat"
+))]
+mod actions;
+#[cfg(feature = "alerts")]
+pub mod alerts;
+#[cfg(feature = "audio_cues")]
+pub mod audio_cues;
+#[cfg(feature = "chatbot")]
+pub mod chatbot;
+#[cfg(feature = "chatbox")]
+pub mod chatbox;
+#[cfg(feature = "donations")]
+pub mod donations;
+#[cfg(feature = "eye_tracking")]
+pub mod eye_tracking;
+#[cfg(feature = "gestures")]
+pub mod gestures;
+#[cfg(feature = "heart_rate")]
+pub mod heart_rate;
+#[cfg(feature = "instance_announcer")]
+pub mod instance_announcer;
+#[cfg(feature = "interactions")]
+pub mod interactions;
+#[cfg(feature = "macros")]
+pub mod macros;
+#[cfg(feature = "media")]
+pub mod media;
 #[cfg(feature = "pishock")]
 pub mod pishock;
+#[cfg(feature = "scenes")]
+pub mod scenes;
+#[cfg(feature = "serial_haptics")]
+pub mod serial_haptics;
+#[cfg(feature = "slimevr")]
+pub mod slimevr;
+#[cfg(feature = "state_machine")]
+pub mod state_machine;
+#[cfg(feature = "steamvr")]
+pub mod steamvr;
+#[cfg(feature = "triggers")]
+pub mod triggers;
+#[cfg(feature = "wake_word")]
+pub mod wake_word;
 #[cfg(feature = "watch")]
 pub mod watch;
+#[cfg(feature = "youtube_chat")]
+pub mod youtube_chat;
+
+/// Creates all plugins compiled into the application, regardless of whether they are enabled.
+pub fn builtin(config: &Arc<Config>) -> Vec<Box<dyn Plugin>> {
+    #[allow(unused_mut)]
+    let mut plugins: Vec<Box<dyn Plugin>> = vec![];
+
+    #[cfg(feature = "watch")]
+    plugins.push(Box::new(watch::Watch::new(config.clone())));
+
+    #[cfg(feature = "pishock")]
+    plugins.push(Box::new(pishock::PiShock::new(config.clone())));
+
+    #[cfg(feature = "macros")]
+    plugins.push(Box::new(macros::Macros::new()));
+
+    #[cfg(feature = "scenes")]
+    plugins.push(Box::new(scenes::Scenes::new()));
+
+    #[cfg(feature = "state_machine")]
+    plugins.push(Box::new(state_machine::StateMachine::new()));
+
+    #[cfg(feature = "triggers")]
+    plugins.push(Box::new(triggers::Triggers::new()));
+
+    #[cfg(feature = "alerts")]
+    plugins.push(Box::new(alerts::Alerts::new()));
+
+    #[cfg(feature = "eye_tracking")]
+    plugins.push(Box::new(eye_tracking::EyeTracking::new()));
+
+    #[cfg(feature = "steamvr")]
+    plugins.push(Box::new(steamvr::SteamVr::new()));
+
+    #[cfg(feature = "slimevr")]
+    plugins.push(Box::new(slimevr::SlimeVr::new()));
+
+    #[cfg(feature = "serial_haptics")]
+    plugins.push(Box::new(serial_haptics::SerialHaptics::new()));
+
+    #[cfg(feature = "heart_rate")]
+    plugins.push(Box::new(heart_rate::HeartRate::new()));
+
+    #[cfg(feature = "media")]
+    plugins.push(Box::new(media::Media::new()));
+
+    #[cfg(feature = "chatbot")]
+    plugins.push(Box::new(chatbot::Chatbot::new()));
+
+    #[cfg(feature = "wake_word")]
+    plugins.push(Box::new(wake_word::WakeWord::new()));
+
+    #[cfg(feature = "gestures")]
+    plugins.push(Box::new(gestures::Gestures::new()));
+
+    #[cfg(feature = "interactions")]
+    plugins.push(Box::new(interactions::Interactions::new()));
+
+    #[cfg(feature = "audio_cues")]
+    plugins.push(Box::new(audio_cues::AudioCues::new()));
+
+    #[cfg(feature = "donations")]
+    plugins.push(Box::new(donations::Donations::new(config.clone())));
+
+    #[cfg(feature = "youtube_chat")]
+    plugins.push(Box::new(youtube_chat::YoutubeChat::new()));
+
+    #[cfg(feature = "instance_announcer")]
+    plugins.push(Box::new(instance_announcer::InstanceAnnouncer::new()));
+
+    #[cfg(feature = "chatbox")]
+    plugins.push(Box::new(chatbox::ChatboxPlugin::new()));
+
+    plugins
+}
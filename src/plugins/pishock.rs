@@ -1,4 +1,4 @@
-use crate::config::Config;
+use crate::config::{Config, PiShockConfig};
 use anyhow::{bail, Context, Result};
 use async_osc::{prelude::OscMessageExt, OscMessage, OscType};
 use debounced::debounced;
@@ -10,13 +10,21 @@ use std::time::Duration;
 use tokio::fs::{metadata, File};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::sync::broadcast::error::RecvError;
-use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio::sync::{mpsc, oneshot};
 use tokio::time::sleep;
 use tokio::{select, spawn};
 use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
 use tokio_stream::wrappers::ReceiverStream;
 use tokio_stream::StreamExt;
 use tokio_util::sync::CancellationToken;
+use vrc_osc_manager_core::check_config;
+use vrc_osc_manager_core::{
+    arming, Arming, ConfigField, ConfigFieldKind, DeviceStatus, Events, Notifier, OscReceiver,
+    OscSender, ParameterAccess, ParameterUsage, Plugin, PluginConfig, PluginContext, PluginFuture,
+};
+
+/// Disarms the shocks while true and arms them again when false, e.g. from an avatar toggle.
+pub const DISARM: &str = "/vrc-osc-manager/pishock/disarm";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
@@ -238,7 +246,7 @@ async fn handle_modifier(
 async fn handle_delta(
     mut delta_rx: mpsc::Receiver<f32>,
     settings_tx: mpsc::Sender<SettingsAction>,
-    osc_tx: mpsc::Sender<OscMessage>,
+    osc_tx: OscSender,
 ) -> Result<()> {
     while let Some(delta) = delta_rx.recv().await {
         let settings = get_settings(&settings_tx).await?;
@@ -286,15 +294,29 @@ struct ShockBody {
     intensity: u8,
 }
 
-async fn send_shock(
-    config: &Arc<Config>,
-    intensity: f32,
-    duration: u8,
-    activity_tx: &mpsc::Sender<u8>,
-) {
-    let intensity = 1 + (99. * intensity) as u8;
+/// Everything needed for sending shocks, shared by the shock buttons and quick shocks.
+#[derive(Clone)]
+struct Shocker {
+    config: Arc<Config>,
+    activity_tx: mpsc::Sender<u8>,
+    arming: Arming,
+    notifier: Notifier,
+    events: Events,
+    device: DeviceStatus,
+}
+
+async fn send_shock(shocker: &Shocker, intensity: f32, duration: u8) {
+    let config = &shocker.config;
+    let activity_tx = &shocker.activity_tx;
+    // Values from the avatar or the menu may be out of range, which the device does not accept.
+    let intensity = 1 + (99. * intensity.clamp(0., 1.)) as u8;
     let duration = duration.clamp(1, 15);
 
+    if !shocker.arming.is_armed() {
+        info!("Ignoring shock while disarmed");
+        return;
+    }
+
     info!(
         "Sending shock with intensity {} and duration {}",
         intensity, duration
@@ -310,6 +332,17 @@ async fn send_shock(
         intensity,
     };
 
+    let failed = |error: &str| {
+        shocker.events.record(
+            "shock_failed",
+            vec![
+                ("intensity", intensity.to_string()),
+                ("duration", duration.to_string()),
+                ("error", error.to_string()),
+            ],
+        );
+    };
+
     let client = reqwest::Client::new();
     let response = client
         .post("https://do.pishock.com/api/apioperate")
@@ -319,24 +352,53 @@ async fn send_shock(
 
     match response {
         Ok(response) => {
+            // The API is all there is to reach of the shocker, so it counts as the device.
+            shocker.device.set_connected(true);
             let status = response.text().await;
 
             match status {
                 Ok(status) => match status.as_str() {
-                    "Not Authorized." => warn!("Invalid credentials"),
+                    "Not Authorized." => {
+                        warn!("Invalid credentials");
+                        failed("Invalid credentials");
+                    }
                     "Operation Succeeded." => {
                         debug!("Shock succeeded");
+                        shocker.events.record(
+                            "shock_sent",
+                            vec![
+                                ("intensity", intensity.to_string()),
+                                ("duration", duration.to_string()),
+                            ],
+                        );
                         let _ = activity_tx.send(duration).await;
+                        let _ = shocker
+                            .notifier
+                            .notify_with_actions(
+                                "PiShock",
+                                format!(
+                                    "Shocked with intensity {} for {} seconds",
+                                    intensity, duration
+                                ),
+                                vec![arming::action(false)],
+                            )
+                            .await;
+                    }
+                    _ => {
+                        warn!("Unknown response: {}", status);
+                        failed(&status);
                     }
-                    _ => warn!("Unknown response: {}", status),
                 },
                 Err(_) => {
                     warn!("Failed to parse response");
+                    failed("Failed to parse response");
                 }
             }
         }
-        Err(_) => {
-            warn!("Failed to contact pishock API");
+        Err(error) => {
+            warn!("Failed to contact pishock API: {}", error);
+            failed(&error.to_string());
+            shocker.device.set_connected(false);
         }
     }
 }
@@ -344,8 +406,7 @@ async fn send_shock(
 async fn handle_shock(
     mut shock_rx: mpsc::Receiver<(ShockButton, bool)>,
     settings_tx: mpsc::Sender<SettingsAction>,
-    config: Arc<Config>,
-    activity_tx: mpsc::Sender<u8>,
+    shocker: Shocker,
 ) -> Result<()> {
     let mut left_pressed = false;
     let mut right_pressed = false;
@@ -361,8 +422,7 @@ async fn handle_shock(
             if shock_cancel.is_none() {
                 let token = CancellationToken::new();
                 shock_cancel = Some(token.clone());
-                let config = config.clone();
-                let activity_tx = activity_tx.clone();
+                let shocker = shocker.clone();
                 let settings_tx = settings_tx.clone();
 
                 spawn(async move {
@@ -370,16 +430,15 @@ async fn handle_shock(
                         let settings = get_settings(&settings_tx).await.unwrap();
 
                         send_shock(
-                            &config,
+                            &shocker,
                             settings.intensity,
-                            config.pishock.duration,
-                            &activity_tx,
+                            shocker.config.pishock.duration,
                         )
                         .await;
 
                         select! {
                             _ = token.cancelled() => return,
-                            _ = sleep(Duration::from_secs(config.pishock.duration as u64)) => continue,
+                            _ = sleep(Duration::from_secs(shocker.config.pishock.duration as u64)) => continue,
                         }
                     }
                 });
@@ -393,7 +452,7 @@ async fn handle_shock(
     Ok(())
 }
 
-async fn handle_activity(mut activity_rx: mpsc::Receiver<u8>, osc_tx: mpsc::Sender<OscMessage>) {
+async fn handle_activity(mut activity_rx: mpsc::Receiver<u8>, osc_tx: OscSender) {
     while let Some(duration) = activity_rx.recv().await {
         let _ = osc_tx
             .send(OscMessage {
@@ -429,29 +488,30 @@ async fn handle_activity(mut activity_rx: mpsc::Receiver<u8>, osc_tx: mpsc::Send
     }
 }
 
-pub struct PiShock {
-    tx: mpsc::Sender<OscMessage>,
-    rx: broadcast::Receiver<OscMessage>,
+struct ButtonHandler {
+    tx: OscSender,
+    rx: OscReceiver,
+    notifier: Notifier,
+    events: Events,
+    arming: Arming,
+    device: DeviceStatus,
     config: Arc<Config>,
     data_dir: PathBuf,
 }
 
-impl PiShock {
-    pub fn new(
-        tx: mpsc::Sender<OscMessage>,
-        rx: broadcast::Receiver<OscMessage>,
-        config: Arc<Config>,
-        data_dir: PathBuf,
-    ) -> Self {
-        Self {
-            tx,
-            rx,
-            config,
-            data_dir,
+impl ButtonHandler {
+    async fn handle_buttons(&mut self) -> Result<()> {
+        if !self.arming.is_armed() {
+            let _ = self
+                .notifier
+                .notify_with_actions(
+                    "PiShock",
+                    "Shocks are disarmed until armed through the tray or this notification",
+                    vec![arming::action(true)],
+                )
+                .await;
         }
-    }
 
-    async fn handle_buttons(&mut self) -> Result<()> {
         let (activity_tx, activity_rx) = mpsc::channel(8);
         let (shock_tx, shock_rx) = mpsc::channel(8);
         let (modifier_tx, modifier_rx) = mpsc::channel(8);
@@ -476,12 +536,18 @@ impl PiShock {
         });
 
         let shock_settings_tx = settings_tx.clone();
-        let shock_config = self.config.clone();
-        let shock_activity_tx = activity_tx.clone();
+        let shocker = Shocker {
+            config: self.config.clone(),
+            activity_tx,
+            arming: self.arming.clone(),
+            notifier: self.notifier.clone(),
+            events: self.events.clone(),
+            device: self.device.clone(),
+        };
+        let shock_shocker = shocker.clone();
 
         spawn(async move {
-            let _ =
-                handle_shock(shock_rx, shock_settings_tx, shock_config, shock_activity_tx).await;
+            let _ = handle_shock(shock_rx, shock_settings_tx, shock_shocker).await;
         });
 
         let activity_osc_tx = self.tx.clone();
@@ -531,13 +597,22 @@ impl PiShock {
                         if value >= 0. {
                             let settings = get_settings(&settings_tx).await?;
 
-                            send_shock(
-                                &self.config,
-                                value.clamp(0., settings.intensity_cap),
-                                1,
-                                &activity_tx,
-                            )
-                            .await;
+                            send_shock(&shocker, value.clamp(0., settings.intensity_cap), 1).await;
+                        }
+                    }
+                    (DISARM, &[OscType::Bool(disarm)]) => {
+                        self.arming.set_armed(!disarm);
+                        info!("PiShock {}", if disarm { "disarmed" } else { "armed" });
+
+                        if disarm {
+                            let _ = self
+                                .notifier
+                                .notify_with_actions(
+                                    "PiShock",
+                                    "Disarmed, shocks are ignored until armed again",
+                                    vec![arming::action(true)],
+                                )
+                                .await;
                         }
                     }
                     ("/avatar/change", &[OscType::String(_)]) => {
@@ -578,7 +653,7 @@ impl PiShock {
         bail!("Message receiver died unexpectedly");
     }
 
-    pub async fn run(mut self, subsys: SubsystemHandle) -> Result<()> {
+    async fn run(mut self, subsys: SubsystemHandle) -> Result<()> {
         match (self.handle_buttons().cancel_on_shutdown(&subsys)).await {
             Ok(Ok(())) => subsys.request_shutdown(),
             Ok(Err(error)) => return Err(error),
@@ -588,3 +663,67 @@ impl PiShock {
         Ok(())
     }
 }
+
+pub struct PiShock {
+    config: Arc<Config>,
+}
+
+impl PiShock {
+    pub fn new(config: Arc<Config>) -> Self {
+        Self { config }
+    }
+}
+
+impl Plugin for PiShock {
+    fn name(&self) -> &'static str {
+        "pishock"
+    }
+
+    fn parameters(&self, _config: &PluginConfig) -> Vec<ParameterUsage> {
+        [
+            ("PS_Minus_Pressed", ParameterAccess::Read),
+            ("PS_Plus_Pressed", ParameterAccess::Read),
+            ("PS_ShockLeft_Pressed", ParameterAccess::Read),
+            ("PS_ShockRight_Pressed", ParameterAccess::Read),
+            ("PS_Intensity", ParameterAccess::ReadWrite),
+            ("PS_IntensityCap", ParameterAccess::ReadWrite),
+            ("PS_QuickShock", ParameterAccess::Read),
+            ("PS_ShockActive", ParameterAccess::Write),
+        ]
+        .into_iter()
+        .map(|(name, access)| ParameterUsage::new(name, access))
+        .collect()
+    }
+
+    fn config_section(&self) -> String {
+        "pishock".to_string()
+    }
+
+    check_config!(PiShockConfig);
+
+    fn config_fields(&self) -> Vec<ConfigField> {
+        vec![
+            ConfigField::new("username", "Username", ConfigFieldKind::String),
+            ConfigField::new("api_key", "API key", ConfigFieldKind::Secret),
+            ConfigField::new("code", "Share code", ConfigFieldKind::String),
+            ConfigField::new("duration", "Duration", ConfigFieldKind::Integer)
+                .menu()
+                .range(1., 15.),
+        ]
+    }
+
+    fn run(self: Box<Self>, context: PluginContext, subsys: SubsystemHandle) -> PluginFuture {
+        let handler = ButtonHandler {
+            tx: context.osc,
+            rx: context.receiver,
+            notifier: context.notifier,
+            events: context.events,
+            arming: context.arming,
+            device: context.device,
+            config: self.config,
+            data_dir: context.data_dir,
+        };
+
+        Box::pin(handler.run(subsys))
+    }
+}
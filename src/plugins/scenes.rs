@@ -0,0 +1,245 @@
+use crate::config::Config;
+use crate::plugins::actions::Value;
+use anyhow::{bail, Context, Result};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use tokio::fs::{create_dir_all, metadata, read_to_string, write};
+use tokio::select;
+use tokio::sync::broadcast::error::RecvError;
+use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
+use vrc_osc_manager_core::addresses::parameter_address;
+use vrc_osc_manager_core::check_config;
+use vrc_osc_manager_core::{
+    Commands, OscReceiver, OscSender, ParameterAccess, ParameterUsage, Plugin, PluginConfig,
+    PluginContext, PluginFuture, Trigger, TriggerEvent,
+};
+
+/// Commands the tray sends to save and recall scenes, carrying the name of the scene.
+pub const SAVE: &str = "save";
+pub const RECALL: &str = "recall";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SceneConfig {
+    name: String,
+    parameters: Vec<String>,
+    save_trigger: Option<String>,
+    recall_trigger: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+struct ScenesConfig {
+    scenes: Vec<SceneConfig>,
+}
+
+/// Stored values of every scene, keyed by scene and then by parameter.
+type Snapshots = BTreeMap<String, BTreeMap<String, Value>>;
+
+/// Names of the configured scenes, used to populate the tray menu.
+pub fn scene_names(config: &Config) -> Vec<String> {
+    config
+        .plugin_config("scenes")
+        .parse::<ScenesConfig>()
+        .map(|config| config.scenes.into_iter().map(|scene| scene.name).collect())
+        .unwrap_or_default()
+}
+
+async fn load_snapshots(path: &Path) -> Result<Snapshots> {
+    if metadata(path).await.is_err() {
+        return Ok(Snapshots::new());
+    }
+
+    let contents = read_to_string(path)
+        .await
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    Ok(toml::from_str(&contents)?)
+}
+
+/// Snapshots the current values of selected parameters as named scenes and recalls them later.
+#[derive(Default)]
+pub struct Scenes;
+
+impl Scenes {
+    pub fn new() -> Self {
+        Self
+    }
+
+    async fn save(
+        scene: &SceneConfig,
+        current: &HashMap<String, Value>,
+        snapshots: &mut Snapshots,
+        path: &Path,
+    ) -> Result<()> {
+        let values: BTreeMap<_, _> = scene
+            .parameters
+            .iter()
+            .filter_map(|parameter| {
+                current
+                    .get(&parameter_address(parameter))
+                    .map(|value| (parameter.clone(), value.clone()))
+            })
+            .collect();
+
+        if values.len() < scene.parameters.len() {
+            warn!(
+                "Scene {} misses parameters which have not been received yet",
+                scene.name
+            );
+        }
+
+        info!("Saving scene {}", scene.name);
+        snapshots.insert(scene.name.clone(), values);
+
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent).await?;
+        }
+
+        write(path, toml::to_string(snapshots)?).await?;
+        Ok(())
+    }
+
+    async fn recall(scene: &SceneConfig, snapshots: &Snapshots, tx: &OscSender) {
+        let Some(values) = snapshots.get(&scene.name) else {
+            warn!("Scene {} has not been saved yet", scene.name);
+            return;
+        };
+
+        info!("Recalling scene {}", scene.name);
+
+        for (parameter, value) in values {
+            let _ = tx.send_parameter(parameter, value.to_osc()).await;
+        }
+    }
+
+    async fn handle(
+        config: ScenesConfig,
+        path: PathBuf,
+        tx: OscSender,
+        mut rx: OscReceiver,
+        mut commands: Commands,
+    ) -> Result<()> {
+        let mut snapshots = load_snapshots(&path).await?;
+        let mut current: HashMap<String, Value> = HashMap::new();
+        let mut triggers: HashMap<String, Trigger> = config
+            .scenes
+            .iter()
+            .flat_map(|scene| [&scene.save_trigger, &scene.recall_trigger])
+            .flatten()
+            .map(|trigger| (parameter_address(trigger), Trigger::default()))
+            .collect();
+
+        loop {
+            let message = select! {
+                message = rx.recv() => match message {
+                    Ok(message) => message,
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!(
+                            "Scenes lagging behind, {} messages have been dropped",
+                            skipped
+                        );
+                        continue;
+                    }
+                    Err(RecvError::Closed) => bail!("Message receiver died unexpectedly"),
+                },
+                command = commands.recv() => {
+                    let Some(scene) = config.scenes.iter().find(|scene| scene.name == command.argument) else {
+                        warn!("Scene {} no longer exists, restart to update the tray menu", command.argument);
+                        continue;
+                    };
+
+                    match command.name {
+                        SAVE => Self::save(scene, &current, &mut snapshots, &path).await?,
+                        RECALL => Self::recall(scene, &snapshots, &tx).await,
+                        _ => {}
+                    }
+
+                    continue;
+                }
+            };
+
+            if let Some(value) = message.args.first().and_then(Value::from_osc) {
+                current.insert(message.addr.clone(), value);
+            }
+
+            let pressed = match (triggers.get_mut(&message.addr), message.args.first()) {
+                (Some(trigger), Some(arg)) => trigger.update(arg) == Some(TriggerEvent::Pressed),
+                _ => false,
+            };
+
+            if !pressed {
+                continue;
+            }
+
+            for scene in &config.scenes {
+                let triggered = |trigger: &Option<String>| {
+                    trigger
+                        .as_ref()
+                        .map_or(false, |trigger| message.addr == parameter_address(trigger))
+                };
+
+                if triggered(&scene.save_trigger) {
+                    Self::save(scene, &current, &mut snapshots, &path).await?;
+                } else if triggered(&scene.recall_trigger) {
+                    Self::recall(scene, &snapshots, &tx).await;
+                }
+            }
+        }
+    }
+}
+
+impl Plugin for Scenes {
+    fn name(&self) -> &'static str {
+        "scenes"
+    }
+
+    check_config!(ScenesConfig);
+
+    fn parameters(&self, config: &PluginConfig) -> Vec<ParameterUsage> {
+        let config: ScenesConfig = config.parse().unwrap_or_default();
+        let mut usages = vec![];
+
+        for scene in &config.scenes {
+            usages.extend(
+                scene
+                    .parameters
+                    .iter()
+                    .map(|parameter| ParameterUsage::new(parameter, ParameterAccess::ReadWrite)),
+            );
+            usages.extend(
+                scene
+                    .save_trigger
+                    .iter()
+                    .chain(&scene.recall_trigger)
+                    .map(|trigger| ParameterUsage::read(trigger)),
+            );
+        }
+
+        usages
+    }
+
+    fn run(self: Box<Self>, context: PluginContext, subsys: SubsystemHandle) -> PluginFuture {
+        Box::pin(async move {
+            let config: ScenesConfig = context.config.parse()?;
+            let path = context.data_dir.join("scenes.toml");
+
+            match (Self::handle(
+                config,
+                path,
+                context.osc,
+                context.receiver,
+                context.commands,
+            )
+            .cancel_on_shutdown(&subsys))
+            .await
+            {
+                Ok(Ok(())) => subsys.request_shutdown(),
+                Ok(Err(error)) => return Err(error),
+                Err(CancelledByShutdown) => {}
+            }
+
+            Ok(())
+        })
+    }
+}
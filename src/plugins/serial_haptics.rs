@@ -0,0 +1,256 @@
+use crate::rules;
+use anyhow::{bail, Context, Result};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use tokio::io::AsyncWriteExt;
+use tokio::select;
+use tokio::sync::broadcast::error::RecvError;
+use tokio_graceful_shutdown::SubsystemHandle;
+use tokio_serial::{SerialPortBuilderExt, SerialStream};
+use vrc_osc_manager_core::check_config;
+use vrc_osc_manager_core::{
+    addresses, DeviceStatus, OscReceiver, ParameterUsage, Plugin, PluginConfig, PluginContext,
+    PluginFuture,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+enum Curve {
+    #[default]
+    Linear,
+    Quadratic,
+    Sqrt,
+}
+
+fn default_max_pwm() -> u16 {
+    255
+}
+
+/// Drives a channel of the haptics board from a contact parameter, whose value between 0.0 and
+/// 1.0 is shaped by the curve and scaled to the PWM range of the motor.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ChannelConfig {
+    parameter: String,
+    channel: u8,
+    #[serde(default)]
+    curve: Curve,
+    #[serde(default)]
+    min_pwm: u16,
+    #[serde(default = "default_max_pwm")]
+    max_pwm: u16,
+}
+
+impl ChannelConfig {
+    fn pwm(&self, value: f64) -> u16 {
+        let value = value.clamp(0., 1.);
+
+        if value == 0. {
+            return 0;
+        }
+
+        let shaped = match self.curve {
+            Curve::Linear => value,
+            Curve::Quadratic => value * value,
+            Curve::Sqrt => value.sqrt(),
+        };
+
+        // Motors need a minimum duty cycle to spin up at all, so any contact starts there.
+        let min = self.min_pwm as f64;
+        let max = self.max_pwm.max(self.min_pwm) as f64;
+        (min + shaped * (max - min)).round() as u16
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct SerialHapticsConfig {
+    port: String,
+    baud_rate: u32,
+    format: String,
+    channels: Vec<ChannelConfig>,
+}
+
+impl Default for SerialHapticsConfig {
+    fn default() -> Self {
+        Self {
+            port: "".to_string(),
+            baud_rate: 115200,
+            format: "{channel}:{value}\n".to_string(),
+            channels: vec![],
+        }
+    }
+}
+
+impl SerialHapticsConfig {
+    fn line(&self, channel: u8, pwm: u16) -> String {
+        self.format
+            .replace("{channel}", &channel.to_string())
+            .replace("{value}", &pwm.to_string())
+    }
+}
+
+/// Why the board is no longer driven.
+enum Stop {
+    Shutdown,
+    Lost(std::io::Error),
+}
+
+/// Writes PWM values for contact parameters over a serial port, for DIY haptics which do not speak
+/// OSC themselves. Every change is written as a line built from the format, in which `{channel}`
+/// and `{value}` get replaced.
+#[derive(Default)]
+pub struct SerialHaptics;
+
+impl SerialHaptics {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Writes a PWM value of 0 to every channel, so no motor keeps running once nothing drives
+    /// it anymore.
+    async fn stop_motors(config: &SerialHapticsConfig, port: &mut SerialStream) -> Result<()> {
+        let mut stopped = HashSet::new();
+
+        for channel in &config.channels {
+            if stopped.insert(channel.channel) {
+                port.write_all(config.line(channel.channel, 0).as_bytes())
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Keeps driving the board, waiting for it to return whenever it is unplugged. The motors are
+    /// stopped on every way out, as the board keeps running them at the last value written.
+    async fn handle(
+        config: SerialHapticsConfig,
+        mut rx: OscReceiver,
+        device: DeviceStatus,
+        subsys: &SubsystemHandle,
+    ) -> Result<()> {
+        let config = &config;
+
+        loop {
+            let mut port = select! {
+                port = device.connect(|| async move {
+                    tokio_serial::new(&config.port, config.baud_rate)
+                        .open_native_async()
+                        .with_context(|| format!("Failed to open serial port {}", config.port))
+                }) => port,
+                _ = subsys.on_shutdown_requested() => return Ok(()),
+            };
+            info!(
+                "Driving haptics on {} at {} baud",
+                config.port, config.baud_rate
+            );
+
+            // A board which kept power while the port was gone still runs the motors at the last
+            // value it got, and whatever arrived meanwhile is outdated by now.
+            if let Err(error) = Self::stop_motors(config, &mut port).await {
+                warn!("Failed to stop the motors on {}: {:#}", config.port, error);
+            }
+
+            rx = rx.resubscribe();
+            let stop = Self::drive(config, &mut port, &mut rx, subsys).await;
+
+            if let Err(error) = Self::stop_motors(config, &mut port).await {
+                warn!("Failed to stop the motors on {}: {:#}", config.port, error);
+            }
+
+            match stop? {
+                Stop::Shutdown => return Ok(()),
+                Stop::Lost(error) => {
+                    warn!("Lost serial port {}: {}", config.port, error);
+                    device.set_connected(false);
+                }
+            }
+        }
+    }
+
+    /// Writes to the port until writing fails, which usually means the board was unplugged, or
+    /// the plugin is stopped.
+    async fn drive(
+        config: &SerialHapticsConfig,
+        port: &mut SerialStream,
+        rx: &mut OscReceiver,
+        subsys: &SubsystemHandle,
+    ) -> Result<Stop> {
+        let channels: HashMap<String, &ChannelConfig> = config
+            .channels
+            .iter()
+            .map(|channel| (addresses::parameter_address(&channel.parameter), channel))
+            .collect();
+        let mut last_pwm: HashMap<u8, u16> = HashMap::new();
+
+        loop {
+            let message = select! {
+                message = rx.recv() => match message {
+                    Ok(message) => message,
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!(
+                            "Serial haptics lagging behind, {} messages have been dropped",
+                            skipped
+                        );
+                        continue;
+                    }
+                    Err(RecvError::Closed) => bail!("Message receiver died unexpectedly"),
+                },
+                _ = subsys.on_shutdown_requested() => return Ok(Stop::Shutdown),
+            };
+
+            let Some(channel) = channels.get(&message.addr) else {
+                continue;
+            };
+
+            let Some(value) = rules::value(&message) else {
+                continue;
+            };
+
+            let pwm = channel.pwm(value);
+
+            if last_pwm.insert(channel.channel, pwm) == Some(pwm) {
+                continue;
+            }
+
+            let line = config.line(channel.channel, pwm);
+
+            if let Err(error) = port.write_all(line.as_bytes()).await {
+                return Ok(Stop::Lost(error));
+            }
+        }
+    }
+}
+
+impl Plugin for SerialHaptics {
+    fn name(&self) -> &'static str {
+        "serial_haptics"
+    }
+
+    check_config!(SerialHapticsConfig);
+
+    fn parameters(&self, config: &PluginConfig) -> Vec<ParameterUsage> {
+        let config: SerialHapticsConfig = config.parse().unwrap_or_default();
+        config
+            .channels
+            .iter()
+            .map(|channel| ParameterUsage::read(&channel.parameter))
+            .collect()
+    }
+
+    fn run(self: Box<Self>, context: PluginContext, subsys: SubsystemHandle) -> PluginFuture {
+        Box::pin(async move {
+            let config: SerialHapticsConfig = context.config.parse()?;
+
+            if config.port.is_empty() {
+                info!("No serial port configured for haptics");
+                subsys.on_shutdown_requested().await;
+                return Ok(());
+            }
+
+            // Not cancelled on shutdown, as the motors have to be stopped first.
+            Self::handle(config, context.receiver, context.device, &subsys).await
+        })
+    }
+}
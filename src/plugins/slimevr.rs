@@ -0,0 +1,415 @@
+use anyhow::{bail, Result};
+use async_osc::OscType;
+use flatbuffers::FlatBufferBuilder;
+use futures_util::SinkExt;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use solarxr_protocol::data_feed::device_data::{DeviceData, DeviceDataMask, DeviceDataMaskArgs};
+use solarxr_protocol::data_feed::tracker::{TrackerDataMask, TrackerDataMaskArgs};
+use solarxr_protocol::data_feed::{
+    DataFeedConfig, DataFeedConfigArgs, DataFeedMessage, DataFeedMessageHeader,
+    DataFeedMessageHeaderArgs, StartDataFeed, StartDataFeedArgs,
+};
+use solarxr_protocol::datatypes::TrackerStatus;
+use solarxr_protocol::rpc::{
+    ResetRequest, ResetRequestArgs, ResetType, RpcMessage, RpcMessageHeader, RpcMessageHeaderArgs,
+};
+use solarxr_protocol::{MessageBundle, MessageBundleArgs};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::select;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::sleep;
+use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
+use tokio_stream::StreamExt;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+use vrc_osc_manager_core::addresses::parameter_address;
+use vrc_osc_manager_core::check_config;
+use vrc_osc_manager_core::{
+    ConfigField, ConfigFieldKind, OscReceiver, OscSender, ParameterUsage, Plugin, PluginConfig,
+    PluginContext, PluginFuture,
+};
+
+/// Milliseconds the server waits at least between two updates of the data feed, as battery
+/// levels and connections change slowly.
+const UPDATE_INTERVAL: u16 = 1000;
+
+/// Time between attempts to connect to the server while it is not running.
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+type Socket = WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>;
+
+/// Publishes the state of a device of the SlimeVR server, found by the name it has there.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct DeviceConfig {
+    name: String,
+    /// Float parameter receiving the battery level between 0.0 and 1.0.
+    #[serde(default)]
+    battery_parameter: Option<String>,
+    /// Bool parameter telling whether the device is connected to the server.
+    #[serde(default)]
+    connected_parameter: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct SlimeVrConfig {
+    url: String,
+    devices: Vec<DeviceConfig>,
+    /// Bool parameters from the avatar menu, resetting the trackers once they turn true.
+    yaw_reset_parameter: Option<String>,
+    full_reset_parameter: Option<String>,
+}
+
+impl Default for SlimeVrConfig {
+    fn default() -> Self {
+        Self {
+            url: "ws://localhost:21110".to_string(),
+            devices: vec![],
+            yaw_reset_parameter: None,
+            full_reset_parameter: None,
+        }
+    }
+}
+
+/// Battery level and connection of a device.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct DeviceState {
+    battery: Option<f32>,
+    connected: bool,
+}
+
+/// Name of a device, which is the one given in the SlimeVR server, falling back to the one of its
+/// hardware.
+fn device_name(device: &DeviceData) -> Option<String> {
+    device
+        .custom_name()
+        .or_else(|| device.hardware_info().and_then(|info| info.display_name()))
+        .map(|name| name.to_string())
+}
+
+fn device_state(device: &DeviceData) -> DeviceState {
+    let connected = device.trackers().map_or(false, |trackers| {
+        trackers.iter().any(|tracker| {
+            matches!(
+                tracker.status(),
+                TrackerStatus::OK | TrackerStatus::BUSY | TrackerStatus::OCCLUDED
+            )
+        })
+    });
+
+    DeviceState {
+        battery: device
+            .hardware_status()
+            .and_then(|status| status.battery_pct_estimate())
+            .map(|battery| battery as f32 / 100.),
+        connected,
+    }
+}
+
+fn start_data_feed() -> Vec<u8> {
+    let mut builder = FlatBufferBuilder::new();
+
+    let tracker_data = TrackerDataMask::create(
+        &mut builder,
+        &TrackerDataMaskArgs {
+            status: true,
+            ..Default::default()
+        },
+    );
+    let data_mask = DeviceDataMask::create(
+        &mut builder,
+        &DeviceDataMaskArgs {
+            tracker_data: Some(tracker_data),
+            device_data: true,
+        },
+    );
+    let data_feed = DataFeedConfig::create(
+        &mut builder,
+        &DataFeedConfigArgs {
+            minimum_time_since_last: UPDATE_INTERVAL,
+            data_mask: Some(data_mask),
+            ..Default::default()
+        },
+    );
+    let data_feeds = builder.create_vector(&[data_feed]);
+    let start = StartDataFeed::create(
+        &mut builder,
+        &StartDataFeedArgs {
+            data_feeds: Some(data_feeds),
+        },
+    );
+    let header = DataFeedMessageHeader::create(
+        &mut builder,
+        &DataFeedMessageHeaderArgs {
+            message_type: DataFeedMessage::StartDataFeed,
+            message: Some(start.as_union_value()),
+        },
+    );
+    let data_feed_msgs = builder.create_vector(&[header]);
+    let bundle = MessageBundle::create(
+        &mut builder,
+        &MessageBundleArgs {
+            data_feed_msgs: Some(data_feed_msgs),
+            ..Default::default()
+        },
+    );
+
+    builder.finish(bundle, None);
+    builder.finished_data().to_vec()
+}
+
+fn reset(reset_type: ResetType) -> Vec<u8> {
+    let mut builder = FlatBufferBuilder::new();
+
+    let request = ResetRequest::create(
+        &mut builder,
+        &ResetRequestArgs {
+            reset_type,
+            ..Default::default()
+        },
+    );
+    let header = RpcMessageHeader::create(
+        &mut builder,
+        &RpcMessageHeaderArgs {
+            message_type: RpcMessage::ResetRequest,
+            message: Some(request.as_union_value()),
+            ..Default::default()
+        },
+    );
+    let rpc_msgs = builder.create_vector(&[header]);
+    let bundle = MessageBundle::create(
+        &mut builder,
+        &MessageBundleArgs {
+            rpc_msgs: Some(rpc_msgs),
+            ..Default::default()
+        },
+    );
+
+    builder.finish(bundle, None);
+    builder.finished_data().to_vec()
+}
+
+/// States of all devices in an update of the data feed, by their name.
+fn parse_update(data: &[u8]) -> Result<HashMap<String, DeviceState>> {
+    let bundle = flatbuffers::root::<MessageBundle>(data)?;
+    let mut states = HashMap::new();
+
+    for header in bundle.data_feed_msgs().into_iter().flatten() {
+        let Some(update) = header.message_as_data_feed_update() else {
+            continue;
+        };
+
+        for device in update.devices().into_iter().flatten() {
+            if let Some(name) = device_name(&device) {
+                states.insert(name, device_state(&device));
+            }
+        }
+    }
+
+    Ok(states)
+}
+
+/// Publishes battery levels and connections of SlimeVR trackers through the SolarXR protocol of
+/// the SlimeVR server, and lets the avatar menu reset them.
+#[derive(Default)]
+pub struct SlimeVr;
+
+impl SlimeVr {
+    pub fn new() -> Self {
+        Self
+    }
+
+    async fn publish(
+        config: &SlimeVrConfig,
+        tx: &OscSender,
+        name: &str,
+        state: DeviceState,
+    ) -> Result<()> {
+        let Some(device) = config.devices.iter().find(|device| device.name == name) else {
+            return Ok(());
+        };
+
+        if let (Some(parameter), Some(battery)) = (&device.battery_parameter, state.battery) {
+            tx.send_parameter(parameter, OscType::Float(battery))
+                .await?;
+        }
+
+        if let Some(parameter) = &device.connected_parameter {
+            tx.send_parameter(parameter, OscType::Bool(state.connected))
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Follows the data feed and sends resets until the connection to the server is lost.
+    async fn session(
+        config: &SlimeVrConfig,
+        socket: &mut Socket,
+        rx: &mut OscReceiver,
+        tx: &OscSender,
+        states: &mut HashMap<String, DeviceState>,
+    ) -> Result<()> {
+        let resets: HashMap<String, ResetType> = config
+            .yaw_reset_parameter
+            .iter()
+            .map(|parameter| (parameter_address(parameter), ResetType::Yaw))
+            .chain(
+                config
+                    .full_reset_parameter
+                    .iter()
+                    .map(|parameter| (parameter_address(parameter), ResetType::Full)),
+            )
+            .collect();
+
+        socket.send(Message::Binary(start_data_feed())).await?;
+
+        loop {
+            select! {
+                message = socket.next() => {
+                    let data = match message {
+                        Some(Ok(Message::Binary(data))) => data,
+                        Some(Ok(Message::Close(_))) | None => bail!("Connection closed"),
+                        Some(Ok(_)) => continue,
+                        Some(Err(error)) => return Err(error.into()),
+                    };
+
+                    let update = match parse_update(&data) {
+                        Ok(update) => update,
+                        Err(error) => {
+                            debug!("Dropped undecodable SlimeVR message: {}", error);
+                            continue;
+                        }
+                    };
+
+                    for (name, state) in update {
+                        if states.insert(name.clone(), state) != Some(state) {
+                            Self::publish(config, tx, &name, state).await?;
+                        }
+                    }
+                }
+                message = rx.recv() => {
+                    let message = match message {
+                        Ok(message) => message,
+                        Err(RecvError::Lagged(skipped)) => {
+                            warn!("SlimeVR lagging behind, {} messages have been dropped", skipped);
+                            continue;
+                        }
+                        Err(RecvError::Closed) => bail!("Message receiver died unexpectedly"),
+                    };
+
+                    let Some(reset_type) = resets.get(&message.addr) else {
+                        continue;
+                    };
+
+                    if let Some(OscType::Bool(true)) = message.args.first() {
+                        socket.send(Message::Binary(reset(*reset_type))).await?;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Keeps connecting to the server, which usually only runs while SteamVR does.
+    async fn handle(config: SlimeVrConfig, mut rx: OscReceiver, tx: OscSender) -> Result<()> {
+        let mut states = HashMap::new();
+
+        loop {
+            let mut socket = match connect_async(&config.url).await {
+                Ok((socket, _)) => socket,
+                Err(error) => {
+                    debug!("Failed to connect to the SlimeVR server: {}", error);
+                    sleep(RETRY_INTERVAL).await;
+                    continue;
+                }
+            };
+            info!("Connected to the SlimeVR server at {}", config.url);
+
+            // Resets pressed while the server was gone are outdated by now.
+            rx = rx.resubscribe();
+
+            if let Err(error) = Self::session(&config, &mut socket, &mut rx, &tx, &mut states).await
+            {
+                warn!("Lost the SlimeVR server: {:#}", error);
+            }
+
+            // Devices count as disconnected while the server is gone.
+            for (name, state) in states.drain() {
+                let state = DeviceState {
+                    connected: false,
+                    ..state
+                };
+                Self::publish(&config, &tx, &name, state).await?;
+            }
+        }
+    }
+}
+
+impl Plugin for SlimeVr {
+    fn name(&self) -> &'static str {
+        "slimevr"
+    }
+
+    check_config!(SlimeVrConfig);
+
+    fn config_fields(&self) -> Vec<ConfigField> {
+        vec![
+            ConfigField::new("url", "Server URL", ConfigFieldKind::String),
+            ConfigField::new(
+                "yaw_reset_parameter",
+                "Yaw reset parameter",
+                ConfigFieldKind::String,
+            ),
+            ConfigField::new(
+                "full_reset_parameter",
+                "Full reset parameter",
+                ConfigFieldKind::String,
+            ),
+        ]
+    }
+
+    fn parameters(&self, config: &PluginConfig) -> Vec<ParameterUsage> {
+        let config: SlimeVrConfig = config.parse().unwrap_or_default();
+        let writes = config.devices.iter().flat_map(|device| {
+            device
+                .battery_parameter
+                .iter()
+                .chain(device.connected_parameter.iter())
+                .map(|parameter| ParameterUsage::write(parameter))
+        });
+        let reads = config
+            .yaw_reset_parameter
+            .iter()
+            .chain(config.full_reset_parameter.iter())
+            .map(|parameter| ParameterUsage::read(parameter));
+
+        writes.chain(reads).collect()
+    }
+
+    fn run(self: Box<Self>, context: PluginContext, subsys: SubsystemHandle) -> PluginFuture {
+        Box::pin(async move {
+            let config: SlimeVrConfig = context.config.parse()?;
+
+            if config.devices.is_empty()
+                && config.yaw_reset_parameter.is_none()
+                && config.full_reset_parameter.is_none()
+            {
+                info!("No devices or reset parameters configured for SlimeVR");
+                subsys.on_shutdown_requested().await;
+                return Ok(());
+            }
+
+            match (Self::handle(config, context.receiver, context.osc).cancel_on_shutdown(&subsys))
+                .await
+            {
+                Ok(Ok(())) => subsys.request_shutdown(),
+                Ok(Err(error)) => return Err(error),
+                Err(CancelledByShutdown) => {}
+            }
+
+            Ok(())
+        })
+    }
+}
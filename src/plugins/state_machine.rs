@@ -0,0 +1,268 @@
+use crate::plugins::actions::{perform_all, Action, Condition};
+use anyhow::{bail, Result};
+use async_osc::OscMessage;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::select;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::{sleep_until, Instant};
+use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
+use tokio_util::sync::CancellationToken;
+use vrc_osc_manager_core::check_config;
+use vrc_osc_manager_core::{
+    OscReceiver, OscSender, ParameterUsage, Plugin, PluginConfig, PluginContext, PluginFuture,
+};
+
+/// Moves to another state when an incoming message meets the condition, or once the state has been
+/// active for the given time.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Transition {
+    to: String,
+    when: Option<Condition>,
+    after_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+struct State {
+    on_enter: Vec<Action>,
+    on_exit: Vec<Action>,
+    transitions: Vec<Transition>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct MachineConfig {
+    name: String,
+    initial: String,
+    states: HashMap<String, State>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+struct StateMachineConfig {
+    machines: Vec<MachineConfig>,
+}
+
+struct Machine {
+    config: MachineConfig,
+    current: String,
+    entered_at: Instant,
+}
+
+impl Machine {
+    fn new(config: MachineConfig) -> Result<Self> {
+        if !config.states.contains_key(&config.initial) {
+            bail!(
+                "Initial state {} of machine {} does not exist",
+                config.initial,
+                config.name
+            );
+        }
+
+        for (name, state) in &config.states {
+            for transition in &state.transitions {
+                if !config.states.contains_key(&transition.to) {
+                    bail!(
+                        "State {} of machine {} transitions to unknown state {}",
+                        name,
+                        config.name,
+                        transition.to
+                    );
+                }
+
+                if transition.when.is_none() && transition.after_ms.is_none() {
+                    bail!(
+                        "Transition from {} to {} of machine {} needs either when or after_ms",
+                        name,
+                        transition.to,
+                        config.name
+                    );
+                }
+            }
+        }
+
+        Ok(Self {
+            current: config.initial.clone(),
+            config,
+            entered_at: Instant::now(),
+        })
+    }
+
+    fn state(&self) -> &State {
+        &self.config.states[&self.current]
+    }
+
+    fn deadline(&self, transition: &Transition) -> Option<Instant> {
+        // Transitions with a condition only fire on messages, the time is an additional constraint.
+        if transition.when.is_some() {
+            return None;
+        }
+
+        transition
+            .after_ms
+            .map(|after_ms| self.entered_at + Duration::from_millis(after_ms))
+    }
+
+    fn next_deadline(&self) -> Option<Instant> {
+        self.state()
+            .transitions
+            .iter()
+            .filter_map(|transition| self.deadline(transition))
+            .min()
+    }
+
+    fn enter(&mut self, to: String, tx: &OscSender, token: &CancellationToken) {
+        perform_all(tx, &self.state().on_exit, token);
+        info!(
+            "State machine {} moves from {} to {}",
+            self.config.name, self.current, to
+        );
+        self.current = to;
+        self.entered_at = Instant::now();
+        perform_all(tx, &self.state().on_enter, token);
+    }
+
+    fn handle_message(&self, message: &OscMessage) -> Option<String> {
+        let now = Instant::now();
+
+        self.state()
+            .transitions
+            .iter()
+            .find(|transition| {
+                transition
+                    .when
+                    .as_ref()
+                    .map_or(false, |when| when.is_met(message))
+                    && transition.after_ms.map_or(true, |after_ms| {
+                        now >= self.entered_at + Duration::from_millis(after_ms)
+                    })
+            })
+            .map(|transition| transition.to.clone())
+    }
+
+    fn handle_timer(&self) -> Option<String> {
+        let now = Instant::now();
+
+        self.state()
+            .transitions
+            .iter()
+            .find(|transition| {
+                self.deadline(transition)
+                    .map_or(false, |deadline| now >= deadline)
+            })
+            .map(|transition| transition.to.clone())
+    }
+}
+
+/// Runs user-defined state machines, sending OSC messages when entering and exiting states.
+#[derive(Default)]
+pub struct StateMachine;
+
+impl StateMachine {
+    pub fn new() -> Self {
+        Self
+    }
+
+    async fn handle(
+        mut machines: Vec<Machine>,
+        tx: OscSender,
+        mut rx: OscReceiver,
+        token: CancellationToken,
+    ) -> Result<()> {
+        for machine in &machines {
+            perform_all(&tx, &machine.state().on_enter, &token);
+        }
+
+        loop {
+            let deadline = machines.iter().filter_map(Machine::next_deadline).min();
+
+            select! {
+                result = rx.recv() => {
+                    let message = match result {
+                        Ok(message) => message,
+                        Err(RecvError::Lagged(skipped)) => {
+                            warn!("State machine lagging behind, {} messages have been dropped", skipped);
+                            continue;
+                        }
+                        Err(RecvError::Closed) => bail!("Message receiver died unexpectedly"),
+                    };
+
+                    for machine in &mut machines {
+                        if let Some(to) = machine.handle_message(&message) {
+                            machine.enter(to, &tx, &token);
+                        }
+                    }
+                }
+                _ = sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                    for machine in &mut machines {
+                        if let Some(to) = machine.handle_timer() {
+                            machine.enter(to, &tx, &token);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Plugin for StateMachine {
+    fn name(&self) -> &'static str {
+        "state_machine"
+    }
+
+    check_config!(StateMachineConfig);
+
+    fn parameters(&self, config: &PluginConfig) -> Vec<ParameterUsage> {
+        let config: StateMachineConfig = config.parse().unwrap_or_default();
+        let mut usages = vec![];
+
+        for state in config
+            .machines
+            .iter()
+            .flat_map(|machine| machine.states.values())
+        {
+            usages.extend(
+                state
+                    .on_enter
+                    .iter()
+                    .chain(&state.on_exit)
+                    .map(Action::usage),
+            );
+            usages.extend(
+                state
+                    .transitions
+                    .iter()
+                    .filter_map(|transition| transition.when.as_ref())
+                    .map(Condition::usage),
+            );
+        }
+
+        usages
+    }
+
+    fn run(self: Box<Self>, context: PluginContext, subsys: SubsystemHandle) -> PluginFuture {
+        Box::pin(async move {
+            let config: StateMachineConfig = context.config.parse()?;
+            let machines = config
+                .machines
+                .into_iter()
+                .map(Machine::new)
+                .collect::<Result<Vec<_>>>()?;
+            let token = CancellationToken::new();
+            let _guard = token.clone().drop_guard();
+
+            match (Self::handle(machines, context.osc, context.receiver, token)
+                .cancel_on_shutdown(&subsys))
+            .await
+            {
+                Ok(Ok(())) => subsys.request_shutdown(),
+                Ok(Err(error)) => return Err(error),
+                Err(CancelledByShutdown) => {}
+            }
+
+            Ok(())
+        })
+    }
+}
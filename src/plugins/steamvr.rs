@@ -0,0 +1,327 @@
+use anyhow::{bail, Context as _, Result};
+use async_osc::OscType;
+use log::{debug, info, warn};
+use openvr::system::event::Event;
+use openvr::{
+    property, ApplicationType, TrackedControllerRole, TrackedDeviceClass, TrackedDeviceIndex,
+    MAX_TRACKED_DEVICE_COUNT,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+use tokio::select;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc as async_mpsc;
+use tokio::task::spawn_blocking;
+use tokio_graceful_shutdown::SubsystemHandle;
+use vrc_osc_manager_core::addresses::parameter_address;
+use vrc_osc_manager_core::check_config;
+use vrc_osc_manager_core::{
+    ConfigField, ConfigFieldKind, OscReceiver, OscSender, ParameterUsage, Plugin, PluginConfig,
+    PluginContext, PluginFuture,
+};
+
+/// Longest pulse OpenVR accepts at once, so longer ones are made of repeated pulses.
+const PULSE: Duration = Duration::from_micros(3999);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct SteamVrConfig {
+    /// Seconds between two readings of the battery levels.
+    interval_seconds: u64,
+    /// Float parameter receiving the battery level of every device between 0.0 and 1.0, where
+    /// {role} is replaced with the role of the device, like `left_foot`.
+    battery_parameter: Option<String>,
+    /// Int parameter from the avatar menu, where a value of n pulses the n-th of `identify_roles`.
+    identify_parameter: Option<String>,
+    identify_roles: Vec<String>,
+    identify_seconds: f64,
+}
+
+impl Default for SteamVrConfig {
+    fn default() -> Self {
+        Self {
+            interval_seconds: 30,
+            battery_parameter: Some("Battery_{role}".to_string()),
+            identify_parameter: Some("IdentifyTracker".to_string()),
+            identify_roles: vec![],
+            identify_seconds: 1.,
+        }
+    }
+}
+
+/// Battery levels by the role of their device.
+type Batteries = HashMap<String, f32>;
+
+/// Role of a tracked device, as used in the names of its parameters. Trackers get theirs from
+/// the role assigned in the SteamVR tracker management, falling back to their serial number.
+fn role(system: &openvr::System, index: TrackedDeviceIndex) -> Option<String> {
+    match system.tracked_device_class(index) {
+        TrackedDeviceClass::HMD => Some("head".to_string()),
+        TrackedDeviceClass::Controller => {
+            match system.get_controller_role_for_tracked_device_index(index) {
+                Some(TrackedControllerRole::LeftHand) => Some("left_hand".to_string()),
+                Some(TrackedControllerRole::RightHand) => Some("right_hand".to_string()),
+                None => None,
+            }
+        }
+        TrackedDeviceClass::GenericTracker => {
+            let controller_type = system
+                .string_tracked_device_property(index, property::ControllerType_String)
+                .ok()
+                .and_then(|value| value.into_string().ok());
+
+            match controller_type
+                .as_deref()
+                .and_then(|value| value.strip_prefix("vive_tracker_"))
+            {
+                Some(role) => Some(role.to_string()),
+                _ => system
+                    .string_tracked_device_property(index, property::SerialNumber_String)
+                    .ok()
+                    .and_then(|value| value.into_string().ok()),
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Connected devices by their role.
+fn devices(system: &openvr::System) -> HashMap<String, TrackedDeviceIndex> {
+    (0..MAX_TRACKED_DEVICE_COUNT as TrackedDeviceIndex)
+        .filter(|index| system.is_tracked_device_connected(*index))
+        .filter_map(|index| role(system, index).map(|role| (role, index)))
+        .collect()
+}
+
+fn batteries(system: &openvr::System) -> Batteries {
+    devices(system)
+        .into_iter()
+        .filter_map(|(role, index)| {
+            system
+                .float_tracked_device_property(index, property::DeviceBatteryPercentage_Float)
+                .ok()
+                .map(|battery| (role, battery))
+        })
+        .collect()
+}
+
+/// Vibrates the device with the given role, which does nothing for trackers without a motor.
+fn identify(system: &openvr::System, role: &str, duration: Duration) {
+    let Some(index) = devices(system).get(role).copied() else {
+        warn!("No device with the role {} to identify", role);
+        return;
+    };
+
+    let start = Instant::now();
+
+    while start.elapsed() < duration {
+        system.trigger_haptic_pulse(index, 0, PULSE.as_micros() as u16);
+        std::thread::sleep(PULSE + Duration::from_millis(1));
+    }
+}
+
+/// Reads the battery levels and identifies devices until SteamVR quits or the plugin stops and
+/// drops the sender of the roles to identify. OpenVR is not thread safe, so all of it happens on
+/// this one thread.
+fn track(
+    interval: Duration,
+    identify_duration: Duration,
+    roles: &mpsc::Receiver<String>,
+    readings: &async_mpsc::Sender<Batteries>,
+) -> Result<()> {
+    let context =
+        unsafe { openvr::init(ApplicationType::Background) }.context("SteamVR is not running")?;
+    let system = context.system()?;
+    info!("Connected to SteamVR");
+
+    let mut next_reading = Instant::now();
+
+    loop {
+        while let Some(event) = system.poll_next_event() {
+            if let Event::Quit(_) = event.event {
+                system.acknowledge_quit_exiting();
+                bail!("SteamVR quit");
+            }
+        }
+
+        if Instant::now() >= next_reading {
+            if readings.blocking_send(batteries(&system)).is_err() {
+                return Ok(());
+            }
+
+            next_reading = Instant::now() + interval;
+        }
+
+        // Woken up regularly, as SteamVR waits for background applications to acknowledge that
+        // it quits.
+        match roles.recv_timeout(Duration::from_secs(1)) {
+            Ok(role) => identify(&system, &role, identify_duration),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        }
+    }
+}
+
+/// Publishes the battery levels of the headset, controllers and trackers in SteamVR, and lets the
+/// avatar menu vibrate a tracker to tell which one it is.
+#[derive(Default)]
+pub struct SteamVr;
+
+impl SteamVr {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Keeps tracking on a blocking thread, connecting again whenever SteamVR comes back.
+    async fn connect(
+        config: SteamVrConfig,
+        roles: mpsc::Receiver<String>,
+        readings: async_mpsc::Sender<Batteries>,
+    ) -> Result<()> {
+        let interval = Duration::from_secs(config.interval_seconds.max(1));
+        let identify_duration = Duration::from_secs_f64(config.identify_seconds.clamp(0., 10.));
+
+        spawn_blocking(move || loop {
+            match track(interval, identify_duration, &roles, &readings) {
+                Ok(()) => return,
+                Err(error) => debug!("Not tracking SteamVR devices: {:#}", error),
+            }
+
+            // Waits before trying again, dropping the roles to identify meanwhile, as there is
+            // nothing to vibrate without SteamVR.
+            loop {
+                match roles.recv_timeout(interval) {
+                    Ok(_) => {}
+                    Err(RecvTimeoutError::Timeout) => break,
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+            }
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    async fn handle(
+        config: SteamVrConfig,
+        mut rx: OscReceiver,
+        tx: OscSender,
+        subsys: &SubsystemHandle,
+    ) -> Result<()> {
+        let identify_address = config.identify_parameter.as_deref().map(parameter_address);
+        let (roles_tx, roles_rx) = mpsc::channel();
+        let (readings_tx, mut readings_rx) = async_mpsc::channel(1);
+        let tracking = tokio::spawn(Self::connect(config.clone(), roles_rx, readings_tx));
+        let mut published: Batteries = HashMap::new();
+
+        loop {
+            select! {
+                readings = readings_rx.recv() => {
+                    let Some(readings) = readings else {
+                        break;
+                    };
+
+                    let Some(template) = &config.battery_parameter else {
+                        continue;
+                    };
+
+                    // Devices which went away would otherwise keep showing their last level.
+                    for role in published.keys() {
+                        if !readings.contains_key(role) {
+                            tx.send_parameter(&template.replace("{role}", role), OscType::Float(0.))
+                                .await?;
+                        }
+                    }
+
+                    for (role, battery) in &readings {
+                        tx.send_parameter(&template.replace("{role}", role), OscType::Float(*battery))
+                            .await?;
+                    }
+
+                    published = readings;
+                }
+                message = rx.recv() => {
+                    let message = match message {
+                        Ok(message) => message,
+                        Err(RecvError::Lagged(skipped)) => {
+                            warn!("SteamVR lagging behind, {} messages have been dropped", skipped);
+                            continue;
+                        }
+                        Err(RecvError::Closed) => bail!("Message receiver died unexpectedly"),
+                    };
+
+                    if identify_address.as_deref() != Some(message.addr.as_str()) {
+                        continue;
+                    }
+
+                    let Some(OscType::Int(value)) = message.args.first() else {
+                        continue;
+                    };
+
+                    if let Some(role) = usize::try_from(*value)
+                        .ok()
+                        .and_then(|value| value.checked_sub(1))
+                        .and_then(|index| config.identify_roles.get(index))
+                    {
+                        let _ = roles_tx.send(role.clone());
+                    }
+                }
+                _ = subsys.on_shutdown_requested() => break,
+            }
+        }
+
+        drop(roles_tx);
+        drop(readings_rx);
+        tracking.await?
+    }
+}
+
+impl Plugin for SteamVr {
+    fn name(&self) -> &'static str {
+        "steamvr"
+    }
+
+    check_config!(SteamVrConfig);
+
+    fn config_fields(&self) -> Vec<ConfigField> {
+        vec![
+            ConfigField::new("interval_seconds", "Interval", ConfigFieldKind::Integer),
+            ConfigField::new(
+                "identify_seconds",
+                "Identification duration",
+                ConfigFieldKind::Float,
+            ),
+        ]
+    }
+
+    fn parameters(&self, config: &PluginConfig) -> Vec<ParameterUsage> {
+        let config: SteamVrConfig = config.parse().unwrap_or_default();
+        config
+            .battery_parameter
+            .iter()
+            .map(|template| ParameterUsage::write(&template.replace("{role}", "*")))
+            .chain(
+                config
+                    .identify_parameter
+                    .iter()
+                    .map(|parameter| ParameterUsage::read(parameter)),
+            )
+            .collect()
+    }
+
+    fn run(self: Box<Self>, context: PluginContext, subsys: SubsystemHandle) -> PluginFuture {
+        Box::pin(async move {
+            let config: SteamVrConfig = context.config.parse()?;
+
+            if config.battery_parameter.is_none() && config.identify_parameter.is_none() {
+                info!("No parameters configured for SteamVR");
+                subsys.on_shutdown_requested().await;
+                return Ok(());
+            }
+
+            Self::handle(config, context.receiver, context.osc, &subsys).await
+        })
+    }
+}
@@ -0,0 +1,118 @@
+use crate::plugins::actions::{perform_all, Action, Condition};
+use anyhow::{bail, Result};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use tokio::sync::broadcast::error::RecvError;
+use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
+use tokio_util::sync::CancellationToken;
+use vrc_osc_manager_core::check_config;
+use vrc_osc_manager_core::{
+    Events, OscReceiver, OscSender, ParameterUsage, Plugin, PluginConfig, PluginContext,
+    PluginFuture,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Trigger {
+    when: Condition,
+    then: Vec<Action>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+struct TriggersConfig {
+    rules: Vec<Trigger>,
+}
+
+/// Performs actions when incoming messages start meeting a condition. A trigger fires once per
+/// address when its condition becomes met, and again only after it stopped being met in between.
+#[derive(Default)]
+pub struct Triggers;
+
+impl Triggers {
+    pub fn new() -> Self {
+        Self
+    }
+
+    async fn handle(
+        config: TriggersConfig,
+        tx: OscSender,
+        mut rx: OscReceiver,
+        events: Events,
+        token: CancellationToken,
+    ) -> Result<()> {
+        let mut met: Vec<HashSet<String>> = vec![HashSet::new(); config.rules.len()];
+
+        loop {
+            let message = match rx.recv().await {
+                Ok(message) => message,
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!(
+                        "Triggers lagging behind, {} messages have been dropped",
+                        skipped
+                    );
+                    continue;
+                }
+                Err(RecvError::Closed) => bail!("Message receiver died unexpectedly"),
+            };
+
+            for (index, (trigger, met)) in config.rules.iter().zip(met.iter_mut()).enumerate() {
+                if !trigger.when.matches_address(&message.addr) {
+                    continue;
+                }
+
+                if !trigger.when.is_met(&message) {
+                    met.remove(&message.addr);
+                } else if met.insert(message.addr.clone()) {
+                    events.record(
+                        "trigger_fired",
+                        vec![
+                            ("rule", index.to_string()),
+                            ("address", message.addr.clone()),
+                        ],
+                    );
+                    perform_all(&tx, &trigger.then, &token);
+                }
+            }
+        }
+    }
+}
+
+impl Plugin for Triggers {
+    fn name(&self) -> &'static str {
+        "triggers"
+    }
+
+    check_config!(TriggersConfig);
+
+    fn parameters(&self, config: &PluginConfig) -> Vec<ParameterUsage> {
+        let config: TriggersConfig = config.parse().unwrap_or_default();
+        let mut usages = vec![];
+
+        for rule in &config.rules {
+            usages.push(rule.when.usage());
+            usages.extend(rule.then.iter().map(Action::usage));
+        }
+
+        usages
+    }
+
+    fn run(self: Box<Self>, context: PluginContext, subsys: SubsystemHandle) -> PluginFuture {
+        Box::pin(async move {
+            let config: TriggersConfig = context.config.parse()?;
+            let token = CancellationToken::new();
+            let _guard = token.clone().drop_guard();
+
+            match (Self::handle(config, context.osc, context.receiver, context.events, token)
+                .cancel_on_shutdown(&subsys))
+            .await
+            {
+                Ok(Ok(())) => subsys.request_shutdown(),
+                Ok(Err(error)) => return Err(error),
+                Err(CancelledByShutdown) => {}
+            }
+
+            Ok(())
+        })
+    }
+}
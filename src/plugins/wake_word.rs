@@ -0,0 +1,148 @@
+use crate::plugins::actions::{perform_all, Action};
+use anyhow::{anyhow, bail, Result};
+use log::info;
+use porcupine::PorcupineBuilder;
+use pv_recorder::PvRecorderBuilder;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tokio::sync::mpsc;
+use tokio::task::spawn_blocking;
+use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
+use tokio_util::sync::CancellationToken;
+use vrc_osc_manager_core::check_config;
+use vrc_osc_manager_core::{OscSender, Plugin, PluginConfig, PluginContext, PluginFuture};
+
+fn default_sensitivity() -> f32 {
+    0.5
+}
+
+/// A phrase trained through the Picovoice console, and the actions to perform when it is heard.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PhraseConfig {
+    keyword_path: String,
+    #[serde(default = "default_sensitivity")]
+    sensitivity: f32,
+    then: Vec<Action>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+struct WakeWordConfig {
+    access_key: String,
+    device_index: i32,
+    phrases: Vec<PhraseConfig>,
+}
+
+/// Listens to the microphone with the local Porcupine wake word engine, and performs the actions
+/// of a phrase whenever it is heard. Audio never leaves the machine.
+#[derive(Default)]
+pub struct WakeWord;
+
+impl WakeWord {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Runs the engine on a blocking thread, reporting the index of every phrase heard, until the
+    /// receiving end is dropped.
+    fn listen(config: &WakeWordConfig, tx: mpsc::Sender<usize>) -> Result<()> {
+        let keyword_paths: Vec<_> = config
+            .phrases
+            .iter()
+            .map(|phrase| phrase.keyword_path.clone())
+            .collect();
+        let sensitivities: Vec<_> = config
+            .phrases
+            .iter()
+            .map(|phrase| phrase.sensitivity)
+            .collect();
+
+        let porcupine =
+            PorcupineBuilder::new_with_keyword_paths(&config.access_key, &keyword_paths)
+                .sensitivities(&sensitivities)
+                .init()
+                .map_err(|error| anyhow!("Failed to initialize Porcupine: {}", error))?;
+        let recorder = PvRecorderBuilder::new(porcupine.frame_length() as i32)
+            .device_index(config.device_index)
+            .init()
+            .map_err(|error| anyhow!("Failed to open microphone: {}", error))?;
+        recorder
+            .start()
+            .map_err(|error| anyhow!("Failed to start recording: {}", error))?;
+
+        while !tx.is_closed() {
+            let frame = recorder
+                .read()
+                .map_err(|error| anyhow!("Failed to read from microphone: {}", error))?;
+            let index = porcupine
+                .process(&frame)
+                .map_err(|error| anyhow!("Failed to process audio: {}", error))?;
+
+            if index >= 0 {
+                let _ = tx.blocking_send(index as usize);
+            }
+        }
+
+        let _ = recorder.stop();
+        Ok(())
+    }
+
+    async fn handle(config: WakeWordConfig, tx: OscSender, token: CancellationToken) -> Result<()> {
+        let (phrase_tx, mut phrase_rx) = mpsc::channel(4);
+        let listen_config = config.clone();
+        let listener = spawn_blocking(move || Self::listen(&listen_config, phrase_tx));
+
+        while let Some(index) = phrase_rx.recv().await {
+            let phrase = &config.phrases[index];
+            info!("Heard phrase {}", phrase.keyword_path);
+            perform_all(&tx, &phrase.then, &token);
+        }
+
+        listener.await??;
+        bail!("Wake word listener stopped unexpectedly");
+    }
+}
+
+impl Plugin for WakeWord {
+    fn name(&self) -> &'static str {
+        "wake_word"
+    }
+
+    check_config!(WakeWordConfig);
+
+    /// A missing keyword file would only be noticed by Porcupine, with a less helpful error.
+    fn setup(&mut self, config: &PluginConfig) -> Result<()> {
+        let config: WakeWordConfig = config.parse()?;
+
+        for phrase in &config.phrases {
+            if !Path::new(&phrase.keyword_path).is_file() {
+                bail!("Keyword file {} not found", phrase.keyword_path);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn run(self: Box<Self>, context: PluginContext, subsys: SubsystemHandle) -> PluginFuture {
+        Box::pin(async move {
+            let config: WakeWordConfig = context.config.parse()?;
+
+            if config.access_key.is_empty() || config.phrases.is_empty() {
+                info!("No access key or phrases configured for wake words");
+                subsys.on_shutdown_requested().await;
+                return Ok(());
+            }
+
+            let token = CancellationToken::new();
+            let _guard = token.clone().drop_guard();
+
+            match (Self::handle(config, context.osc, token).cancel_on_shutdown(&subsys)).await {
+                Ok(Ok(())) => subsys.request_shutdown(),
+                Ok(Err(error)) => return Err(error),
+                Err(CancelledByShutdown) => {}
+            }
+
+            Ok(())
+        })
+    }
+}
@@ -1,51 +1,124 @@
-use anyhow::Result;
-use async_osc::{OscMessage, OscType};
+use crate::bitpack;
+use crate::config::{Config, WatchConfig, WatchMode};
+use async_osc::OscType;
 use chrono::{Local, Timelike};
+use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::mpsc;
-use tokio::time::sleep;
 use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
+use vrc_osc_manager_core::check_config;
+use vrc_osc_manager_core::{
+    Activity, Chatbox, ConfigField, ConfigFieldKind, OscSender, ParameterUsage, Plugin,
+    PluginConfig, PluginContext, PluginFuture,
+};
+
+async fn send_bits(tx: &OscSender, parameters: &[String], value: u32) {
+    for message in bitpack::encode(parameters, value) {
+        let _ = tx.send(message).await;
+    }
+}
 
 pub struct Watch {
-    tx: mpsc::Sender<OscMessage>,
+    config: Arc<Config>,
 }
 
 impl Watch {
-    pub fn new(tx: mpsc::Sender<OscMessage>) -> Self {
-        Self { tx }
+    pub fn new(config: Arc<Config>) -> Self {
+        Self { config }
     }
 
-    async fn send_time(&mut self) {
+    async fn send_time(&self, tx: &OscSender, chatbox: &Chatbox, mut activity: Activity) {
+        let watch_config = &self.config.watch;
+
         loop {
             let now = Local::now();
-            let hour = ((now.hour() % 12) as f32 + now.minute() as f32 / 60.) / 6. - 1.;
-            let minute = (now.minute() as f32 + now.second() as f32 / 60.) / 30. - 1.;
-
-            let _ = self
-                .tx
-                .send(OscMessage {
-                    addr: "/avatar/parameters/RMBA_WatchHours".to_string(),
-                    args: vec![OscType::Float(hour)],
-                })
-                .await;
-            let _ = self
-                .tx
-                .send(OscMessage {
-                    addr: "/avatar/parameters/RMBA_WatchMinutes".to_string(),
-                    args: vec![OscType::Float(minute)],
-                })
-                .await;
-
-            sleep(Duration::from_secs(10)).await;
+
+            if let Some(template) = &watch_config.chatbox {
+                let time = if watch_config.twenty_four_hour {
+                    now.format("%H:%M")
+                } else {
+                    now.format("%-I:%M %p")
+                };
+                chatbox.set_line(&template.replace("{time}", &time.to_string()));
+            }
+
+            match watch_config.mode {
+                WatchMode::Float => {
+                    let hour = ((now.hour() % 12) as f32 + now.minute() as f32 / 60.) / 6. - 1.;
+                    let minute = (now.minute() as f32 + now.second() as f32 / 60.) / 30. - 1.;
+
+                    let _ = tx
+                        .send_parameter("RMBA_WatchHours", OscType::Float(hour))
+                        .await;
+                    let _ = tx
+                        .send_parameter("RMBA_WatchMinutes", OscType::Float(minute))
+                        .await;
+                }
+                WatchMode::Bits => {
+                    let hour = if watch_config.twenty_four_hour {
+                        now.hour()
+                    } else {
+                        now.hour() % 12
+                    };
+
+                    send_bits(tx, &watch_config.hour_parameters, hour).await;
+                    send_bits(tx, &watch_config.minute_parameters, now.minute()).await;
+                }
+            }
+
+            activity.sleep(Duration::from_secs(10)).await;
         }
     }
+}
 
-    pub async fn run(mut self, subsys: SubsystemHandle) -> Result<()> {
-        match (self.send_time().cancel_on_shutdown(&subsys)).await {
-            Ok(()) => subsys.request_shutdown(),
-            Err(CancelledByShutdown) => {}
+impl Plugin for Watch {
+    fn name(&self) -> &'static str {
+        "watch"
+    }
+
+    fn parameters(&self, _config: &PluginConfig) -> Vec<ParameterUsage> {
+        let watch_config = &self.config.watch;
+
+        match watch_config.mode {
+            WatchMode::Float => vec![
+                ParameterUsage::write("RMBA_WatchHours"),
+                ParameterUsage::write("RMBA_WatchMinutes"),
+            ],
+            WatchMode::Bits => watch_config
+                .hour_parameters
+                .iter()
+                .chain(&watch_config.minute_parameters)
+                .map(|parameter| ParameterUsage::write(parameter))
+                .collect(),
         }
+    }
+
+    fn config_section(&self) -> String {
+        "watch".to_string()
+    }
+
+    check_config!(WatchConfig);
+
+    fn config_fields(&self) -> Vec<ConfigField> {
+        vec![
+            ConfigField::new("mode", "Mode", ConfigFieldKind::Choice(&["float", "bits"])).menu(),
+            ConfigField::new("twenty_four_hour", "24-hour format", ConfigFieldKind::Bool).menu(),
+        ]
+    }
+
+    fn run(self: Box<Self>, context: PluginContext, subsys: SubsystemHandle) -> PluginFuture {
+        Box::pin(async move {
+            let chatbox = context.chatbox();
+
+            match (self
+                .send_time(&context.osc, &chatbox, context.activity.clone())
+                .cancel_on_shutdown(&subsys))
+            .await
+            {
+                Ok(()) => subsys.request_shutdown(),
+                Err(CancelledByShutdown) => {}
+            }
 
-        Ok(())
+            Ok(())
+        })
     }
 }
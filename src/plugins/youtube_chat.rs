@@ -0,0 +1,435 @@
+use crate::plugins::actions::{perform_all, Action};
+use anyhow::{Context, Result};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::{sleep, Instant};
+use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
+use tokio_util::sync::CancellationToken;
+use vrc_osc_manager_core::check_config;
+use vrc_osc_manager_core::{
+    Chatbox, ConfigField, ConfigFieldKind, Events, OscSender, ParameterUsage, Plugin, PluginConfig,
+    PluginContext, PluginFuture, Readiness,
+};
+
+const API: &str = "https://www.googleapis.com/youtube/v3";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Command {
+    /// Text a chat message has to start with, like `!boop`.
+    command: String,
+    #[serde(default)]
+    actions: Vec<Action>,
+    /// Chatbox message, where {author} and {text} are replaced.
+    chatbox: Option<String>,
+    /// Seconds after which the command can be used again by anyone.
+    #[serde(default)]
+    cooldown_seconds: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SuperChatTier {
+    /// Smallest amount reaching this tier, regardless of the currency.
+    min_amount: f64,
+    #[serde(default)]
+    actions: Vec<Action>,
+    /// Chatbox message, where {author}, {amount} and {text} are replaced.
+    chatbox: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct YoutubeChatConfig {
+    api_key: String,
+    /// ID of the video of the broadcast, as found in its link after `v=`.
+    video_id: String,
+    /// Seconds between two polls at least, as every poll costs 5 of the 10000 units of the daily
+    /// API quota. The default lasts for a whole day.
+    min_interval_seconds: u64,
+    /// Commands reacted to, all other messages are ignored.
+    commands: Vec<Command>,
+    /// Seconds after which the same author can use a command again.
+    user_cooldown_seconds: u64,
+    /// Reactions to super chats, of which the highest tier reached is performed.
+    super_chats: Vec<SuperChatTier>,
+}
+
+impl Default for YoutubeChatConfig {
+    fn default() -> Self {
+        Self {
+            api_key: "".to_string(),
+            video_id: "".to_string(),
+            min_interval_seconds: 45,
+            commands: vec![],
+            user_cooldown_seconds: 30,
+            super_chats: vec![],
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VideoList {
+    items: Vec<Video>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Video {
+    live_streaming_details: Option<LiveStreamingDetails>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LiveStreamingDetails {
+    active_live_chat_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MessageList {
+    next_page_token: Option<String>,
+    polling_interval_millis: u64,
+    items: Vec<Message>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Message {
+    snippet: Snippet,
+    author_details: AuthorDetails,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Snippet {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    display_message: String,
+    super_chat_details: Option<SuperChatDetails>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SuperChatDetails {
+    /// Amount in millionths of the currency, as string.
+    amount_micros: String,
+    amount_display_string: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AuthorDetails {
+    channel_id: String,
+    display_name: String,
+}
+
+/// Reacts to commands and super chats in the live chat of a YouTube broadcast on the avatar and
+/// in the chatbox.
+#[derive(Default)]
+pub struct YoutubeChat;
+
+/// When commands were used last, overall and by author.
+#[derive(Default)]
+struct Cooldowns {
+    commands: HashMap<usize, Instant>,
+    authors: HashMap<String, Instant>,
+}
+
+impl Cooldowns {
+    /// Whether the command is available to the author, in which case its use is recorded.
+    fn take(&mut self, config: &YoutubeChatConfig, index: usize, author: &str) -> bool {
+        let now = Instant::now();
+        let command_cooldown = Duration::from_secs(config.commands[index].cooldown_seconds);
+        let user_cooldown = Duration::from_secs(config.user_cooldown_seconds);
+
+        if self
+            .commands
+            .get(&index)
+            .map_or(false, |used| now - *used < command_cooldown)
+            || self
+                .authors
+                .get(author)
+                .map_or(false, |used| now - *used < user_cooldown)
+        {
+            return false;
+        }
+
+        self.commands.insert(index, now);
+        self.authors.insert(author.to_string(), now);
+        true
+    }
+}
+
+struct Reactor {
+    config: YoutubeChatConfig,
+    tx: OscSender,
+    chatbox: Chatbox,
+    events: Events,
+    token: CancellationToken,
+    cooldowns: Cooldowns,
+}
+
+impl Reactor {
+    fn command(&mut self, message: &Message) {
+        let text = message.snippet.display_message.trim();
+        let Some(index) =
+            self.config.commands.iter().position(|command| {
+                text.split_whitespace().next() == Some(command.command.as_str())
+            })
+        else {
+            return;
+        };
+
+        let author = &message.author_details;
+
+        if !self.cooldowns.take(&self.config, index, &author.channel_id) {
+            debug!("{} is cooling down", text);
+            return;
+        }
+
+        let command = &self.config.commands[index];
+        info!("{} used {}", author.display_name, command.command);
+        self.events.record(
+            "chat_command",
+            vec![
+                ("author", author.display_name.clone()),
+                ("command", command.command.clone()),
+            ],
+        );
+        perform_all(&self.tx, &command.actions, &self.token);
+
+        if let Some(template) = &command.chatbox {
+            self.chatbox.send(
+                &template
+                    .replace("{author}", &author.display_name)
+                    .replace("{text}", text),
+                false,
+            );
+        }
+    }
+
+    fn super_chat(&self, message: &Message, details: &SuperChatDetails) -> Result<()> {
+        let amount = details
+            .amount_micros
+            .parse::<f64>()
+            .context("Invalid super chat amount")?
+            / 1_000_000.;
+        let author = &message.author_details.display_name;
+        info!(
+            "{} sent a super chat of {}",
+            author, details.amount_display_string
+        );
+        self.events.record(
+            "super_chat",
+            vec![
+                ("author", author.clone()),
+                ("amount", details.amount_display_string.clone()),
+            ],
+        );
+
+        let Some(tier) = self
+            .config
+            .super_chats
+            .iter()
+            .filter(|tier| amount >= tier.min_amount)
+            .max_by(|a, b| a.min_amount.total_cmp(&b.min_amount))
+        else {
+            return Ok(());
+        };
+
+        perform_all(&self.tx, &tier.actions, &self.token);
+
+        if let Some(template) = &tier.chatbox {
+            self.chatbox.send(
+                &template
+                    .replace("{author}", author)
+                    .replace("{amount}", &details.amount_display_string)
+                    .replace("{text}", &message.snippet.display_message),
+                true,
+            );
+        }
+
+        Ok(())
+    }
+
+    fn react(&mut self, message: &Message) -> Result<()> {
+        match (
+            &message.snippet.kind[..],
+            &message.snippet.super_chat_details,
+        ) {
+            ("textMessageEvent", _) => self.command(message),
+            ("superChatEvent", Some(details)) => self.super_chat(message, details)?,
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
+
+impl YoutubeChat {
+    pub fn new() -> Self {
+        Self
+    }
+
+    async fn live_chat_id(client: &reqwest::Client, config: &YoutubeChatConfig) -> Result<String> {
+        let videos: VideoList = client
+            .get(format!("{}/videos", API))
+            .query(&[
+                ("part", "liveStreamingDetails"),
+                ("id", &config.video_id),
+                ("key", &config.api_key),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        videos
+            .items
+            .into_iter()
+            .next()
+            .and_then(|video| video.live_streaming_details)
+            .and_then(|details| details.active_live_chat_id)
+            .with_context(|| format!("Video {} has no active live chat", config.video_id))
+    }
+
+    async fn messages(
+        client: &reqwest::Client,
+        config: &YoutubeChatConfig,
+        live_chat_id: &str,
+        page_token: Option<&str>,
+    ) -> Result<MessageList> {
+        let mut query = vec![
+            ("liveChatId", live_chat_id),
+            ("part", "snippet,authorDetails"),
+            ("key", config.api_key.as_str()),
+        ];
+
+        if let Some(page_token) = page_token {
+            query.push(("pageToken", page_token));
+        }
+
+        Ok(client
+            .get(format!("{}/liveChat/messages", API))
+            .query(&query)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+
+    async fn poll(mut reactor: Reactor, readiness: Readiness) -> Result<()> {
+        let client = reqwest::Client::new();
+        let config = reactor.config.clone();
+        let live_chat_id = Self::live_chat_id(&client, &config).await?;
+        readiness.set_ready();
+        let min_interval = Duration::from_secs(config.min_interval_seconds.max(1));
+        let mut page_token: Option<String> = None;
+        info!("Following the live chat of {}", config.video_id);
+
+        loop {
+            let messages = match Self::messages(
+                &client,
+                &config,
+                &live_chat_id,
+                page_token.as_deref(),
+            )
+            .await
+            {
+                Ok(messages) => messages,
+                Err(error) => {
+                    warn!("Failed to poll the live chat: {:#}", error);
+                    sleep(min_interval).await;
+                    continue;
+                }
+            };
+
+            // The first page holds messages from before the plugin started, which are not reacted
+            // to anymore.
+            if page_token.is_some() {
+                for message in &messages.items {
+                    if let Err(error) = reactor.react(message) {
+                        warn!("Failed to react to a chat message: {:#}", error);
+                    }
+                }
+            }
+
+            page_token = messages.next_page_token;
+            sleep(Duration::from_millis(messages.polling_interval_millis).max(min_interval)).await;
+        }
+    }
+}
+
+impl Plugin for YoutubeChat {
+    fn name(&self) -> &'static str {
+        "youtube_chat"
+    }
+
+    check_config!(YoutubeChatConfig);
+
+    fn config_fields(&self) -> Vec<ConfigField> {
+        vec![
+            ConfigField::new("api_key", "API key", ConfigFieldKind::Secret),
+            ConfigField::new("video_id", "Video ID", ConfigFieldKind::String),
+        ]
+    }
+
+    fn parameters(&self, config: &PluginConfig) -> Vec<ParameterUsage> {
+        let config: YoutubeChatConfig = config.parse().unwrap_or_default();
+        config
+            .commands
+            .iter()
+            .flat_map(|command| command.actions.iter())
+            .chain(
+                config
+                    .super_chats
+                    .iter()
+                    .flat_map(|tier| tier.actions.iter()),
+            )
+            .map(Action::usage)
+            .collect()
+    }
+
+    /// Looking up the live chat of the video waits on the YouTube API, so the plugin reports when
+    /// it is done.
+    fn lazy(&self) -> bool {
+        true
+    }
+
+    fn run(self: Box<Self>, context: PluginContext, subsys: SubsystemHandle) -> PluginFuture {
+        Box::pin(async move {
+            let config: YoutubeChatConfig = context.config.parse()?;
+
+            if config.api_key.is_empty() || config.video_id.is_empty() {
+                info!("No API key or video configured for the YouTube chat");
+                context.readiness.set_ready();
+                subsys.on_shutdown_requested().await;
+                return Ok(());
+            }
+
+            let token = CancellationToken::new();
+            let _guard = token.clone().drop_guard();
+            let reactor = Reactor {
+                config,
+                tx: context.osc.clone(),
+                chatbox: context.chatbox(),
+                events: context.events,
+                token,
+                cooldowns: Cooldowns::default(),
+            };
+
+            match (Self::poll(reactor, context.readiness).cancel_on_shutdown(&subsys)).await {
+                Ok(Ok(())) => subsys.request_shutdown(),
+                Ok(Err(error)) => return Err(error),
+                Err(CancelledByShutdown) => {}
+            }
+
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,142 @@
+use crate::config::ProxyConfig;
+use crate::recording::{Argument, Direction, Record};
+use anyhow::{bail, Context, Result};
+use async_osc::{OscMessage, OscPacket, OscSocket};
+use log::{info, warn};
+use std::time::Instant;
+use tokio::fs::{File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::select;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc;
+use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
+use tokio_stream::StreamExt;
+use vrc_osc_manager_core::{OscReceiver, OutgoingMessage};
+
+/// Appends the proxied messages to a file in the format of recordings.
+struct Recorder {
+    file: File,
+    started: Instant,
+}
+
+impl Recorder {
+    async fn record(&mut self, direction: Direction, message: &OscMessage) -> Result<()> {
+        let record = Record {
+            time: self.started.elapsed().as_secs_f64(),
+            direction,
+            addr: message.addr.clone(),
+            args: message.args.iter().filter_map(Argument::from_osc).collect(),
+        };
+
+        let mut line = serde_json::to_string(&record)?;
+        line.push('\n');
+        self.file.write_all(line.as_bytes()).await?;
+        Ok(())
+    }
+}
+
+/// Unpacks the messages of a packet, including those of nested bundles. They are passed on one by
+/// one like the messages of plugins, so they go through the same processing, while the time tags
+/// are dropped, as VRChat applies bundles right away anyway.
+fn messages(packet: OscPacket) -> Vec<OscMessage> {
+    match packet {
+        OscPacket::Message(message) => vec![message],
+        OscPacket::Bundle(bundle) => bundle.content.into_iter().flat_map(messages).collect(),
+    }
+}
+
+/// Lets another OSC application talk to VRChat through the manager, so its messages go through
+/// the same processing as the ones of plugins and the traffic can be recorded.
+pub struct Proxy {
+    config: ProxyConfig,
+    rx: Option<OscReceiver>,
+    tx: mpsc::Sender<OutgoingMessage>,
+}
+
+impl Proxy {
+    pub fn new(config: &ProxyConfig, rx: OscReceiver, tx: mpsc::Sender<OutgoingMessage>) -> Self {
+        Self {
+            config: config.clone(),
+            // Messages from VRChat are passed on by the receiver like to any downstream
+            // application, so they are only of use here when recorded.
+            rx: config.record.is_some().then_some(rx),
+            tx,
+        }
+    }
+
+    async fn proxy(&mut self) -> Result<()> {
+        let mut recorder = match &self.config.record {
+            Some(path) => Some(Recorder {
+                file: OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .await
+                    .with_context(|| format!("Failed to open {}", path.display()))?,
+                started: Instant::now(),
+            }),
+            None => None,
+        };
+
+        let mut socket = OscSocket::bind((self.config.listen_address, self.config.port)).await?;
+        info!(
+            "Proxying messages sent to {}:{}",
+            self.config.listen_address, self.config.port
+        );
+        loop {
+            select! {
+                packet = socket.next() => {
+                    let Some(packet) = packet else {
+                        bail!("Proxy stream closed unexpectedly");
+                    };
+
+                    let (packet, _) = match packet {
+                        Ok(packet) => packet,
+                        Err(error) => {
+                            warn!("Dropped undecodable packet of the proxied application: {}", error);
+                            continue;
+                        }
+                    };
+
+                    for message in messages(packet) {
+                        if let Some(recorder) = &mut recorder {
+                            recorder.record(Direction::Outgoing, &message).await?;
+                        }
+
+                        self.tx.send(OutgoingMessage::new("proxy", message)).await?;
+                    }
+                }
+                message = async { self.rx.as_mut().unwrap().recv().await }, if self.rx.is_some() => {
+                    let message = match message {
+                        Ok(message) => message,
+                        Err(RecvError::Lagged(skipped)) => {
+                            warn!("Proxy lagging behind, {} messages have been dropped", skipped);
+                            continue;
+                        }
+                        Err(RecvError::Closed) => bail!("Message receiver died unexpectedly"),
+                    };
+
+                    if let Some(recorder) = &mut recorder {
+                        recorder.record(Direction::Incoming, &message).await?;
+                    }
+                }
+            }
+        }
+    }
+
+    pub async fn run(mut self, subsys: SubsystemHandle) -> Result<()> {
+        if !self.config.enabled {
+            drop(self);
+            subsys.on_shutdown_requested().await;
+            return Ok(());
+        }
+
+        match (self.proxy().cancel_on_shutdown(&subsys)).await {
+            Ok(Ok(())) => subsys.request_shutdown(),
+            Ok(Err(error)) => return Err(error),
+            Err(CancelledByShutdown) => {}
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,90 @@
+use crate::config::is_local_host;
+use crate::StatusEvent;
+use anyhow::Result;
+use log::{debug, info, warn};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::sync::{mpsc, watch};
+use tokio::time::{sleep, timeout};
+use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
+
+/// An OSC message without arguments, which VRChat ignores as it targets an unknown address.
+const PROBE: &[u8] = b"/vrc-osc-manager/ping\0\0\0,\0\0\0";
+
+const PROBE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Time to wait for an ICMP error in response to the probe. This has to cover address resolution
+/// on the local network, which is what fails once a headset got assigned a different IP.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Periodically probes a remote send target, as UDP would otherwise silently send into the void.
+pub struct ReachabilityCheck {
+    target: watch::Receiver<String>,
+    port: watch::Receiver<u16>,
+    tx: mpsc::Sender<StatusEvent>,
+}
+
+impl ReachabilityCheck {
+    pub fn new(
+        target: watch::Receiver<String>,
+        port: watch::Receiver<u16>,
+        tx: mpsc::Sender<StatusEvent>,
+    ) -> Self {
+        Self { target, port, tx }
+    }
+
+    async fn probe(&self, host: &str, port: u16) -> Result<()> {
+        if is_local_host(host) {
+            return Ok(());
+        }
+
+        let socket = UdpSocket::bind("0.0.0.0:0").await?;
+        socket.connect((host, port)).await?;
+        socket.send(PROBE).await?;
+
+        // Silence is the expected answer, only an ICMP error shows up as a receive error here.
+        match timeout(PROBE_TIMEOUT, socket.recv(&mut [0; 1])).await {
+            Ok(Err(error)) => Err(error.into()),
+            _ => Ok(()),
+        }
+    }
+
+    async fn check(&self) -> Result<()> {
+        let mut reachable = true;
+
+        loop {
+            let host = self.target.borrow().clone();
+            let port = *self.port.borrow();
+            debug!("Probing send target {}:{}", host, port);
+
+            match self.probe(&host, port).await {
+                Ok(()) if !reachable => {
+                    info!("Send target {}:{} is reachable again", host, port);
+                    reachable = true;
+                    self.tx.send(StatusEvent::TargetReachable(true)).await?;
+                }
+                Err(error) if reachable => {
+                    warn!(
+                        "Send target {}:{} is unreachable, did its IP change? {}",
+                        host, port, error
+                    );
+                    reachable = false;
+                    self.tx.send(StatusEvent::TargetReachable(false)).await?;
+                }
+                _ => {}
+            }
+
+            sleep(PROBE_INTERVAL).await;
+        }
+    }
+
+    pub async fn run(self, subsys: SubsystemHandle) -> Result<()> {
+        match (self.check().cancel_on_shutdown(&subsys)).await {
+            Ok(Ok(())) => subsys.request_shutdown(),
+            Ok(Err(error)) => return Err(error),
+            Err(CancelledByShutdown) => {}
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,160 @@
+use anyhow::{Context, Result};
+use async_osc::{OscMessage, OscType};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Instant;
+use tokio::fs::{create_dir_all, read_to_string, write};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    Incoming,
+    Outgoing,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Argument {
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    Bool(bool),
+    String(String),
+}
+
+impl Argument {
+    pub fn from_osc(arg: &OscType) -> Option<Self> {
+        match arg {
+            OscType::Int(value) => Some(Argument::Int(*value)),
+            OscType::Long(value) => Some(Argument::Long(*value)),
+            OscType::Float(value) => Some(Argument::Float(*value)),
+            OscType::Double(value) => Some(Argument::Double(*value)),
+            OscType::Bool(value) => Some(Argument::Bool(*value)),
+            OscType::String(value) => Some(Argument::String(value.clone())),
+            _ => None,
+        }
+    }
+
+    pub fn to_osc(&self) -> OscType {
+        match self {
+            Argument::Int(value) => OscType::Int(*value),
+            Argument::Long(value) => OscType::Long(*value),
+            Argument::Float(value) => OscType::Float(*value),
+            Argument::Double(value) => OscType::Double(*value),
+            Argument::Bool(value) => OscType::Bool(*value),
+            Argument::String(value) => OscType::String(value.clone()),
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Argument::Int(value) => Some(*value as f64),
+            Argument::Long(value) => Some(*value as f64),
+            Argument::Float(value) => Some(*value as f64),
+            Argument::Double(value) => Some(*value),
+            Argument::Bool(value) => Some(if *value { 1. } else { 0. }),
+            Argument::String(_) => None,
+        }
+    }
+}
+
+/// A single recorded OSC message, stored as one JSON object per line. The time is given in seconds
+/// since the recording was started.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Record {
+    pub time: f64,
+    pub direction: Direction,
+    pub addr: String,
+    pub args: Vec<Argument>,
+}
+
+impl Record {
+    pub fn new(time: f64, direction: Direction, message: &OscMessage) -> Self {
+        Self {
+            time,
+            direction,
+            addr: message.addr.clone(),
+            args: message.args.iter().filter_map(Argument::from_osc).collect(),
+        }
+    }
+
+    pub fn to_message(&self) -> OscMessage {
+        OscMessage {
+            addr: self.addr.clone(),
+            args: self.args.iter().map(Argument::to_osc).collect(),
+        }
+    }
+
+    /// The record as it is stored, including the line break.
+    pub fn to_line(&self) -> Result<Vec<u8>> {
+        let mut line = serde_json::to_vec(self)?;
+        line.push(b'\n');
+        Ok(line)
+    }
+}
+
+/// Keeps a short recording in memory until it is saved, with times counting from the first
+/// message recorded.
+#[derive(Debug, Default)]
+pub struct Recorder {
+    started: Option<Instant>,
+    records: Vec<Record>,
+}
+
+impl Recorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, direction: Direction, message: &OscMessage) {
+        let started = *self.started.get_or_insert_with(Instant::now);
+        self.records.push(Record::new(
+            started.elapsed().as_secs_f64(),
+            direction,
+            message,
+        ));
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        let mut contents = vec![];
+
+        for record in &self.records {
+            contents.extend(record.to_line()?);
+        }
+
+        if let Some(dir) = path.parent() {
+            create_dir_all(dir)
+                .await
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+
+        write(path, contents)
+            .await
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+pub async fn read_recording(path: &Path) -> Result<Vec<Record>> {
+    let contents = read_to_string(path)
+        .await
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line)
+                .with_context(|| format!("Invalid record in {}:{}", path.display(), index + 1))
+        })
+        .collect()
+}
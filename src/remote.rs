@@ -0,0 +1,154 @@
+use crate::control::Status;
+use anyhow::{bail, Context, Result};
+use reqwest::{Certificate, Client, Method, RequestBuilder, Response};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+pub enum RemoteAction<'a> {
+    Status,
+    Reload,
+    Pause,
+    Resume,
+    Enable(&'a str),
+    Disable(&'a str),
+}
+
+/// Client for the control API of an instance, e.g. a headless one running on a dedicated VR PC.
+pub struct Remote {
+    client: Client,
+    url: String,
+    token: String,
+}
+
+impl Remote {
+    pub async fn new(url: &str, token: &str, cert: Option<&Path>) -> Result<Self> {
+        let mut builder = Client::builder();
+
+        // The generated certificate is self-signed, so it has to be trusted explicitly.
+        if let Some(cert) = cert {
+            let pem = tokio::fs::read(cert)
+                .await
+                .with_context(|| format!("Failed to open {}", cert.display()))?;
+            builder = builder.add_root_certificate(Certificate::from_pem(&pem)?);
+        }
+
+        Ok(Self {
+            client: builder.build()?,
+            url: url.trim_end_matches('/').to_string(),
+            token: token.to_string(),
+        })
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        self.client
+            .request(method, format!("{}/{}", self.url, path))
+            .bearer_auth(&self.token)
+    }
+
+    async fn send(&self, request: RequestBuilder) -> Result<Response> {
+        let response = request
+            .send()
+            .await
+            .with_context(|| format!("Failed to connect to {}", self.url))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            bail!("Request failed with {}: {}", status, response.text().await?);
+        }
+
+        Ok(response)
+    }
+
+    pub async fn perform(&self, action: &RemoteAction<'_>) -> Result<()> {
+        let (method, path) = match action {
+            RemoteAction::Status => (Method::GET, "status".to_string()),
+            RemoteAction::Reload => (Method::POST, "reload".to_string()),
+            RemoteAction::Pause => (Method::POST, "pause".to_string()),
+            RemoteAction::Resume => (Method::POST, "resume".to_string()),
+            RemoteAction::Enable(name) => (Method::POST, format!("plugins/{}/enable", name)),
+            RemoteAction::Disable(name) => (Method::POST, format!("plugins/{}/disable", name)),
+        };
+
+        self.send(self.request(method, &path)).await?;
+        Ok(())
+    }
+
+    pub async fn status(&self) -> Result<Status> {
+        Ok(self
+            .send(self.request(Method::GET, "status"))
+            .await?
+            .json()
+            .await?)
+    }
+
+    pub async fn parameters(&self) -> Result<BTreeMap<String, Value>> {
+        Ok(self
+            .send(self.request(Method::GET, "parameters"))
+            .await?
+            .json()
+            .await?)
+    }
+
+    pub async fn send_message(&self, address: &str, value: Value) -> Result<()> {
+        self.send(
+            self.request(Method::POST, "send")
+                .json(&serde_json::json!({ "address": address, "value": value })),
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+pub fn print_status(status: &Status) {
+    println!("Version: {}", status.version);
+    println!(
+        "Plugins: {}",
+        if status.plugins_running {
+            "running"
+        } else {
+            "stopped"
+        }
+    );
+    println!(
+        "Output: {}",
+        if status.output_paused {
+            "paused"
+        } else {
+            "active"
+        }
+    );
+
+    if !status.disabled_plugins.is_empty() {
+        println!("Disabled plugins: {}", status.disabled_plugins.join(", "));
+    }
+
+    println!("Messages sent: {}", status.sent);
+    println!("Messages received: {}", status.received);
+
+    if let Some(world) = &status.world {
+        println!("World: {}", world);
+    }
+
+    if let Some(round_trip_ms) = status.round_trip_ms {
+        println!("Round trip: {} ms", round_trip_ms);
+    }
+}
+
+pub async fn run(
+    url: &str,
+    token: &str,
+    cert: Option<&Path>,
+    action: RemoteAction<'_>,
+) -> Result<()> {
+    let remote = Remote::new(url, token, cert).await?;
+
+    if let RemoteAction::Status = action {
+        print_status(&remote.status().await?);
+    } else {
+        remote.perform(&action).await?;
+        println!("Done");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,63 @@
+use crate::config::ResetDetectionConfig;
+use crate::rules;
+use async_osc::{OscMessage, OscType};
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+/// Recognizes the burst of default values VRChat sends after loading or resetting an avatar.
+///
+/// Only bool and int parameters are counted, as float parameters like those of face and eye
+/// tracking sit at zero all the time while nothing moves. Each parameter counts once per window,
+/// so a single one streaming zeros cannot make up a burst either.
+pub struct ResetDetector {
+    enabled: bool,
+    min_messages: u32,
+    window: Duration,
+    window_start: Instant,
+    reset: HashSet<String>,
+    detected: bool,
+}
+
+impl ResetDetector {
+    pub fn new(config: &ResetDetectionConfig) -> Self {
+        Self {
+            enabled: config.enabled,
+            min_messages: config.min_messages,
+            window: Duration::from_millis(config.window_ms),
+            window_start: Instant::now(),
+            reset: HashSet::new(),
+            detected: false,
+        }
+    }
+
+    /// Returns true once per burst, when enough parameters got set to their default value within
+    /// the window.
+    pub fn record(&mut self, message: &OscMessage) -> bool {
+        if !self.enabled || !message.addr.starts_with("/avatar/parameters/") {
+            return false;
+        }
+
+        let now = Instant::now();
+
+        if now.duration_since(self.window_start) >= self.window {
+            self.window_start = now;
+            self.reset.clear();
+            self.detected = false;
+        }
+
+        if matches!(message.args.first(), Some(OscType::Float(_)))
+            || rules::value(message) != Some(0.)
+        {
+            return false;
+        }
+
+        self.reset.insert(message.addr.clone());
+
+        if self.detected || (self.reset.len() as u32) < self.min_messages {
+            return false;
+        }
+
+        self.detected = true;
+        true
+    }
+}
@@ -0,0 +1,342 @@
+use crate::config::{RuleConfig, RuleDirection, UnitConversion};
+use crate::schedule::Schedules;
+use async_osc::{OscMessage, OscType};
+use vrc_osc_manager_core::osc;
+
+/// Matches an address against a pattern, in which `*` matches any sequence of characters.
+pub fn matches(pattern: &str, addr: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == addr,
+        Some((prefix, rest)) => {
+            let Some(remainder) = addr.strip_prefix(prefix) else {
+                return false;
+            };
+
+            remainder
+                .char_indices()
+                .map(|(index, _)| index)
+                .chain(std::iter::once(remainder.len()))
+                .any(|index| matches(rest, &remainder[index..]))
+        }
+    }
+}
+
+pub fn value(message: &OscMessage) -> Option<f64> {
+    osc::value(message.args.first()?)
+}
+
+/// Highest heart rate an int parameter can hold, mapped to 1 when normalizing.
+const MAX_BPM: f64 = 255.;
+
+fn convert(conversion: UnitConversion, value: f64) -> f64 {
+    match conversion {
+        UnitConversion::CelsiusToFahrenheit => value * 9. / 5. + 32.,
+        UnitConversion::FahrenheitToCelsius => (value - 32.) * 5. / 9.,
+        UnitConversion::BpmToNormalized => (value / MAX_BPM).clamp(0., 1.),
+        UnitConversion::NormalizedToBpm => value.clamp(0., 1.) * MAX_BPM,
+        UnitConversion::MetersToFeet => value / 0.3048,
+        UnitConversion::FeetToMeters => value * 0.3048,
+    }
+}
+
+fn transform(rule: &RuleConfig, arg: &mut OscType) {
+    let map = |value: f64| {
+        let value = rule
+            .convert
+            .map_or(value, |conversion| convert(conversion, value));
+        let value = value * rule.scale.unwrap_or(1.) + rule.offset.unwrap_or(0.);
+        let value = rule.clamp_min.map_or(value, |min| value.max(min));
+        rule.clamp_max.map_or(value, |max| value.min(max))
+    };
+
+    // Normalized values lie between whole numbers, so ints turn into floats.
+    if rule.convert == Some(UnitConversion::BpmToNormalized) {
+        if let OscType::Int(value) = arg {
+            *arg = OscType::Float(map(*value as f64) as f32);
+            return;
+        }
+    }
+
+    match arg {
+        OscType::Int(value) => *value = map(*value as f64).round() as i32,
+        OscType::Long(value) => *value = map(*value as f64).round() as i64,
+        OscType::Float(value) => *value = map(*value as f64) as f32,
+        OscType::Double(value) => *value = map(*value),
+        _ => {}
+    }
+}
+
+/// Applies the configured rules to the traffic of a single direction.
+pub struct Rules {
+    rules: Vec<RuleConfig>,
+    schedules: Schedules,
+}
+
+impl Rules {
+    pub fn new(configs: &[RuleConfig], direction: RuleDirection, schedules: Schedules) -> Self {
+        Self {
+            schedules,
+            rules: configs
+                .iter()
+                .filter(|rule| rule.direction == direction || rule.direction == RuleDirection::Both)
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Runs the message through all matching rules in order, returning it together with its
+    /// duplicates, or nothing when it got dropped.
+    pub fn apply(&self, mut message: OscMessage) -> Vec<OscMessage> {
+        let mut duplicates = vec![];
+
+        for rule in &self.rules {
+            if !matches(&rule.address, &message.addr)
+                || !self.schedules.allows(rule.schedule.as_deref())
+            {
+                continue;
+            }
+
+            let value = value(&message);
+
+            if rule.min.is_some() || rule.max.is_some() {
+                let within = value.map_or(false, |value| {
+                    rule.min.map_or(true, |min| value >= min)
+                        && rule.max.map_or(true, |max| value <= max)
+                });
+
+                if !within {
+                    continue;
+                }
+            }
+
+            if rule.drop {
+                return vec![];
+            }
+
+            if let Some(arg) = message.args.first_mut() {
+                transform(rule, arg);
+            }
+
+            if let Some(rename) = &rule.rename {
+                message.addr = rename.clone();
+            }
+
+            for addr in &rule.duplicate {
+                duplicates.push(OscMessage {
+                    addr: addr.clone(),
+                    args: message.args.clone(),
+                });
+            }
+        }
+
+        let mut messages = vec![message];
+        messages.append(&mut duplicates);
+        messages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(toml: &str, direction: RuleDirection) -> Rules {
+        #[derive(serde::Deserialize)]
+        struct Config {
+            rules: Vec<RuleConfig>,
+        }
+
+        let config: Config = toml::from_str(toml).unwrap();
+        Rules::new(&config.rules, direction, Schedules::default())
+    }
+
+    fn message(addr: &str, arg: OscType) -> OscMessage {
+        OscMessage {
+            addr: addr.to_string(),
+            args: vec![arg],
+        }
+    }
+
+    #[test]
+    fn matches_wildcards() {
+        assert!(matches("/avatar/parameters/Hat", "/avatar/parameters/Hat"));
+        assert!(!matches(
+            "/avatar/parameters/Hat",
+            "/avatar/parameters/HatColor"
+        ));
+        assert!(matches(
+            "/avatar/parameters/Hat*",
+            "/avatar/parameters/HatColor"
+        ));
+        assert!(matches("/avatar/parameters/Hat*", "/avatar/parameters/Hat"));
+        assert!(matches("/avatar/*/Hat", "/avatar/parameters/Hat"));
+        assert!(matches("*Color", "/avatar/parameters/HatColor"));
+        assert!(!matches("*Color", "/avatar/parameters/ColorHat"));
+        assert!(matches("/a*b*c", "/axxbyyc"));
+        assert!(!matches("/a*b*c", "/axxcyyb"));
+    }
+
+    #[test]
+    fn drops_renames_and_duplicates() {
+        let rules = rules(
+            r#"
+            [[rules]]
+            address = "/avatar/parameters/Drop"
+            drop = true
+
+            [[rules]]
+            address = "/avatar/parameters/Old*"
+            rename = "/avatar/parameters/New"
+            duplicate = ["/avatar/parameters/Copy"]
+            "#,
+            RuleDirection::Outbound,
+        );
+
+        assert!(rules
+            .apply(message("/avatar/parameters/Drop", OscType::Int(1)))
+            .is_empty());
+        assert_eq!(
+            rules.apply(message("/avatar/parameters/OldName", OscType::Int(1))),
+            vec![
+                message("/avatar/parameters/New", OscType::Int(1)),
+                message("/avatar/parameters/Copy", OscType::Int(1)),
+            ]
+        );
+        assert_eq!(
+            rules.apply(message("/avatar/parameters/Other", OscType::Int(1))),
+            vec![message("/avatar/parameters/Other", OscType::Int(1))]
+        );
+    }
+
+    #[test]
+    fn only_applies_within_min_and_max() {
+        let rules = rules(
+            r#"
+            [[rules]]
+            address = "/avatar/parameters/Value"
+            min = 0.5
+            max = 1.0
+            drop = true
+            "#,
+            RuleDirection::Inbound,
+        );
+
+        assert_eq!(
+            rules.apply(message("/avatar/parameters/Value", OscType::Float(0.25))),
+            vec![message("/avatar/parameters/Value", OscType::Float(0.25))]
+        );
+        assert!(rules
+            .apply(message("/avatar/parameters/Value", OscType::Float(0.75)))
+            .is_empty());
+        assert_eq!(
+            rules.apply(message(
+                "/avatar/parameters/Value",
+                OscType::String("x".to_string())
+            )),
+            vec![message(
+                "/avatar/parameters/Value",
+                OscType::String("x".to_string())
+            )]
+        );
+    }
+
+    #[test]
+    fn scales_offsets_and_clamps() {
+        let rules = rules(
+            r#"
+            [[rules]]
+            address = "/avatar/parameters/Value"
+            scale = 2.0
+            offset = 1.0
+            clamp_max = 10.0
+            "#,
+            RuleDirection::Outbound,
+        );
+
+        assert_eq!(
+            rules.apply(message("/avatar/parameters/Value", OscType::Int(3))),
+            vec![message("/avatar/parameters/Value", OscType::Int(7))]
+        );
+        assert_eq!(
+            rules.apply(message("/avatar/parameters/Value", OscType::Float(6.))),
+            vec![message("/avatar/parameters/Value", OscType::Float(10.))]
+        );
+    }
+
+    #[test]
+    fn keeps_to_the_direction() {
+        let toml = r#"
+            [[rules]]
+            address = "/avatar/parameters/In"
+            direction = "inbound"
+            drop = true
+
+            [[rules]]
+            address = "/avatar/parameters/Both"
+            drop = true
+            "#;
+        let outbound = rules(toml, RuleDirection::Outbound);
+        let inbound = rules(toml, RuleDirection::Inbound);
+
+        assert_eq!(
+            outbound.apply(message("/avatar/parameters/In", OscType::Int(1))),
+            vec![message("/avatar/parameters/In", OscType::Int(1))]
+        );
+        assert!(inbound
+            .apply(message("/avatar/parameters/In", OscType::Int(1)))
+            .is_empty());
+        assert!(outbound
+            .apply(message("/avatar/parameters/Both", OscType::Int(1)))
+            .is_empty());
+    }
+
+    #[test]
+    fn converts_units() {
+        assert_eq!(convert(UnitConversion::CelsiusToFahrenheit, 100.), 212.);
+        assert_eq!(convert(UnitConversion::FahrenheitToCelsius, 212.), 100.);
+        assert_eq!(convert(UnitConversion::BpmToNormalized, 510.), 1.);
+        assert_eq!(convert(UnitConversion::NormalizedToBpm, 0.2), 51.);
+        assert!((convert(UnitConversion::MetersToFeet, 0.3048) - 1.).abs() < 1e-9);
+        assert!((convert(UnitConversion::FeetToMeters, 1.) - 0.3048).abs() < 1e-9);
+    }
+
+    #[test]
+    fn converts_before_scaling() {
+        let rules = rules(
+            r#"
+            [[rules]]
+            address = "/avatar/parameters/Temperature"
+            convert = "celsius_to_fahrenheit"
+            offset = -32.0
+            "#,
+            RuleDirection::Inbound,
+        );
+
+        assert_eq!(
+            rules.apply(message(
+                "/avatar/parameters/Temperature",
+                OscType::Float(10.)
+            )),
+            vec![message(
+                "/avatar/parameters/Temperature",
+                OscType::Float(18.)
+            )]
+        );
+    }
+
+    #[test]
+    fn normalizes_int_heart_rates_into_floats() {
+        let rules = rules(
+            r#"
+            [[rules]]
+            address = "/avatar/parameters/HeartRate"
+            convert = "bpm_to_normalized"
+            "#,
+            RuleDirection::Outbound,
+        );
+
+        assert_eq!(
+            rules.apply(message("/avatar/parameters/HeartRate", OscType::Int(51))),
+            vec![message("/avatar/parameters/HeartRate", OscType::Float(0.2))]
+        );
+    }
+}
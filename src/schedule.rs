@@ -0,0 +1,131 @@
+use crate::config::{ScheduleConfig, Weekday};
+use chrono::{DateTime, Datelike, Local, NaiveTime};
+use std::collections::HashMap;
+
+impl From<chrono::Weekday> for Weekday {
+    fn from(weekday: chrono::Weekday) -> Self {
+        match weekday {
+            chrono::Weekday::Mon => Weekday::Mon,
+            chrono::Weekday::Tue => Weekday::Tue,
+            chrono::Weekday::Wed => Weekday::Wed,
+            chrono::Weekday::Thu => Weekday::Thu,
+            chrono::Weekday::Fri => Weekday::Fri,
+            chrono::Weekday::Sat => Weekday::Sat,
+            chrono::Weekday::Sun => Weekday::Sun,
+        }
+    }
+}
+
+fn is_active(schedule: &ScheduleConfig, now: DateTime<Local>) -> bool {
+    let time = now.time();
+    let start = schedule.start.map_or(NaiveTime::MIN, |start| start.0);
+    let end = schedule.end.map(|end| end.0);
+
+    // A window ending before it starts runs past midnight, where it still belongs to the day it
+    // started on.
+    let (within, day) = match end {
+        Some(end) if end <= start => {
+            if time >= start {
+                (true, now.weekday())
+            } else {
+                (time < end, now.weekday().pred())
+            }
+        }
+        Some(end) => (time >= start && time < end, now.weekday()),
+        None => (time >= start, now.weekday()),
+    };
+
+    within && (schedule.weekdays.is_empty() || schedule.weekdays.contains(&day.into()))
+}
+
+/// Evaluates the named schedules from the config, which plugins, rules and routes can refer to.
+#[derive(Clone, Default)]
+pub struct Schedules {
+    schedules: HashMap<String, ScheduleConfig>,
+}
+
+impl Schedules {
+    pub fn new(schedules: &HashMap<String, ScheduleConfig>) -> Self {
+        Self {
+            schedules: schedules.clone(),
+        }
+    }
+
+    /// Unknown schedules are rejected when loading the config, so they count as always active.
+    pub fn is_active(&self, name: &str) -> bool {
+        self.schedules
+            .get(name)
+            .map_or(true, |schedule| is_active(schedule, Local::now()))
+    }
+
+    pub fn allows(&self, schedule: Option<&str>) -> bool {
+        schedule.map_or(true, |name| self.is_active(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn schedule(toml: &str) -> ScheduleConfig {
+        toml::from_str(toml).unwrap()
+    }
+
+    /// A time in the week of Monday, 2026-10-12.
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(2026, 10, 12 + day, hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn is_active_within_the_window() {
+        let schedule = schedule(
+            r#"
+            start = "09:00"
+            end = "17:00"
+            "#,
+        );
+
+        assert!(!is_active(&schedule, at(0, 8, 59)));
+        assert!(is_active(&schedule, at(0, 9, 0)));
+        assert!(is_active(&schedule, at(0, 16, 59)));
+        assert!(!is_active(&schedule, at(0, 17, 0)));
+    }
+
+    #[test]
+    fn is_active_past_midnight_on_the_day_it_started() {
+        let schedule = schedule(
+            r#"
+            start = "22:00"
+            end = "02:00"
+            weekdays = ["fri"]
+            "#,
+        );
+
+        assert!(!is_active(&schedule, at(4, 21, 59)));
+        assert!(is_active(&schedule, at(4, 22, 0)));
+        assert!(is_active(&schedule, at(5, 1, 59)));
+        assert!(!is_active(&schedule, at(5, 2, 0)));
+        assert!(!is_active(&schedule, at(5, 22, 0)));
+        assert!(!is_active(&schedule, at(4, 1, 0)));
+    }
+
+    #[test]
+    fn is_active_from_the_start_without_an_end() {
+        let schedule = schedule(r#"start = "18:00""#);
+
+        assert!(!is_active(&schedule, at(2, 17, 59)));
+        assert!(is_active(&schedule, at(2, 23, 59)));
+        assert!(is_active(&ScheduleConfig::default(), at(2, 0, 0)));
+    }
+
+    #[test]
+    fn unknown_and_missing_schedules_are_active() {
+        let schedules = Schedules::new(&HashMap::new());
+
+        assert!(schedules.is_active("unknown"));
+        assert!(schedules.allows(None));
+    }
+}
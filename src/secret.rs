@@ -0,0 +1,5 @@
+/// Compares in time independent of the position of the first difference, so a token or secret
+/// cannot be guessed byte by byte by timing the responses.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
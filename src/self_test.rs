@@ -0,0 +1,163 @@
+use crate::config::SelfTestConfig;
+use crate::rules;
+use crate::StatusEvent;
+use anyhow::{bail, Result};
+use async_osc::{OscMessage, OscType};
+use log::{info, warn};
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc;
+use tokio::time::{sleep, timeout, Instant};
+use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
+use vrc_osc_manager_core::{Notifier, OscReceiver, OutgoingMessage};
+
+/// Time given to VRChat to turn the parameter off before it is turned on for the test.
+const SETTLE: Duration = Duration::from_millis(500);
+
+/// Verifies once after startup that messages flow in both directions, by toggling a parameter of
+/// the avatar and waiting for VRChat to report the change back.
+pub struct SelfTest {
+    config: SelfTestConfig,
+    rx: OscReceiver,
+    sender_tx: mpsc::Sender<OutgoingMessage>,
+    notifier: Notifier,
+    status_tx: mpsc::Sender<StatusEvent>,
+}
+
+impl SelfTest {
+    pub fn new(
+        config: &SelfTestConfig,
+        rx: OscReceiver,
+        sender_tx: mpsc::Sender<OutgoingMessage>,
+        notifier: Notifier,
+        status_tx: mpsc::Sender<StatusEvent>,
+    ) -> Self {
+        Self {
+            config: config.clone(),
+            rx,
+            sender_tx,
+            notifier,
+            status_tx,
+        }
+    }
+
+    /// Waits for a message matching the predicate, giving up once the deadline passed.
+    async fn wait_for(
+        &mut self,
+        deadline: Instant,
+        predicate: impl Fn(&OscMessage) -> bool,
+    ) -> Result<bool> {
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+
+            let message = match timeout(remaining, self.rx.recv()).await {
+                Ok(Ok(message)) => message,
+                Ok(Err(RecvError::Lagged(_))) => continue,
+                Ok(Err(RecvError::Closed)) => bail!("Message receiver died unexpectedly"),
+                Err(_) => return Ok(false),
+            };
+
+            if predicate(&message) {
+                return Ok(true);
+            }
+        }
+    }
+
+    async fn send(&self, value: bool) -> Result<()> {
+        self.sender_tx
+            .send(OutgoingMessage::new(
+                "self_test",
+                OscMessage {
+                    addr: self.config.parameter.clone(),
+                    args: vec![OscType::Bool(value)],
+                },
+            ))
+            .await?;
+        Ok(())
+    }
+
+    async fn report(&self, verified: bool, details: &str) -> Result<()> {
+        if verified {
+            info!("Link to VRChat verified");
+        } else {
+            warn!("OSC self-test failed: {}", details);
+        }
+
+        self.status_tx
+            .send(StatusEvent::LinkVerified(verified))
+            .await?;
+        let _ = self
+            .notifier
+            .notify(
+                if verified {
+                    "Link to VRChat verified"
+                } else {
+                    "OSC self-test failed"
+                },
+                details,
+            )
+            .await;
+        Ok(())
+    }
+
+    async fn test(&mut self) -> Result<()> {
+        info!("Waiting for VRChat to start the OSC self-test");
+
+        // Anything arriving proves that VRChat reaches us, which also means it is running.
+        loop {
+            match self.rx.recv().await {
+                Ok(_) | Err(RecvError::Lagged(_)) => break,
+                Err(RecvError::Closed) => bail!("Message receiver died unexpectedly"),
+            }
+        }
+
+        // VRChat only reports changes back, so the parameter is turned off first, in case it
+        // already was on.
+        self.send(false).await?;
+        sleep(SETTLE).await;
+
+        let deadline = Instant::now() + Duration::from_secs(self.config.timeout_seconds);
+        let parameter = self.config.parameter.clone();
+        self.send(true).await?;
+
+        let echoed = self
+            .wait_for(deadline, |message| {
+                message.addr == parameter && rules::value(message) == Some(1.)
+            })
+            .await?;
+        self.send(false).await?;
+
+        if echoed {
+            self.report(true, "Messages are sent to and received from VRChat")
+                .await
+        } else {
+            self.report(
+                false,
+                &format!(
+                    "VRChat did not report {} back, make sure your avatar has it as bool parameter",
+                    parameter
+                ),
+            )
+            .await
+        }
+    }
+
+    pub async fn run(mut self, subsys: SubsystemHandle) -> Result<()> {
+        if !self.config.enabled {
+            subsys.on_shutdown_requested().await;
+            return Ok(());
+        }
+
+        match (self.test().cancel_on_shutdown(&subsys)).await {
+            Ok(Ok(())) => {}
+            Ok(Err(error)) => return Err(error),
+            Err(CancelledByShutdown) => return Ok(()),
+        }
+
+        // The test only runs once, which is no reason to shut down the application. Unread messages
+        // would pile up in the receiver though.
+        drop(self);
+        subsys.on_shutdown_requested().await;
+        Ok(())
+    }
+}
@@ -0,0 +1,242 @@
+use crate::accounting::PluginUsageStatus;
+use crate::state::SharedState;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::Path;
+use std::time::Instant;
+use tokio::fs::OpenOptions;
+use tokio::io::AsyncWriteExt;
+use vrc_osc_manager_core::MetricSummary;
+
+/// Counters at the start of a session, so the summary only covers the session itself.
+struct Counters {
+    sent: u64,
+    received: u64,
+    avatar_changes: u64,
+    plugins: HashMap<String, PluginUsageStatus>,
+}
+
+impl Counters {
+    fn read(state: &SharedState) -> Self {
+        Self {
+            sent: state.stats.sent(),
+            received: state.stats.received(),
+            avatar_changes: state.stats.avatar_changes(),
+            plugins: state
+                .accounting
+                .snapshot()
+                .into_iter()
+                .map(|usage| (usage.name.clone(), usage))
+                .collect(),
+        }
+    }
+}
+
+/// A stretch of time VRChat was running, summarized once it stops.
+pub struct Session {
+    started: Instant,
+    started_at: DateTime<Local>,
+    counters: Counters,
+    worlds: Vec<String>,
+    players: Vec<String>,
+    failed_plugins: Vec<String>,
+}
+
+impl Session {
+    pub fn start(state: &SharedState) -> Self {
+        // Whatever was recorded before belongs to no session.
+        state.metrics.take();
+        state.metrics.take_counts();
+
+        Self {
+            started: Instant::now(),
+            started_at: Local::now(),
+            counters: Counters::read(state),
+            worlds: vec![],
+            players: vec![],
+            failed_plugins: vec![],
+        }
+    }
+
+    pub fn record_world(&mut self, name: &str) {
+        if self.worlds.last().map_or(true, |last| last != name) {
+            self.worlds.push(name.to_string());
+        }
+    }
+
+    pub fn record_player(&mut self, name: &str) {
+        if !self.players.iter().any(|player| player == name) {
+            self.players.push(name.to_string());
+        }
+    }
+
+    pub fn record_failure(&mut self, name: &str) {
+        if !self.failed_plugins.iter().any(|failed| failed == name) {
+            self.failed_plugins.push(name.to_string());
+        }
+    }
+
+    pub fn finish(self, state: &SharedState) -> Summary {
+        let counters = Counters::read(state);
+        let mut plugins: Vec<_> = counters
+            .plugins
+            .into_values()
+            .map(|mut usage| {
+                if let Some(start) = self.counters.plugins.get(&usage.name) {
+                    usage.sent -= start.sent;
+                    usage.received -= start.received;
+                }
+
+                usage
+            })
+            .filter(|usage| usage.sent > 0 || usage.received > 0)
+            .collect();
+        plugins.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Summary {
+            started_at: self.started_at,
+            minutes: self.started.elapsed().as_secs() / 60,
+            sent: counters.sent - self.counters.sent,
+            received: counters.received - self.counters.received,
+            avatar_changes: counters.avatar_changes - self.counters.avatar_changes,
+            worlds: self.worlds,
+            players: self.players,
+            plugins,
+            failed_plugins: self.failed_plugins,
+            metrics: state.metrics.take(),
+            counts: state.metrics.take_counts(),
+        }
+    }
+}
+
+pub struct Summary {
+    started_at: DateTime<Local>,
+    minutes: u64,
+    sent: u64,
+    received: u64,
+    avatar_changes: u64,
+    worlds: Vec<String>,
+    players: Vec<String>,
+    plugins: Vec<PluginUsageStatus>,
+    failed_plugins: Vec<String>,
+    metrics: Vec<(String, MetricSummary)>,
+    counts: Vec<(String, u64)>,
+}
+
+impl Summary {
+    fn duration(&self) -> String {
+        match (self.minutes / 60, self.minutes % 60) {
+            (0, minutes) => format!("{}m", minutes),
+            (hours, minutes) => format!("{}h {}m", hours, minutes),
+        }
+    }
+
+    /// Short version for the notification.
+    pub fn brief(&self) -> String {
+        let mut brief = format!(
+            "{} in VRChat, {} avatar changes, {} messages received and {} sent",
+            self.duration(),
+            self.avatar_changes,
+            self.received,
+            self.sent
+        );
+
+        if !self.worlds.is_empty() {
+            let _ = write!(brief, ", {} worlds visited", self.worlds.len());
+        }
+
+        if !self.players.is_empty() {
+            let _ = write!(brief, ", {} players met", self.players.len());
+        }
+
+        for (name, summary) in &self.metrics {
+            let _ = write!(
+                brief,
+                ", {} {:.0} on average",
+                name.to_lowercase(),
+                summary.average()
+            );
+        }
+
+        for (name, count) in &self.counts {
+            let _ = write!(brief, ", {} {}", count, name.to_lowercase());
+        }
+
+        if !self.failed_plugins.is_empty() {
+            let _ = write!(brief, ", {} plugins failed", self.failed_plugins.len());
+        }
+
+        brief
+    }
+
+    fn report(&self) -> String {
+        let mut report = String::new();
+        let _ = writeln!(
+            report,
+            "Session from {} to {} ({})",
+            self.started_at.format("%Y-%m-%d %H:%M"),
+            Local::now().format("%H:%M"),
+            self.duration()
+        );
+        let _ = writeln!(
+            report,
+            "Messages: {} received, {} sent",
+            self.received, self.sent
+        );
+        let _ = writeln!(report, "Avatar changes: {}", self.avatar_changes);
+
+        if !self.worlds.is_empty() {
+            let _ = writeln!(report, "Worlds: {}", self.worlds.join(", "));
+        }
+
+        if !self.players.is_empty() {
+            let _ = writeln!(report, "Players: {}", self.players.join(", "));
+        }
+
+        for (name, summary) in &self.metrics {
+            let _ = writeln!(
+                report,
+                "{}: {:.0} average, {:.0} min, {:.0} max",
+                name,
+                summary.average(),
+                summary.min,
+                summary.max
+            );
+        }
+
+        for (name, count) in &self.counts {
+            let _ = writeln!(report, "{}: {}", name, count);
+        }
+
+        for usage in &self.plugins {
+            let _ = writeln!(
+                report,
+                "Plugin {}: {} received, {} sent",
+                usage.name, usage.received, usage.sent
+            );
+        }
+
+        if !self.failed_plugins.is_empty() {
+            let _ = writeln!(report, "Failed plugins: {}", self.failed_plugins.join(", "));
+        }
+
+        report
+    }
+
+    /// Appends the full summary to the session log in the data directory.
+    pub async fn append(&self, data_dir: &Path) -> Result<()> {
+        let path = data_dir.join("sessions.log");
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .await
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+
+        file.write_all(format!("{}\n", self.report()).as_bytes())
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,154 @@
+use crate::accounting::Accounting;
+use crate::backpressure::Channels;
+use crate::plugin_logs::PluginLogs;
+use crate::rules;
+use crate::stats::Stats;
+use async_osc::OscMessage;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+use vrc_osc_manager_core::{Arming, CommandSender, Event, Heartbeats, Metrics};
+
+/// Number of events kept in memory for the dashboard.
+const RECENT_EVENTS: usize = 1000;
+
+/// What a plugin is doing, as shown in the tray and the terminal UI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PluginState {
+    Failed,
+    Disabled,
+    Starting,
+    Running,
+    /// Enabled, but held back by its schedule or while idle.
+    Held,
+    Stopped,
+}
+
+impl PluginState {
+    pub fn label(self) -> &'static str {
+        match self {
+            PluginState::Failed => "failed",
+            PluginState::Disabled => "disabled",
+            PluginState::Starting => "starting",
+            PluginState::Running => "running",
+            PluginState::Held => "held back",
+            PluginState::Stopped => "stopped",
+        }
+    }
+}
+
+/// State shared between the subsystems, reported through the tray and the control API.
+#[derive(Debug, Default)]
+pub struct SharedState {
+    pub stats: Stats,
+    pub channels: Channels,
+    pub accounting: Accounting,
+    pub plugin_logs: PluginLogs,
+    pub output_paused: AtomicBool,
+    pub plugins_running: AtomicBool,
+    pub disabled_plugins: Mutex<Vec<String>>,
+    /// Plugins which failed since the plugins were last started.
+    pub failed_plugins: Mutex<Vec<String>>,
+    /// Plugins which are still initializing.
+    pub starting_plugins: Mutex<Vec<String>>,
+    /// Enabled plugins which are not running because of their schedule or while idle.
+    pub held_plugins: Mutex<Vec<String>>,
+    /// Signals that VRChat lost its parameters, so the keepalive values have to be sent again.
+    pub resync: Notify,
+    /// Signals that every plugin which runs got its receiver, so messages reach them from then on.
+    pub plugins_subscribed: Notify,
+    /// Whether a recording is replayed in place of VRChat, whose own messages are dropped meanwhile.
+    pub replaying: AtomicBool,
+    /// ID of the avatar VRChat reported last.
+    pub avatar: Mutex<Option<String>>,
+    /// Name of the world VRChat is in, as far as it is known.
+    pub world: Mutex<Option<String>>,
+    /// Commands for the plugins from the tray, the control API and notification actions.
+    pub commands: CommandSender,
+    /// Last message received per address.
+    pub parameters: Mutex<HashMap<String, Arc<OscMessage>>>,
+    /// Address patterns whose parameters hold their current value, as writes of plugins are dropped.
+    pub frozen: Mutex<Vec<String>>,
+    /// Liveness of the sender, the receiver and the plugins, checked by the watchdog.
+    pub heartbeats: Heartbeats,
+    /// Values recorded by plugins for the session summary.
+    pub metrics: Metrics,
+    /// Events recorded lately with their Unix time, kept even while the event log is disabled.
+    pub recent_events: Mutex<VecDeque<(i64, Event)>>,
+    /// Whether shocks may be sent, which outlives restarts of the plugins sending them.
+    pub arming: Arming,
+}
+
+impl SharedState {
+    pub fn plugin_state(&self, name: &str) -> PluginState {
+        let running = self.plugins_running.load(Ordering::Relaxed);
+
+        if self
+            .failed_plugins
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|failed| failed == name)
+        {
+            PluginState::Failed
+        } else if self
+            .disabled_plugins
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|disabled| disabled == name)
+        {
+            PluginState::Disabled
+        } else if running
+            && self
+                .starting_plugins
+                .lock()
+                .unwrap()
+                .iter()
+                .any(|starting| starting == name)
+        {
+            PluginState::Starting
+        } else if running
+            && self
+                .held_plugins
+                .lock()
+                .unwrap()
+                .iter()
+                .any(|held| held == name)
+        {
+            PluginState::Held
+        } else if running {
+            PluginState::Running
+        } else {
+            PluginState::Stopped
+        }
+    }
+
+    pub fn remember_event(&self, event: &Event) {
+        let mut events = self.recent_events.lock().unwrap();
+
+        if events.len() == RECENT_EVENTS {
+            events.pop_front();
+        }
+
+        events.push_back((chrono::Local::now().timestamp(), event.clone()));
+    }
+
+    pub fn set_frozen(&self, pattern: &str, frozen: bool) {
+        let mut patterns = self.frozen.lock().unwrap();
+        patterns.retain(|other| other != pattern);
+
+        if frozen {
+            patterns.push(pattern.to_string());
+        }
+    }
+
+    pub fn is_frozen(&self, addr: &str) -> bool {
+        self.frozen
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|pattern| rules::matches(pattern, addr))
+    }
+}
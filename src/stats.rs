@@ -0,0 +1,68 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Stored in place of a round trip while none was measured.
+const NO_ROUND_TRIP: u64 = u64::MAX;
+
+/// Counters of the OSC traffic, shared between the sender, the receiver and anything reporting on
+/// them.
+#[derive(Debug)]
+pub struct Stats {
+    sent: AtomicU64,
+    received: AtomicU64,
+    avatar_changes: AtomicU64,
+    /// Round trip through the avatar measured last, in milliseconds.
+    round_trip_ms: AtomicU64,
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Self {
+            sent: AtomicU64::new(0),
+            received: AtomicU64::new(0),
+            avatar_changes: AtomicU64::new(0),
+            round_trip_ms: AtomicU64::new(NO_ROUND_TRIP),
+        }
+    }
+}
+
+impl Stats {
+    pub fn record_sent(&self) {
+        self.sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_received(&self) {
+        self.received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_avatar_change(&self) {
+        self.avatar_changes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records a measured round trip, or `None` once a ping got lost.
+    pub fn record_round_trip(&self, round_trip: Option<Duration>) {
+        self.round_trip_ms.store(
+            round_trip.map_or(NO_ROUND_TRIP, |round_trip| round_trip.as_millis() as u64),
+            Ordering::Relaxed,
+        );
+    }
+
+    pub fn sent(&self) -> u64 {
+        self.sent.load(Ordering::Relaxed)
+    }
+
+    pub fn received(&self) -> u64 {
+        self.received.load(Ordering::Relaxed)
+    }
+
+    pub fn avatar_changes(&self) -> u64 {
+        self.avatar_changes.load(Ordering::Relaxed)
+    }
+
+    pub fn round_trip_ms(&self) -> Option<u64> {
+        match self.round_trip_ms.load(Ordering::Relaxed) {
+            NO_ROUND_TRIP => None,
+            round_trip => Some(round_trip),
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use crate::config::SteamPresenceConfig;
+use crate::state::SharedState;
+use crate::StatusEvent;
+use anyhow::Result;
+use log::{debug, warn};
+use serde::Deserialize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::{interval, MissedTickBehavior};
+use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
+use vrc_osc_manager_core::InstanceWatcher;
+
+/// Smallest 64-bit Steam ID of an individual account.
+const STEAM_ID_BASE: u64 = 76561197960265728;
+
+/// Steam app ID of VRChat.
+const VRCHAT_APP_ID: &str = "438100";
+
+const PLAYER_SUMMARIES_URL: &str = "https://api.steampowered.com/ISteamUser/GetPlayerSummaries/v2/";
+
+#[derive(Debug, Deserialize)]
+struct PlayerSummaries {
+    response: PlayerSummariesResponse,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlayerSummariesResponse {
+    players: Vec<PlayerSummary>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlayerSummary {
+    /// App ID of the game being played, missing while not in a game.
+    gameid: Option<String>,
+    /// What Steam shows about the game being played.
+    gameextrainfo: Option<String>,
+}
+
+/// What Steam shows beyond the name of VRChat, if the account is in VRChat right now.
+fn rich_presence(summaries: PlayerSummaries) -> Option<String> {
+    let player = summaries.response.players.into_iter().next()?;
+
+    if player.gameid.as_deref() != Some(VRCHAT_APP_ID) {
+        return None;
+    }
+
+    let text = player.gameextrainfo?;
+    let text = text.trim();
+
+    (!text.is_empty() && text != "VRChat").then(|| text.to_string())
+}
+
+/// Follows the world through the rich presence VRChat shows on Steam, as fallback for setups where
+/// the VRChat log is out of reach.
+pub struct SteamPresence {
+    config: SteamPresenceConfig,
+    state: Arc<SharedState>,
+    instance: InstanceWatcher,
+    status_tx: mpsc::Sender<StatusEvent>,
+}
+
+impl SteamPresence {
+    pub fn new(
+        config: &SteamPresenceConfig,
+        state: Arc<SharedState>,
+        instance: InstanceWatcher,
+        status_tx: mpsc::Sender<StatusEvent>,
+    ) -> Self {
+        Self {
+            config: config.clone(),
+            state,
+            instance,
+            status_tx,
+        }
+    }
+
+    async fn lookup(&self, client: &reqwest::Client) -> Result<Option<String>> {
+        let summaries = client
+            .get(PLAYER_SUMMARIES_URL)
+            .query(&[
+                ("key", self.config.api_key.clone()),
+                ("steamids", self.config.steam_id.to_string()),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(rich_presence(summaries))
+    }
+
+    async fn follow(&self) -> Result<()> {
+        let client = reqwest::Client::new();
+        let mut interval = interval(Duration::from_secs(self.config.interval_seconds.max(10)));
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            interval.tick().await;
+
+            // There is no world to find while VRChat is not running.
+            if !self.state.plugins_running.load(Ordering::Relaxed) {
+                continue;
+            }
+
+            // The VRChat log knows the world sooner and more reliably.
+            if self.instance.borrow().is_some() {
+                continue;
+            }
+
+            let world = match self.lookup(&client).await {
+                Ok(world) => world,
+                Err(error) => {
+                    warn!("Failed to read the Steam player summary: {:#}", error);
+                    continue;
+                }
+            };
+
+            let Some(world) = world else {
+                debug!("No rich presence shown on Steam");
+                continue;
+            };
+
+            if self.state.world.lock().unwrap().as_deref() != Some(world.as_str()) {
+                self.status_tx
+                    .send(StatusEvent::WorldChanged(world))
+                    .await?;
+            }
+        }
+    }
+
+    pub async fn run(self, subsys: SubsystemHandle) -> Result<()> {
+        if !self.config.enabled {
+            subsys.on_shutdown_requested().await;
+            return Ok(());
+        }
+
+        if self.config.steam_id < STEAM_ID_BASE || self.config.api_key.is_empty() {
+            warn!("Set steam_id and api_key in the [steam_presence] section to follow the world on Steam");
+            subsys.on_shutdown_requested().await;
+            return Ok(());
+        }
+
+        match (self.follow().cancel_on_shutdown(&subsys)).await {
+            Ok(Ok(())) => subsys.request_shutdown(),
+            Ok(Err(error)) => return Err(error),
+            Err(CancelledByShutdown) => {}
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,109 @@
+use anyhow::Result;
+use std::collections::VecDeque;
+use tokio::sync::mpsc;
+use vrc_osc_manager_core::{Command, Notification};
+use windows::core::{IInspectable, Interface, HSTRING};
+use windows::Data::Xml::Dom::XmlDocument;
+use windows::Foundation::TypedEventHandler;
+use windows::UI::Notifications::{
+    ToastActivatedEventArgs, ToastNotification, ToastNotificationManager, ToastNotifier,
+};
+
+/// Unpackaged applications cannot register their own ID without a shortcut in the start menu, so
+/// the toasts are shown on behalf of PowerShell like most command line tools do.
+const APP_ID: &str =
+    r"{1AC14E77-02E7-4E5D-B744-2EB1AE5198B7}\WindowsPowerShell\v1.0\powershell.exe";
+
+/// Number of toasts kept alive, as their activation handler is dropped together with them.
+const KEPT_TOASTS: usize = 16;
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn toast_xml(notification: &Notification) -> String {
+    let mut xml = format!(
+        "<toast><visual><binding template=\"ToastGeneric\"><text>{}</text><text>{}</text>\
+        </binding></visual>",
+        escape(&notification.title),
+        escape(&notification.body),
+    );
+
+    if !notification.actions.is_empty() {
+        xml.push_str("<actions>");
+
+        // The index of the action is passed back as argument when it gets clicked.
+        for (index, action) in notification.actions.iter().enumerate() {
+            xml.push_str(&format!(
+                "<action content=\"{}\" arguments=\"{}\" activationType=\"foreground\"/>",
+                escape(&action.label),
+                index
+            ));
+        }
+
+        xml.push_str("</actions>");
+    }
+
+    xml.push_str("</toast>");
+    xml
+}
+
+/// Shows notifications as native toasts, reporting the commands of clicked actions.
+pub struct Toasts {
+    notifier: ToastNotifier,
+    toasts: VecDeque<ToastNotification>,
+    action_tx: mpsc::UnboundedSender<Command>,
+}
+
+impl Toasts {
+    pub fn new(action_tx: mpsc::UnboundedSender<Command>) -> Result<Self> {
+        Ok(Self {
+            notifier: ToastNotificationManager::CreateToastNotifierWithId(&HSTRING::from(APP_ID))?,
+            toasts: VecDeque::new(),
+            action_tx,
+        })
+    }
+
+    pub fn show(&mut self, notification: &Notification) -> Result<()> {
+        let xml = XmlDocument::new()?;
+        xml.LoadXml(&HSTRING::from(toast_xml(notification)))?;
+        let toast = ToastNotification::CreateToastNotification(&xml)?;
+
+        let actions = notification.actions.clone();
+        let action_tx = self.action_tx.clone();
+
+        toast.Activated(&TypedEventHandler::new(
+            move |_: &Option<ToastNotification>, args: &Option<IInspectable>| {
+                let Some(args) = args else {
+                    return Ok(());
+                };
+
+                let arguments = args.cast::<ToastActivatedEventArgs>()?.Arguments()?;
+
+                if let Some(action) = arguments
+                    .to_string()
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|index| actions.get(index))
+                {
+                    let _ = action_tx.send(action.command.clone());
+                }
+
+                Ok(())
+            },
+        ))?;
+
+        self.notifier.Show(&toast)?;
+
+        if self.toasts.len() == KEPT_TOASTS {
+            self.toasts.pop_front();
+        }
+
+        self.toasts.push_back(toast);
+        Ok(())
+    }
+}
@@ -1,7 +1,24 @@
+use crate::state::PluginState;
 use anyhow::Result;
+use log::warn;
+use std::collections::HashMap;
 use tokio::sync::mpsc;
 use tray_item::{IconSource, TrayItem};
 
+#[cfg(feature = "discovery")]
+use std::sync::{Arc, Mutex};
+
+/// Number of tray entries reserved for discovered VRChat clients.
+#[cfg(feature = "discovery")]
+const SEND_TARGET_SLOTS: usize = 4;
+
+#[cfg(target_os = "linux")]
+use anyhow::Context;
+#[cfg(target_os = "linux")]
+use directories::BaseDirs;
+#[cfg(target_os = "linux")]
+use std::path::Path;
+
 #[cfg(target_os = "linux")]
 const DARK_INACTIVE_ICON: &[u8] =
     include_bytes!(concat!(env!("OUT_DIR"), "/linux-dark-inactive-icon"));
@@ -17,34 +34,6 @@ const LIGHT_INACTIVE_ICON: &[u8] =
 const LIGHT_ACTIVE_ICON: &[u8] =
     include_bytes!(concat!(env!("OUT_DIR"), "/linux-light-active-icon"));
 
-#[cfg(target_os = "linux")]
-fn get_inactive_icon(dark_mode: bool) -> IconSource {
-    IconSource::Data {
-        width: 64,
-        height: 64,
-        data: if dark_mode {
-            DARK_INACTIVE_ICON
-        } else {
-            LIGHT_INACTIVE_ICON
-        }
-        .to_vec(),
-    }
-}
-
-#[cfg(target_os = "linux")]
-fn get_active_icon(dark_mode: bool) -> IconSource {
-    IconSource::Data {
-        width: 64,
-        height: 64,
-        data: if dark_mode {
-            DARK_ACTIVE_ICON
-        } else {
-            LIGHT_ACTIVE_ICON
-        }
-        .to_vec(),
-    }
-}
-
 #[cfg(target_os = "windows")]
 fn get_inactive_icon(dark_mode: bool) -> IconSource {
     IconSource::Resource(if dark_mode {
@@ -63,44 +52,593 @@ fn get_active_icon(dark_mode: bool) -> IconSource {
     })
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrayState {
+    Inactive,
+    Running,
+    Starting,
+    Traffic,
+    Paused,
+    Error,
+}
+
+#[cfg(target_os = "linux")]
+#[derive(Clone)]
+struct IconData {
+    width: i32,
+    height: i32,
+    data: Vec<u8>,
+}
+
+#[cfg(target_os = "linux")]
+impl IconData {
+    fn builtin(data: &[u8]) -> Self {
+        Self {
+            width: 64,
+            height: 64,
+            data: data.to_vec(),
+        }
+    }
+
+    fn load(path: &Path) -> Result<Self> {
+        let image = image::open(path)
+            .with_context(|| format!("Failed to load {}", path.display()))?
+            .to_rgba8();
+        let (width, height) = image.dimensions();
+        let mut data = image.into_raw();
+
+        // The tray expects ARGB instead of RGBA pixels.
+        for pixel in data.chunks_exact_mut(4) {
+            pixel.rotate_right(1);
+        }
+
+        Ok(Self {
+            width: width as i32,
+            height: height as i32,
+            data,
+        })
+    }
+
+    fn load_optional(path: &Path) -> Result<Option<Self>> {
+        if path.exists() {
+            Ok(Some(Self::load(path)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Draws a round badge with the given color into the bottom right corner.
+    fn with_badge(&self, [red, green, blue]: [u8; 3]) -> Self {
+        let mut icon = self.clone();
+        let radius = icon.width.min(icon.height) / 6;
+        let center_x = icon.width - radius - 1;
+        let center_y = icon.height - radius - 1;
+
+        for (index, pixel) in icon.data.chunks_exact_mut(4).enumerate() {
+            let x = index as i32 % icon.width;
+            let y = index as i32 / icon.width;
+            let (dx, dy) = (x - center_x, y - center_y);
+
+            if dx * dx + dy * dy <= radius * radius {
+                pixel.copy_from_slice(&[255, red, green, blue]);
+            }
+        }
+
+        icon
+    }
+
+    /// Desaturates the icon and makes it half transparent.
+    fn grayed(&self) -> Self {
+        let mut icon = self.clone();
+
+        for pixel in icon.data.chunks_exact_mut(4) {
+            let alpha = pixel[0] / 2;
+            let luminance =
+                (0.3 * pixel[1] as f32 + 0.59 * pixel[2] as f32 + 0.11 * pixel[3] as f32) as u8;
+            pixel.copy_from_slice(&[alpha, luminance, luminance, luminance]);
+        }
+
+        icon
+    }
+
+    fn icon(&self) -> IconSource {
+        IconSource::Data {
+            width: self.width,
+            height: self.height,
+            data: self.data.clone(),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+struct IconTheme {
+    normal: IconData,
+    running: IconData,
+    starting: IconData,
+    traffic: IconData,
+    paused: IconData,
+    error: IconData,
+}
+
+#[cfg(target_os = "linux")]
+impl IconTheme {
+    fn new(
+        normal: IconData,
+        running: IconData,
+        starting: Option<IconData>,
+        traffic: Option<IconData>,
+        paused: Option<IconData>,
+        error: Option<IconData>,
+    ) -> Self {
+        Self {
+            starting: starting.unwrap_or_else(|| running.with_badge([240, 180, 40])),
+            traffic: traffic.unwrap_or_else(|| running.with_badge([255, 255, 255])),
+            paused: paused.unwrap_or_else(|| running.grayed()),
+            error: error.unwrap_or_else(|| normal.with_badge([220, 40, 40])),
+            normal,
+            running,
+        }
+    }
+
+    fn builtin(dark_mode: bool) -> Self {
+        let (normal, running) = if dark_mode {
+            (DARK_INACTIVE_ICON, DARK_ACTIVE_ICON)
+        } else {
+            (LIGHT_INACTIVE_ICON, LIGHT_ACTIVE_ICON)
+        };
+
+        Self::new(
+            IconData::builtin(normal),
+            IconData::builtin(running),
+            None,
+            None,
+            None,
+            None,
+        )
+    }
+
+    fn load_custom(name: &str) -> Result<Self> {
+        let base_dirs = BaseDirs::new().context("Base directories not available")?;
+        let theme_dir = base_dirs
+            .config_dir()
+            .join("vrc-osc-manager/themes")
+            .join(name);
+
+        Ok(Self::new(
+            IconData::load(&theme_dir.join("normal.png"))?,
+            IconData::load(&theme_dir.join("running.png"))?,
+            IconData::load_optional(&theme_dir.join("starting.png"))?,
+            IconData::load_optional(&theme_dir.join("traffic.png"))?,
+            IconData::load_optional(&theme_dir.join("paused.png"))?,
+            IconData::load_optional(&theme_dir.join("error.png"))?,
+        ))
+    }
+
+    fn icon(&self, state: TrayState) -> IconSource {
+        match state {
+            TrayState::Inactive => self.normal.icon(),
+            TrayState::Running => self.running.icon(),
+            TrayState::Starting => self.starting.icon(),
+            TrayState::Traffic => self.traffic.icon(),
+            TrayState::Paused => self.paused.icon(),
+            TrayState::Error => self.error.icon(),
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+struct IconTheme {
+    dark_mode: bool,
+}
+
+#[cfg(target_os = "windows")]
+impl IconTheme {
+    fn builtin(dark_mode: bool) -> Self {
+        Self { dark_mode }
+    }
+
+    fn load_custom(_name: &str) -> Result<Self> {
+        anyhow::bail!("Custom icon themes are only supported on Linux");
+    }
+
+    fn icon(&self, state: TrayState) -> IconSource {
+        match state {
+            TrayState::Running | TrayState::Starting | TrayState::Traffic => {
+                get_active_icon(self.dark_mode)
+            }
+            TrayState::Inactive | TrayState::Paused | TrayState::Error => {
+                get_inactive_icon(self.dark_mode)
+            }
+        }
+    }
+}
+
+impl IconTheme {
+    fn load(name: &str, dark_mode: bool) -> Self {
+        if name.is_empty() {
+            return Self::builtin(dark_mode);
+        }
+
+        match Self::load_custom(name) {
+            Ok(theme) => theme,
+            Err(error) => {
+                warn!("Falling back to built-in icons: {:#}", error);
+                Self::builtin(dark_mode)
+            }
+        }
+    }
+}
+
+/// The tray icon is provided through the StatusNotifierItem protocol on Linux, which requires a
+/// host implementing the watcher to be running on the session bus. Without one, e.g. on GNOME without
+/// the AppIndicator extension, the icon silently never shows up.
+#[cfg(target_os = "linux")]
+fn check_status_notifier_watcher() {
+    let has_watcher = dbus::blocking::Connection::new_session().and_then(|connection| {
+        let (has_owner,): (bool,) = connection
+            .with_proxy(
+                "org.freedesktop.DBus",
+                "/org/freedesktop/DBus",
+                std::time::Duration::from_secs(1),
+            )
+            .method_call(
+                "org.freedesktop.DBus",
+                "NameHasOwner",
+                ("org.kde.StatusNotifierWatcher",),
+            )?;
+        Ok(has_owner)
+    });
+
+    match has_watcher {
+        Ok(true) => {}
+        Ok(false) => warn!(
+            "No StatusNotifierItem host found, the tray icon will not be visible. On GNOME, install \
+            the AppIndicator extension"
+        ),
+        Err(error) => warn!("Failed to query the session bus for a tray host: {}", error),
+    }
+}
+
+fn plugin_label(name: &str, state: PluginState) -> String {
+    format!("Plugin {} ({})", name, state.label())
+}
+
+#[derive(Debug, Clone)]
 pub enum TrayMessage {
     ReloadPlugins,
+    /// Restarts a single plugin without reloading the config, sent by the watchdog.
+    RestartPlugin(&'static str),
+    #[cfg(feature = "gui")]
+    ManagePlugins,
+    TogglePause,
+    ToggleForcedPlugins,
+    #[cfg(feature = "clipboard")]
+    CopyPluginLogs,
+    #[cfg(feature = "clipboard")]
+    SendClipboard,
+    #[cfg(any(feature = "control", feature = "dbus_service", feature = "ipc"))]
+    SetPaused(bool),
+    #[cfg(any(
+        feature = "control",
+        feature = "dbus_service",
+        feature = "ipc",
+        feature = "tui"
+    ))]
+    SetPluginEnabled(String, bool),
+    TogglePlugin(&'static str),
+    #[cfg(any(feature = "control", feature = "dbus_service", feature = "ipc"))]
+    Send(async_osc::OscMessage),
+    SendBookmark(String),
+    ToggleFreeze,
+    SetArmed(bool),
+    ToggleArmed,
+    #[cfg(feature = "discovery")]
+    SelectTarget(String),
+    #[cfg(feature = "scenes")]
+    SaveScene(String),
+    #[cfg(feature = "scenes")]
+    RecallScene(String),
+    #[cfg(feature = "macros")]
+    RecordMacro(String),
+    #[cfg(feature = "macros")]
+    PlayMacro(String),
     Exit,
 }
 
 pub struct Tray {
-    tray: TrayItem,
-    dark_mode_icons: bool,
+    /// Missing when running headless, where the state is still tracked but never shown.
+    tray: Option<TrayItem>,
+    message_tx: mpsc::Sender<TrayMessage>,
+    /// Discovered VRChat clients in the order of the reserved entries they fill.
+    #[cfg(feature = "discovery")]
+    send_targets: Arc<Mutex<Vec<String>>>,
+    #[cfg(feature = "discovery")]
+    send_target_ids: Vec<u32>,
+    /// Menu entry IDs of the plugins, with the state their label shows.
+    plugin_entries: HashMap<&'static str, (u32, PluginState)>,
+    theme: IconTheme,
+    live_status: bool,
+    state: TrayState,
+    running: bool,
+    paused: bool,
+    config_error: bool,
+    plugin_error: bool,
+    target_unreachable: bool,
+    flooded: bool,
+    congested: bool,
+    link_failed: bool,
+    device_missing: bool,
+    plugins_starting: bool,
+    traffic_phase: bool,
 }
 
 impl Tray {
-    pub fn new(message_tx: mpsc::Sender<TrayMessage>, dark_mode_icons: bool) -> Result<Self> {
-        let mut tray = TrayItem::new("VRC OSC Manager", get_inactive_icon(dark_mode_icons))?;
+    pub fn new(
+        message_tx: mpsc::Sender<TrayMessage>,
+        dark_mode_icons: bool,
+        icon_theme: &str,
+        live_status: bool,
+        headless: bool,
+    ) -> Result<Self> {
+        let theme = IconTheme::load(icon_theme, dark_mode_icons);
 
-        let reload_plugins_tx = message_tx.clone();
-        tray.add_menu_item("Reload plugins", move || {
-            reload_plugins_tx
-                .blocking_send(TrayMessage::ReloadPlugins)
-                .unwrap();
+        let tray = if headless {
+            None
+        } else {
+            #[cfg(target_os = "linux")]
+            check_status_notifier_watcher();
+
+            Some(TrayItem::new(
+                "VRC OSC Manager",
+                theme.icon(TrayState::Inactive),
+            )?)
+        };
+
+        let mut tray = Self {
+            tray,
+            message_tx,
+            #[cfg(feature = "discovery")]
+            send_targets: Arc::new(Mutex::new(Vec::new())),
+            #[cfg(feature = "discovery")]
+            send_target_ids: Vec::new(),
+            plugin_entries: HashMap::new(),
+            theme,
+            live_status,
+            state: TrayState::Inactive,
+            running: false,
+            paused: false,
+            config_error: false,
+            plugin_error: false,
+            target_unreachable: false,
+            flooded: false,
+            congested: false,
+            link_failed: false,
+            device_missing: false,
+            plugins_starting: false,
+            traffic_phase: false,
+        };
+
+        tray.add_entry("Reload plugins", TrayMessage::ReloadPlugins)?;
+        #[cfg(feature = "gui")]
+        tray.add_entry("Manage plugins", TrayMessage::ManagePlugins)?;
+        tray.add_entry("Pause/resume output", TrayMessage::TogglePause)?;
+        tray.add_entry("Force plugins on/off", TrayMessage::ToggleForcedPlugins)?;
+        #[cfg(feature = "clipboard")]
+        tray.add_entry("Copy plugin logs", TrayMessage::CopyPluginLogs)?;
+        #[cfg(feature = "clipboard")]
+        tray.add_entry("Send clipboard to chatbox", TrayMessage::SendClipboard)?;
+
+        Ok(tray)
+    }
+
+    /// Adds the "Exit" entry. Entries can only be appended, so this needs to be called after all
+    /// other entries have been added to keep it at the bottom of the menu.
+    pub fn add_exit(&mut self) -> Result<()> {
+        self.add_entry("Exit", TrayMessage::Exit)
+    }
+
+    /// Adds a menu entry sending the message when clicked. Entries are appended to the end of the
+    /// menu and cannot be removed again.
+    pub fn add_entry(&mut self, label: &str, message: TrayMessage) -> Result<()> {
+        let Some(tray) = &mut self.tray else {
+            return Ok(());
+        };
+
+        let message_tx = self.message_tx.clone();
+        tray.add_menu_item(label, move || {
+            message_tx.blocking_send(message.clone()).unwrap();
         })?;
+        Ok(())
+    }
+
+    /// Adds a menu entry showing the state of a plugin, which starts or stops just that plugin when
+    /// clicked.
+    pub fn add_plugin_entry(&mut self, name: &'static str, state: PluginState) -> Result<()> {
+        let Some(tray) = &mut self.tray else {
+            return Ok(());
+        };
 
-        tray.add_menu_item("Exit", move || {
-            message_tx.blocking_send(TrayMessage::Exit).unwrap();
+        let message_tx = self.message_tx.clone();
+        let id = tray.add_menu_item_with_id(&plugin_label(name, state), move || {
+            message_tx
+                .blocking_send(TrayMessage::TogglePlugin(name))
+                .unwrap();
         })?;
+        self.plugin_entries.insert(name, (id, state));
+        Ok(())
+    }
 
-        Ok(Self {
-            tray,
-            dark_mode_icons,
-        })
+    /// Updates the labels of the plugin entries whose state changed.
+    pub fn update_plugin_entries(&mut self, state: impl Fn(&str) -> PluginState) -> Result<()> {
+        let Some(tray) = &mut self.tray else {
+            return Ok(());
+        };
+
+        for (name, (id, shown)) in &mut self.plugin_entries {
+            let state = state(name);
+
+            if *shown != state {
+                tray.set_menu_item_label(&plugin_label(name, state), *id)?;
+                *shown = state;
+            }
+        }
+
+        Ok(())
     }
 
-    pub fn set_running(&mut self, running: bool) -> Result<()> {
-        self.tray.set_icon(if running {
-            get_active_icon(self.dark_mode_icons)
+    /// Adds the entries for choosing the send target among the discovered VRChat clients. Clients
+    /// are only found after the menu is complete, so a fixed number of entries is reserved up front
+    /// and labeled once a client fills them.
+    #[cfg(feature = "discovery")]
+    pub fn add_send_targets(&mut self) -> Result<()> {
+        self.add_entry(
+            "Send to configured host",
+            TrayMessage::SelectTarget(String::new()),
+        )?;
+
+        let Some(tray) = &mut self.tray else {
+            return Ok(());
+        };
+
+        for slot in 0..SEND_TARGET_SLOTS {
+            let message_tx = self.message_tx.clone();
+            let send_targets = self.send_targets.clone();
+            let id = tray.add_menu_item_with_id("Send to (not found yet)", move || {
+                let name = send_targets.lock().unwrap().get(slot).cloned();
+
+                if let Some(name) = name {
+                    message_tx
+                        .blocking_send(TrayMessage::SelectTarget(name))
+                        .unwrap();
+                }
+            })?;
+            self.send_target_ids.push(id);
+        }
+
+        Ok(())
+    }
+
+    /// Labels the next reserved entry with a discovered VRChat client. Entries cannot be removed
+    /// again, so an entry stays around while the client is offline and picks up its new IP once
+    /// rediscovered.
+    #[cfg(feature = "discovery")]
+    pub fn add_send_target(&mut self, name: &str) -> Result<()> {
+        let slot = {
+            let mut send_targets = self.send_targets.lock().unwrap();
+
+            if send_targets.iter().any(|target| target == name) {
+                return Ok(());
+            }
+            if send_targets.len() == SEND_TARGET_SLOTS {
+                warn!(
+                    "Discovered {}, but all {} tray entries for send targets are taken",
+                    name, SEND_TARGET_SLOTS
+                );
+                return Ok(());
+            }
+
+            send_targets.push(name.to_string());
+            send_targets.len() - 1
+        };
+
+        if let (Some(tray), Some(id)) = (&mut self.tray, self.send_target_ids.get(slot)) {
+            tray.set_menu_item_label(&format!("Send to {}", name), *id)?;
+        }
+
+        Ok(())
+    }
+
+    fn update_icon(&mut self) -> Result<()> {
+        let state = if self.config_error
+            || self.plugin_error
+            || self.target_unreachable
+            || self.flooded
+            || self.congested
+            || self.link_failed
+            || self.device_missing
+        {
+            TrayState::Error
+        } else if self.paused {
+            TrayState::Paused
+        } else if self.running && self.plugins_starting {
+            TrayState::Starting
+        } else if self.running && self.traffic_phase {
+            TrayState::Traffic
+        } else if self.running {
+            TrayState::Running
         } else {
-            get_inactive_icon(self.dark_mode_icons)
-        })?;
+            TrayState::Inactive
+        };
+
+        if state != self.state {
+            self.state = state;
+
+            if let Some(tray) = &mut self.tray {
+                tray.set_icon(self.theme.icon(state))?;
+            }
+        }
+
         Ok(())
     }
+
+    pub fn set_running(&mut self, running: bool) -> Result<()> {
+        self.running = running;
+        self.update_icon()
+    }
+
+    pub fn set_config_error(&mut self, error: bool) -> Result<()> {
+        self.config_error = error;
+        self.update_icon()
+    }
+
+    pub fn set_plugin_error(&mut self, error: bool) -> Result<()> {
+        self.plugin_error = error;
+        self.update_icon()
+    }
+
+    pub fn set_target_unreachable(&mut self, unreachable: bool) -> Result<()> {
+        self.target_unreachable = unreachable;
+        self.update_icon()
+    }
+
+    pub fn set_flooded(&mut self, flooded: bool) -> Result<()> {
+        self.flooded = flooded;
+        self.update_icon()
+    }
+
+    pub fn set_congested(&mut self, congested: bool) -> Result<()> {
+        self.congested = congested;
+        self.update_icon()
+    }
+
+    pub fn set_link_failed(&mut self, failed: bool) -> Result<()> {
+        self.link_failed = failed;
+        self.update_icon()
+    }
+
+    pub fn set_device_missing(&mut self, missing: bool) -> Result<()> {
+        self.device_missing = missing;
+        self.update_icon()
+    }
+
+    pub fn set_plugins_starting(&mut self, starting: bool) -> Result<()> {
+        self.plugins_starting = starting;
+        self.update_icon()
+    }
+
+    pub fn set_paused(&mut self, paused: bool) -> Result<()> {
+        self.paused = paused;
+        self.update_icon()
+    }
+
+    /// Called periodically with whether OSC traffic flowed since the last call. While it does, the
+    /// icon blinks between its running and traffic variants.
+    pub fn tick_traffic(&mut self, traffic: bool) -> Result<()> {
+        if !self.live_status {
+            return Ok(());
+        }
+
+        self.traffic_phase = traffic && !self.traffic_phase;
+        self.update_icon()
+    }
 }
@@ -0,0 +1,375 @@
+use crate::config::Config;
+use crate::plugins;
+use crate::state::{PluginState, SharedState};
+use crate::tray::TrayMessage;
+use anyhow::Result;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::execute;
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use ratatui::backend::{Backend, CrosstermBackend};
+use ratatui::layout::{Constraint, Direction, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Sparkline};
+use ratatui::{Frame, Terminal};
+use simplelog::SharedLogger;
+use std::collections::{HashMap, VecDeque};
+use std::io::{stdout, Stdout};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::select;
+use tokio::sync::mpsc;
+use tokio::time::interval;
+use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
+
+/// Number of log records kept for the errors panel.
+const LOG_CAPACITY: usize = 100;
+
+/// Number of seconds of traffic shown in the chart.
+const TRAFFIC_SECONDS: usize = 120;
+
+/// Share of a second a plugin spent running and the messages it handled within that second.
+#[derive(Clone, Copy, Default)]
+struct PluginRates {
+    busy_percent: f64,
+    received: u64,
+    sent: u64,
+}
+
+/// Logger keeping the most recent warnings and errors, taking the place of the terminal logger
+/// while the TUI owns the terminal.
+#[derive(Clone, Default)]
+pub struct LogBuffer {
+    records: Arc<Mutex<VecDeque<(Level, String)>>>,
+}
+
+impl LogBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Log for LogBuffer {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= Level::Warn
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let mut records = self.records.lock().unwrap();
+
+        if records.len() == LOG_CAPACITY {
+            records.pop_front();
+        }
+
+        records.push_back((
+            record.level(),
+            format!(
+                "{} {}",
+                chrono::Local::now().format("%H:%M:%S"),
+                record.args()
+            ),
+        ));
+    }
+
+    fn flush(&self) {}
+}
+
+impl SharedLogger for LogBuffer {
+    fn level(&self) -> LevelFilter {
+        LevelFilter::Warn
+    }
+
+    fn config(&self) -> Option<&simplelog::Config> {
+        None
+    }
+
+    fn as_log(self: Box<Self>) -> Box<dyn Log> {
+        Box::new(*self)
+    }
+}
+
+/// Dashboard in the terminal, for machines without a tray, e.g. when connected through SSH.
+pub struct Tui {
+    plugins: Vec<&'static str>,
+    state: Arc<SharedState>,
+    logs: LogBuffer,
+    tray_tx: mpsc::Sender<TrayMessage>,
+}
+
+impl Tui {
+    pub fn new(
+        config: &Arc<Config>,
+        state: Arc<SharedState>,
+        logs: LogBuffer,
+        tray_tx: mpsc::Sender<TrayMessage>,
+    ) -> Self {
+        Self {
+            plugins: plugins::builtin(config)
+                .iter()
+                .map(|plugin| plugin.name())
+                .collect(),
+            state,
+            logs,
+            tray_tx,
+        }
+    }
+
+    fn draw<B: Backend>(
+        &self,
+        frame: &mut Frame<'_, B>,
+        list: &mut ListState,
+        traffic: &[u64],
+        rates: &HashMap<String, PluginRates>,
+    ) {
+        let [status_area, main_area, logs_area, help_area]: [_; 4] = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(3),
+                Constraint::Min(8),
+                Constraint::Length(8),
+                Constraint::Length(1),
+            ])
+            .split(frame.size())
+            .as_ref()
+            .try_into()
+            .unwrap();
+        let [plugins_area, traffic_area]: [_; 2] = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(35), Constraint::Percentage(65)])
+            .split(main_area)
+            .as_ref()
+            .try_into()
+            .unwrap();
+
+        let plugins_running = self.state.plugins_running.load(Ordering::Relaxed);
+        let mut status = format!(
+            "Plugins {}   Output {}   Sent {}   Received {}",
+            if plugins_running {
+                "running"
+            } else {
+                "stopped"
+            },
+            if self.state.output_paused.load(Ordering::Relaxed) {
+                "paused"
+            } else {
+                "active"
+            },
+            self.state.stats.sent(),
+            self.state.stats.received(),
+        );
+
+        if let Some(round_trip_ms) = self.state.stats.round_trip_ms() {
+            status.push_str(&format!("   Ping {} ms", round_trip_ms));
+        }
+
+        frame.render_widget(
+            Paragraph::new(status).block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!(" VRC OSC Manager v{} ", crate::VERSION)),
+            ),
+            status_area,
+        );
+
+        let items: Vec<ListItem> = self
+            .plugins
+            .iter()
+            .map(|name| {
+                let state = self.state.plugin_state(name);
+                let label = state.label();
+                let color = match state {
+                    PluginState::Failed => Color::Red,
+                    PluginState::Disabled => Color::DarkGray,
+                    PluginState::Starting => Color::Cyan,
+                    PluginState::Running => Color::Green,
+                    PluginState::Held => Color::Blue,
+                    PluginState::Stopped => Color::Yellow,
+                };
+
+                let line = match rates.get(*name) {
+                    Some(rates) if label == "running" => format!(
+                        "{:<16} {:<8} {:>5.1}% {:>4} in {:>4} out",
+                        name, label, rates.busy_percent, rates.received, rates.sent
+                    ),
+                    _ => format!("{:<16} {}", name, label),
+                };
+
+                ListItem::new(line).style(Style::default().fg(color))
+            })
+            .collect();
+        frame.render_stateful_widget(
+            List::new(items)
+                .block(Block::default().borders(Borders::ALL).title(" Plugins "))
+                .highlight_style(Style::default().add_modifier(Modifier::REVERSED)),
+            plugins_area,
+            list,
+        );
+
+        frame.render_widget(
+            Sparkline::default()
+                .block(Block::default().borders(Borders::ALL).title(format!(
+                    " Traffic, {} msg/s ",
+                    traffic.last().copied().unwrap_or_default()
+                )))
+                .data(traffic)
+                .style(Style::default().fg(Color::Cyan)),
+            traffic_area,
+        );
+
+        let logs = self.logs.records.lock().unwrap();
+        let visible = logs_area.height.saturating_sub(2) as usize;
+        let items: Vec<ListItem> = logs
+            .iter()
+            .skip(logs.len().saturating_sub(visible))
+            .map(|(level, line)| {
+                let color = if *level == Level::Error {
+                    Color::Red
+                } else {
+                    Color::Yellow
+                };
+
+                ListItem::new(line.clone()).style(Style::default().fg(color))
+            })
+            .collect();
+        frame.render_widget(
+            List::new(items).block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(" Recent errors "),
+            ),
+            logs_area,
+        );
+
+        frame.render_widget(
+            Paragraph::new(
+                " p pause/resume   r reload   ↑↓ select plugin   space enable/disable   q quit",
+            ),
+            help_area,
+        );
+    }
+
+    /// Handles pending key presses, returning false once the user asked to quit.
+    async fn handle_input(&self, list: &mut ListState) -> Result<bool> {
+        while event::poll(Duration::ZERO)? {
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+
+            let selected = list.selected().unwrap_or_default();
+
+            match key.code {
+                KeyCode::Char('q') => return Ok(false),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    return Ok(false)
+                }
+                KeyCode::Char('p') => self.tray_tx.send(TrayMessage::TogglePause).await?,
+                KeyCode::Char('r') => self.tray_tx.send(TrayMessage::ReloadPlugins).await?,
+                KeyCode::Up => list.select(Some(selected.saturating_sub(1))),
+                KeyCode::Down => list.select(Some(
+                    (selected + 1).min(self.plugins.len().saturating_sub(1)),
+                )),
+                KeyCode::Char(' ') | KeyCode::Enter => {
+                    if let Some(name) = self.plugins.get(selected) {
+                        let disabled = self
+                            .state
+                            .disabled_plugins
+                            .lock()
+                            .unwrap()
+                            .iter()
+                            .any(|disabled| disabled == name);
+
+                        self.tray_tx
+                            .send(TrayMessage::SetPluginEnabled(name.to_string(), disabled))
+                            .await?;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(true)
+    }
+
+    async fn show(&self, terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Result<()> {
+        let mut list = ListState::default();
+        list.select(Some(0));
+
+        let mut traffic = VecDeque::from(vec![0; TRAFFIC_SECONDS]);
+        let mut last_traffic = self.state.stats.sent() + self.state.stats.received();
+        let mut rates = HashMap::new();
+        let mut last_usage = self.state.accounting.snapshot();
+        let mut redraw_interval = interval(Duration::from_millis(100));
+        let mut traffic_interval = interval(Duration::from_secs(1));
+
+        loop {
+            select! {
+                _ = redraw_interval.tick() => {
+                    if !self.handle_input(&mut list).await? {
+                        return Ok(());
+                    }
+
+                    let data: Vec<u64> = traffic.iter().copied().collect();
+                    terminal.draw(|frame| self.draw(frame, &mut list, &data, &rates))?;
+                }
+                _ = traffic_interval.tick() => {
+                    let current = self.state.stats.sent() + self.state.stats.received();
+                    traffic.pop_front();
+                    traffic.push_back(current - last_traffic);
+                    last_traffic = current;
+
+                    let usage = self.state.accounting.snapshot();
+                    rates = usage
+                        .iter()
+                        .map(|current| {
+                            let last = last_usage.iter().find(|last| last.name == current.name);
+                            let (busy_ms, received, sent) =
+                                last.map_or((0, 0, 0), |last| (last.busy_ms, last.received, last.sent));
+
+                            (
+                                current.name.clone(),
+                                PluginRates {
+                                    busy_percent: (current.busy_ms - busy_ms) as f64 / 10.,
+                                    received: current.received - received,
+                                    sent: current.sent - sent,
+                                },
+                            )
+                        })
+                        .collect();
+                    last_usage = usage;
+                }
+            }
+        }
+    }
+
+    pub async fn run(self, subsys: SubsystemHandle) -> Result<()> {
+        enable_raw_mode()?;
+        execute!(stdout(), EnterAlternateScreen)?;
+        let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+
+        // The terminal has to be restored on shutdown as well, so the future gets cancelled first.
+        let result = self.show(&mut terminal).cancel_on_shutdown(&subsys).await;
+
+        disable_raw_mode()?;
+        execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+        terminal.show_cursor()?;
+
+        match result {
+            Ok(Ok(())) => subsys.request_shutdown(),
+            Ok(Err(error)) => return Err(error),
+            Err(CancelledByShutdown) => {}
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,372 @@
+//! Client for the VRChat web API, authenticated with the session of `vrchat-login`, which is kept
+//! in the keyring of the system rather than in the config file.
+
+use crate::config::VrchatApiConfig;
+use crate::state::SharedState;
+use crate::StatusEvent;
+use anyhow::{bail, Context, Result};
+use async_osc::{OscMessage, OscType};
+use log::{debug, info, warn};
+use reqwest::header::{COOKIE, SET_COOKIE};
+use reqwest::{RequestBuilder, Response};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::{interval, MissedTickBehavior};
+use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
+use vrc_osc_manager_core::{InstanceWatcher, OutgoingMessage};
+
+const BASE_URL: &str = "https://api.vrchat.cloud/api/1";
+
+/// VRChat rejects requests without a user agent naming the application and a way to contact its
+/// developers.
+const USER_AGENT: &str = concat!(
+    "vrc-osc-manager/",
+    env!("CARGO_PKG_VERSION"),
+    " https://github.com/DASPRiD/vrc-osc-manager"
+);
+
+const KEYRING_SERVICE: &str = "vrc-osc-manager";
+const KEYRING_USER: &str = "vrchat-api";
+
+#[derive(Debug, Default, Deserialize)]
+struct Presence {
+    #[serde(default)]
+    world: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CurrentUser {
+    #[serde(default)]
+    display_name: String,
+    #[serde(default)]
+    online_friends: Vec<String>,
+    #[serde(default)]
+    requires_two_factor_auth: Vec<String>,
+    #[serde(default)]
+    presence: Presence,
+}
+
+#[derive(Debug, Deserialize)]
+struct World {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct User {
+    display_name: String,
+}
+
+/// Names of worlds and players, which rarely change, so they are kept across restarts instead of
+/// being looked up again.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct NameCache {
+    worlds: HashMap<String, String>,
+    #[serde(default)]
+    users: HashMap<String, String>,
+}
+
+fn keyring_entry() -> Result<keyring::Entry> {
+    Ok(keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER)?)
+}
+
+/// Cookies set by a response, without their attributes.
+fn cookies(response: &Response) -> Vec<String> {
+    response
+        .headers()
+        .get_all(SET_COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .filter_map(|value| value.split(';').next())
+        .map(str::to_string)
+        .collect()
+}
+
+#[derive(Clone)]
+pub struct VrchatApi {
+    client: reqwest::Client,
+    cookie: String,
+    cache: Arc<Mutex<NameCache>>,
+    cache_path: PathBuf,
+}
+
+impl VrchatApi {
+    /// Creates a client with the session stored in the keyring.
+    pub fn from_keyring(data_dir: &Path) -> Result<Self> {
+        let cookie = keyring_entry()?.get_password().context(
+            "No VRChat session found in the keyring, run vrc-osc-manager vrchat-login first",
+        )?;
+        let cache_path = data_dir.join("vrchat-names.json");
+        let cache = std::fs::read_to_string(&cache_path)
+            .ok()
+            .and_then(|cache| serde_json::from_str(&cache).ok())
+            .unwrap_or_default();
+
+        Ok(Self {
+            client: reqwest::Client::builder().user_agent(USER_AGENT).build()?,
+            cookie,
+            cache: Arc::new(Mutex::new(cache)),
+            cache_path,
+        })
+    }
+
+    fn request(&self, path: &str) -> RequestBuilder {
+        self.client
+            .get(format!("{}/{}", BASE_URL, path))
+            .header(COOKIE, &self.cookie)
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        let response = self.request(path).send().await?;
+
+        if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+            bail!("The VRChat session expired, run vrc-osc-manager vrchat-login again");
+        }
+
+        Ok(response.error_for_status()?.json().await?)
+    }
+
+    async fn current_user(&self) -> Result<CurrentUser> {
+        self.get("auth/user").await
+    }
+
+    pub async fn world_name(&self, id: &str) -> Result<String> {
+        if let Some(name) = self.cache.lock().unwrap().worlds.get(id) {
+            return Ok(name.clone());
+        }
+
+        let world: World = self.get(&format!("worlds/{}", id)).await?;
+        self.cache
+            .lock()
+            .unwrap()
+            .worlds
+            .insert(id.to_string(), world.name.clone());
+        self.save_cache().await?;
+
+        Ok(world.name)
+    }
+
+    pub async fn user_name(&self, id: &str) -> Result<String> {
+        if let Some(name) = self.cache.lock().unwrap().users.get(id) {
+            return Ok(name.clone());
+        }
+
+        let user: User = self.get(&format!("users/{}", id)).await?;
+        self.cache
+            .lock()
+            .unwrap()
+            .users
+            .insert(id.to_string(), user.display_name.clone());
+        self.save_cache().await?;
+
+        Ok(user.display_name)
+    }
+
+    /// Display name of a player the VRChat log only names by their user ID, and the name as
+    /// logged otherwise.
+    pub async fn player_name(&self, player: String) -> String {
+        if !player.starts_with("usr_") {
+            return player;
+        }
+
+        match self.user_name(&player).await {
+            Ok(name) => name,
+            Err(error) => {
+                warn!("Failed to look up the name of {}: {:#}", player, error);
+                player
+            }
+        }
+    }
+
+    async fn save_cache(&self) -> Result<()> {
+        let cache = serde_json::to_string(&*self.cache.lock().unwrap())?;
+
+        if let Err(error) = tokio::fs::write(&self.cache_path, cache).await {
+            warn!("Failed to write {}: {}", self.cache_path.display(), error);
+        }
+
+        Ok(())
+    }
+}
+
+fn prompt(label: &str) -> Result<String> {
+    print!("{}: ", label);
+    std::io::stdout().flush()?;
+    let mut line = String::new();
+    std::io::stdin().read_line(&mut line)?;
+    Ok(line.trim().to_string())
+}
+
+/// Logs in interactively, including two-factor authentication, and stores the session in the
+/// keyring.
+pub async fn login() -> Result<()> {
+    let username = prompt("VRChat username or email")?;
+    let password = rpassword::prompt_password("Password: ")?;
+    let client = reqwest::Client::builder().user_agent(USER_AGENT).build()?;
+
+    let response = client
+        .get(format!("{}/auth/user", BASE_URL))
+        .basic_auth(&username, Some(&password))
+        .send()
+        .await?
+        .error_for_status()
+        .context("Login failed, check your username and password")?;
+    let mut cookie = cookies(&response);
+    let user: CurrentUser = response.json().await?;
+
+    if let Some(method) = user.requires_two_factor_auth.first() {
+        let (path, label) = if method == "emailOtp" {
+            ("emailotp", "Code sent to your email")
+        } else {
+            ("totp", "Code of your authenticator app")
+        };
+        let code = prompt(label)?;
+        let response = client
+            .post(format!("{}/auth/twofactorauth/{}/verify", BASE_URL, path))
+            .header(COOKIE, cookie.join("; "))
+            .json(&serde_json::json!({ "code": code }))
+            .send()
+            .await?
+            .error_for_status()
+            .context("Two-factor authentication failed")?;
+        cookie.extend(cookies(&response));
+    }
+
+    keyring_entry()?.set_password(&cookie.join("; "))?;
+
+    let user: CurrentUser = VrchatApi {
+        client,
+        cookie: cookie.join("; "),
+        cache: Default::default(),
+        cache_path: PathBuf::new(),
+    }
+    .current_user()
+    .await?;
+    println!("Logged in as {}", user.display_name);
+    Ok(())
+}
+
+/// Polls the account for the number of online friends and the current world.
+pub struct VrchatApiPoller {
+    config: VrchatApiConfig,
+    api: Option<VrchatApi>,
+    sender_tx: mpsc::Sender<OutgoingMessage>,
+    status_tx: mpsc::Sender<StatusEvent>,
+    state: Arc<SharedState>,
+    instance: InstanceWatcher,
+}
+
+impl VrchatApiPoller {
+    pub fn new(
+        config: &VrchatApiConfig,
+        api: Option<VrchatApi>,
+        sender_tx: mpsc::Sender<OutgoingMessage>,
+        status_tx: mpsc::Sender<StatusEvent>,
+        state: Arc<SharedState>,
+        instance: InstanceWatcher,
+    ) -> Self {
+        Self {
+            config: config.clone(),
+            api,
+            sender_tx,
+            status_tx,
+            state,
+            instance,
+        }
+    }
+
+    /// Polls the account once. The number of friends is only sent when it changed, or when the
+    /// avatar changed since it was last sent, which resets the parameter.
+    async fn update(
+        &self,
+        api: &VrchatApi,
+        sent: &mut Option<(usize, Option<String>)>,
+    ) -> Result<()> {
+        let user = api.current_user().await?;
+        debug!("{} friends online", user.online_friends.len());
+        let current = (
+            user.online_friends.len(),
+            self.state.avatar.lock().unwrap().clone(),
+        );
+
+        match &self.config.friends_parameter {
+            Some(parameter) if sent.as_ref() != Some(&current) => {
+                // Int parameters in VRChat only hold a single byte.
+                self.sender_tx
+                    .send(OutgoingMessage::new(
+                        "vrchat_api",
+                        OscMessage {
+                            addr: parameter.clone(),
+                            args: vec![OscType::Int(current.0.min(255) as i32)],
+                        },
+                    ))
+                    .await?;
+                *sent = Some(current);
+            }
+            _ => {}
+        }
+
+        // Private instances and loading screens show up as placeholders instead of worlds. While
+        // the VRChat log is available, it tells the world without asking the API.
+        if self.config.world
+            && user.presence.world.starts_with("wrld_")
+            && self.instance.borrow().is_none()
+        {
+            let name = api.world_name(&user.presence.world).await?;
+
+            if self.state.world.lock().unwrap().as_deref() != Some(name.as_str()) {
+                self.status_tx.send(StatusEvent::WorldChanged(name)).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn poll(&self, api: VrchatApi) -> Result<()> {
+        let mut sent = None;
+        let mut interval = interval(Duration::from_secs(self.config.interval_seconds.max(60)));
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        info!("Polling the VRChat API");
+
+        loop {
+            interval.tick().await;
+
+            if !self.state.plugins_running.load(Ordering::Relaxed) {
+                continue;
+            }
+
+            if let Err(error) = self.update(&api, &mut sent).await {
+                warn!("Failed to query the VRChat API: {:#}", error);
+            }
+        }
+    }
+
+    pub async fn run(self, subsys: SubsystemHandle) -> Result<()> {
+        if !self.config.enabled {
+            subsys.on_shutdown_requested().await;
+            return Ok(());
+        }
+
+        // Without a session, which was already warned about, there is nothing to poll.
+        let Some(api) = self.api.clone() else {
+            subsys.on_shutdown_requested().await;
+            return Ok(());
+        };
+
+        match (self.poll(api).cancel_on_shutdown(&subsys)).await {
+            Ok(Ok(())) => subsys.request_shutdown(),
+            Ok(Err(error)) => return Err(error),
+            Err(CancelledByShutdown) => {}
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,355 @@
+use crate::avatar;
+use crate::state::SharedState;
+use crate::StatusEvent;
+use anyhow::Result;
+use directories::BaseDirs;
+use log::{debug, warn};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::select;
+use tokio::sync::{broadcast, mpsc, watch};
+use tokio::task::spawn_blocking;
+use tokio::time::{interval, MissedTickBehavior};
+use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
+use vrc_osc_manager_core::{Instance, VrchatLogEvent};
+
+#[cfg(feature = "vrchat_api")]
+use crate::vrchat_api::VrchatApi;
+
+/// VRChat starts a new log file with every launch, named after the time it was started.
+fn newest_log(dir: &Path) -> Option<PathBuf> {
+    std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            name.starts_with("output_log_") && name.ends_with(".txt")
+        })
+        .max_by_key(|entry| entry.metadata().and_then(|meta| meta.modified()).ok())
+        .map(|entry| entry.path())
+}
+
+/// Reads the lines appended to a log file since the last call.
+struct Tail {
+    path: PathBuf,
+    offset: u64,
+    partial: String,
+}
+
+impl Tail {
+    fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            offset: 0,
+            partial: String::new(),
+        }
+    }
+
+    fn read(&mut self) -> Result<Vec<String>> {
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(self.offset))?;
+        let mut buffer = vec![];
+        self.offset += file.read_to_end(&mut buffer)? as u64;
+
+        self.partial.push_str(&String::from_utf8_lossy(&buffer));
+        let Some((complete, partial)) = self.partial.rsplit_once('\n') else {
+            return Ok(vec![]);
+        };
+
+        let lines = complete.lines().map(str::to_string).collect();
+        self.partial = partial.to_string();
+        Ok(lines)
+    }
+}
+
+/// The instance being joined, which VRChat logs in two lines whose order is not guaranteed.
+#[derive(Default)]
+struct Joining {
+    location: Option<String>,
+    world_name: Option<String>,
+}
+
+impl Joining {
+    fn feed(&mut self, event: &str) {
+        if let Some(location) = event.strip_prefix("Joining wrld_") {
+            // The name logged for the previous instance must not stick when leaving it was not
+            // logged, while the name of the new one may still follow.
+            self.location = Some(format!("wrld_{}", location.trim()));
+            self.world_name = None;
+        } else if let Some(world_name) = event.strip_prefix("Entering Room: ") {
+            self.world_name = Some(world_name.trim().to_string());
+        } else if event.starts_with("OnLeftRoom") {
+            *self = Self::default();
+        }
+    }
+
+    fn instance(&self) -> Option<Instance> {
+        Instance::parse(self.location.as_deref()?, self.world_name.as_deref()?)
+    }
+}
+
+/// Name of a player as logged, which newer versions of VRChat follow with their user ID.
+fn player_name(name: &str) -> String {
+    let name = name.trim();
+    name.rsplit_once(" (usr_")
+        .map_or(name, |(name, _)| name)
+        .to_string()
+}
+
+/// Parses the player events among the lines VRChat logs as `[Behaviour] …`.
+fn player_event(event: &str) -> Option<VrchatLogEvent> {
+    if let Some(name) = event.strip_prefix("OnPlayerJoined ") {
+        Some(VrchatLogEvent::PlayerJoined(player_name(name)))
+    } else if let Some(name) = event.strip_prefix("OnPlayerLeft ") {
+        Some(VrchatLogEvent::PlayerLeft(player_name(name)))
+    } else {
+        let (player, avatar) = event
+            .strip_prefix("Switching ")?
+            .split_once(" to avatar ")?;
+        Some(VrchatLogEvent::AvatarChanged {
+            player: player.trim().to_string(),
+            avatar: avatar.trim().to_string(),
+        })
+    }
+}
+
+/// Follows the VRChat log to tell plugins which instance the user is in and what happens in it.
+pub struct VrchatLog {
+    dir: Option<PathBuf>,
+    tail: Option<Tail>,
+    joining: Joining,
+    /// Display name of the user, who is reported to join like every other player.
+    user: Option<String>,
+    instance_tx: watch::Sender<Option<Instance>>,
+    /// Whether VRChat is running, as the instance is left without a trace in the log when it stops.
+    vrchat_running: watch::Receiver<Option<bool>>,
+    event_tx: broadcast::Sender<VrchatLogEvent>,
+    status_tx: mpsc::Sender<StatusEvent>,
+    state: Arc<SharedState>,
+    /// Looks up the names of players some versions of VRChat only log by their user ID.
+    #[cfg(feature = "vrchat_api")]
+    api: Option<VrchatApi>,
+}
+
+impl VrchatLog {
+    pub fn new(
+        instance_tx: watch::Sender<Option<Instance>>,
+        vrchat_running: watch::Receiver<Option<bool>>,
+        event_tx: broadcast::Sender<VrchatLogEvent>,
+        status_tx: mpsc::Sender<StatusEvent>,
+        state: Arc<SharedState>,
+    ) -> Self {
+        Self {
+            dir: BaseDirs::new().map(|base_dirs| avatar::vrchat_dir(&base_dirs)),
+            tail: None,
+            joining: Joining::default(),
+            user: None,
+            instance_tx,
+            vrchat_running,
+            event_tx,
+            status_tx,
+            state,
+            #[cfg(feature = "vrchat_api")]
+            api: None,
+        }
+    }
+
+    #[cfg(feature = "vrchat_api")]
+    pub fn resolve_names(mut self, api: VrchatApi) -> Self {
+        self.api = Some(api);
+        self
+    }
+
+    #[cfg(feature = "vrchat_api")]
+    async fn resolve(&self, event: VrchatLogEvent) -> VrchatLogEvent {
+        let Some(api) = &self.api else {
+            return event;
+        };
+
+        match event {
+            VrchatLogEvent::PlayerJoined(player) => {
+                VrchatLogEvent::PlayerJoined(api.player_name(player).await)
+            }
+            VrchatLogEvent::PlayerLeft(player) => {
+                VrchatLogEvent::PlayerLeft(api.player_name(player).await)
+            }
+            VrchatLogEvent::AvatarChanged { player, avatar } => VrchatLogEvent::AvatarChanged {
+                player: api.player_name(player).await,
+                avatar,
+            },
+            event => event,
+        }
+    }
+
+    fn read(&mut self) -> Vec<String> {
+        let Some(tail) = &mut self.tail else {
+            return vec![];
+        };
+
+        tail.read().unwrap_or_else(|error| {
+            warn!("Failed to read {}: {}", tail.path.display(), error);
+            vec![]
+        })
+    }
+
+    /// Updates the instance with a line of the log, returning the events it stands for.
+    fn feed(&mut self, line: &str) -> Vec<VrchatLogEvent> {
+        let Some((_, event)) = line.split_once("[Behaviour] ") else {
+            return vec![];
+        };
+
+        if let Some(name) = event.strip_prefix("User Authenticated: ") {
+            self.user = Some(player_name(name));
+        }
+
+        self.joining.feed(event);
+        let instance = self.joining.instance();
+        let mut events = vec![];
+
+        if *self.instance_tx.borrow() != instance {
+            if self.instance_tx.send_replace(instance.clone()).is_some() {
+                events.push(VrchatLogEvent::WorldLeft);
+            }
+
+            if let Some(instance) = instance {
+                events.push(VrchatLogEvent::WorldJoined(instance));
+            }
+        }
+
+        events.extend(player_event(event));
+        events
+    }
+
+    async fn follow(&mut self) -> Result<()> {
+        let Some(dir) = self.dir.clone() else {
+            debug!("No home directory to find the VRChat log in");
+            return Ok(());
+        };
+
+        // The log of a running VRChat is read first, so the instance joined before startup is
+        // known but not mistaken for a fresh join. Logs of long sessions grow large, so this
+        // happens on a blocking task.
+        if let Some(path) = newest_log(&dir) {
+            let mut tail = Tail::new(path);
+            let (tail, lines) = spawn_blocking(move || {
+                let lines = tail.read();
+                (tail, lines)
+            })
+            .await?;
+
+            match lines {
+                Ok(lines) => {
+                    for line in lines {
+                        self.feed(&line);
+                    }
+                }
+                Err(error) => warn!("Failed to read {}: {}", tail.path.display(), error),
+            }
+
+            self.tail = Some(tail);
+        }
+
+        // The newest log may be left from an earlier session, whose instance is long gone.
+        while self.vrchat_running.borrow().is_none() {
+            if self.vrchat_running.changed().await.is_err() {
+                break;
+            }
+        }
+
+        if *self.vrchat_running.borrow() == Some(false) {
+            self.instance_tx.send_replace(None);
+            self.joining = Joining::default();
+        }
+
+        if let Some(instance) = self.instance_tx.borrow().clone() {
+            self.status_tx
+                .send(StatusEvent::WorldChanged(instance.world_name))
+                .await?;
+        }
+
+        let mut interval = interval(Duration::from_secs(2));
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            select! {
+                _ = interval.tick() => {}
+                Ok(()) = self.vrchat_running.changed() => {
+                    let stopped = *self.vrchat_running.borrow() == Some(false);
+
+                    if stopped {
+                        self.leave().await?;
+                    }
+
+                    continue;
+                }
+            }
+
+            if let Some(path) = newest_log(&dir) {
+                if self.tail.as_ref().map(|tail| &tail.path) != Some(&path) {
+                    debug!("Following {}", path.display());
+                    self.tail = Some(Tail::new(path));
+                    self.joining = Joining::default();
+                }
+            }
+
+            for line in self.read() {
+                for event in self.feed(&line) {
+                    #[cfg(feature = "vrchat_api")]
+                    let event = self.resolve(event).await;
+                    self.publish(event).await?;
+                }
+            }
+        }
+    }
+
+    /// Leaves the instance once VRChat stopped, which does not log leaving it.
+    async fn leave(&mut self) -> Result<()> {
+        self.joining = Joining::default();
+
+        if let Some(instance) = self.instance_tx.send_replace(None) {
+            debug!("Left {} as VRChat stopped", instance.link());
+            self.publish(VrchatLogEvent::WorldLeft).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn publish(&self, event: VrchatLogEvent) -> Result<()> {
+        match &event {
+            VrchatLogEvent::WorldJoined(instance) => {
+                debug!("Joined {}", instance.link());
+
+                if self.state.world.lock().unwrap().as_deref() != Some(instance.world_name.as_str())
+                {
+                    self.status_tx
+                        .send(StatusEvent::WorldChanged(instance.world_name.clone()))
+                        .await?;
+                }
+            }
+            VrchatLogEvent::PlayerJoined(name) if self.user.as_ref() != Some(name) => {
+                self.status_tx
+                    .send(StatusEvent::PlayerJoined(name.clone()))
+                    .await?;
+            }
+            _ => {}
+        }
+
+        // Nobody listens while no plugin subscribed.
+        let _ = self.event_tx.send(event);
+        Ok(())
+    }
+
+    pub async fn run(mut self, subsys: SubsystemHandle) -> Result<()> {
+        match (self.follow().cancel_on_shutdown(&subsys)).await {
+            Ok(Ok(())) => subsys.on_shutdown_requested().await,
+            Ok(Err(error)) => return Err(error),
+            Err(CancelledByShutdown) => {}
+        }
+
+        Ok(())
+    }
+}
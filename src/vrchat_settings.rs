@@ -0,0 +1,165 @@
+//! Reads and changes the settings VRChat keeps as Unity player preferences, which live in the
+//! registry on Windows and in the registry of the Proton prefix on Linux.
+
+#[cfg(not(target_os = "windows"))]
+use crate::avatar;
+use anyhow::{bail, Context, Result};
+use directories::BaseDirs;
+
+#[cfg(not(target_os = "windows"))]
+use std::path::PathBuf;
+#[cfg(target_os = "windows")]
+use tokio::process::Command;
+
+/// Prefix of the preference storing whether OSC is enabled, followed by a hash of its name.
+const OSC_SETTING: &str = "UI.Settings.Osc";
+
+#[cfg(target_os = "windows")]
+const KEY: &str = r"HKCU\Software\VRChat\VRChat";
+
+#[cfg(not(target_os = "windows"))]
+const SECTION: &str = r"[Software\\VRChat\\VRChat]";
+
+struct Setting {
+    name: String,
+    enabled: bool,
+}
+
+#[cfg(target_os = "windows")]
+async fn find_osc_setting(_base_dirs: &BaseDirs) -> Result<Option<Setting>> {
+    let output = Command::new("reg").args(["query", KEY]).output().await?;
+
+    // The key is missing until VRChat was started once.
+    if !output.status.success() {
+        return Ok(None);
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| {
+            let mut parts = line.split_whitespace();
+            let name = parts.next()?;
+
+            if !name.starts_with(OSC_SETTING) {
+                return None;
+            }
+
+            Some(Setting {
+                name: name.to_string(),
+                enabled: parts.nth(1)? != "0x0",
+            })
+        }))
+}
+
+#[cfg(target_os = "windows")]
+async fn write_osc_setting(_base_dirs: &BaseDirs, setting: &Setting) -> Result<()> {
+    let status = Command::new("reg")
+        .args([
+            "add",
+            KEY,
+            "/v",
+            &setting.name,
+            "/t",
+            "REG_DWORD",
+            "/d",
+            "1",
+            "/f",
+        ])
+        .status()
+        .await?;
+
+    if !status.success() {
+        bail!("Failed to write {}\\{}: {}", KEY, setting.name, status);
+    }
+
+    Ok(())
+}
+
+#[cfg(not(target_os = "windows"))]
+fn registry_path(base_dirs: &BaseDirs) -> PathBuf {
+    avatar::proton_prefix(base_dirs).join("user.reg")
+}
+
+/// Finds the line of the setting within the VRChat section of a Wine registry file.
+#[cfg(not(target_os = "windows"))]
+fn find_line(registry: &str) -> Option<(usize, &str)> {
+    registry
+        .lines()
+        .enumerate()
+        .skip_while(|(_, line)| !line.starts_with(SECTION))
+        .skip(1)
+        .take_while(|(_, line)| !line.starts_with('['))
+        .find(|(_, line)| line.starts_with(&format!("\"{}", OSC_SETTING)))
+}
+
+#[cfg(not(target_os = "windows"))]
+async fn find_osc_setting(base_dirs: &BaseDirs) -> Result<Option<Setting>> {
+    let path = registry_path(base_dirs);
+
+    let Ok(registry) = tokio::fs::read_to_string(&path).await else {
+        return Ok(None);
+    };
+
+    Ok(find_line(&registry).and_then(|(_, line)| {
+        let (name, value) = line.split_once('=')?;
+
+        Some(Setting {
+            name: name.trim_matches('"').to_string(),
+            enabled: value != "dword:00000000",
+        })
+    }))
+}
+
+#[cfg(not(target_os = "windows"))]
+async fn write_osc_setting(base_dirs: &BaseDirs, setting: &Setting) -> Result<()> {
+    let path = registry_path(base_dirs);
+    let registry = tokio::fs::read_to_string(&path)
+        .await
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let Some((index, _)) = find_line(&registry) else {
+        bail!("{} is missing from {}", setting.name, path.display());
+    };
+
+    let mut lines: Vec<String> = registry.lines().map(str::to_string).collect();
+    lines[index] = format!("\"{}\"=dword:00000001", setting.name);
+    lines.push(String::new());
+
+    // The file holds the whole registry of the prefix, so it is backed up and replaced at once
+    // instead of being rewritten in place, where a crash would leave it cut off.
+    let backup = path.with_extension("reg.bak");
+    tokio::fs::copy(&path, &backup)
+        .await
+        .with_context(|| format!("Failed to back up {}", path.display()))?;
+
+    let temporary = path.with_extension("reg.tmp");
+    tokio::fs::write(&temporary, lines.join("\n"))
+        .await
+        .with_context(|| format!("Failed to write {}", temporary.display()))?;
+    tokio::fs::rename(&temporary, &path)
+        .await
+        .with_context(|| format!("Failed to replace {}", path.display()))
+}
+
+/// Whether OSC is enabled in VRChat, or `None` if VRChat never stored the setting, e.g. as it was
+/// not started on this machine yet.
+pub async fn osc_enabled() -> Result<Option<bool>> {
+    let base_dirs = BaseDirs::new().context("Base directories not available")?;
+    Ok(find_osc_setting(&base_dirs)
+        .await?
+        .map(|setting| setting.enabled))
+}
+
+/// Turns on OSC in the VRChat settings. VRChat writes its settings when closing, so this only has
+/// an effect while it is not running.
+pub async fn enable_osc() -> Result<()> {
+    let base_dirs = BaseDirs::new().context("Base directories not available")?;
+    let Some(setting) = find_osc_setting(&base_dirs).await? else {
+        bail!("VRChat did not store its OSC setting yet, start it once and enable OSC in the action menu under Options > OSC");
+    };
+
+    if !setting.enabled {
+        write_osc_setting(&base_dirs, &setting).await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,103 @@
+use crate::config::WatchdogConfig;
+use crate::osc::{RECEIVER, SENDER};
+use crate::state::SharedState;
+use crate::tray::TrayMessage;
+use anyhow::Result;
+use log::warn;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::{interval, Instant, MissedTickBehavior};
+use tokio_graceful_shutdown::{errors::CancelledByShutdown, FutureExt, SubsystemHandle};
+use vrc_osc_manager_core::{heartbeat, Notifier};
+
+/// Watches the heartbeats of the sender, the receiver and the plugins, and starts over the ones
+/// which stopped beating.
+pub struct Watchdog {
+    config: WatchdogConfig,
+    state: Arc<SharedState>,
+    tray_tx: mpsc::Sender<TrayMessage>,
+    notifier: Notifier,
+}
+
+impl Watchdog {
+    pub fn new(
+        config: &WatchdogConfig,
+        state: Arc<SharedState>,
+        tray_tx: mpsc::Sender<TrayMessage>,
+        notifier: Notifier,
+    ) -> Self {
+        Self {
+            config: config.clone(),
+            state,
+            tray_tx,
+            notifier,
+        }
+    }
+
+    async fn recover(&self, name: &'static str) -> Result<()> {
+        self.state.heartbeats.recover(name);
+
+        // The sender and receiver start over on their own once asked to recover.
+        if name != SENDER && name != RECEIVER {
+            warn!("Plugin {} stopped responding, restarting it", name);
+            self.tray_tx.send(TrayMessage::RestartPlugin(name)).await?;
+        }
+
+        if self.config.notify {
+            let _ = self
+                .notifier
+                .notify(
+                    "Recovered from a hang",
+                    format!("{} stopped responding and was restarted", name),
+                )
+                .await;
+        }
+
+        Ok(())
+    }
+
+    async fn watch(&self) -> Result<()> {
+        let timeout = Duration::from_secs(self.config.timeout_seconds).max(heartbeat::INTERVAL * 2);
+        let period = timeout / 2;
+        let mut check = interval(period);
+        check.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut last_check = Instant::now();
+
+        loop {
+            check.tick().await;
+
+            // Coming back from suspend, everything looks stuck for a moment.
+            let late = last_check.elapsed() > period * 2;
+            last_check = Instant::now();
+
+            if late {
+                continue;
+            }
+
+            for name in self.state.heartbeats.stalled(timeout) {
+                warn!(
+                    "{} did not respond for more than {} seconds",
+                    name,
+                    timeout.as_secs()
+                );
+                self.recover(name).await?;
+            }
+        }
+    }
+
+    pub async fn run(self, subsys: SubsystemHandle) -> Result<()> {
+        if !self.config.enabled {
+            subsys.on_shutdown_requested().await;
+            return Ok(());
+        }
+
+        match (self.watch().cancel_on_shutdown(&subsys)).await {
+            Ok(Ok(())) => subsys.request_shutdown(),
+            Ok(Err(error)) => return Err(error),
+            Err(CancelledByShutdown) => {}
+        }
+
+        Ok(())
+    }
+}